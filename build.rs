@@ -1,58 +1,676 @@
-use image::io::Reader as ImageReader;
-use std::{fs::File, io::Write, path::PathBuf};
+use fontdue::{Font, FontSettings};
+use image::{imageops::FilterType, io::Reader as ImageReader};
+use std::{
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+};
 use walkdir::WalkDir;
 
-fn convert_rgb8_to_rgb565(src: &[u8], width: usize, height: usize) -> Vec<u8> {
-    let mut dst = Vec::with_capacity(width * height * 2);
+/// Size of display. Pictures of themes cover the whole display.
+const DISPLAY_WIDTH: u32 = 135;
+const DISPLAY_HEIGHT: u32 = 240;
+
+/// Jpeg artifacts in black backgrounds break runs, so pixels with sum of
+/// components below this are made black
+const BLACK_THRESHOLD: u16 = 24;
+/// Pixels with alpha below this are transparent
+const ALPHA_THRESHOLD: u8 = 0x80;
+
+/// Dithering applied when images are reduced to RGB565. Gradients band
+/// without it, but dithered images compress worse.
+#[derive(Clone, Copy, PartialEq)]
+enum Dither {
+    None,
+    /// Ordered dithering with 4x4 Bayer matrix
+    Bayer,
+    /// Error diffusion
+    FloydSteinberg,
+}
+
+impl Dither {
+    fn parse(text: &str) -> Option<Self> {
+        match text {
+            "none" => Some(Self::None),
+            "bayer" => Some(Self::Bayer),
+            "floyd-steinberg" => Some(Self::FloydSteinberg),
+            _ => None,
+        }
+    }
+}
+
+const BAYER_4X4: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+/// Bits of red, green and blue in RGB565
+const RGB565_BITS: [u32; 3] = [5, 6, 5];
+
+/// Reduces 8 bit component to given number of bits. Returns reduced
+/// component and the value it stands for in 8 bits.
+fn quantize(value: f32, bits: u32) -> (u16, f32) {
+    let reduced = (value.clamp(0.0, 255.0) as u16) >> (8 - bits);
+    // Bits are repeated, so the largest value stands for 255
+    let restored = (reduced << (8 - bits)) | (reduced >> (2 * bits - 8));
+    (reduced, restored as f32)
+}
+
+/// Converts image to RGB565. Transparent pixels are None.
+fn convert_rgba8_to_rgb565(
+    src: &[u8],
+    width: usize,
+    height: usize,
+    dither: Dither,
+) -> Vec<Option<u16>> {
+    // Errors of quantization carried to pixels not converted yet
+    let mut errors = vec![[0.0f32; 3]; width * height];
+    let mut dst = Vec::with_capacity(width * height);
     for row in 0..height {
         for col in 0..width {
-            let offset = (row * width + col) * 3;
+            let offset = (row * width + col) * 4;
             let r = src[offset] as u16;
             let g = src[offset + 1] as u16;
             let b = src[offset + 2] as u16;
+            let a = src[offset + 3];
+            if a < ALPHA_THRESHOLD {
+                dst.push(None);
+                continue;
+            }
+            if r + g + b < BLACK_THRESHOLD {
+                dst.push(Some(0));
+                continue;
+            }
 
-            let b = b >> 3;
-            let g = (g >> 2) << 5;
-            let r = (r >> 3) << 11;
+            let mut rgb = 0;
+            for (i, (value, bits)) in [r, g, b].into_iter().zip(RGB565_BITS).enumerate() {
+                let mut value = value as f32;
+                match dither {
+                    Dither::None => {}
+                    Dither::Bayer => {
+                        let step = (1 << (8 - bits)) as f32;
+                        let threshold = (BAYER_4X4[row % 4][col % 4] as f32 + 0.5) / 16.0;
+                        value += threshold * step;
+                    }
+                    Dither::FloydSteinberg => value += errors[row * width + col][i],
+                }
 
-            let rgb = r | g | b;
+                let (reduced, restored) = quantize(value, bits);
+                rgb = (rgb << bits) | reduced;
 
-            dst.push((rgb >> 8) as u8);
-            dst.push((rgb & 0xFF) as u8);
+                if dither == Dither::FloydSteinberg {
+                    let error = value - restored;
+                    let mut carry = |col: usize, row: usize, weight: f32| {
+                        if col < width && row < height {
+                            errors[row * width + col][i] += error * weight / 16.0;
+                        }
+                    };
+                    carry(col + 1, row, 7.0);
+                    if col > 0 {
+                        carry(col - 1, row + 1, 3.0);
+                    }
+                    carry(col, row + 1, 5.0);
+                    carry(col + 1, row + 1, 1.0);
+                }
+            }
+
+            dst.push(Some(rgb));
         }
     }
 
     dst
 }
 
-fn main() {
-    let target_dir = PathBuf::from("target/img/");
-    let src_dir = "misc/img";
+/// Images are compressed with run-length encoding, otherwise all digit sets
+/// don't fit in flash. Each packet starts with header byte. Its 2 high bits
+/// are type of packet and the rest is number of pixels in packet minus 1.
+/// Pixels of literal packet follow it as is, repeat packet is followed by
+/// single pixel and transparent packet has no pixels. Pixels are big endian.
+const PACKET_LITERAL: u8 = 0x00;
+const PACKET_REPEAT: u8 = 0x80;
+const PACKET_TRANSPARENT: u8 = 0x40;
+const PACKET_MAX_LEN: usize = 0x40;
+
+fn compress_rle(src: &[Option<u16>]) -> Vec<u8> {
+    let mut dst = Vec::new();
+    let mut i = 0;
+    while i < src.len() {
+        let run = src[i..]
+            .iter()
+            .take(PACKET_MAX_LEN)
+            .take_while(|&&pixel| pixel == src[i])
+            .count();
+        let Some(pixel) = src[i] else {
+            dst.push(PACKET_TRANSPARENT | (run - 1) as u8);
+            i += run;
+            continue;
+        };
+        if run >= 2 {
+            dst.push(PACKET_REPEAT | (run - 1) as u8);
+            dst.extend_from_slice(&pixel.to_be_bytes());
+            i += run;
+            continue;
+        }
+
+        // Literal packet lasts until next run of at least 2 pixels or
+        // transparent pixel
+        let mut end = i + 1;
+        while end < src.len()
+            && end - i < PACKET_MAX_LEN
+            && src[end].is_some()
+            && src.get(end + 1) != Some(&src[end])
+        {
+            end += 1;
+        }
+        dst.push(PACKET_LITERAL | (end - i - 1) as u8);
+        for pixel in src[i..end].iter().flatten() {
+            dst.extend_from_slice(&pixel.to_be_bytes());
+        }
+        i = end;
+    }
+
+    dst
+}
+
+/// Fonts contain printable ASCII characters (' '..='~')
+const FONT_CHARS: std::ops::RangeInclusive<char> = ' '..='~';
+
+/// Fonts rasterized from TTF files in misc/font. Each has name of generated
+/// constant, file and size in pixels.
+const FONTS: [(&str, &str, f32); 2] = [
+    ("FONT_LARGE", "DejaVuSansMono-Bold.ttf", 64.0),
+    ("FONT_SMALL", "DejaVuSansMono-Bold.ttf", 24.0),
+];
+
+/// Font of large digits
+const LARGE_DIGITS_FONT: &str = "DejaVuSansMono-Bold.ttf";
+
+/// Rasterizes monospace font into glyph bitmaps. Each glyph takes 1 bit per
+/// pixel (set if pixel is mostly covered), rows go from top to bottom, msb
+/// first.
+fn rasterize_font(ttf: &[u8], px: f32) -> (usize, usize, Vec<u8>) {
+    let font = Font::from_bytes(ttf, FontSettings::default()).unwrap();
+    let line = font.horizontal_line_metrics(px).unwrap();
+    let ascent = line.ascent.ceil() as i32;
+    let glyph_width = font.metrics('M', px).advance_width.ceil() as usize;
+    let glyph_height = (line.ascent - line.descent).ceil() as usize;
+    let glyph_size = (glyph_width * glyph_height + 7) / 8;
+
+    let mut dst = vec![0; glyph_size * FONT_CHARS.count()];
+    for (c, bitmap) in FONT_CHARS.zip(dst.chunks_exact_mut(glyph_size)) {
+        let (metrics, coverage) = font.rasterize(c, px);
+        for (row, y) in coverage.chunks_exact(metrics.width.max(1)).zip(0..) {
+            for (&coverage, x) in row.iter().zip(0..) {
+                // Glyph is placed on baseline
+                let x = metrics.xmin + x;
+                let y = ascent - metrics.ymin - metrics.height as i32 + y;
+                let is_inside =
+                    (0..glyph_width as i32).contains(&x) && (0..glyph_height as i32).contains(&y);
+                if is_inside && coverage >= 0x80 {
+                    let bit = y as usize * glyph_width + x as usize;
+                    bitmap[bit / 8] |= 0x80 >> (bit % 8);
+                }
+            }
+        }
+    }
+
+    (glyph_width, glyph_height, dst)
+}
+
+/// Size of large digits, the size of display. Hours and minutes of large time
+/// layout are 270x240 images split in half between two displays, so each
+/// digit fills a display.
+const LARGE_DIGIT_WIDTH: usize = DISPLAY_WIDTH as usize;
+const LARGE_DIGIT_HEIGHT: usize = DISPLAY_HEIGHT as usize;
+/// Space between large digits and edges of display
+const LARGE_DIGIT_MARGIN: usize = 8;
+
+/// Rasterizes digits as large as they fit on display, with bitmaps laid out
+/// the same way as glyphs of fonts. Digits share baseline and are centered.
+fn rasterize_large_digits(ttf: &[u8]) -> Vec<u8> {
+    let font = Font::from_bytes(ttf, FontSettings::default()).unwrap();
+    // Size of ink of all digits: widest one, top of the tallest one and
+    // bottom of the lowest one
+    let ink = |px| {
+        ('0'..='9').map(|c| font.metrics(c, px)).fold(
+            (0, i32::MIN, i32::MAX),
+            |(width, top, bottom), m| {
+                (
+                    width.max(m.width),
+                    top.max(m.ymin + m.height as i32),
+                    bottom.min(m.ymin),
+                )
+            },
+        )
+    };
+    let (width, top, bottom) = ink(100.0);
+    let px = 100.0
+        * ((LARGE_DIGIT_WIDTH - 2 * LARGE_DIGIT_MARGIN) as f32 / width as f32)
+            .min((LARGE_DIGIT_HEIGHT - 2 * LARGE_DIGIT_MARGIN) as f32 / (top - bottom) as f32);
+    let (_, top, bottom) = ink(px);
+    let baseline = (LARGE_DIGIT_HEIGHT as i32 - (top - bottom)) / 2 + top;
+
+    let glyph_size = (LARGE_DIGIT_WIDTH * LARGE_DIGIT_HEIGHT).div_ceil(8);
+    let mut dst = vec![0; glyph_size * 10];
+    for (c, bitmap) in ('0'..='9').zip(dst.chunks_exact_mut(glyph_size)) {
+        let (metrics, coverage) = font.rasterize(c, px);
+        let left = (LARGE_DIGIT_WIDTH as i32 - metrics.width as i32) / 2;
+        for (row, y) in coverage.chunks_exact(metrics.width.max(1)).zip(0..) {
+            for (&coverage, x) in row.iter().zip(0..) {
+                let x = left + x;
+                let y = baseline - metrics.ymin - metrics.height as i32 + y;
+                let is_inside = (0..LARGE_DIGIT_WIDTH as i32).contains(&x)
+                    && (0..LARGE_DIGIT_HEIGHT as i32).contains(&y);
+                if is_inside && coverage >= 0x80 {
+                    let bit = y as usize * LARGE_DIGIT_WIDTH + x as usize;
+                    bitmap[bit / 8] |= 0x80 >> (bit % 8);
+                }
+            }
+        }
+    }
+
+    dst
+}
+
+/// Directory of generated files. Converted assets are included from there.
+fn out_dir() -> PathBuf {
+    PathBuf::from(std::env::var_os("OUT_DIR").unwrap())
+}
+
+/// Makes name of constant from name of asset file
+fn const_name(prefix: &str, path: &Path) -> String {
+    let stem = path.file_stem().unwrap().to_string_lossy();
+    let stem: String = stem
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{prefix}_{stem}")
+}
+
+/// Writes glyph bitmaps of every font and large digits, and fonts_gen.rs
+/// with constants that include them
+fn convert_fonts() {
+    let target_dir = out_dir().join("font");
+    let src_dir = Path::new("misc/font");
+    std::fs::create_dir_all(&target_dir).ok();
+
+    let mut table = String::new();
+    for (name, file, px) in FONTS {
+        let path = src_dir.join(file);
+        let ttf = std::fs::read(&path).unwrap();
+        let (glyph_width, glyph_height, glyphs) = rasterize_font(&ttf, px);
+
+        let target_name = target_dir.join(name.to_lowercase()).with_extension("bin");
+        std::fs::write(&target_name, glyphs).unwrap();
+
+        table += &format!(
+            "pub const {name}: Font = Font {{ glyph_width: {glyph_width}, glyph_height: \
+             {glyph_height}, glyphs: include_bytes!({target_name:?}) }};\n"
+        );
+    }
+
+    let path = src_dir.join(LARGE_DIGITS_FONT);
+    let digits = rasterize_large_digits(&std::fs::read(&path).unwrap());
+    let target_name = target_dir.join("large_digits.bin");
+    std::fs::write(&target_name, digits).unwrap();
+    table += &format!(
+        "pub const LARGE_DIGITS: LargeDigits = LargeDigits {{ width: {LARGE_DIGIT_WIDTH}, \
+         height: {LARGE_DIGIT_HEIGHT}, glyphs: include_bytes!({target_name:?}) }};\n"
+    );
+
+    std::fs::write(out_dir().join("fonts_gen.rs"), table).unwrap();
+}
+
+/// Dithering of image, set by theme.txt of its theme
+fn image_dither(path: &Path, src_dir: &str) -> Dither {
+    path.ancestors()
+        .skip(1)
+        .take_while(|dir| dir.starts_with(src_dir))
+        .map(|dir| dir.join("theme.txt"))
+        .find(|settings| settings.exists())
+        .map_or(Dither::None, |settings| {
+            parse_theme_settings(&settings).dither
+        })
+}
+
+/// Converts every image in src_dir to target_dir keeping directory structure.
+/// If size is given, images have to be of that size. Larger or smaller ones
+/// of the same aspect ratio are resized, others fail the build.
+fn convert_images(src_dir: &str, target_dir: &Path, size: Option<(u32, u32)>) {
     for entry in WalkDir::new(src_dir).into_iter().filter_map(|e| e.ok()) {
         let metadata = entry.metadata().unwrap();
         if !metadata.is_file() {
             continue;
         }
         let path = entry.path();
-        if let Ok(image) = ImageReader::open(path).unwrap().decode() {
-            let image = image.into_rgb8();
-            let dim = image.dimensions();
+        if let Ok(mut image) = ImageReader::open(path).unwrap().decode() {
+            if let Some((width, height)) = size {
+                let (w, h) = (image.width(), image.height());
+                if (w, h) != (width, height) {
+                    assert!(
+                        w * height == h * width,
+                        "{} is {w}x{h}, expected {width}x{height} or the same aspect ratio",
+                        path.display()
+                    );
+                    println!(
+                        "cargo:warning={} is {w}x{h}, resized to {width}x{height}",
+                        path.display()
+                    );
+                    image = image.resize_exact(width, height, FilterType::Lanczos3);
+                }
+            }
+            let image = image.into_rgba8();
+            let (width, height) = image.dimensions();
+            let dither = image_dither(path, src_dir);
+
+            let mut target_name = target_dir.join(path.strip_prefix(src_dir).unwrap());
+            target_name.set_extension("bin");
+            write_image(&target_name, &image.into_raw(), width, height, dither);
+        }
+    }
+}
 
-            let img_raw = image.into_raw();
-            let img_raw = convert_rgb8_to_rgb565(&img_raw, dim.0 as usize, dim.1 as usize);
+/// Writes RGBA image in format of images.rs: width, height and transparency
+/// flag followed by run length encoded pixels
+fn write_image(target_name: &Path, rgba: &[u8], width: u32, height: u32, dither: Dither) {
+    let img_raw = convert_rgba8_to_rgb565(rgba, width as usize, height as usize, dither);
+    let is_transparent = img_raw.contains(&None) as u32;
+    let img_raw = compress_rle(&img_raw);
 
-            let dim_raw = [dim.0.to_le_bytes(), dim.1.to_le_bytes()].concat();
+    let dim_raw = [
+        width.to_le_bytes(),
+        height.to_le_bytes(),
+        is_transparent.to_le_bytes(),
+    ]
+    .concat();
 
-            let path = path.strip_prefix(src_dir).unwrap();
-            let mut target_name = target_dir.join(path);
-            target_name.set_extension("bin");
-            std::fs::create_dir_all(target_name.parent().unwrap()).ok();
+    std::fs::create_dir_all(target_name.parent().unwrap()).ok();
+    let mut file = File::create(target_name).unwrap();
+    file.write_all(&dim_raw).unwrap();
+    file.write_all(&img_raw).unwrap();
+}
 
-            let mut file = File::create(target_name).unwrap();
-            file.write_all(&dim_raw).unwrap();
-            file.write_all(&img_raw).unwrap();
+/// Icons of moon phases are this many pixels on a side
+const MOON_ICON_SIZE: u32 = 64;
+const MOON_RADIUS: f32 = 30.0;
+const MOON_PHASES: u32 = 8;
+const MOON_LIT: [u8; 3] = [0xF0, 0xE8, 0xC0];
+const MOON_DARK: [u8; 3] = [0x38, 0x38, 0x38];
 
-            println!("cargo:rerun-if-changed={}", path.to_str().unwrap());
+/// Draws icon of each moon phase, from new moon through full moon and back.
+/// Moon is seen from northern hemisphere, so waxing moon is lit on the right.
+fn generate_moon_phases() {
+    let target_dir = out_dir().join("moon");
+    let center = (MOON_ICON_SIZE as f32 - 1.0) / 2.0;
+    for phase in 0..MOON_PHASES {
+        let cos = (phase as f32 / MOON_PHASES as f32 * std::f32::consts::TAU).cos();
+        let mut rgba = Vec::with_capacity((MOON_ICON_SIZE * MOON_ICON_SIZE * 4) as usize);
+        for y in 0..MOON_ICON_SIZE {
+            for x in 0..MOON_ICON_SIZE {
+                let (dx, dy) = (x as f32 - center, y as f32 - center);
+                let half_width = (MOON_RADIUS * MOON_RADIUS - dy * dy).max(0.0).sqrt();
+                // Terminator crosses row at this distance from the middle
+                let terminator = half_width * cos;
+                let is_lit = if phase <= MOON_PHASES / 2 {
+                    dx > terminator
+                } else {
+                    dx < -terminator
+                };
+                if dx.abs() > half_width || half_width == 0.0 {
+                    rgba.extend([0, 0, 0, 0]);
+                } else if is_lit {
+                    rgba.extend(MOON_LIT.into_iter().chain([0xFF]));
+                } else {
+                    rgba.extend(MOON_DARK.into_iter().chain([0xFF]));
+                }
+            }
         }
+
+        let target_name = target_dir.join(phase.to_string()).with_extension("bin");
+        write_image(
+            &target_name,
+            &rgba,
+            MOON_ICON_SIZE,
+            MOON_ICON_SIZE,
+            Dither::None,
+        );
     }
 }
+
+/// Settings of theme read from theme.txt. It consists of `key = value` lines,
+/// comments start with #.
+struct ThemeSettings {
+    accent: String,
+    background: String,
+    led: String,
+    /// Dithering of images of theme
+    dither: Dither,
+    /// Theme to borrow menu pictures from
+    menu: Option<String>,
+}
+
+fn parse_theme_settings(path: &Path) -> ThemeSettings {
+    let text =
+        std::fs::read_to_string(path).unwrap_or_else(|_| panic!("theme has no {}", path.display()));
+    let mut settings = ThemeSettings {
+        accent: "ff0000".into(),
+        background: "000000".into(),
+        led: "Sin".into(),
+        dither: Dither::None,
+        menu: None,
+    };
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (key, value) = line
+            .split_once('=')
+            .unwrap_or_else(|| panic!("{}: expected key = value", path.display()));
+        let value = value.trim().to_string();
+        match key.trim() {
+            "accent" => settings.accent = value,
+            "background" => settings.background = value,
+            "led" => settings.led = value,
+            "dither" => {
+                settings.dither = Dither::parse(&value)
+                    .unwrap_or_else(|| panic!("{}: unknown dither {value}", path.display()))
+            }
+            "menu" => settings.menu = Some(value),
+            key => panic!("{}: unknown key {key}", path.display()),
+        }
+    }
+
+    settings
+}
+
+/// Makes ColorRGB8 literal from hex RGB
+fn color_literal(hex: &str) -> String {
+    let rgb = u32::from_str_radix(hex, 16).unwrap_or_else(|_| panic!("bad color {hex}"));
+    format!(
+        "ColorRGB8 {{ r: {:#04x}, g: {:#04x}, b: {:#04x} }}",
+        rgb >> 16,
+        (rgb >> 8) & 0xFF,
+        rgb & 0xFF
+    )
+}
+
+/// Makes array of Image literals including converted images with given names
+fn images_literal(dir: &Path, names: impl Iterator<Item = usize>) -> String {
+    let images: Vec<String> = names
+        .map(|name| {
+            let path = dir.join(name.to_string()).with_extension("bin");
+            assert!(path.exists(), "missing {}", path.display());
+            format!("Image::new(include_bytes!({path:?}))")
+        })
+        .collect();
+    format!("[{}]", images.join(", "))
+}
+
+/// Names and settings of themes, one for each directory in misc/img, in order
+/// of names
+fn read_themes() -> Vec<(String, ThemeSettings)> {
+    let src_dir = Path::new("misc/img");
+    let mut names: Vec<String> = std::fs::read_dir(src_dir)
+        .unwrap()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().is_dir())
+        .map(|e| e.file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+
+    let themes: Vec<_> = names
+        .iter()
+        .map(|name| {
+            let settings = parse_theme_settings(&src_dir.join(name).join("theme.txt"));
+            (name.clone(), settings)
+        })
+        .collect();
+    for (name, settings) in &themes {
+        if let Some(menu) = &settings.menu {
+            assert!(
+                names.contains(menu),
+                "theme {name} uses unknown theme {menu}"
+            );
+        }
+    }
+    themes
+}
+
+/// Writes images_gen.rs with constants of every converted asset: size of
+/// pictures, icons, moon phases, digits and menu pictures of themes. Images
+/// must be converted by now.
+fn generate_images(themes: &[(String, ThemeSettings)]) {
+    let img_dir = out_dir().join("img");
+    let icon_dir = out_dir().join("icon");
+
+    let mut table = format!(
+        "pub const PICTURE_WIDTH: u32 = {DISPLAY_WIDTH};\n\
+         pub const PICTURE_HEIGHT: u32 = {DISPLAY_HEIGHT};\n"
+    );
+
+    let mut icons: Vec<PathBuf> = std::fs::read_dir(&icon_dir)
+        .map(|dir| dir.filter_map(|e| e.ok()).map(|e| e.path()).collect())
+        .unwrap_or_default();
+    icons.sort();
+    for path in icons {
+        let name = const_name("ICON", &path);
+        table += &format!("pub const {name}: Image = Image::new(include_bytes!({path:?}));\n");
+    }
+
+    let phases = images_literal(&out_dir().join("moon"), 0..MOON_PHASES as usize);
+    table += &format!("pub const MOON_PHASES: [Image; {MOON_PHASES}] = {phases};\n");
+
+    for (name, settings) in themes {
+        let digits = images_literal(&img_dir.join(name).join("numpic"), 0..10);
+        table += &format!("pub const NUMPIC_{name}: Numpic = Numpic::new({digits});\n");
+        if settings.menu.is_none() {
+            let pics = images_literal(&img_dir.join(name).join("menupic"), 1..7);
+            table += &format!("pub const MENUPIC_{name}: Menupic = Menupic::new({pics});\n");
+        }
+    }
+
+    std::fs::write(out_dir().join("images_gen.rs"), table).unwrap();
+}
+
+/// Writes themes_gen.rs with table of themes
+fn generate_themes(themes: &[(String, ThemeSettings)]) {
+    let mut table = String::new();
+    let entries: Vec<String> = themes
+        .iter()
+        .map(|(name, settings)| {
+            let menu = settings.menu.as_ref().unwrap_or(name);
+            format!(
+                "Theme {{ numpic: &images::NUMPIC_{name}, menupic: &images::MENUPIC_{menu}, \
+                 accent: {}, background: {}, led_mode: LedMode::{} }}",
+                color_literal(&settings.accent),
+                color_literal(&settings.background),
+                settings.led
+            )
+        })
+        .collect();
+    let numpics: Vec<String> = themes
+        .iter()
+        .map(|(name, _)| format!("&images::NUMPIC_{name}"))
+        .collect();
+    table += &format!(
+        "pub const THEMES: [Theme; {}] = [{}];\n",
+        entries.len(),
+        entries.join(", ")
+    );
+    table += &format!(
+        "pub const NUMPICS: [&Numpic; {}] = [{}];\n",
+        numpics.len(),
+        numpics.join(", ")
+    );
+
+    std::fs::write(out_dir().join("themes_gen.rs"), table).unwrap();
+}
+
+/// Passes commit and date of build to firmware as GIT_HASH and BUILD_DATE
+/// variables, shown on about screen. Builds outside of git repository have
+/// "unknown" commit. SOURCE_DATE_EPOCH overrides date for reproducible builds.
+fn emit_build_info() {
+    let git_hash = std::process::Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map_or_else(|| "unknown".to_string(), |hash| hash.trim().to_string());
+    println!("cargo:rustc-env=GIT_HASH={git_hash}");
+
+    let secs = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse().ok())
+        .unwrap_or_else(|| {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs())
+        });
+    let (year, month, day) = civil_from_days((secs / (24 * 60 * 60)) as i64);
+    println!("cargo:rustc-env=BUILD_DATE={year:04}-{month:02}-{day:02}");
+}
+
+/// Date of given day since 1970-01-01, see
+/// <http://howardhinnant.github.io/date_algorithms.html#civil_from_days>
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    // Year is counted from March, so leap day is the last one
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    (year, month, day)
+}
+
+fn main() {
+    // Whole directory is watched, so new assets are picked up too
+    println!("cargo:rerun-if-changed=misc");
+    println!("cargo:rerun-if-changed=build.rs");
+    // Commit shown on about screen follows checkouts and new commits
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs/heads");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+
+    emit_build_info();
+
+    convert_images(
+        "misc/img",
+        &out_dir().join("img"),
+        Some((DISPLAY_WIDTH, DISPLAY_HEIGHT)),
+    );
+    convert_images("misc/icon", &out_dir().join("icon"), None);
+    generate_moon_phases();
+    let themes = read_themes();
+    generate_images(&themes);
+    generate_themes(&themes);
+    convert_fonts();
+}
@@ -0,0 +1,232 @@
+//! AT24Cxx serial EEPROM with two byte addressing (AT24C32 to AT24C512).
+//!
+//! Reads can span the whole memory. Writes are split at page boundaries, as
+//! chip wraps around within page otherwise. After each page chip is busy with
+//! internal write cycle and doesn't acknowledge its address, which is polled
+//! until it answers again.
+
+use embedded_hal::blocking::i2c::{Write, WriteRead};
+
+/// Largest page among supported variants
+const MAX_PAGE_SIZE: usize = 128;
+/// Attempts to address chip while it finishes write cycle. Each attempt takes
+/// about 0.1ms on 100kHz bus and write cycle takes at most 10ms.
+const WRITE_CYCLE_POLLS: u32 = 200;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Variant {
+    AT24C32,
+    AT24C64,
+    AT24C128,
+    AT24C256,
+    AT24C512,
+}
+
+impl Variant {
+    /// Size of memory in bytes
+    pub fn capacity(self) -> usize {
+        match self {
+            Self::AT24C32 => 4 * 1024,
+            Self::AT24C64 => 8 * 1024,
+            Self::AT24C128 => 16 * 1024,
+            Self::AT24C256 => 32 * 1024,
+            Self::AT24C512 => 64 * 1024,
+        }
+    }
+
+    /// Most bytes that can be written at once
+    pub fn page_size(self) -> usize {
+        match self {
+            Self::AT24C32 | Self::AT24C64 => 32,
+            Self::AT24C128 | Self::AT24C256 => 64,
+            Self::AT24C512 => MAX_PAGE_SIZE,
+        }
+    }
+}
+
+pub struct AT24CxxState {
+    addr: u8,
+    variant: Variant,
+}
+
+impl AT24CxxState {
+    pub fn new(addr: u8, variant: Variant) -> Self {
+        Self { addr, variant }
+    }
+}
+
+/// AT24Cxx Driver
+pub struct AT24Cxx<I2C> {
+    i2c: I2C,
+    state: AT24CxxState,
+}
+
+impl<I2C> AT24Cxx<I2C> {
+    pub fn new(i2c: I2C, state: AT24CxxState) -> Self {
+        Self { i2c, state }
+    }
+
+    pub fn release(self) -> (I2C, AT24CxxState) {
+        (self.i2c, self.state)
+    }
+
+    pub fn variant(&self) -> Variant {
+        self.state.variant
+    }
+
+    fn check_range(&self, offset: u16, len: usize) -> Result<(), Error> {
+        if offset as usize + len > self.state.variant.capacity() {
+            return Err(Error::OutOfRange);
+        }
+
+        Ok(())
+    }
+}
+
+impl<I2C> AT24Cxx<I2C>
+where
+    I2C: Write + WriteRead,
+{
+    /// Reads bytes starting at offset in single transaction
+    pub fn read(&mut self, offset: u16, buffer: &mut [u8]) -> Result<(), Error> {
+        self.check_range(offset, buffer.len())?;
+        self.i2c
+            .write_read(self.state.addr, &offset.to_be_bytes(), buffer)
+            .map_err(|_| Error::BusRead)
+    }
+
+    /// Writes bytes starting at offset. Returns once chip has finished
+    /// writing.
+    pub fn write(&mut self, offset: u16, bytes: &[u8]) -> Result<(), Error> {
+        self.check_range(offset, bytes.len())?;
+
+        let page_size = self.state.variant.page_size();
+        let mut offset = offset as usize;
+        let mut bytes = bytes;
+        while !bytes.is_empty() {
+            let len = bytes.len().min(page_size - offset % page_size);
+            let (page, rest) = bytes.split_at(len);
+            self.write_page(offset as u16, page)?;
+            offset += len;
+            bytes = rest;
+        }
+
+        Ok(())
+    }
+
+    fn write_page(&mut self, offset: u16, bytes: &[u8]) -> Result<(), Error> {
+        let mut buf = [0u8; 2 + MAX_PAGE_SIZE];
+        buf[..2].copy_from_slice(&offset.to_be_bytes());
+        buf[2..2 + bytes.len()].copy_from_slice(bytes);
+        self.i2c
+            .write(self.state.addr, &buf[..2 + bytes.len()])
+            .map_err(|_| Error::BusWrite)?;
+
+        self.wait_write_cycle(offset)
+    }
+
+    /// Polls chip until it acknowledges address. Address pointer is written
+    /// as part of polling, as some buses can't send empty writes.
+    fn wait_write_cycle(&mut self, offset: u16) -> Result<(), Error> {
+        for _ in 0..WRITE_CYCLE_POLLS {
+            if self
+                .i2c
+                .write(self.state.addr, &offset.to_be_bytes())
+                .is_ok()
+            {
+                return Ok(());
+            }
+        }
+
+        Err(Error::WriteTimeout)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Error {
+    BusRead,
+    BusWrite,
+    /// Access goes past the end of memory
+    OutOfRange,
+    /// Chip hasn't finished write cycle in time
+    WriteTimeout,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_hal_mock::eh0::{
+        i2c::{Mock, Transaction},
+        MockError,
+    };
+
+    const ADDR: u8 = 0x50;
+
+    fn with_eeprom(expectations: &[Transaction], f: impl FnOnce(&mut AT24Cxx<Mock>)) {
+        let state = AT24CxxState::new(ADDR, Variant::AT24C32);
+        let mut eeprom = AT24Cxx::new(Mock::new(expectations), state);
+        f(&mut eeprom);
+        let (mut i2c, _) = eeprom.release();
+        i2c.done();
+    }
+
+    /// Chip doesn't acknowledge its address during write cycle
+    fn busy(bytes: Vec<u8>) -> Transaction {
+        Transaction::write(ADDR, bytes).with_error(MockError::Io(std::io::ErrorKind::Other))
+    }
+
+    #[test]
+    fn read_sends_big_endian_offset() {
+        let expectations = [Transaction::write_read(
+            ADDR,
+            vec![0x01, 0x02],
+            vec![1, 2, 3],
+        )];
+        with_eeprom(&expectations, |eeprom| {
+            let mut buffer = [0u8; 3];
+            eeprom.read(0x0102, &mut buffer).unwrap();
+            assert_eq!(buffer, [1, 2, 3]);
+        });
+    }
+
+    #[test]
+    fn write_is_split_at_page_boundary() {
+        let expectations = [
+            Transaction::write(ADDR, vec![0x00, 30, 1, 2]),
+            Transaction::write(ADDR, vec![0x00, 30]),
+            Transaction::write(ADDR, vec![0x00, 32, 3]),
+            Transaction::write(ADDR, vec![0x00, 32]),
+        ];
+        with_eeprom(&expectations, |eeprom| {
+            eeprom.write(30, &[1, 2, 3]).unwrap();
+        });
+    }
+
+    #[test]
+    fn write_waits_for_write_cycle() {
+        let expectations = [
+            Transaction::write(ADDR, vec![0x00, 0x10, 42]),
+            busy(vec![0x00, 0x10]),
+            busy(vec![0x00, 0x10]),
+            Transaction::write(ADDR, vec![0x00, 0x10]),
+        ];
+        with_eeprom(&expectations, |eeprom| {
+            eeprom.write(0x10, &[42]).unwrap();
+        });
+    }
+
+    #[test]
+    fn rejects_access_past_end() {
+        with_eeprom(&[], |eeprom| {
+            let mut buffer = [0u8; 2];
+            assert!(matches!(
+                eeprom.read(4095, &mut buffer),
+                Err(Error::OutOfRange)
+            ));
+            assert!(matches!(
+                eeprom.write(4095, &[1, 2]),
+                Err(Error::OutOfRange)
+            ));
+        });
+    }
+}
@@ -0,0 +1,1005 @@
+//! BME280 temperature, humidity and pressure sensor
+//!
+//! Compatible BMP280 (without humidity) and BME680 (gas sensor is not used)
+//! are supported as well. Variant is detected by chip id at init.
+
+use embedded_hal::blocking::i2c::{Write, WriteRead};
+
+#[derive(Clone, Copy)]
+pub struct Temperature(i32);
+
+impl Temperature {
+    pub fn from_raw(raw: i32) -> Self {
+        Self(raw)
+    }
+
+    pub fn as_celcius(self) -> f32 {
+        self.0 as f32 / 100.
+    }
+
+    pub fn as_fahrenheit(self) -> f32 {
+        self.as_celcius() * 9. / 5. + 32.
+    }
+}
+
+impl core::fmt::Debug for Temperature {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Temperature")
+            .field("celcius", &self.as_celcius())
+            .finish()
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Pressure(u32);
+
+/// Standard atmospheric pressure at sea level
+pub const SEA_LEVEL_PRESSURE_PAS: f32 = 101325.;
+
+impl Pressure {
+    pub fn from_raw(raw: u32) -> Self {
+        Self(raw)
+    }
+
+    pub fn from_pas(pas: f32) -> Self {
+        Self((pas * 256.) as u32)
+    }
+
+    pub fn as_pas(self) -> f32 {
+        self.0 as f32 / 256.
+    }
+
+    /// Estimates altitude in meters using international barometric formula
+    /// given current pressure at sea level. There is no powf in core, so it
+    /// has to be supplied by caller.
+    pub fn altitude(self, sea_level: Pressure, powf: impl Fn(f32, f32) -> f32) -> f32 {
+        44330. * (1. - powf(self.as_pas() / sea_level.as_pas(), 1. / 5.255))
+    }
+
+    /// Inverse of altitude: pressure at sea level given altitude (in meters)
+    /// where this pressure was measured. This is the pressure reported by
+    /// weather stations, as it does not depend on their location.
+    pub fn sea_level(self, altitude: f32, powf: impl Fn(f32, f32) -> f32) -> Pressure {
+        Self::from_pas(self.as_pas() / powf(1. - altitude / 44330., 5.255))
+    }
+}
+
+impl core::fmt::Debug for Pressure {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Pressure")
+            .field("pas", &self.as_pas())
+            .finish()
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Humidity(u32);
+
+impl Humidity {
+    pub fn from_raw(raw: u32) -> Self {
+        Self(raw)
+    }
+
+    pub fn as_percent(self) -> f32 {
+        self.0 as f32 / 1024.
+    }
+}
+
+impl core::fmt::Debug for Humidity {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Humidity")
+            .field("percents", &self.as_percent())
+            .finish()
+    }
+}
+
+/// Oversampling of single measurement channel. Higher oversampling reduces
+/// noise but makes measurement longer.
+#[derive(Clone, Copy, Debug)]
+pub enum Oversampling {
+    /// Channel is not measured
+    Skip = 0,
+    X1 = 1,
+    X2 = 2,
+    X4 = 3,
+    X8 = 4,
+    X16 = 5,
+}
+
+/// Coefficient of IIR filter applied to temperature and pressure. Filter
+/// suppresses short disturbances (like slammed door) at cost of slower
+/// response.
+#[derive(Clone, Copy, Debug)]
+pub enum Filter {
+    Off = 0,
+    X2 = 1,
+    X4 = 2,
+    X8 = 3,
+    X16 = 4,
+}
+
+/// Inactive time between measurements in normal mode
+#[derive(Clone, Copy, Debug)]
+pub enum Standby {
+    Ms0_5 = 0,
+    Ms10 = 6,
+    Ms20 = 7,
+    Ms62_5 = 1,
+    Ms125 = 2,
+    Ms250 = 3,
+    Ms500 = 4,
+    Ms1000 = 5,
+}
+
+/// Measurement settings of sensor
+#[derive(Clone, Copy, Debug)]
+pub struct Config {
+    pub temperature: Oversampling,
+    pub pressure: Oversampling,
+    pub humidity: Oversampling,
+    pub filter: Filter,
+    /// Only used in normal mode (see [`BME280::start_normal_mode`])
+    pub standby: Standby,
+}
+
+impl Config {
+    /// Single sample of temperature and pressure, 16 samples of humidity and
+    /// no filtering
+    pub const fn new() -> Self {
+        Self {
+            temperature: Oversampling::X1,
+            pressure: Oversampling::X1,
+            humidity: Oversampling::X16,
+            filter: Filter::Off,
+            standby: Standby::Ms1000,
+        }
+    }
+
+    /// Settings recommended by datasheet for weather monitoring. Lowest
+    /// power consumption and no filtering, which is enough for measurements
+    /// taken every few seconds.
+    pub const fn weather_monitoring() -> Self {
+        Self {
+            temperature: Oversampling::X1,
+            pressure: Oversampling::X1,
+            humidity: Oversampling::X1,
+            filter: Filter::Off,
+            standby: Standby::Ms1000,
+        }
+    }
+
+    /// Settings recommended by datasheet for indoor navigation. Lowest noise
+    /// with heavy filtering.
+    pub const fn indoor_navigation() -> Self {
+        Self {
+            temperature: Oversampling::X2,
+            pressure: Oversampling::X16,
+            humidity: Oversampling::X1,
+            filter: Filter::X16,
+            standby: Standby::Ms0_5,
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Chips supported by this driver. They share most of register layout and
+/// measurement procedure.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Variant {
+    /// Does not measure humidity
+    BMP280,
+    BME280,
+    /// Does not have normal mode
+    BME680,
+}
+
+impl Variant {
+    fn from_chip_id(chip_id: u8) -> Option<Self> {
+        match chip_id {
+            // 0x56 and 0x57 are used by samples of BMP280
+            0x56..=0x58 => Some(Self::BMP280),
+            0x60 => Some(Self::BME280),
+            0x61 => Some(Self::BME680),
+            _ => None,
+        }
+    }
+
+    pub fn has_humidity(self) -> bool {
+        !matches!(self, Self::BMP280)
+    }
+
+    pub fn has_normal_mode(self) -> bool {
+        !matches!(self, Self::BME680)
+    }
+
+    fn ctrl_hum(self) -> Register {
+        match self {
+            Self::BME680 => Register::BME680CtrlHum,
+            _ => Register::CtrlHum,
+        }
+    }
+
+    fn ctrl_meas(self) -> Register {
+        match self {
+            Self::BME680 => Register::BME680CtrlMeas,
+            _ => Register::CtrlMeas,
+        }
+    }
+
+    fn config(self) -> Register {
+        match self {
+            Self::BME680 => Register::BME680Config,
+            _ => Register::Config,
+        }
+    }
+
+    /// First of data registers. Order of values is the same for all
+    /// variants: pressure, temperature and humidity.
+    fn data(self) -> Register {
+        match self {
+            Self::BME680 => Register::BME680PressMSB,
+            _ => Register::PressMSB,
+        }
+    }
+}
+
+/// Values acquired in single measurement
+#[derive(Clone, Copy, Debug)]
+pub struct Measurement {
+    pub temperature: Temperature,
+    pub pressure: Pressure,
+    /// None if sensor does not measure humidity
+    pub humidity: Option<Humidity>,
+}
+
+pub struct BME280State {
+    addr: u8,
+    config: Config,
+    variant: Option<Variant>,
+    compensator: Option<Compensator>,
+}
+
+impl BME280State {
+    pub fn new(addr: u8) -> Self {
+        Self {
+            addr,
+            config: Default::default(),
+            variant: None,
+            compensator: None,
+        }
+    }
+}
+
+pub struct BME280<I2C> {
+    i2c: I2C,
+    state: BME280State,
+}
+
+impl<I2C> BME280<I2C> {
+    pub fn new(i2c: I2C, state: BME280State) -> Self {
+        Self { i2c, state }
+    }
+
+    pub fn release(self) -> (I2C, BME280State) {
+        (self.i2c, self.state)
+    }
+}
+
+impl<I2C> BME280<I2C>
+where
+    I2C: Write + WriteRead,
+{
+    fn write_reg(&mut self, reg: Register, value: u8) -> Result<(), Error> {
+        let buf = [reg as u8, value];
+        self.i2c
+            .write(self.state.addr, &buf)
+            .map_err(|_| Error::BusWrite)
+    }
+
+    fn read_regs(&mut self, regs: &[Register], dst: &mut [u8]) -> Result<(), Error> {
+        for (i, &reg) in regs.iter().enumerate() {
+            self.i2c
+                .write_read(self.state.addr, &[reg as u8], &mut dst[i..i + 1])
+                .map_err(|_| Error::BusRead)?;
+        }
+
+        Ok(())
+        // TODO: Why this does not work?
+        // self.i2c
+        //     .write_read(
+        //         self.addr,
+        //         // SAFETY: Safe because Register has repr(u8)
+        //         unsafe { core::mem::transmute(regs) },
+        //         dst,
+        //     )
+        //     .map_err(|_| Error::BusRead)
+    }
+
+    /// Reads consecutive registers starting from reg in single transaction
+    fn read_burst(&mut self, reg: u8, dst: &mut [u8]) -> Result<(), Error> {
+        self.i2c
+            .write_read(self.state.addr, &[reg], dst)
+            .map_err(|_| Error::BusRead)
+    }
+
+    fn initialized_variant(&self) -> Result<Variant, Error> {
+        self.state.variant.ok_or(Error::NotInitialized)
+    }
+
+    pub fn init(&mut self, config: Config) -> Result<(), Error> {
+        let mut chip_id = [0u8];
+        self.read_regs(&[Register::ChipId], &mut chip_id)?;
+        let variant = Variant::from_chip_id(chip_id[0]).ok_or(Error::WrongChipId)?;
+
+        self.state.variant.replace(variant);
+        self.state.config = config;
+        self.set_settings()?;
+        self.calibrate()
+    }
+
+    /// Variant of sensor detected at init
+    pub fn variant(&self) -> Option<Variant> {
+        self.state.variant
+    }
+
+    fn set_settings(&mut self) -> Result<(), Error> {
+        let variant = self.initialized_variant()?;
+        let config = self.state.config;
+        if variant.has_humidity() {
+            // Changes to humidity oversampling become effective only after
+            // write to CtrlMeas, which is done in set_mode
+            self.write_reg(variant.ctrl_hum(), config.humidity as u8)?;
+        }
+
+        // Sensor sleeps until measurement is requested with measure_once.
+        // Config register is only guaranteed to be written in sleep mode.
+        self.set_mode(SLEEP_MODE)?;
+
+        const SPI_ENABLE: u8 = 0; // disable
+        let standby = if variant.has_normal_mode() {
+            config.standby as u8
+        } else {
+            0
+        };
+        self.write_reg(
+            variant.config(),
+            (standby << 5) | ((config.filter as u8) << 2) | SPI_ENABLE,
+        )?;
+
+        Ok(())
+    }
+
+    fn set_mode(&mut self, mode: u8) -> Result<(), Error> {
+        let variant = self.initialized_variant()?;
+        let config = self.state.config;
+        self.write_reg(
+            variant.ctrl_meas(),
+            ((config.temperature as u8) << 5) | ((config.pressure as u8) << 2) | mode,
+        )
+    }
+
+    /// Makes sensor measure continuously, sleeping for configured standby
+    /// time between measurements. Latest values are read with read_params.
+    pub fn start_normal_mode(&mut self) -> Result<(), Error> {
+        if !self.initialized_variant()?.has_normal_mode() {
+            return Err(Error::Unsupported);
+        }
+
+        self.set_mode(NORMAL_MODE)
+    }
+
+    /// Performs single measurement in forced mode. Sensor returns to sleep
+    /// mode after conversion is done, so it does not waste power and heat
+    /// itself up between measurements.
+    pub fn measure_once(&mut self) -> Result<Measurement, Error> {
+        let ctrl_meas_reg = self.initialized_variant()?.ctrl_meas();
+        self.set_mode(FORCED_MODE)?;
+
+        // Mode bits read back as forced until conversion is completed. With
+        // maximal oversampling it takes about 113ms.
+        const POLL_ATTEMPTS: usize = 200;
+        let mut ctrl_meas = [0u8];
+        for _ in 0..POLL_ATTEMPTS {
+            crate::delay_us(1000);
+            self.read_regs(&[ctrl_meas_reg], &mut ctrl_meas)?;
+            if ctrl_meas[0] & MODE_MASK == SLEEP_MODE {
+                return self.read_params();
+            }
+        }
+
+        Err(Error::MeasurementTimeout)
+    }
+
+    fn calibrate(&mut self) -> Result<(), Error> {
+        let compensator = match self.initialized_variant()? {
+            Variant::BMP280 => Compensator::BME280(self.read_bme280_calibration(false)?),
+            Variant::BME280 => Compensator::BME280(self.read_bme280_calibration(true)?),
+            Variant::BME680 => Compensator::BME680(self.read_bme680_calibration()?),
+        };
+        self.state.compensator.replace(compensator);
+
+        Ok(())
+    }
+
+    fn read_bme280_calibration(&mut self, has_humidity: bool) -> Result<ADCCompensator, Error> {
+        use Register::*;
+
+        let t_regs = [DigT1LSB, DigT1MSB, DigT2LSB, DigT2MSB, DigT3LSB, DigT3MSB];
+        let mut t_bytes = [0u8; 6];
+        self.read_regs(&t_regs, &mut t_bytes)?;
+
+        let p_regs = [
+            DigP1LSB, DigP1MSB, DigP2LSB, DigP2MSB, DigP3LSB, DigP3MSB, DigP4LSB, DigP4MSB,
+            DigP5LSB, DigP5MSB, DigP6LSB, DigP6MSB, DigP7LSB, DigP7MSB, DigP8LSB, DigP8MSB,
+            DigP9LSB, DigP9MSB,
+        ];
+        let mut p_bytes = [0u8; 18];
+        self.read_regs(&p_regs, &mut p_bytes)?;
+
+        let h_regs = [
+            DigH1,
+            DigH2LSB,
+            DigH2MSB,
+            DigH3,
+            DigH4MSB,
+            DigH4LSBDigH5MSB,
+            DigH5LSB,
+            DigH6,
+        ];
+        let mut h_bytes = [0u8; 8];
+        if has_humidity {
+            self.read_regs(&h_regs, &mut h_bytes)?;
+        }
+
+        Ok(ADCCompensator {
+            digt1: u16::from_le_bytes(t_bytes[0..2].try_into().unwrap()),
+            digt2: i16::from_le_bytes(t_bytes[2..4].try_into().unwrap()),
+            digt3: i16::from_le_bytes(t_bytes[4..6].try_into().unwrap()),
+
+            digp1: u16::from_le_bytes(p_bytes[0..2].try_into().unwrap()),
+            digp2: i16::from_le_bytes(p_bytes[2..4].try_into().unwrap()),
+            digp3: i16::from_le_bytes(p_bytes[4..6].try_into().unwrap()),
+            digp4: i16::from_le_bytes(p_bytes[6..8].try_into().unwrap()),
+            digp5: i16::from_le_bytes(p_bytes[8..10].try_into().unwrap()),
+            digp6: i16::from_le_bytes(p_bytes[10..12].try_into().unwrap()),
+            digp7: i16::from_le_bytes(p_bytes[12..14].try_into().unwrap()),
+            digp8: i16::from_le_bytes(p_bytes[14..16].try_into().unwrap()),
+            digp9: i16::from_le_bytes(p_bytes[16..18].try_into().unwrap()),
+
+            digh1: h_bytes[0],
+            digh2: i16::from_le_bytes(h_bytes[1..3].try_into().unwrap()),
+            digh3: h_bytes[3],
+            digh4: ((h_bytes[4] as i16) << 4) | (h_bytes[5] & 0x0F) as i16,
+            digh5: ((h_bytes[6] as i16) << 4) | (((h_bytes[5] >> 4) & 0x0F) as i16),
+            digh6: h_bytes[7] as i8,
+        })
+    }
+
+    fn read_bme680_calibration(&mut self) -> Result<BME680Compensator, Error> {
+        // Calibration data is stored in two blocks. Offsets are taken from
+        // BME68x Sensor API.
+        const COEFF1: u8 = 0x8A;
+        const COEFF1_LEN: usize = 23;
+        const COEFF2: u8 = 0xE1;
+        const COEFF2_LEN: usize = 14;
+        let mut c = [0u8; COEFF1_LEN + COEFF2_LEN];
+        self.read_burst(COEFF1, &mut c[..COEFF1_LEN])?;
+        self.read_burst(COEFF2, &mut c[COEFF1_LEN..])?;
+
+        Ok(BME680Compensator {
+            part1: u16::from_le_bytes([c[31], c[32]]),
+            part2: i16::from_le_bytes([c[0], c[1]]),
+            part3: c[2] as i8,
+
+            parp1: u16::from_le_bytes([c[4], c[5]]),
+            parp2: i16::from_le_bytes([c[6], c[7]]),
+            parp3: c[8] as i8,
+            parp4: i16::from_le_bytes([c[10], c[11]]),
+            parp5: i16::from_le_bytes([c[12], c[13]]),
+            parp6: c[15] as i8,
+            parp7: c[14] as i8,
+            parp8: i16::from_le_bytes([c[18], c[19]]),
+            parp9: i16::from_le_bytes([c[20], c[21]]),
+            parp10: c[22],
+
+            parh1: ((c[25] as u16) << 4) | (c[24] & 0x0F) as u16,
+            parh2: ((c[23] as u16) << 4) | (c[24] >> 4) as u16,
+            parh3: c[26] as i8,
+            parh4: c[27] as i8,
+            parh5: c[28] as i8,
+            parh6: c[29],
+            parh7: c[30] as i8,
+        })
+    }
+
+    pub fn read_params(&mut self) -> Result<Measurement, Error> {
+        let variant = self.initialized_variant()?;
+        // Data is read in a single burst, so all values belong to the same
+        // measurement
+        let mut bytes = [0u8; 8];
+        let len = if variant.has_humidity() { 8 } else { 6 };
+        self.read_burst(variant.data() as u8, &mut bytes[..len])?;
+
+        let Some(compensator) = &mut self.state.compensator else {
+            return Err(Error::NotInitialized);
+        };
+
+        let p = ((bytes[0] as i32) << 12) | ((bytes[1] as i32) << 4) | ((bytes[2] as i32) >> 4);
+        let t = ((bytes[3] as i32) << 12) | ((bytes[4] as i32) << 4) | ((bytes[5] as i32) >> 4);
+        let h = ((bytes[6] as i32) << 8) | (bytes[7] as i32);
+
+        let (t, p, h) = match compensator {
+            Compensator::BME280(compensator) => compensator.compensate_tph(t, p, h),
+            Compensator::BME680(compensator) => compensator.compensate_tph(t, p, h),
+        };
+        Ok(Measurement {
+            temperature: Temperature::from_raw(t),
+            pressure: Pressure::from_raw(p),
+            humidity: variant.has_humidity().then(|| Humidity::from_raw(h)),
+        })
+    }
+}
+
+enum Compensator {
+    /// Used for BMP280 as well, humidity is just never compensated
+    BME280(ADCCompensator),
+    BME680(BME680Compensator),
+}
+
+#[derive(Default, Debug)]
+struct ADCCompensator {
+    // Temperature compensation
+    digt1: u16,
+    digt2: i16,
+    digt3: i16,
+    // Pressure compensation
+    digp1: u16,
+    digp2: i16,
+    digp3: i16,
+    digp4: i16,
+    digp5: i16,
+    digp6: i16,
+    digp7: i16,
+    digp8: i16,
+    digp9: i16,
+    // Humidity compensation
+    digh1: u8,
+    digh2: i16,
+    digh3: u8,
+    digh4: i16,
+    digh5: i16,
+    digh6: i8,
+}
+
+impl ADCCompensator {
+    fn compensate_tph(&mut self, t: i32, p: i32, h: i32) -> (i32, u32, u32) {
+        let (t, t_fine) = self.compensate_t(t);
+        let p = self.compensate_p(p, t_fine);
+        let h = self.compensate_h(h, t_fine);
+        (t, p, h)
+    }
+
+    fn compensate_t(&mut self, adc_t: i32) -> (i32, i32) {
+        let var1 = (((adc_t >> 3) - ((self.digt1 as i32) << 1)) * (self.digt2 as i32)) >> 11;
+        let var2 =
+            (((((adc_t >> 4) - (self.digt1 as i32)) * ((adc_t >> 4) - (self.digt1 as i32))) >> 12)
+                * (self.digt3 as i32))
+                >> 14;
+
+        let t_fine = var1 + var2;
+        ((t_fine * 5 + 128) >> 8, t_fine)
+    }
+
+    fn compensate_p(&self, adc_p: i32, t_fine: i32) -> u32 {
+        let a = t_fine as i64 - 128000;
+        let b = a * a * self.digp6 as i64;
+        let b = b + ((a * self.digp5 as i64) << 17);
+        let b = b + ((self.digp4 as i64) << 35);
+        let a = ((a * a * (self.digp3 as i64)) >> 8) + ((a * (self.digp2 as i64)) << 12);
+        let a = (((1 << 47) + a) * (self.digp1 as i64)) >> 33;
+        if a == 0 {
+            return 0;
+        }
+
+        let p = 1048576 - adc_p as i64;
+        let p = (((p << 31) - b) * 3125) / a;
+        let a = ((self.digp9 as i64) * (p >> 13) * (p >> 13)) >> 25;
+        let b = ((self.digp8 as i64) * p) >> 19;
+        let p = ((p + a + b) >> 8) + ((self.digp7 as i64) << 4);
+
+        p as u32
+    }
+
+    fn compensate_h(&self, adc_h: i32, t_fine: i32) -> u32 {
+        let v_x1_u32r = t_fine - 76800;
+        let v_x1_u32r =
+            ((((adc_h << 14) - ((self.digh4 as i32) << 20) - ((self.digh5 as i32) * v_x1_u32r))
+                + 16384)
+                >> 15)
+                * (((((((v_x1_u32r * (self.digh6 as i32)) >> 10)
+                    * (((v_x1_u32r * (self.digh3 as i32)) >> 11) + 32768))
+                    >> 10)
+                    + 2097152)
+                    * (self.digh2 as i32)
+                    + 8192)
+                    >> 14);
+
+        let v_x1_u32r = v_x1_u32r
+            - (((((v_x1_u32r >> 15) * (v_x1_u32r >> 15)) >> 7) * (self.digh1 as i32)) >> 4);
+        let v_x1_u32r = if v_x1_u32r < 0 { 0 } else { v_x1_u32r };
+        let v_x1_u32r = if v_x1_u32r > 419430400 {
+            419430400
+        } else {
+            v_x1_u32r
+        };
+        (v_x1_u32r >> 12) as u32
+    }
+}
+
+#[derive(Default, Debug)]
+struct BME680Compensator {
+    // Temperature compensation
+    part1: u16,
+    part2: i16,
+    part3: i8,
+    // Pressure compensation
+    parp1: u16,
+    parp2: i16,
+    parp3: i8,
+    parp4: i16,
+    parp5: i16,
+    parp6: i8,
+    parp7: i8,
+    parp8: i16,
+    parp9: i16,
+    parp10: u8,
+    // Humidity compensation
+    parh1: u16,
+    parh2: u16,
+    parh3: i8,
+    parh4: i8,
+    parh5: i8,
+    parh6: u8,
+    parh7: i8,
+}
+
+/// Formulas are taken from integer implementation of BME68x Sensor API. They
+/// produce values in the same units as BME280 ones.
+impl BME680Compensator {
+    fn compensate_tph(&self, t: i32, p: i32, h: i32) -> (i32, u32, u32) {
+        let (t, t_fine) = self.compensate_t(t);
+        let p = self.compensate_p(p, t_fine);
+        let h = self.compensate_h(h, t_fine);
+        (t, p, h)
+    }
+
+    /// Returns temperature in 0.01 degrees of celcius
+    fn compensate_t(&self, adc_t: i32) -> (i32, i32) {
+        let var1 = (adc_t >> 3) - ((self.part1 as i32) << 1);
+        let var2 = (var1 * (self.part2 as i32)) >> 11;
+        let var3 = ((var1 >> 1) * (var1 >> 1)) >> 12;
+        let var3 = (var3 * ((self.part3 as i32) << 4)) >> 14;
+
+        let t_fine = var2 + var3;
+        ((t_fine * 5 + 128) >> 8, t_fine)
+    }
+
+    /// Returns pressure in Q24.8 pascals
+    fn compensate_p(&self, adc_p: i32, t_fine: i32) -> u32 {
+        let var1 = (t_fine >> 1) - 64000;
+        let var2 = ((((var1 >> 2) * (var1 >> 2)) >> 11) * (self.parp6 as i32)) >> 2;
+        let var2 = var2 + ((var1 * (self.parp5 as i32)) << 1);
+        let var2 = (var2 >> 2) + ((self.parp4 as i32) << 16);
+        let var1 = (((((var1 >> 2) * (var1 >> 2)) >> 13) * ((self.parp3 as i32) << 5)) >> 3)
+            + (((self.parp2 as i32) * var1) >> 1);
+        let var1 = var1 >> 18;
+        let var1 = ((32768 + var1) * (self.parp1 as i32)) >> 15;
+        if var1 == 0 {
+            return 0;
+        }
+
+        // Reference implementation uses unsigned multiplication here
+        let p = (1048576 - adc_p - (var2 >> 12)) as i64 * 3125;
+        let p = if p >= 1 << 30 {
+            (p / var1 as i64) << 1
+        } else {
+            (p << 1) / var1 as i64
+        } as i32;
+        let var1 = ((self.parp9 as i32) * (((p >> 3) * (p >> 3)) >> 13)) >> 12;
+        let var2 = ((p >> 2) * (self.parp8 as i32)) >> 13;
+        let var3 = (((p >> 8) as i64).pow(3) * (self.parp10 as i64)) >> 17;
+        let var3 = var3 as i32;
+        let p = p + ((var1 + var2 + var3 + ((self.parp7 as i32) << 7)) >> 4);
+
+        (p as u32) << 8
+    }
+
+    /// Returns humidity in Q22.10 percents
+    fn compensate_h(&self, adc_h: i32, t_fine: i32) -> u32 {
+        let temp_scaled = (t_fine * 5 + 128) >> 8;
+        let var1 =
+            (adc_h - (self.parh1 as i32) * 16) - (((temp_scaled * (self.parh3 as i32)) / 100) >> 1);
+        let var2 = ((self.parh2 as i32)
+            * (((temp_scaled * (self.parh4 as i32)) / 100)
+                + (((temp_scaled * ((temp_scaled * (self.parh5 as i32)) / 100)) >> 6) / 100)
+                + (1 << 14)))
+            >> 10;
+        let var3 = var1 * var2;
+        let var4 = (self.parh6 as i32) << 7;
+        let var4 = (var4 + ((temp_scaled * (self.parh7 as i32)) / 100)) >> 4;
+        let var5 = ((var3 >> 14) * (var3 >> 14)) >> 10;
+        let var6 = (var4 * var5) >> 1;
+        // Thousandths of percent
+        let h = (((var3 + var6) >> 10) * 1000) >> 12;
+        let h = h.clamp(0, 100_000);
+
+        (h as u32 * 1024) / 1000
+    }
+}
+
+const SLEEP_MODE: u8 = 0;
+const FORCED_MODE: u8 = 1;
+const NORMAL_MODE: u8 = 3;
+const MODE_MASK: u8 = 0x03;
+
+/// Complete register map, some registers are never accessed by name
+#[allow(dead_code)]
+#[repr(u8)]
+#[derive(Clone, Copy, Debug)]
+enum Register {
+    DigT1LSB = 0x88,
+    DigT1MSB = 0x89,
+    DigT2LSB = 0x8A,
+    DigT2MSB = 0x8B,
+    DigT3LSB = 0x8C,
+    DigT3MSB = 0x8D,
+    DigP1LSB = 0x8E,
+    DigP1MSB = 0x8F,
+    DigP2LSB = 0x90,
+    DigP2MSB = 0x91,
+    DigP3LSB = 0x92,
+    DigP3MSB = 0x93,
+    DigP4LSB = 0x94,
+    DigP4MSB = 0x95,
+    DigP5LSB = 0x96,
+    DigP5MSB = 0x97,
+    DigP6LSB = 0x98,
+    DigP6MSB = 0x99,
+    DigP7LSB = 0x9A,
+    DigP7MSB = 0x9B,
+    DigP8LSB = 0x9C,
+    DigP8MSB = 0x9D,
+    DigP9LSB = 0x9E,
+    DigP9MSB = 0x9F,
+
+    DigH1 = 0xA1,
+    DigH2LSB = 0xE1,
+    DigH2MSB = 0xE2,
+    DigH3 = 0xE3,
+    DigH4MSB = 0xE4,
+    DigH4LSBDigH5MSB = 0xE5,
+    DigH5LSB = 0xE6,
+    DigH6 = 0xE7,
+
+    PressMSB = 0xF7,
+    PressLSB = 0xF8,
+    PressXLSB = 0xF9,
+    TempMSB = 0xFA,
+    TempLSB = 0xFB,
+    TempXLSB = 0xFC,
+    HumMSB = 0xFD,
+    HumLSB = 0xFE,
+
+    CtrlHum = 0xF2,
+    CtrlMeas = 0xF4,
+    Config = 0xF5,
+
+    ChipId = 0xD0,
+
+    BME680PressMSB = 0x1F,
+    BME680CtrlHum = 0x72,
+    BME680CtrlMeas = 0x74,
+    BME680Config = 0x75,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    BusRead,
+    BusWrite,
+    WrongChipId,
+    NotInitialized,
+    MeasurementTimeout,
+    /// Operation is not supported by this variant of sensor
+    Unsupported,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_hal_mock::eh0::i2c::{Mock, Transaction};
+
+    const ADDR: u8 = 0x76;
+
+    /// Raw readings of example in datasheet. Datasheet has no example for
+    /// humidity, so reading is made up.
+    const ADC_T: i32 = 519888;
+    const ADC_P: i32 = 415148;
+    const ADC_H: i32 = 28200;
+
+    /// Calibration of temperature and pressure from example in datasheet,
+    /// calibration of humidity is typical for real sensors
+    fn compensator() -> ADCCompensator {
+        ADCCompensator {
+            digt1: 27504,
+            digt2: 26435,
+            digt3: -1000,
+            digp1: 36477,
+            digp2: -10685,
+            digp3: 3024,
+            digp4: 2855,
+            digp5: 140,
+            digp6: -7,
+            digp7: 15500,
+            digp8: -14600,
+            digp9: 6000,
+            digh1: 75,
+            digh2: 362,
+            digh3: 0,
+            digh4: 313,
+            digh5: 50,
+            digh6: 30,
+        }
+    }
+
+    /// Calibration registers holding the same values as compensator()
+    fn calibration_regs() -> [u8; 256] {
+        let c = compensator();
+        let mut regs = [0u8; 256];
+        let words = [
+            c.digt1,
+            c.digt2 as u16,
+            c.digt3 as u16,
+            c.digp1,
+            c.digp2 as u16,
+            c.digp3 as u16,
+            c.digp4 as u16,
+            c.digp5 as u16,
+            c.digp6 as u16,
+            c.digp7 as u16,
+            c.digp8 as u16,
+            c.digp9 as u16,
+        ];
+        for (i, word) in words.iter().enumerate() {
+            regs[0x88 + 2 * i..][..2].copy_from_slice(&word.to_le_bytes());
+        }
+        regs[0xA1] = c.digh1;
+        regs[0xE1..0xE3].copy_from_slice(&c.digh2.to_le_bytes());
+        regs[0xE3] = c.digh3;
+        // dig_H4 and dig_H5 are 12 bits and share 0xE5
+        regs[0xE4] = (c.digh4 >> 4) as u8;
+        regs[0xE5] = (c.digh4 & 0x0F) as u8 | ((c.digh5 & 0x0F) << 4) as u8;
+        regs[0xE6] = (c.digh5 >> 4) as u8;
+        regs[0xE7] = c.digh6 as u8;
+        regs
+    }
+
+    /// Burst of data registers, starting at press_msb
+    fn data_regs() -> Vec<u8> {
+        let (p, t, h) = (ADC_P as u32, ADC_T as u32, ADC_H as u32);
+        vec![
+            (p >> 12) as u8,
+            (p >> 4) as u8,
+            (p << 4) as u8,
+            (t >> 12) as u8,
+            (t >> 4) as u8,
+            (t << 4) as u8,
+            (h >> 8) as u8,
+            h as u8,
+        ]
+    }
+
+    fn assert_datasheet_values(measurement: &Measurement) {
+        // 25.08°C
+        assert_eq!(measurement.temperature.0, 2508);
+        let pressure = measurement.pressure.as_pas();
+        assert!((pressure - 100653.27).abs() < 0.05, "{pressure}");
+        // Value of floating point formula from datasheet
+        let humidity = measurement.humidity.unwrap().as_percent();
+        assert!((humidity - 44.975).abs() < 0.01, "{humidity}");
+    }
+
+    #[test]
+    fn compensation_matches_datasheet() {
+        let mut compensator = compensator();
+        let (t, t_fine) = compensator.compensate_t(ADC_T);
+        assert_eq!(t_fine, 128422);
+        let measurement = Measurement {
+            temperature: Temperature::from_raw(t),
+            pressure: Pressure::from_raw(compensator.compensate_p(ADC_P, t_fine)),
+            humidity: Some(Humidity::from_raw(compensator.compensate_h(ADC_H, t_fine))),
+        };
+        assert_datasheet_values(&measurement);
+    }
+
+    fn init_expectations(config: Config) -> Vec<Transaction> {
+        let regs = calibration_regs();
+        let mut expectations = vec![
+            Transaction::write_read(ADDR, vec![0xD0], vec![0x60]),
+            Transaction::write(ADDR, vec![0xF2, config.humidity as u8]),
+            Transaction::write(
+                ADDR,
+                vec![
+                    0xF4,
+                    ((config.temperature as u8) << 5) | ((config.pressure as u8) << 2) | SLEEP_MODE,
+                ],
+            ),
+            Transaction::write(
+                ADDR,
+                vec![
+                    0xF5,
+                    ((config.standby as u8) << 5) | ((config.filter as u8) << 2),
+                ],
+            ),
+        ];
+        // Calibration is read register by register
+        let calibration = (0x88..=0x9F).chain([0xA1]).chain(0xE1..=0xE7);
+        expectations.extend(
+            calibration
+                .map(|reg| Transaction::write_read(ADDR, vec![reg], vec![regs[reg as usize]])),
+        );
+        expectations
+    }
+
+    fn with_sensor(expectations: &[Transaction], f: impl FnOnce(&mut BME280<Mock>)) {
+        let mut sensor = BME280::new(Mock::new(expectations), BME280State::new(ADDR));
+        f(&mut sensor);
+        let (mut i2c, _) = sensor.release();
+        i2c.done();
+    }
+
+    #[test]
+    fn init_decodes_calibration() {
+        let config = Config::new();
+        let mut expectations = init_expectations(config);
+        expectations.push(Transaction::write_read(ADDR, vec![0xF7], data_regs()));
+        with_sensor(&expectations, |sensor| {
+            sensor.init(config).unwrap();
+            assert_eq!(sensor.variant(), Some(Variant::BME280));
+            assert_datasheet_values(&sensor.read_params().unwrap());
+        });
+    }
+
+    #[test]
+    fn measure_once_waits_for_conversion() {
+        let config = Config::new();
+        let ctrl_meas = ((config.temperature as u8) << 5) | ((config.pressure as u8) << 2);
+        let mut expectations = init_expectations(config);
+        expectations.extend([
+            Transaction::write(ADDR, vec![0xF4, ctrl_meas | FORCED_MODE]),
+            Transaction::write_read(ADDR, vec![0xF4], vec![ctrl_meas | FORCED_MODE]),
+            Transaction::write_read(ADDR, vec![0xF4], vec![ctrl_meas | SLEEP_MODE]),
+            Transaction::write_read(ADDR, vec![0xF7], data_regs()),
+        ]);
+        with_sensor(&expectations, |sensor| {
+            sensor.init(config).unwrap();
+            assert_datasheet_values(&sensor.measure_once().unwrap());
+        });
+    }
+
+    #[test]
+    fn wrong_chip_id() {
+        let expectations = [Transaction::write_read(ADDR, vec![0xD0], vec![0x00])];
+        with_sensor(&expectations, |sensor| {
+            assert!(matches!(
+                sensor.init(Config::new()),
+                Err(Error::WrongChipId)
+            ));
+            assert_eq!(sensor.variant(), None);
+        });
+    }
+}
@@ -0,0 +1,230 @@
+//! Debounced push buttons
+//!
+//! Debounce is measured in milliseconds of a monotonic clock passed to every
+//! update, so buttons behave the same however often they are polled.
+
+use core::convert::Infallible;
+use embedded_hal::digital::v2::InputPin;
+use unwrap_infallible::UnwrapInfallible;
+
+#[derive(Debug, Clone, Copy)]
+pub enum ButtonEvent {
+    Press,
+    Release,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum ButtonState {
+    Released,
+    Pressed,
+}
+
+pub struct Button<P>
+where
+    P: InputPin,
+{
+    pin: Debounce<P>,
+    state: ButtonState,
+}
+
+impl<P> Button<P>
+where
+    P: InputPin<Error = Infallible>,
+{
+    pub fn new(pin: Debounce<P>) -> Self {
+        Self {
+            pin,
+            state: ButtonState::Released,
+        }
+    }
+
+    pub fn is_pressed(&self) -> bool {
+        self.pin.is_pressed()
+    }
+
+    /// Polls pin at given time in milliseconds, see [`Debounce::update`]
+    pub fn update(&mut self, now_ms: u32) -> Option<ButtonEvent> {
+        self.pin.update(now_ms);
+        match self.state {
+            ButtonState::Released => {
+                if self.pin.is_pressed() {
+                    self.state = ButtonState::Pressed;
+                    return Some(ButtonEvent::Press);
+                }
+            }
+            ButtonState::Pressed => {
+                if !self.pin.is_pressed() {
+                    self.state = ButtonState::Released;
+                    return Some(ButtonEvent::Release);
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// Pin that is pressed while high. Its level is accepted once it has stayed
+/// the same for debounce duration.
+pub struct Debounce<P>
+where
+    P: InputPin,
+{
+    pin: P,
+    debounce_ms: u32,
+    /// Level last read from pin
+    level: bool,
+    /// Time level last changed at
+    changed_ms: u32,
+    output: bool,
+}
+
+impl<P> Debounce<P>
+where
+    P: InputPin<Error = Infallible>,
+{
+    pub fn new(pin: P, debounce_ms: u32) -> Self {
+        Self {
+            pin,
+            debounce_ms,
+            level: false,
+            changed_ms: 0,
+            output: false,
+        }
+    }
+
+    pub fn is_pressed(&self) -> bool {
+        self.output
+    }
+
+    /// Reads pin at given time in milliseconds. Time may wrap around, only
+    /// differences between calls matter.
+    pub fn update(&mut self, now_ms: u32) {
+        let level = self.pin.is_high().unwrap_infallible();
+        if level != self.level {
+            self.level = level;
+            self.changed_ms = now_ms;
+        } else if now_ms.wrapping_sub(self.changed_ms) >= self.debounce_ms {
+            self.output = level;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::cell::Cell;
+    use std::rc::Rc;
+
+    /// Pin driven by test. Pins of embedded-hal-mock can fail, while buttons
+    /// only accept infallible pins.
+    #[derive(Clone, Default)]
+    struct TestPin(Rc<Cell<bool>>);
+
+    impl TestPin {
+        fn set(&self, is_high: bool) {
+            self.0.set(is_high);
+        }
+    }
+
+    impl InputPin for TestPin {
+        type Error = Infallible;
+
+        fn is_high(&self) -> Result<bool, Infallible> {
+            Ok(self.0.get())
+        }
+
+        fn is_low(&self) -> Result<bool, Infallible> {
+            Ok(!self.0.get())
+        }
+    }
+
+    const DEBOUNCE_MS: u32 = 20;
+
+    #[test]
+    fn debounce_needs_stable_level() {
+        let pin = TestPin::default();
+        let mut debounce = Debounce::new(pin.clone(), DEBOUNCE_MS);
+
+        pin.set(true);
+        debounce.update(1000);
+        debounce.update(1000 + DEBOUNCE_MS - 1);
+        assert!(!debounce.is_pressed());
+        debounce.update(1000 + DEBOUNCE_MS);
+        assert!(debounce.is_pressed());
+
+        // Short glitch does not release button
+        pin.set(false);
+        debounce.update(1030);
+        pin.set(true);
+        debounce.update(1035);
+        debounce.update(1040);
+        assert!(debounce.is_pressed());
+
+        pin.set(false);
+        debounce.update(1050);
+        debounce.update(1050 + DEBOUNCE_MS - 1);
+        assert!(debounce.is_pressed());
+        debounce.update(1050 + DEBOUNCE_MS);
+        assert!(!debounce.is_pressed());
+    }
+
+    #[test]
+    fn debounce_ignores_bouncing() {
+        let pin = TestPin::default();
+        let mut debounce = Debounce::new(pin.clone(), DEBOUNCE_MS);
+        for i in 0..10 {
+            pin.set(i % 2 == 0);
+            debounce.update(i * DEBOUNCE_MS);
+            assert!(!debounce.is_pressed());
+        }
+    }
+
+    #[test]
+    fn debounce_does_not_depend_on_poll_rate() {
+        // Polled every millisecond or once in a while, button is pressed
+        // after the same time
+        for period_ms in [1, 5, 10] {
+            let pin = TestPin::default();
+            let mut debounce = Debounce::new(pin.clone(), DEBOUNCE_MS);
+            pin.set(true);
+            for now_ms in (0..DEBOUNCE_MS).step_by(period_ms) {
+                debounce.update(now_ms);
+                assert!(!debounce.is_pressed());
+            }
+            debounce.update(DEBOUNCE_MS);
+            assert!(debounce.is_pressed());
+        }
+    }
+
+    #[test]
+    fn debounce_survives_wrap_around() {
+        let pin = TestPin::default();
+        let mut debounce = Debounce::new(pin.clone(), DEBOUNCE_MS);
+        pin.set(true);
+        debounce.update(u32::MAX - 9);
+        debounce.update(DEBOUNCE_MS - 11);
+        assert!(!debounce.is_pressed());
+        debounce.update(DEBOUNCE_MS - 10);
+        assert!(debounce.is_pressed());
+    }
+
+    #[test]
+    fn button_reports_edges_once() {
+        let pin = TestPin::default();
+        let mut button = Button::new(Debounce::new(pin.clone(), DEBOUNCE_MS));
+
+        let mut events = Vec::new();
+        let levels = [true; 5].into_iter().chain([false; 5]);
+        for (i, is_high) in levels.enumerate() {
+            pin.set(is_high);
+            events.extend(button.update(i as u32 * DEBOUNCE_MS));
+        }
+
+        assert!(matches!(
+            events.as_slice(),
+            [ButtonEvent::Press, ButtonEvent::Release]
+        ));
+        assert!(!button.is_pressed());
+    }
+}
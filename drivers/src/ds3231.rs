@@ -0,0 +1,928 @@
+//! DS3231 RTC
+//! DS3231 Uses SMBUS which is a modified version of I2C. The only difference,
+//! however, is only payload-connected: each transmission has to include the
+//! command number. Otherwise it uses plain I2C.
+
+use embedded_hal::blocking::i2c::{Write, WriteRead};
+
+/// Temperature as acquired from rtc. It consists of 2 parts - 8 bits of degree
+/// celcius and 2 bits of quartes of a degree. Because we have no FPU delay the
+/// construction of float until usage/presentation of temperature and store it
+/// as integer.
+pub struct Temperature(u16);
+
+impl Temperature {
+    pub fn as_celcius(&self) -> f32 {
+        (self.0 >> 2) as f32 + (self.0 & 0x3) as f32 * 0.25
+    }
+
+    pub fn as_fahrenheit(&self) -> f32 {
+        self.as_celcius() * 9. / 5. + 32.
+    }
+}
+
+/// Day of week
+#[derive(Debug, Clone, Copy)]
+pub enum Day {
+    Sunday = 1,
+    Monday = 2,
+    Tuesday = 3,
+    Wednesday = 4,
+    Thursday = 5,
+    Friday = 6,
+    Saturday = 7,
+}
+
+impl TryFrom<u8> for Day {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        use Day::*;
+        match value {
+            1 => Ok(Sunday),
+            2 => Ok(Monday),
+            3 => Ok(Tuesday),
+            4 => Ok(Wednesday),
+            5 => Ok(Thursday),
+            6 => Ok(Friday),
+            7 => Ok(Saturday),
+            _ => Err(Error::DaysRange),
+        }
+    }
+}
+
+impl Day {
+    /// Day of week of given date in Gregorian calendar (Sakamoto's method)
+    pub fn from_date(date: Date) -> Self {
+        const MONTH_OFFSETS: [u16; 12] = [0, 3, 2, 5, 0, 3, 5, 1, 4, 6, 2, 4];
+        let year = if date.month < 3 {
+            date.year - 1
+        } else {
+            date.year
+        };
+        let month_offset = MONTH_OFFSETS[(date.month as usize).clamp(1, 12) - 1];
+        let days = year + year / 4 - year / 100 + year / 400 + month_offset + date.date as u16;
+        // 0 is Sunday
+        match days % 7 {
+            0 => Day::Sunday,
+            1 => Day::Monday,
+            2 => Day::Tuesday,
+            3 => Day::Wednesday,
+            4 => Day::Thursday,
+            5 => Day::Friday,
+            _ => Day::Saturday,
+        }
+    }
+}
+
+/// Number of days in month of given year, 0 if month is invalid
+pub fn days_in_month(year: u16, month: u8) -> u8 {
+    let is_leap_year =
+        year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400));
+    match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if is_leap_year => 29,
+        2 => 28,
+        _ => 0,
+    }
+}
+
+/// Moves date and time by given number of minutes, e.g. from UTC to local
+/// time. Offset must be shorter than a day.
+pub fn offset_date_time(date: Date, time: Time, offset_mins: i16) -> (Date, Time) {
+    const MINS_PER_DAY: i16 = 24 * 60;
+    let mins = time.hours as i16 * 60 + time.mins as i16 + offset_mins;
+    let mins_of_day = mins.rem_euclid(MINS_PER_DAY);
+    let time = Time {
+        hours: (mins_of_day / 60) as u8,
+        mins: (mins_of_day % 60) as u8,
+        secs: time.secs,
+    };
+
+    let date = if mins >= MINS_PER_DAY {
+        add_days(date, 1)
+    } else if mins < 0 {
+        add_days(date, -1)
+    } else {
+        date
+    };
+
+    (date, time)
+}
+
+/// Moves date by given number of days, carrying into months and years
+pub fn add_days(date: Date, days: i16) -> Date {
+    let mut date = date;
+    for _ in 0..days.unsigned_abs() {
+        if days > 0 {
+            date.date += 1;
+            if date.date > days_in_month(date.year, date.month) {
+                date.date = 1;
+                date = add_months(date, 1);
+            }
+        } else {
+            date.date -= 1;
+            if date.date == 0 {
+                date = add_months(date, -1);
+                date.date = days_in_month(date.year, date.month);
+            }
+        }
+    }
+
+    date
+}
+
+/// Moves date by given number of months, carrying into years. Day is cut to
+/// length of new month.
+pub fn add_months(date: Date, months: i16) -> Date {
+    let months = date.year as i32 * 12 + date.month as i32 - 1 + months as i32;
+    let year = months.div_euclid(12) as u16;
+    let month = months.rem_euclid(12) as u8 + 1;
+    Date {
+        year,
+        month,
+        date: date.date.min(days_in_month(year, month)),
+    }
+}
+
+impl From<Day> for u8 {
+    fn from(value: Day) -> Self {
+        value as u8
+    }
+}
+
+/// Frequency of square wave on INT/SQW pin
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SquareWave {
+    Hz1 = 0b00,
+    Hz1024 = 0b01,
+    Hz4096 = 0b10,
+    Hz8192 = 0b11,
+}
+
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Default, Clone, Copy)]
+pub struct Date {
+    pub year: u16,
+    pub month: u8,
+    pub date: u8,
+}
+
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Default, Clone, Copy)]
+pub struct Time {
+    pub hours: u8,
+    pub mins: u8,
+    pub secs: u8,
+}
+
+pub struct DS3231State {
+    addr: u8,
+}
+
+impl DS3231State {
+    pub fn new(addr: u8) -> Self {
+        Self { addr }
+    }
+}
+
+/// DS3231 Driver
+pub struct DS3231<I2C> {
+    i2c: I2C,
+    state: DS3231State,
+}
+
+impl<I2C> DS3231<I2C> {
+    pub fn new(i2c: I2C, state: DS3231State) -> Self {
+        Self { i2c, state }
+    }
+
+    pub fn release(self) -> (I2C, DS3231State) {
+        (self.i2c, self.state)
+    }
+}
+
+impl<I2C> DS3231<I2C>
+where
+    I2C: Write + WriteRead,
+{
+    pub fn init(&mut self) -> Result<(), Error> {
+        // Enable tracking of temperature
+        let status = self.read_reg(Register::Control)? | TEMP_BIT;
+        self.write_reg(Register::Control, status)
+    }
+
+    fn read_reg(&mut self, reg: Register) -> Result<u8, Error> {
+        let src = [reg as u8];
+        let mut dst = [0u8];
+        self.i2c
+            .write_read(self.state.addr, &src, &mut dst)
+            .map_err(|_| Error::BusRead)?;
+
+        Ok(dst[0])
+    }
+
+    /// Reads consecutive registers starting from reg in single transaction
+    fn read_regs(&mut self, reg: Register, dst: &mut [u8]) -> Result<(), Error> {
+        let src = [reg as u8];
+        self.i2c
+            .write_read(self.state.addr, &src, dst)
+            .map_err(|_| Error::BusRead)
+    }
+
+    fn write_reg(&mut self, reg: Register, value: u8) -> Result<(), Error> {
+        let buf = [reg as u8, value];
+        self.i2c
+            .write(self.state.addr, &buf)
+            .map_err(|_| Error::BusWrite)
+    }
+
+    /// Replaces bits selected by mask with ones from value
+    fn update_reg(&mut self, reg: Register, mask: u8, value: u8) -> Result<(), Error> {
+        let old = self.read_reg(reg)?;
+        self.write_reg(reg, (old & !mask) | (value & mask))
+    }
+
+    /// Outputs square wave of given frequency on INT/SQW pin. Alarm interrupts
+    /// are not signaled on the pin while square wave is enabled.
+    pub fn enable_square_wave(&mut self, freq: SquareWave) -> Result<(), Error> {
+        let rs = (freq as u8) << RS_SHIFT;
+        self.update_reg(Register::Control, INTCN_BIT | RS_MASK, rs)
+    }
+
+    /// Switches INT/SQW pin back to signaling alarm interrupts
+    pub fn disable_square_wave(&mut self) -> Result<(), Error> {
+        self.update_reg(Register::Control, INTCN_BIT, INTCN_BIT)
+    }
+
+    /// Should square wave be output when running from battery? By default it
+    /// is only output while Vcc is present.
+    pub fn set_battery_backed_square_wave(&mut self, enabled: bool) -> Result<(), Error> {
+        let value = if enabled { BBSQW_BIT } else { 0 };
+        self.update_reg(Register::Control, BBSQW_BIT, value)
+    }
+
+    /// Has oscillator stopped at some point since flag was last cleared? This
+    /// happens on first power up and when battery has run out, in which case
+    /// timekeeping data is invalid.
+    pub fn has_oscillator_stopped(&mut self) -> Result<bool, Error> {
+        Ok(self.read_reg(Register::Status)? & OSF_BIT != 0)
+    }
+
+    pub fn clear_oscillator_stop_flag(&mut self) -> Result<(), Error> {
+        self.update_reg(Register::Status, OSF_BIT, 0)
+    }
+
+    /// Enables or disables 32kHz output on 32KHZ pin
+    pub fn set_32khz_output(&mut self, enabled: bool) -> Result<(), Error> {
+        let value = if enabled { EN32KHZ_BIT } else { 0 };
+        self.update_reg(Register::Status, EN32KHZ_BIT, value)
+    }
+
+    pub fn get_secs(&mut self) -> Result<u8, Error> {
+        let secs = self.read_reg(Register::Seconds)?;
+        Ok(secs.bcd_to_dec())
+    }
+
+    pub fn set_secs(&mut self, secs: u8) -> Result<(), Error> {
+        if (0..=59).contains(&secs) {
+            self.write_reg(Register::Seconds, secs.dec_to_bsd())
+        } else {
+            Err(Error::SecondsRange)
+        }
+    }
+
+    pub fn get_mins(&mut self) -> Result<u8, Error> {
+        let mins = self.read_reg(Register::Minutes)?;
+        Ok(mins.bcd_to_dec())
+    }
+
+    pub fn set_mins(&mut self, mins: u8) -> Result<(), Error> {
+        if (0..=59).contains(&mins) {
+            self.write_reg(Register::Minutes, mins.dec_to_bsd())
+        } else {
+            Err(Error::MinutesRange)
+        }
+    }
+
+    #[allow(dead_code)]
+    fn set_hour_info(&mut self, info: HourInfo) -> Result<(), Error> {
+        let hour = self.read_reg(Register::Hours)? & !H12_BIT & !PM_BIT;
+
+        let hour = match info {
+            HourInfo::H12PM => hour | H12_BIT | PM_BIT,
+            HourInfo::H12AM => hour | H12_BIT,
+            HourInfo::H24 => hour,
+        };
+
+        self.write_reg(Register::Hours, hour)
+    }
+
+    pub fn get_hours(&mut self) -> Result<u8, Error> {
+        self.read_reg(Register::Hours).map(decode_hours)
+    }
+
+    pub fn set_hours(&mut self, hours: u8) -> Result<(), Error> {
+        if !(0..=23).contains(&hours) {
+            return Err(Error::HoursRange);
+        }
+
+        let mode = extract_hour_info(self.read_reg(Register::Hours)?);
+        let hours = match mode {
+            HourInfo::H12PM | HourInfo::H12AM => {
+                let pm = if hours >= 12 { PM_BIT } else { 0 };
+                // Midnight and noon are 12
+                let hours = if hours.is_multiple_of(12) {
+                    12
+                } else {
+                    hours % 12
+                };
+                H12_BIT | pm | hours.dec_to_bsd()
+            }
+            HourInfo::H24 => hours.dec_to_bsd(),
+        };
+
+        self.write_reg(Register::Hours, hours)
+    }
+
+    pub fn get_days(&mut self) -> Result<Day, Error> {
+        let days = self.read_reg(Register::Days)?;
+        days.try_into()
+    }
+
+    pub fn set_days(&mut self, days: Day) -> Result<(), Error> {
+        self.write_reg(Register::Days, days.into())
+    }
+
+    pub fn get_date(&mut self) -> Result<u8, Error> {
+        self.read_reg(Register::Date).map(|d| d.bcd_to_dec())
+    }
+
+    /// Sets day of month. Day of week is updated to match.
+    pub fn set_date(&mut self, date: u8) -> Result<(), Error> {
+        if (0..=31).contains(&date) {
+            self.write_reg(Register::Date, date.dec_to_bsd())?;
+            self.update_days()
+        } else {
+            Err(Error::DateRange)
+        }
+    }
+
+    pub fn get_month(&mut self) -> Result<u8, Error> {
+        self.read_reg(Register::Month)
+            .map(|m| (m & MONTH_MASK).bcd_to_dec())
+    }
+
+    /// Sets month. Day of week is updated to match.
+    pub fn set_month(&mut self, month: u8) -> Result<(), Error> {
+        let century_bit = self.read_reg(Register::Month)? & CENTURY_BIT;
+        if (1..=12).contains(&month) {
+            self.write_reg(Register::Month, month.dec_to_bsd() | century_bit)?;
+            self.update_days()
+        } else {
+            Err(Error::MonthRange)
+        }
+    }
+
+    pub fn get_year(&mut self) -> Result<u16, Error> {
+        let month = self.read_reg(Register::Month)?;
+        self.read_reg(Register::Year)
+            .map(|year| decode_year(month, year))
+    }
+
+    /// Sets year. Day of week is updated to match.
+    pub fn set_year(&mut self, year: u16) -> Result<(), Error> {
+        if (1900..=2099).contains(&year) {
+            let year = (year - YEAR_OFFSET) as u8;
+            let month_reg = self.read_reg(Register::Month)? & !CENTURY_BIT
+                | if year >= 100 { CENTURY_BIT } else { 0 };
+            self.write_reg(Register::Month, month_reg)?;
+            let year = year % 100;
+
+            self.write_reg(Register::Year, year.dec_to_bsd())?;
+            self.update_days()
+        } else {
+            Err(Error::YearRange)
+        }
+    }
+
+    /// Sets day of week from date kept by rtc, so it stays consistent after
+    /// part of date is changed
+    fn update_days(&mut self) -> Result<(), Error> {
+        let date = self.get_calendar()?;
+        self.set_days(Day::from_date(date))
+    }
+
+    pub fn get_temperature(&mut self) -> Result<Temperature, Error> {
+        let high = self.read_reg(Register::TemperatureMSB)? as u16;
+        let low = self.read_reg(Register::TemperatureLSB)? as u16;
+        Ok(Temperature(high << 2 | (low >> 6)))
+    }
+
+    pub fn get_calendar(&mut self) -> Result<Date, Error> {
+        self.get_date_time().map(|(date, _)| date)
+    }
+
+    pub fn get_time(&mut self) -> Result<Time, Error> {
+        self.get_date_time().map(|(_, time)| time)
+    }
+
+    /// Sets all timekeeping registers in single burst, including day of week
+    /// computed from date. Writing seconds restarts current second, so time
+    /// set is exact. Rtc is switched to 24 hour mode.
+    pub fn set_date_time(&mut self, date: Date, time: Time) -> Result<(), Error> {
+        check_time(time)?;
+        if !(1900..=2099).contains(&date.year) {
+            return Err(Error::YearRange);
+        }
+        if !(1..=12).contains(&date.month) {
+            return Err(Error::MonthRange);
+        }
+        if !(1..=days_in_month(date.year, date.month)).contains(&date.date) {
+            return Err(Error::DateRange);
+        }
+
+        let year = date.year - YEAR_OFFSET;
+        let century_bit = if year >= 100 { CENTURY_BIT } else { 0 };
+        let buf = [
+            Register::Seconds as u8,
+            time.secs.dec_to_bsd(),
+            time.mins.dec_to_bsd(),
+            time.hours.dec_to_bsd(),
+            Day::from_date(date).into(),
+            date.date.dec_to_bsd(),
+            date.month.dec_to_bsd() | century_bit,
+            ((year % 100) as u8).dec_to_bsd(),
+        ];
+        self.i2c
+            .write(self.state.addr, &buf)
+            .map_err(|_| Error::BusWrite)
+    }
+
+    /// Sets alarm 1 to go off every day at given time. Alarm is only signaled
+    /// on INT/SQW pin if its interrupt is enabled and square wave is disabled.
+    pub fn set_daily_alarm1(&mut self, time: Time) -> Result<(), Error> {
+        check_time(time)?;
+
+        // Masking day/date makes alarm match hours, minutes and seconds only
+        let buf = [
+            Register::Alarm1Seconds as u8,
+            time.secs.dec_to_bsd(),
+            time.mins.dec_to_bsd(),
+            time.hours.dec_to_bsd(),
+            ALARM_MASK_BIT,
+        ];
+        self.i2c
+            .write(self.state.addr, &buf)
+            .map_err(|_| Error::BusWrite)
+    }
+
+    /// Should INT/SQW pin be pulled low while alarm 1 flag is set?
+    pub fn set_alarm1_interrupt(&mut self, enabled: bool) -> Result<(), Error> {
+        let value = if enabled { A1IE_BIT } else { 0 };
+        self.update_reg(Register::Control, A1IE_BIT, value)
+    }
+
+    /// Returns configuration to defaults firmware expects: hours kept in 24
+    /// hour mode, alarm interrupt disabled and its flag cleared. Time itself
+    /// is kept, hours of 12 hour mode are converted.
+    pub fn reset_config(&mut self) -> Result<(), Error> {
+        let hours = self.get_hours()?;
+        self.write_reg(Register::Hours, hours.dec_to_bsd())?;
+        self.set_alarm1_interrupt(false)?;
+        self.clear_alarm1_flag()
+    }
+
+    /// Has alarm 1 gone off since flag was last cleared?
+    pub fn has_alarm1_fired(&mut self) -> Result<bool, Error> {
+        Ok(self.read_reg(Register::Status)? & A1F_BIT != 0)
+    }
+
+    /// Clearing flag releases INT/SQW pin
+    pub fn clear_alarm1_flag(&mut self) -> Result<(), Error> {
+        self.update_reg(Register::Status, A1F_BIT, 0)
+    }
+
+    /// Reads all timekeeping registers in single burst, so they can't roll
+    /// over in the middle of reading (e.g. reading 10:59 and then :00).
+    pub fn get_date_time(&mut self) -> Result<(Date, Time), Error> {
+        let mut regs = [0u8; 7];
+        self.read_regs(Register::Seconds, &mut regs)?;
+        let [secs, mins, hours, _days, date, month, year] = regs;
+
+        let time = Time {
+            hours: decode_hours(hours),
+            mins: mins.bcd_to_dec(),
+            secs: secs.bcd_to_dec(),
+        };
+        let date = Date {
+            year: decode_year(month, year),
+            month: (month & MONTH_MASK).bcd_to_dec(),
+            date: date.bcd_to_dec(),
+        };
+        Ok((date, time))
+    }
+}
+
+trait Bcd2Dec<T> {
+    fn bcd_to_dec(self) -> T;
+    fn dec_to_bsd(self) -> T;
+}
+
+impl Bcd2Dec<u8> for u8 {
+    fn bcd_to_dec(self) -> u8 {
+        (((self & 0xF0) >> 4) * 10) + (self & 0x0F)
+    }
+
+    fn dec_to_bsd(self) -> u8 {
+        ((self / 10) << 4) | (self % 10)
+    }
+}
+
+const H12_BIT: u8 = 0x40; // bit 6
+const PM_BIT: u8 = 0x20; // bit 5
+const H12_MASK: u8 = 0x1F; // bits 4-0 in 12 hours mode is BCD
+const H24_MASK: u8 = 0x3F; // bits 5-0 in 24 hours mode is BCD
+const CENTURY_BIT: u8 = 0x80; // bit 7
+const MONTH_MASK: u8 = 0x1F; // bits 4-0 are BCD
+const YEAR_OFFSET: u16 = 1900;
+const TEMP_BIT: u8 = 0x20;
+const BBSQW_BIT: u8 = 0x40; // bit 6 of control
+const RS_MASK: u8 = 0x18; // bits 4-3 of control
+const RS_SHIFT: u8 = 3;
+const INTCN_BIT: u8 = 0x04; // bit 2 of control
+const OSF_BIT: u8 = 0x80; // bit 7 of status
+const EN32KHZ_BIT: u8 = 0x08; // bit 3 of status
+const A1IE_BIT: u8 = 0x01; // bit 0 of control
+const A1F_BIT: u8 = 0x01; // bit 0 of status
+const ALARM_MASK_BIT: u8 = 0x80; // bit 7 of alarm registers
+
+fn check_time(time: Time) -> Result<(), Error> {
+    if time.secs > 59 {
+        return Err(Error::SecondsRange);
+    }
+    if time.mins > 59 {
+        return Err(Error::MinutesRange);
+    }
+    if time.hours > 23 {
+        return Err(Error::HoursRange);
+    }
+
+    Ok(())
+}
+
+fn extract_hour_info(hours: u8) -> HourInfo {
+    if hours & H12_BIT != 0 {
+        if hours & PM_BIT != 0 {
+            HourInfo::H12PM
+        } else {
+            HourInfo::H12AM
+        }
+    } else {
+        HourInfo::H24
+    }
+}
+
+fn decode_hours(hours: u8) -> u8 {
+    // Midnight and noon are 12 in 12 hours mode
+    let hours_12 = (hours & H12_MASK).bcd_to_dec() % 12;
+    match extract_hour_info(hours) {
+        HourInfo::H12PM => 12 + hours_12,
+        HourInfo::H12AM => hours_12,
+        HourInfo::H24 => (hours & H24_MASK).bcd_to_dec(),
+    }
+}
+
+/// Century is stored in month register
+fn decode_year(month: u8, year: u8) -> u16 {
+    let century = if month & CENTURY_BIT != 0 { 100 } else { 0 };
+    year.bcd_to_dec() as u16 + century + YEAR_OFFSET
+}
+
+enum HourInfo {
+    H24,
+    H12AM,
+    H12PM,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Error {
+    BusRead,
+    BusWrite,
+
+    SecondsRange,
+    MinutesRange,
+    HoursRange,
+    DaysRange,
+    DateRange,
+    MonthRange,
+    YearRange,
+}
+
+#[derive(Clone, Copy)]
+enum Register {
+    Seconds = 0x00,
+    Minutes = 0x01,
+    Hours = 0x02,
+    Days = 0x03,
+    Date = 0x04,
+    Month = 0x05,
+    Year = 0x06,
+
+    Alarm1Seconds = 0x07,
+
+    Control = 0x0E,
+    Status = 0x0F,
+
+    TemperatureMSB = 0x11,
+    TemperatureLSB = 0x12,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_hal_mock::eh0::i2c::{Mock, Transaction};
+
+    const ADDR: u8 = 0x68;
+
+    fn with_rtc(expectations: &[Transaction], f: impl FnOnce(&mut DS3231<Mock>)) {
+        let mut rtc = DS3231::new(Mock::new(expectations), DS3231State::new(ADDR));
+        f(&mut rtc);
+        let (mut i2c, _) = rtc.release();
+        i2c.done();
+    }
+
+    fn read(reg: Register, value: u8) -> Transaction {
+        Transaction::write_read(ADDR, vec![reg as u8], vec![value])
+    }
+
+    fn write(reg: Register, value: u8) -> Transaction {
+        Transaction::write(ADDR, vec![reg as u8, value])
+    }
+
+    #[test]
+    fn bcd_round_trip() {
+        for value in 0..=99u8 {
+            let bcd = value.dec_to_bsd();
+            assert_eq!(bcd >> 4, value / 10);
+            assert_eq!(bcd & 0x0F, value % 10);
+            assert_eq!(bcd.bcd_to_dec(), value);
+        }
+    }
+
+    #[test]
+    fn get_hours_24() {
+        with_rtc(&[read(Register::Hours, 0x23)], |rtc| {
+            assert_eq!(rtc.get_hours().unwrap(), 23);
+        });
+    }
+
+    #[test]
+    fn get_hours_12() {
+        let cases = [
+            (H12_BIT | 0x12, 0),
+            (H12_BIT | 0x01, 1),
+            (H12_BIT | 0x11, 11),
+            (H12_BIT | PM_BIT | 0x12, 12),
+            (H12_BIT | PM_BIT | 0x01, 13),
+            (H12_BIT | PM_BIT | 0x11, 23),
+        ];
+        for (reg, hours) in cases {
+            with_rtc(&[read(Register::Hours, reg)], |rtc| {
+                assert_eq!(rtc.get_hours().unwrap(), hours, "{reg:#04x}");
+            });
+        }
+    }
+
+    #[test]
+    fn set_hours_keeps_mode() {
+        let expectations = [
+            read(Register::Hours, 0x05),
+            write(Register::Hours, 0x17),
+            read(Register::Hours, H12_BIT | 0x05),
+            write(Register::Hours, H12_BIT | PM_BIT | 0x05),
+            read(Register::Hours, H12_BIT | 0x05),
+            write(Register::Hours, H12_BIT | 0x12),
+        ];
+        with_rtc(&expectations, |rtc| {
+            rtc.set_hours(17).unwrap();
+            rtc.set_hours(17).unwrap();
+            rtc.set_hours(0).unwrap();
+        });
+    }
+
+    #[test]
+    fn out_of_range_is_not_written() {
+        with_rtc(&[], |rtc| {
+            assert!(matches!(rtc.set_secs(60), Err(Error::SecondsRange)));
+            assert!(matches!(rtc.set_mins(60), Err(Error::MinutesRange)));
+            assert!(matches!(rtc.set_hours(24), Err(Error::HoursRange)));
+            assert!(matches!(rtc.set_date(32), Err(Error::DateRange)));
+            assert!(matches!(rtc.set_year(2100), Err(Error::YearRange)));
+
+            let time = Time::default();
+            let feb_29 = Date {
+                year: 2023,
+                month: 2,
+                date: 29,
+            };
+            assert!(matches!(
+                rtc.set_date_time(feb_29, time),
+                Err(Error::DateRange)
+            ));
+            let month_13 = Date {
+                year: 2023,
+                month: 13,
+                date: 1,
+            };
+            assert!(matches!(
+                rtc.set_date_time(month_13, time),
+                Err(Error::MonthRange)
+            ));
+        });
+    }
+
+    #[test]
+    fn set_date_time_writes_burst() {
+        let date = Date {
+            year: 2023,
+            month: 10,
+            date: 28,
+        };
+        let time = Time {
+            hours: 21,
+            mins: 5,
+            secs: 9,
+        };
+        let burst = vec![
+            0x00,
+            0x09,
+            0x05,
+            0x21,
+            Day::Saturday as u8,
+            0x28,
+            0x90,
+            0x23,
+        ];
+        with_rtc(&[Transaction::write(ADDR, burst)], |rtc| {
+            rtc.set_date_time(date, time).unwrap();
+        });
+    }
+
+    #[test]
+    fn set_month_updates_day_of_week() {
+        let regs = vec![0x00, 0x00, 0x00, Day::Monday as u8, 0x28, 0x90, 0x23];
+        let expectations = [
+            read(Register::Month, 0x89),
+            write(Register::Month, 0x90),
+            Transaction::write_read(ADDR, vec![0x00], regs),
+            write(Register::Days, Day::Saturday as u8),
+        ];
+        with_rtc(&expectations, |rtc| rtc.set_month(10).unwrap());
+    }
+
+    #[test]
+    fn get_date_time_reads_burst() {
+        let regs = vec![0x56, 0x34, 0x12, Day::Friday as u8, 0x31, 0x12, 0x99];
+        let expectations = [Transaction::write_read(ADDR, vec![0x00], regs)];
+        with_rtc(&expectations, |rtc| {
+            let (date, time) = rtc.get_date_time().unwrap();
+            let expected_date = Date {
+                year: 1999,
+                month: 12,
+                date: 31,
+            };
+            let expected_time = Time {
+                hours: 12,
+                mins: 34,
+                secs: 56,
+            };
+            assert_eq!(date, expected_date);
+            assert_eq!(time, expected_time);
+        });
+    }
+
+    #[test]
+    fn daily_alarm1_masks_date() {
+        let time = Time {
+            hours: 7,
+            mins: 30,
+            secs: 0,
+        };
+        let burst = vec![0x07, 0x00, 0x30, 0x07, 0x80];
+        with_rtc(&[Transaction::write(ADDR, burst)], |rtc| {
+            rtc.set_daily_alarm1(time).unwrap();
+        });
+    }
+
+    #[test]
+    fn clearing_alarm1_flag_keeps_other_flags() {
+        let expectations = [read(Register::Status, 0x89), write(Register::Status, 0x88)];
+        with_rtc(&expectations, |rtc| rtc.clear_alarm1_flag().unwrap());
+    }
+
+    #[test]
+    fn reset_config_switches_to_24_hours() {
+        let expectations = [
+            read(Register::Hours, H12_BIT | PM_BIT | 0x01),
+            write(Register::Hours, 0x13),
+            read(Register::Control, 0x1D),
+            write(Register::Control, 0x1C),
+            read(Register::Status, 0x89),
+            write(Register::Status, 0x88),
+        ];
+        with_rtc(&expectations, |rtc| rtc.reset_config().unwrap());
+    }
+
+    #[test]
+    fn offset_crosses_day_boundaries() {
+        let date = |year, month, date| Date { year, month, date };
+        let time = |hours, mins| Time {
+            hours,
+            mins,
+            secs: 30,
+        };
+        let cases = [
+            (
+                date(2023, 6, 15),
+                time(12, 0),
+                120,
+                date(2023, 6, 15),
+                time(14, 0),
+            ),
+            (
+                date(2023, 6, 15),
+                time(23, 30),
+                45,
+                date(2023, 6, 16),
+                time(0, 15),
+            ),
+            (
+                date(2023, 6, 30),
+                time(22, 0),
+                180,
+                date(2023, 7, 1),
+                time(1, 0),
+            ),
+            (
+                date(2023, 12, 31),
+                time(20, 0),
+                300,
+                date(2024, 1, 1),
+                time(1, 0),
+            ),
+            (
+                date(2023, 6, 15),
+                time(1, 0),
+                -90,
+                date(2023, 6, 14),
+                time(23, 30),
+            ),
+            (
+                date(2024, 3, 1),
+                time(2, 0),
+                -300,
+                date(2024, 2, 29),
+                time(21, 0),
+            ),
+            (
+                date(2024, 1, 1),
+                time(0, 0),
+                -60,
+                date(2023, 12, 31),
+                time(23, 0),
+            ),
+        ];
+        for (date, time, offset, expected_date, expected_time) in cases {
+            assert_eq!(
+                offset_date_time(date, time, offset),
+                (expected_date, expected_time),
+                "{date:?} {time:?} {offset}"
+            );
+        }
+    }
+
+    #[test]
+    fn date_arithmetic_carries() {
+        let date = |year, month, date| Date { year, month, date };
+        assert_eq!(add_days(date(2023, 12, 31), 1), date(2024, 1, 1));
+        assert_eq!(add_days(date(2024, 3, 1), -1), date(2024, 2, 29));
+        assert_eq!(add_days(date(2023, 6, 25), 10), date(2023, 7, 5));
+        assert_eq!(add_months(date(2023, 1, 31), 1), date(2023, 2, 28));
+        assert_eq!(add_months(date(2023, 11, 15), 3), date(2024, 2, 15));
+        assert_eq!(add_months(date(2023, 2, 15), -12), date(2022, 2, 15));
+    }
+
+    #[test]
+    fn day_of_week() {
+        let cases = [
+            (2000, 1, 1, Day::Saturday),
+            (2000, 2, 29, Day::Tuesday),
+            (2023, 4, 1, Day::Saturday),
+            (2099, 12, 31, Day::Thursday),
+        ];
+        for (year, month, date, day) in cases {
+            let date = Date { year, month, date };
+            assert_eq!(Day::from_date(date) as u8, day as u8, "{date:?}");
+        }
+    }
+}
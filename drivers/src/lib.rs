@@ -0,0 +1,30 @@
+//! Drivers for chips found on LCD-Clock-A and protocols of its optional
+//! modules. They only depend on embedded-hal traits, so they can be used on
+//! any microcontroller.
+//!
+//! Tests run on host with I2C and pins mocked by
+//! [embedded-hal-mock](https://crates.io/crates/embedded-hal-mock), see
+//! readme.
+#![cfg_attr(not(test), no_std)]
+
+pub mod at24cxx;
+pub mod bme280;
+pub mod buttons;
+pub mod ds3231;
+pub mod mpr121;
+pub mod nmea;
+pub mod shared_i2c;
+pub mod st7789vwx6;
+
+/// Cycles of core clock in microsecond. Drivers busy wait, so waits are at
+/// least as long as they need to be on cores up to 125MHz, the clock of
+/// RP2040.
+const CYCLES_PER_US: u32 = 125;
+
+/// Busy waits given number of microseconds. Tests run on host, where
+/// cortex-m can't wait, and skip waits.
+pub(crate) fn delay_us(us: u32) {
+    if cfg!(not(test)) {
+        cortex_m::asm::delay(us * CYCLES_PER_US);
+    }
+}
@@ -0,0 +1,261 @@
+//! MPR121 capacitive touch controller with up to 12 electrodes.
+//!
+//! Builds without mechanical buttons can use electrodes in their place. Each
+//! [`Electrode`] is an input pin that reads high while electrode is touched,
+//! so it goes through the same [`Debounce`](super::buttons::Debounce) and
+//! [`Button`](super::buttons::Button) as mechanical buttons do. Single key
+//! touch modules like TTP223 drive a GPIO directly and need no driver at all.
+
+use core::{cell::RefCell, convert::Infallible};
+use embedded_hal::{
+    blocking::i2c::{Write, WriteRead},
+    digital::v2::InputPin,
+};
+
+/// Sensitivity, as drop of electrode reading from baseline. Values are the
+/// ones recommended by application notes.
+const TOUCH_THRESHOLD: u8 = 12;
+const RELEASE_THRESHOLD: u8 = 6;
+/// Value written to soft reset register to reset chip
+const SOFT_RESET_VALUE: u8 = 0x63;
+/// Value of CONFIG2 after reset, used to check that chip is MPR121
+const CONFIG2_RESET_VALUE: u8 = 0x24;
+/// Baseline tracking is enabled with 10 most significant bits of first
+/// reading as initial baseline
+const ECR_BASELINE_TRACKING: u8 = 0x80;
+/// Only 12 lowest bits of touch status are electrodes
+const TOUCH_STATUS_MASK: u16 = 0x0FFF;
+pub const ELECTRODES: u8 = 12;
+
+/// Rising, falling and touched baseline filter settings, from MHDR to FDLT
+const BASELINE_FILTER: [u8; 11] = [
+    0x01, 0x01, 0x0E, 0x00, 0x01, 0x05, 0x01, 0x00, 0x00, 0x00, 0x00,
+];
+
+#[derive(Clone, Copy)]
+enum Register {
+    TouchStatus = 0x00,
+    /// First of baseline filter registers
+    Mhdr = 0x2B,
+    /// Touch and release thresholds of electrode 0, the rest follow in pairs
+    Thresholds = 0x41,
+    Debounce = 0x5B,
+    Config1 = 0x5C,
+    Config2 = 0x5D,
+    /// Electrode configuration, selects electrodes and starts measurements
+    Ecr = 0x5E,
+    SoftReset = 0x80,
+}
+
+#[derive(Clone, Copy)]
+pub struct MPR121State {
+    addr: u8,
+}
+
+impl MPR121State {
+    pub fn new(addr: u8) -> Self {
+        Self { addr }
+    }
+}
+
+/// MPR121 Driver
+pub struct MPR121<I2C> {
+    i2c: I2C,
+    state: MPR121State,
+}
+
+impl<I2C> MPR121<I2C> {
+    pub fn new(i2c: I2C, state: MPR121State) -> Self {
+        Self { i2c, state }
+    }
+
+    pub fn release(self) -> (I2C, MPR121State) {
+        (self.i2c, self.state)
+    }
+}
+
+impl<I2C> MPR121<I2C>
+where
+    I2C: Write + WriteRead,
+{
+    /// Resets chip and starts measuring electrodes 0 to electrodes-1
+    pub fn init(&mut self, electrodes: u8) -> Result<(), Error> {
+        if electrodes == 0 || electrodes > ELECTRODES {
+            return Err(Error::ElectrodesRange);
+        }
+
+        // Chip is in stop mode after reset, which is the only mode registers
+        // can be written in
+        self.write_regs(Register::SoftReset, &[SOFT_RESET_VALUE])?;
+        let mut config2 = [0u8];
+        self.read_regs(Register::Config2, &mut config2)?;
+        if config2[0] != CONFIG2_RESET_VALUE {
+            return Err(Error::WrongChip);
+        }
+
+        let mut thresholds = [0u8; 2 * ELECTRODES as usize];
+        for pair in thresholds.chunks_exact_mut(2) {
+            pair.copy_from_slice(&[TOUCH_THRESHOLD, RELEASE_THRESHOLD]);
+        }
+        self.write_regs(Register::Thresholds, &thresholds)?;
+        self.write_regs(Register::Mhdr, &BASELINE_FILTER)?;
+        // No debounce, filtering is left to Debounce of buttons
+        self.write_regs(Register::Debounce, &[0])?;
+        // 16uA charge current, 6 samples in first filter, 0.5us charge time,
+        // 4 samples in second filter, 1ms period
+        self.write_regs(Register::Config1, &[0x10])?;
+        self.write_regs(Register::Config2, &[0x20])?;
+        self.write_regs(Register::Ecr, &[ECR_BASELINE_TRACKING | electrodes])
+    }
+
+    /// Bit mask of touched electrodes
+    pub fn touched(&mut self) -> Result<u16, Error> {
+        read_touched(&mut self.i2c, self.state.addr)
+    }
+
+    /// Registers are written one after another starting from reg
+    fn write_regs(&mut self, reg: Register, values: &[u8]) -> Result<(), Error> {
+        let mut buf = [0u8; 1 + 2 * ELECTRODES as usize];
+        buf[0] = reg as u8;
+        buf[1..1 + values.len()].copy_from_slice(values);
+        self.i2c
+            .write(self.state.addr, &buf[..1 + values.len()])
+            .map_err(|_| Error::BusWrite)
+    }
+
+    fn read_regs(&mut self, reg: Register, dst: &mut [u8]) -> Result<(), Error> {
+        self.i2c
+            .write_read(self.state.addr, &[reg as u8], dst)
+            .map_err(|_| Error::BusRead)
+    }
+}
+
+fn read_touched<I2C: WriteRead>(i2c: &mut I2C, addr: u8) -> Result<u16, Error> {
+    let mut status = [0u8; 2];
+    i2c.write_read(addr, &[Register::TouchStatus as u8], &mut status)
+        .map_err(|_| Error::BusRead)?;
+
+    Ok(u16::from_le_bytes(status) & TOUCH_STATUS_MASK)
+}
+
+/// Single electrode of initialized chip used as input pin. It has its own
+/// handle to I2C bus, see [`SharedI2C`](super::shared_i2c::SharedI2C).
+pub struct Electrode<I2C> {
+    i2c: RefCell<I2C>,
+    state: MPR121State,
+    index: u8,
+}
+
+impl<I2C> Electrode<I2C> {
+    pub fn new(i2c: I2C, state: MPR121State, index: u8) -> Self {
+        Self {
+            i2c: RefCell::new(i2c),
+            state,
+            index,
+        }
+    }
+}
+
+impl<I2C> InputPin for Electrode<I2C>
+where
+    I2C: WriteRead,
+{
+    type Error = Infallible;
+
+    /// Is electrode touched? Bus errors read as released, so broken
+    /// controller doesn't leave buttons pressed.
+    fn is_high(&self) -> Result<bool, Infallible> {
+        let touched = read_touched(&mut *self.i2c.borrow_mut(), self.state.addr);
+        Ok(touched.is_ok_and(|touched| touched & (1 << self.index) != 0))
+    }
+
+    fn is_low(&self) -> Result<bool, Infallible> {
+        self.is_high().map(|is_high| !is_high)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Error {
+    BusRead,
+    BusWrite,
+    /// Chip doesn't look like MPR121 after reset
+    WrongChip,
+    ElectrodesRange,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_hal_mock::eh0::{
+        i2c::{Mock, Transaction},
+        MockError,
+    };
+
+    const ADDR: u8 = 0x5A;
+
+    fn with_touch(expectations: &[Transaction], f: impl FnOnce(&mut MPR121<Mock>)) {
+        let mut touch = MPR121::new(Mock::new(expectations), MPR121State::new(ADDR));
+        f(&mut touch);
+        let (mut i2c, _) = touch.release();
+        i2c.done();
+    }
+
+    fn write(reg: Register, values: &[u8]) -> Transaction {
+        let mut bytes = vec![reg as u8];
+        bytes.extend_from_slice(values);
+        Transaction::write(ADDR, bytes)
+    }
+
+    fn touch_status(status: u16) -> Transaction {
+        Transaction::write_read(ADDR, vec![0x00], status.to_le_bytes().to_vec())
+    }
+
+    #[test]
+    fn init_configures_electrodes() {
+        let expectations = [
+            write(Register::SoftReset, &[0x63]),
+            Transaction::write_read(ADDR, vec![0x5D], vec![0x24]),
+            write(Register::Thresholds, &[12, 6].repeat(12)),
+            write(Register::Mhdr, &BASELINE_FILTER),
+            write(Register::Debounce, &[0]),
+            write(Register::Config1, &[0x10]),
+            write(Register::Config2, &[0x20]),
+            write(Register::Ecr, &[0x83]),
+        ];
+        with_touch(&expectations, |touch| touch.init(3).unwrap());
+    }
+
+    #[test]
+    fn init_rejects_other_chip() {
+        let expectations = [
+            write(Register::SoftReset, &[0x63]),
+            Transaction::write_read(ADDR, vec![0x5D], vec![0x00]),
+        ];
+        with_touch(&expectations, |touch| {
+            assert!(matches!(touch.init(3), Err(Error::WrongChip)));
+        });
+    }
+
+    #[test]
+    fn touched_ignores_overcurrent_flag() {
+        with_touch(&[touch_status(0x8005)], |touch| {
+            assert_eq!(touch.touched().unwrap(), 0x0005);
+        });
+    }
+
+    #[test]
+    fn electrode_reads_its_bit() {
+        let error = MockError::Io(std::io::ErrorKind::Other);
+        let expectations = [
+            touch_status(0b100),
+            touch_status(0b011),
+            Transaction::write_read(ADDR, vec![0x00], vec![0, 0]).with_error(error),
+        ];
+        let electrode = Electrode::new(Mock::new(&expectations), MPR121State::new(ADDR), 2);
+        assert_eq!(electrode.is_high(), Ok(true));
+        assert_eq!(electrode.is_high(), Ok(false));
+        // Bus error reads as released
+        assert_eq!(electrode.is_high(), Ok(false));
+        electrode.i2c.into_inner().done();
+    }
+}
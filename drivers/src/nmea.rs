@@ -0,0 +1,243 @@
+//! NMEA 0183 receiver (e.g. GPS module) connected over UART.
+//!
+//! Receiver sends text sentences like
+//! `$GPRMC,123519.00,A,4807.038,N,01131.000,E,022.4,084.4,230394,,*6A`.
+//! Only RMC sentences are decoded, as they carry date and time. The rest is
+//! skipped.
+
+use embedded_hal::serial::Read;
+use heapless::Vec;
+
+use crate::ds3231::{days_in_month, Date, Time};
+
+/// Longest sentence allowed by standard, including `$` and checksum
+const SENTENCE_LEN: usize = 82;
+
+/// Recommended minimum data (RMC sentence)
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Rmc {
+    /// UTC date and time, None if receiver has no fix
+    pub date_time: Option<(Date, Time)>,
+}
+
+impl Rmc {
+    /// Parses single sentence without line ending. Returns None if sentence
+    /// is not RMC or is malformed.
+    pub fn parse(sentence: &[u8]) -> Option<Self> {
+        let sentence = core::str::from_utf8(sentence).ok()?;
+        let (data, checksum) = sentence.strip_prefix('$')?.split_once('*')?;
+        let expected = data.bytes().fold(0, |sum, byte| sum ^ byte);
+        if u8::from_str_radix(checksum, 16).ok()? != expected {
+            return None;
+        }
+
+        let mut fields = data.split(',');
+        // Talker (GP for GPS, GN for combined systems, etc.) is not checked
+        let kind = fields.next()?;
+        if kind.len() != 5 || !kind.ends_with("RMC") {
+            return None;
+        }
+
+        let time = fields.next()?;
+        let status = fields.next()?;
+        // Latitude, longitude, speed and course
+        let date = fields.nth(6)?;
+        if status != "A" {
+            return Some(Self { date_time: None });
+        }
+
+        Some(Self {
+            date_time: Some((parse_date(date)?, parse_time(time)?)),
+        })
+    }
+}
+
+/// Time is `hhmmss` optionally followed by fraction of second
+fn parse_time(text: &str) -> Option<Time> {
+    let hours = parse_number(text.get(0..2)?)?;
+    let mins = parse_number(text.get(2..4)?)?;
+    let secs = parse_number(text.get(4..6)?)?;
+    let is_valid = hours < 24 && mins < 60 && secs < 60;
+    is_valid.then_some(Time { hours, mins, secs })
+}
+
+/// Date is `ddmmyy`, years are in 21st century
+fn parse_date(text: &str) -> Option<Date> {
+    if text.len() != 6 {
+        return None;
+    }
+
+    let date = parse_number(text.get(0..2)?)?;
+    let month = parse_number(text.get(2..4)?)?;
+    let year = 2000 + parse_number(text.get(4..6)?)? as u16;
+    let is_valid = (1..=12).contains(&month) && (1..=days_in_month(year, month)).contains(&date);
+    is_valid.then_some(Date { year, month, date })
+}
+
+fn parse_number(text: &str) -> Option<u8> {
+    if !text.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+
+    text.parse().ok()
+}
+
+pub struct Nmea<UART> {
+    uart: UART,
+    /// Sentence being received
+    sentence: Vec<u8, SENTENCE_LEN>,
+    /// Is sentence being received corrupted (too long or UART error)? It is
+    /// dropped once it ends.
+    is_corrupted: bool,
+}
+
+impl<UART> Nmea<UART> {
+    pub fn new(uart: UART) -> Self {
+        Self {
+            uart,
+            sentence: Vec::new(),
+            is_corrupted: false,
+        }
+    }
+
+    pub fn release(self) -> UART {
+        self.uart
+    }
+
+    /// Collects sentence byte by byte. Returns RMC once it is complete.
+    fn receive(&mut self, byte: u8) -> Option<Rmc> {
+        match byte {
+            // Start of sentence, anything received before is garbage
+            b'$' => {
+                self.sentence.clear();
+                self.is_corrupted = false;
+                self.sentence.push(byte).ok();
+                None
+            }
+            b'\r' | b'\n' => {
+                let is_complete = !self.sentence.is_empty() && !self.is_corrupted;
+                let rmc = is_complete.then(|| Rmc::parse(&self.sentence)).flatten();
+                self.sentence.clear();
+                self.is_corrupted = false;
+                rmc
+            }
+            _ => {
+                if self.sentence.push(byte).is_err() {
+                    self.is_corrupted = true;
+                }
+                None
+            }
+        }
+    }
+}
+
+impl<UART> Nmea<UART>
+where
+    UART: Read<u8>,
+{
+    /// Reads everything received so far. Returns the last RMC sentence
+    /// completed by it.
+    pub fn poll(&mut self) -> Option<Rmc> {
+        let mut rmc = None;
+        loop {
+            match self.uart.read() {
+                Ok(byte) => {
+                    if let Some(sentence) = self.receive(byte) {
+                        rmc = Some(sentence);
+                    }
+                }
+                Err(nb::Error::WouldBlock) => break,
+                // Overrun or framing error, some bytes are lost
+                Err(nb::Error::Other(_)) => self.is_corrupted = true,
+            }
+        }
+
+        rmc
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_hal_mock::eh0::{
+        serial::{Mock, Transaction},
+        MockError,
+    };
+
+    /// Example from NMEA reference of u-blox receivers
+    const RMC: &[u8] = b"$GPRMC,083559.00,A,4717.11437,N,00833.91522,E,0.004,77.52,091202,,,A*57";
+
+    fn date_time() -> (Date, Time) {
+        let date = Date {
+            year: 2002,
+            month: 12,
+            date: 9,
+        };
+        let time = Time {
+            hours: 8,
+            mins: 35,
+            secs: 59,
+        };
+        (date, time)
+    }
+
+    #[test]
+    fn parses_rmc() {
+        let rmc = Rmc::parse(RMC).unwrap();
+        assert_eq!(rmc.date_time, Some(date_time()));
+    }
+
+    #[test]
+    fn rmc_without_fix() {
+        let rmc = Rmc::parse(b"$GNRMC,,V,,,,,,,,,,N*4D").unwrap();
+        assert_eq!(rmc.date_time, None);
+    }
+
+    #[test]
+    fn rejects_bad_sentences() {
+        // Corrupted checksum
+        let mut corrupted = RMC.to_vec();
+        *corrupted.last_mut().unwrap() = b'8';
+        assert_eq!(Rmc::parse(&corrupted), None);
+        // Not RMC
+        assert_eq!(
+            Rmc::parse(b"$GPGLL,4717.11364,N,00833.91565,E,092321.00,A,A*60"),
+            None
+        );
+        // Fix with invalid date
+        assert_eq!(Rmc::parse(b"$GPRMC,083559.00,A,,,,,,,320202,,,A*66"), None);
+    }
+
+    #[test]
+    fn poll_assembles_sentences() {
+        // Garbage before sentence start is skipped, sentence without fix is
+        // followed by sentence with fix in the same poll
+        let mut stream = b"4*1C\r\n$GNRMC,,V,,,,,,,,,,N*4D\r\n".to_vec();
+        stream.extend_from_slice(RMC);
+        stream.extend_from_slice(b"\r\n$GPGLL,");
+        let expectations = [
+            Transaction::read_many(stream),
+            Transaction::read_error(nb::Error::WouldBlock),
+        ];
+        let mut nmea = Nmea::new(Mock::new(&expectations));
+        assert_eq!(nmea.poll().unwrap().date_time, Some(date_time()));
+        nmea.release().done();
+    }
+
+    #[test]
+    fn uart_error_drops_sentence() {
+        let (head, tail) = RMC.split_at(20);
+        let expectations = [
+            Transaction::read_many(head),
+            Transaction::read_error(nb::Error::Other(MockError::Io(
+                std::io::ErrorKind::InvalidData,
+            ))),
+            Transaction::read_many(tail),
+            Transaction::read(b'\n'),
+            Transaction::read_error(nb::Error::WouldBlock),
+        ];
+        let mut nmea = Nmea::new(Mock::new(&expectations));
+        assert_eq!(nmea.poll(), None);
+        nmea.release().done();
+    }
+}
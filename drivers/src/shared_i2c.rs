@@ -0,0 +1,102 @@
+//! I2C bus shared by several drivers.
+//!
+//! Every driver owns its bus, so drivers of chips on the same bus can't exist
+//! at the same time. [`SharedI2C`] keeps the bus behind a mutex and hands out
+//! [`I2CProxy`] handles, which drivers own instead. Each transaction runs in
+//! critical section, so proxies can be used from interrupts as well.
+
+use core::cell::RefCell;
+
+use cortex_m::interrupt::{self, Mutex};
+use embedded_hal::blocking::i2c::{Read, Write, WriteRead};
+
+pub struct SharedI2C<I2C> {
+    bus: Mutex<RefCell<I2C>>,
+}
+
+impl<I2C> SharedI2C<I2C> {
+    pub const fn new(bus: I2C) -> Self {
+        Self {
+            bus: Mutex::new(RefCell::new(bus)),
+        }
+    }
+
+    /// Creates handle to be given to driver
+    pub fn acquire(&self) -> I2CProxy<'_, I2C> {
+        I2CProxy { bus: &self.bus }
+    }
+}
+
+impl<I2C> SharedI2C<I2C>
+where
+    I2C: Read,
+{
+    /// Finds devices that acknowledge their address. Each address is probed
+    /// by reading single byte, which doesn't change state of common chips.
+    pub fn scan(&self) -> I2CScan {
+        let mut scan = I2CScan::default();
+        for addr in I2CScan::ADDRESSES {
+            let mut byte = [0u8];
+            let found =
+                interrupt::free(|cs| self.bus.borrow(cs).borrow_mut().read(addr, &mut byte));
+            if found.is_ok() {
+                scan.0 |= 1 << addr;
+            }
+        }
+
+        scan
+    }
+}
+
+/// Addresses of devices found on bus by [`SharedI2C::scan`]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct I2CScan(u128);
+
+impl I2CScan {
+    /// Addresses outside of this range are reserved by I2C specification
+    const ADDRESSES: core::ops::Range<u8> = 0x08..0x78;
+
+    pub fn contains(self, addr: u8) -> bool {
+        addr < 128 && self.0 & (1 << addr) != 0
+    }
+
+    pub fn addresses(self) -> impl Iterator<Item = u8> {
+        Self::ADDRESSES.filter(move |&addr| self.contains(addr))
+    }
+}
+
+pub struct I2CProxy<'a, I2C> {
+    bus: &'a Mutex<RefCell<I2C>>,
+}
+
+impl<I2C> Write for I2CProxy<'_, I2C>
+where
+    I2C: Write,
+{
+    type Error = I2C::Error;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Self::Error> {
+        interrupt::free(|cs| self.bus.borrow(cs).borrow_mut().write(address, bytes))
+    }
+}
+
+impl<I2C> WriteRead for I2CProxy<'_, I2C>
+where
+    I2C: WriteRead,
+{
+    type Error = I2C::Error;
+
+    fn write_read(
+        &mut self,
+        address: u8,
+        bytes: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<(), Self::Error> {
+        interrupt::free(|cs| {
+            self.bus
+                .borrow(cs)
+                .borrow_mut()
+                .write_read(address, bytes, buffer)
+        })
+    }
+}
@@ -0,0 +1,894 @@
+//! Driver fot ST7789VW
+//!
+//! LCD-Nixie-Clock has 6 135x240 displays. They all use SPI interface on a
+//! single line and use 3 CS (chip select) pins to select display per draw
+//! command. Variants of the kit with 4 and 8 displays are wired the same
+//! way, so number of displays is a const generic of the driver.
+//!
+//! The system has some hardware quirks that we have to consider - thus the
+//! implementation of display driver is not generic. Firstly, there are some
+//! hardware settings that had to be copied from sample code provided by
+//! waveshare. They regard voltage and gamma - things that are usually not
+//! covered in generic drivers. Secondly, screen locations have to be offsetted.
+//! Thirdly, there are 3 CS lines - instead of usual for these kind of displays
+//! one. Waveshare most probably placed a binary decoder circuit that transforms
+//! display number set on 3 CS lines into CS for each display independently.
+//!
+//! Another addition is a pin that controls brightness of displays. We can
+//! attach it to PWM and set brightness dynamically.
+//!
+//! Panels refresh themselves from frame memory at 60Hz, so a large write
+//! overtaken by refresh shows half of old and half of new image. Their TE
+//! (tearing effect) output marks vertical blanking, and when TE of a display
+//! is wired to a pin large writes to that display start on it, staying ahead
+//! of refresh. TE of each panel runs on its own oscillator, so a single pin
+//! only synchronizes the display it comes from.
+use core::convert::Infallible;
+use embedded_hal::{
+    blocking::spi::Write,
+    digital::v2::{InputPin, OutputPin, PinState},
+    PwmPin,
+};
+use unwrap_infallible::UnwrapInfallible;
+
+pub const WIDTH: u16 = 135;
+pub const HEIGHT: u16 = 240;
+/// Backlight fades in this many steps, ~300ms when stepped every frame
+const FADE_STEPS: u16 = 18;
+
+/// Most displays 3 CS lines can select
+pub const MAX_DISPLAYS: usize = 8;
+/// Longest wait for blanking, a frame at 60Hz with some margin
+const TE_TIMEOUT_US: u32 = 20_000;
+/// Period TE pin is polled with while waiting for blanking
+const TE_POLL_US: u32 = 10;
+
+/// One of the displays left-to-right.
+/// These are identical and are driven by 3 CS lines.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Display {
+    D1,
+    D2,
+    D3,
+    D4,
+    D5,
+    D6,
+    D7,
+    D8,
+}
+
+impl Display {
+    const ALL: [Self; MAX_DISPLAYS] = [
+        Self::D1,
+        Self::D2,
+        Self::D3,
+        Self::D4,
+        Self::D5,
+        Self::D6,
+        Self::D7,
+        Self::D8,
+    ];
+
+    /// Value of CS lines selecting display. With fewer than 8 displays all
+    /// CS lines high select nothing, with 8 they select one of the edge
+    /// displays while nothing is sent.
+    fn into_cs_value(self, count: usize, order: CsOrder) -> usize {
+        match order {
+            CsOrder::Descending => count - 1 - self as usize,
+            CsOrder::Ascending => self as usize,
+        }
+    }
+
+    fn into_cs_states(self, count: usize, order: CsOrder) -> (PinState, PinState, PinState) {
+        let value = self.into_cs_value(count, order);
+        (
+            (value & 0x1 != 0).into(),
+            (value & 0x2 != 0).into(),
+            (value & 0x4 != 0).into(),
+        )
+    }
+
+    /// First `count` displays left-to-right
+    pub fn first(count: usize) -> impl Iterator<Item = Self> {
+        Self::ALL.into_iter().take(count)
+    }
+
+    /// Display at given position left-to-right
+    pub fn from_index(index: usize) -> Option<Self> {
+        Self::ALL.get(index).copied()
+    }
+}
+
+/// Order in which CS decoder numbers displays
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum CsOrder {
+    /// The last display is selected with 0, as on LCD-Clock-A
+    #[default]
+    Descending,
+    /// D1 is selected with 0
+    Ascending,
+}
+
+/// Stands for TE pin on boards that don't have it wired
+pub struct NoTe;
+
+impl InputPin for NoTe {
+    type Error = Infallible;
+
+    fn is_high(&self) -> Result<bool, Infallible> {
+        Ok(false)
+    }
+
+    fn is_low(&self) -> Result<bool, Infallible> {
+        Ok(true)
+    }
+}
+
+/// Driver for N ST7789VW displays, 6 on LCD-Clock-A. Drawing on displays
+/// past N does nothing, so screens laid out for 6 displays can be shown on
+/// smaller kits.
+pub struct ST7789VWx6<PINS, SPI, BL, const N: usize = 6, TE = NoTe> {
+    pins: PINS,
+    spi: SPI,
+    bl: BL,
+
+    width: u16,
+    height: u16,
+    cs_order: CsOrder,
+    /// Duty of backlight PWM that is faded to
+    brightness: u16,
+    /// Current duty of backlight PWM
+    duty: u16,
+    /// Change of duty in each step of fade
+    fade_step: u16,
+    /// Are controllers in sleep mode?
+    is_sleeping: bool,
+    /// Displays not turned off with set_display_on
+    is_on: [bool; N],
+    /// TE output of a display and the display it comes from, if wired
+    te: Option<(TE, Display)>,
+}
+
+impl<PINS, SPI, BL, const N: usize> ST7789VWx6<PINS, SPI, BL, N> {
+    pub fn new(
+        pins: PINS,
+        spi: SPI,
+        bl: BL,
+        width: u16,
+        height: u16,
+        cs_order: CsOrder,
+        brightness: u16,
+    ) -> Self {
+        Self {
+            pins,
+            spi,
+            bl,
+            width,
+            height,
+            cs_order,
+            brightness,
+            duty: brightness,
+            fade_step: 0,
+            is_sleeping: false,
+            is_on: [true; N],
+            te: None,
+        }
+    }
+}
+
+impl<PINS, SPI, BL, const N: usize, TE> ST7789VWx6<PINS, SPI, BL, N, TE> {
+    /// Synchronizes large writes to display with its TE output wired to
+    /// given pin. TE is enabled by [`init`](Self::init).
+    pub fn with_te<P>(self, te: P, display: Display) -> ST7789VWx6<PINS, SPI, BL, N, P> {
+        ST7789VWx6 {
+            pins: self.pins,
+            spi: self.spi,
+            bl: self.bl,
+            width: self.width,
+            height: self.height,
+            cs_order: self.cs_order,
+            brightness: self.brightness,
+            duty: self.duty,
+            fade_step: self.fade_step,
+            is_sleeping: self.is_sleeping,
+            is_on: self.is_on,
+            te: Some((te, display)),
+        }
+    }
+
+    /// Displays driven, left-to-right
+    pub fn displays() -> impl Iterator<Item = Display> {
+        Display::first(N)
+    }
+
+    fn is_present(display: Display) -> bool {
+        (display as usize) < N
+    }
+
+    pub fn width(&self) -> u16 {
+        self.width
+    }
+
+    pub fn height(&self) -> u16 {
+        self.height
+    }
+}
+
+impl<PINS, SPI, BL, const N: usize, TE> ST7789VWx6<PINS, SPI, BL, N, TE>
+where
+    PINS: Pins,
+    SPI: Write<u8>,
+    BL: PwmPin<Duty = u16>,
+    TE: InputPin<Error = Infallible>,
+{
+    /// Sets brightness at once, stopping fade if there is one
+    pub fn set_brightness(&mut self, brightness: u16) {
+        self.brightness = brightness;
+        self.duty = brightness;
+        self.bl.set_duty(self.duty);
+    }
+
+    /// Starts fading backlight to given brightness. Fade is done by
+    /// [`update_fade`](Self::update_fade) in FADE_STEPS calls.
+    pub fn fade_brightness(&mut self, brightness: u16) {
+        self.brightness = brightness;
+        self.fade_step = (self.duty.abs_diff(brightness) / FADE_STEPS).max(1);
+    }
+
+    /// Has backlight reached brightness being faded to?
+    pub fn is_faded(&self) -> bool {
+        self.duty == self.brightness
+    }
+
+    /// Moves backlight one step closer to brightness being faded to. Should be
+    /// called every frame.
+    pub fn update_fade(&mut self) {
+        if self.duty == self.brightness {
+            return;
+        }
+
+        self.duty = if self.duty < self.brightness {
+            self.duty
+                .saturating_add(self.fade_step)
+                .min(self.brightness)
+        } else {
+            self.duty
+                .saturating_sub(self.fade_step)
+                .max(self.brightness)
+        };
+        self.bl.set_duty(self.duty);
+    }
+
+    fn cs_low(&mut self, display: Display) {
+        let states = display.into_cs_states(N, self.cs_order);
+        self.pins.csa1().set_state(states.0).unwrap_infallible();
+        self.pins.csa2().set_state(states.1).unwrap_infallible();
+        self.pins.csa3().set_state(states.2).unwrap_infallible();
+    }
+
+    fn cs_high(&mut self) {
+        self.pins.csa1().set_high().unwrap_infallible();
+        self.pins.csa2().set_high().unwrap_infallible();
+        self.pins.csa3().set_high().unwrap_infallible();
+    }
+
+    fn with_cs<Res>(&mut self, display: Display, f: impl FnOnce(&mut Self) -> Res) -> Res {
+        self.cs_low(display);
+        let result = f(self);
+        self.cs_high();
+
+        result
+    }
+
+    fn send_commands(&mut self, cmds: &[u8]) -> Result<(), Error> {
+        self.pins.dc().set_low().unwrap_infallible();
+        self.spi.write(cmds).map_err(|_| Error::BusWrite)
+    }
+
+    fn send_command(&mut self, cmd: Command) -> Result<(), Error> {
+        self.send_commands(&[cmd as u8])
+    }
+
+    fn send_data(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.pins.dc().set_high().unwrap_infallible();
+        self.spi.write(data).map_err(|_| Error::BusWrite)
+    }
+
+    fn set_region(
+        &mut self,
+        mut x_start: u16,
+        mut y_start: u16,
+        mut x_end: u16,
+        mut y_end: u16,
+    ) -> Result<(), Error> {
+        x_start += 52;
+        x_end += 52 - 1;
+        y_start += 40;
+        y_end += 40 - 1;
+        self.send_command(Command::CASET)?;
+        let mut x = [0u8; 4];
+        x[0..2].copy_from_slice(&x_start.to_be_bytes());
+        x[2..4].copy_from_slice(&x_end.to_be_bytes());
+        self.send_data(&x)?;
+        self.send_command(Command::RASET)?;
+        let mut y = [0u8; 4];
+        y[0..2].copy_from_slice(&y_start.to_be_bytes());
+        y[2..4].copy_from_slice(&y_end.to_be_bytes());
+        self.send_data(&y)?;
+
+        Ok(())
+    }
+
+    /// Waits for vertical blanking of display if its TE is wired. Gives up
+    /// after a frame, so clock keeps going if TE never comes.
+    fn wait_for_blanking(&mut self, display: Display) {
+        let Some((te, te_display)) = &self.te else {
+            return;
+        };
+        if *te_display != display {
+            return;
+        }
+
+        for _ in 0..TE_TIMEOUT_US / TE_POLL_US {
+            if te.is_high().unwrap_infallible() {
+                return;
+            }
+            crate::delay_us(TE_POLL_US);
+        }
+    }
+
+    /// Starts writing pixels into region of display. Writes of half of
+    /// display or more start on blanking, see [`with_te`](Self::with_te).
+    fn start_ram_write(
+        &mut self,
+        display: Display,
+        x_start: u16,
+        y_start: u16,
+        x_end: u16,
+        y_end: u16,
+    ) -> Result<(), Error> {
+        self.set_region(x_start, y_start, x_end, y_end)?;
+        if y_end - y_start >= self.height / 2 {
+            self.wait_for_blanking(display);
+        }
+        self.send_command(Command::RAMWR)
+    }
+
+    fn hard_reset(&mut self) {
+        self.pins.rst().set_high().unwrap_infallible();
+        // reset for at least 10 us as specified in datasheet.
+        crate::delay_us(10);
+        self.pins.rst().set_low().unwrap_infallible();
+        crate::delay_us(10);
+        self.pins.rst().set_high().unwrap_infallible();
+        crate::delay_us(10);
+    }
+
+    fn init_display(&mut self) -> Result<(), Error> {
+        // refresh from left to right, bottom from to top, use rgb
+        self.send_command(Command::MADCTL)?;
+        self.send_data(&[0b0000_0000])?;
+        // 65k 16 bits/pixel colors
+        self.send_command(Command::COLMOD)?;
+        self.send_data(&[0b0101_0101])?;
+        // have no idea what it does...
+        self.send_command(Command::PORCTRL)?;
+        self.send_data(&[0x0C, 0x0C, 0x00, 0x33, 0x33])?;
+
+        self.send_command(Command::GCTRL)?;
+        self.send_data(&[0x35])?;
+
+        self.send_command(Command::VCOMS)?;
+        self.send_data(&[0x19])?;
+
+        self.send_command(Command::LCMCTRL)?;
+        self.send_data(&[0x2C])?;
+
+        self.send_command(Command::VDVVRHEN)?;
+        self.send_data(&[0x01])?;
+
+        self.send_command(Command::VRHS)?;
+        self.send_data(&[0x12])?;
+
+        self.send_command(Command::VDVS)?;
+        self.send_data(&[0x20])?;
+
+        self.send_command(Command::FRCTRL2)?;
+        self.send_data(&[0x0F])?;
+
+        self.send_command(Command::PWCTRL1)?;
+        self.send_data(&[0xA4, 0xA1])?;
+
+        self.send_command(Command::PVGAMCTRL)?;
+        self.send_data(&[
+            0xD0, 0x04, 0x0D, 0x11, 0x13, 0x2B, 0x3F, 0x54, 0x4C, 0x18, 0x0D, 0x0B, 0x1F, 0x23,
+        ])?;
+
+        self.send_command(Command::NVGAMCTRL)?;
+        self.send_data(&[
+            0xD0, 0x04, 0x0C, 0x11, 0x13, 0x2C, 0x3F, 0x44, 0x51, 0x2F, 0x1F, 0x1F, 0x20, 0x23,
+        ])?;
+
+        self.send_command(Command::INVON)?;
+        // exit sleep mode
+        self.send_command(Command::SLPOUT)?;
+        // turn on display
+        self.send_command(Command::DISPON)?;
+
+        Ok(())
+    }
+
+    pub fn init(&mut self) -> Result<(), Error> {
+        self.hard_reset();
+        self.set_brightness(self.brightness);
+
+        for display in Self::displays() {
+            self.with_cs(display, Self::init_display)?;
+        }
+        if let Some(display) = self.te.as_ref().map(|&(_, display)| display) {
+            // TE output is high during vertical blanking only
+            self.with_cs(display, |d| {
+                d.send_command(Command::TEON)?;
+                d.send_data(&[0x00])
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Turns backlight off and puts controllers into sleep mode, where they
+    /// draw only a few microamps. Frame memory keeps its content.
+    pub fn sleep(&mut self) -> Result<(), Error> {
+        if self.is_sleeping {
+            return Ok(());
+        }
+
+        self.set_brightness(0);
+        for display in Self::displays() {
+            self.with_cs(display, |d| {
+                d.send_command(Command::DISPOFF)?;
+                d.send_command(Command::SLPIN)
+            })?;
+        }
+        self.is_sleeping = true;
+
+        Ok(())
+    }
+
+    /// Turns panel of single display on or off. Backlight is shared by all
+    /// displays, so it stays as it is. Displays turned off stay off after
+    /// wake.
+    pub fn set_display_on(&mut self, display: Display, is_on: bool) -> Result<(), Error> {
+        if !Self::is_present(display) || self.is_on[display as usize] == is_on {
+            return Ok(());
+        }
+
+        self.is_on[display as usize] = is_on;
+        if self.is_sleeping {
+            return Ok(());
+        }
+        let command = if is_on {
+            Command::DISPON
+        } else {
+            Command::DISPOFF
+        };
+        self.with_cs(display, |d| d.send_command(command))
+    }
+
+    /// Wakes controllers from sleep mode. Backlight stays off until
+    /// brightness is set or faded to.
+    pub fn wake(&mut self) -> Result<(), Error> {
+        if !self.is_sleeping {
+            return Ok(());
+        }
+
+        for display in Self::displays() {
+            self.with_cs(display, |d| d.send_command(Command::SLPOUT))?;
+        }
+        // Supply voltages of panel settle within 120ms after sleep out, it
+        // is turned on only then to avoid flicker
+        crate::delay_us(120 * 1000);
+        for display in Self::displays() {
+            if self.is_on[display as usize] {
+                self.with_cs(display, |d| d.send_command(Command::DISPON))?;
+            }
+        }
+        self.is_sleeping = false;
+
+        Ok(())
+    }
+
+    /// Changes SPI bus with `f`, e.g. its clock, and initializes displays
+    /// again. Panels that couldn't keep up with the old clock may have taken
+    /// garbage for commands, so they are reset and frame memory has to be
+    /// drawn again. Sleeping panels are left as they are, they receive
+    /// nothing at either clock.
+    pub fn reconfigure_spi<Res>(&mut self, f: impl FnOnce(&mut SPI) -> Res) -> Result<Res, Error> {
+        // CS lines are idle while bus changes, as between commands
+        self.cs_high();
+        let result = f(&mut self.spi);
+        if self.is_sleeping {
+            return Ok(result);
+        }
+
+        self.init()?;
+        for display in Self::displays() {
+            if !self.is_on[display as usize] {
+                self.with_cs(display, |d| d.send_command(Command::DISPOFF))?;
+            }
+        }
+
+        Ok(result)
+    }
+
+    pub fn set_pixels(
+        &mut self,
+        display: Display,
+        x_start: u16,
+        y_start: u16,
+        x_end: u16,
+        y_end: u16,
+        colors: &[u8],
+    ) -> Result<(), Error> {
+        if !Self::is_present(display) {
+            return Ok(());
+        }
+
+        self.with_cs(display, |d| {
+            d.start_ram_write(display, x_start, y_start, x_end, y_end)?;
+            d.send_data(colors)?;
+
+            Ok(())
+        })
+    }
+
+    pub fn set_pixels_iter<T>(
+        &mut self,
+        display: Display,
+        x_start: u16,
+        y_start: u16,
+        x_end: u16,
+        y_end: u16,
+        colors: T,
+    ) -> Result<(), Error>
+    where
+        T: IntoIterator<Item = u8>,
+    {
+        if !Self::is_present(display) {
+            return Ok(());
+        }
+
+        self.with_cs(display, |d| {
+            d.start_ram_write(display, x_start, y_start, x_end, y_end)?;
+
+            let mut buf = [0u8; 256];
+            let mut i = 0;
+
+            for v in colors.into_iter() {
+                buf[i] = v;
+                i += 1;
+
+                if i == buf.len() {
+                    d.send_data(&buf)?;
+                    i = 0;
+                }
+            }
+
+            if i != 0 {
+                d.send_data(&buf[..i])?;
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Fills region of several displays, each with a color of its own. CS
+    /// decoder selects only one display at a time, so displays can't be
+    /// written with a single transfer. Instead buffer of pixels is filled
+    /// once per color and streamed to displays one after another, which
+    /// saves encoding every pixel for every display.
+    pub fn fill_displays(
+        &mut self,
+        x_start: u16,
+        y_start: u16,
+        x_end: u16,
+        y_end: u16,
+        colors: impl IntoIterator<Item = (Display, [u8; 2])>,
+    ) -> Result<(), Error> {
+        let len = 2 * (x_end - x_start) as usize * (y_end - y_start) as usize;
+        let mut buf = [0u8; 256];
+        let mut buf_color = None;
+        for (display, color) in colors {
+            if !Self::is_present(display) {
+                continue;
+            }
+
+            if buf_color != Some(color) {
+                for pixel in buf.chunks_exact_mut(2) {
+                    pixel.copy_from_slice(&color);
+                }
+                buf_color = Some(color);
+            }
+            self.with_cs(display, |d| {
+                d.start_ram_write(display, x_start, y_start, x_end, y_end)?;
+
+                let mut left = len;
+                while left != 0 {
+                    let chunk = left.min(buf.len());
+                    d.send_data(&buf[..chunk])?;
+                    left -= chunk;
+                }
+
+                Ok(())
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
+pub trait Pins {
+    type CSA1: OutputPin<Error = Infallible>;
+    type CSA2: OutputPin<Error = Infallible>;
+    type CSA3: OutputPin<Error = Infallible>;
+    type DC: OutputPin<Error = Infallible>;
+    type RST: OutputPin<Error = Infallible>;
+
+    fn csa1(&mut self) -> &mut Self::CSA1;
+    fn csa2(&mut self) -> &mut Self::CSA2;
+    fn csa3(&mut self) -> &mut Self::CSA3;
+    fn dc(&mut self) -> &mut Self::DC;
+    fn rst(&mut self) -> &mut Self::RST;
+}
+
+impl<
+        CSA1: OutputPin<Error = Infallible>,
+        CSA2: OutputPin<Error = Infallible>,
+        CSA3: OutputPin<Error = Infallible>,
+        DC: OutputPin<Error = Infallible>,
+        RST: OutputPin<Error = Infallible>,
+    > Pins for (CSA1, CSA2, CSA3, DC, RST)
+{
+    type CSA1 = CSA1;
+    type CSA2 = CSA2;
+    type CSA3 = CSA3;
+    type DC = DC;
+    type RST = RST;
+
+    fn csa1(&mut self) -> &mut CSA1 {
+        &mut self.0
+    }
+
+    fn csa2(&mut self) -> &mut CSA2 {
+        &mut self.1
+    }
+
+    fn csa3(&mut self) -> &mut CSA3 {
+        &mut self.2
+    }
+
+    fn dc(&mut self) -> &mut DC {
+        &mut self.3
+    }
+
+    fn rst(&mut self) -> &mut RST {
+        &mut self.4
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    BusWrite,
+    /// Display past number of displays of the kit
+    NoDisplay,
+}
+
+#[allow(clippy::upper_case_acronyms)]
+enum Command {
+    /// Memory data access control
+    MADCTL = 0x36,
+    /// Interface pixel format
+    COLMOD = 0x3A,
+    /// Porch setting
+    PORCTRL = 0xB2,
+    /// Gate control
+    GCTRL = 0xB7,
+    /// VCOMS setting
+    VCOMS = 0xBB,
+    /// LCM Control
+    LCMCTRL = 0xC0,
+    /// VDV and VRH command enable
+    VDVVRHEN = 0xC2,
+    /// VRH set
+    VRHS = 0xC3,
+    /// VDV set
+    VDVS = 0xC4,
+    /// Frame rate control
+    FRCTRL2 = 0xC6,
+    /// Power control
+    PWCTRL1 = 0xD0,
+    /// Positive voltage gamma control
+    PVGAMCTRL = 0xE0,
+    /// Negative voltage gamma control
+    NVGAMCTRL = 0xE1,
+    /// Display inversion on
+    INVON = 0x21,
+    /// Sleep in
+    SLPIN = 0x10,
+    /// Sleep out
+    SLPOUT = 0x11,
+    /// Display off
+    DISPOFF = 0x28,
+    /// Display on
+    DISPON = 0x29,
+    /// Tearing effect line on
+    TEON = 0x35,
+    /// Column address set
+    CASET = 0x2A,
+    /// Row address set
+    RASET = 0x2B,
+    /// Memory write
+    RAMWR = 0x2C,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_hal_mock::eh0::spi::{Mock, Transaction};
+    use std::{cell::Cell, rc::Rc};
+
+    #[test]
+    fn descending_order_selects_last_display_with_zero() {
+        let order = CsOrder::Descending;
+        assert_eq!(Display::D1.into_cs_value(6, order), 5);
+        assert_eq!(Display::D6.into_cs_value(6, order), 0);
+        assert_eq!(Display::D1.into_cs_value(4, order), 3);
+        assert_eq!(Display::D4.into_cs_value(4, order), 0);
+        assert_eq!(Display::D8.into_cs_value(8, order), 0);
+    }
+
+    #[test]
+    fn ascending_order_selects_first_display_with_zero() {
+        let order = CsOrder::Ascending;
+        assert_eq!(Display::D1.into_cs_value(6, order), 0);
+        assert_eq!(Display::D6.into_cs_value(6, order), 5);
+        assert_eq!(Display::D8.into_cs_value(8, order), 7);
+    }
+
+    #[test]
+    fn first_displays_are_driven() {
+        let displays = ST7789VWx6::<(), (), (), 4>::displays();
+        assert!(displays.eq([Display::D1, Display::D2, Display::D3, Display::D4]));
+        assert_eq!(Display::from_index(5), Some(Display::D6));
+        assert_eq!(Display::from_index(MAX_DISPLAYS), None);
+    }
+
+    struct NoPin;
+
+    impl OutputPin for NoPin {
+        type Error = Infallible;
+
+        fn set_low(&mut self) -> Result<(), Infallible> {
+            Ok(())
+        }
+
+        fn set_high(&mut self) -> Result<(), Infallible> {
+            Ok(())
+        }
+    }
+
+    struct NoBacklight;
+
+    impl PwmPin for NoBacklight {
+        type Duty = u16;
+
+        fn disable(&mut self) {}
+        fn enable(&mut self) {}
+        fn get_duty(&self) -> u16 {
+            0
+        }
+        fn get_max_duty(&self) -> u16 {
+            0
+        }
+        fn set_duty(&mut self, _duty: u16) {}
+    }
+
+    #[test]
+    fn fill_displays_streams_buffer_to_each_display() {
+        let region = |x_end: u8, y_end: u8| {
+            [
+                Transaction::write(vec![Command::CASET as u8]),
+                Transaction::write(vec![0, 52, 0, 52 + x_end - 1]),
+                Transaction::write(vec![Command::RASET as u8]),
+                Transaction::write(vec![0, 40, 0, 40 + y_end - 1]),
+                Transaction::write(vec![Command::RAMWR as u8]),
+            ]
+        };
+        let pixels = |color: [u8; 2], len: usize| color.repeat(len / 2);
+        let mut expectations = Vec::new();
+        // 10x20 region is 400 bytes, sent in chunks of buffer
+        for color in [[0x12, 0x34], [0x56, 0x78]] {
+            expectations.extend(region(10, 20));
+            expectations.push(Transaction::write(pixels(color, 256)));
+            expectations.push(Transaction::write(pixels(color, 144)));
+        }
+        let spi = Mock::new(&expectations);
+        let pins = (NoPin, NoPin, NoPin, NoPin, NoPin);
+        let mut displays = ST7789VWx6::<_, _, _, 2>::new(
+            pins,
+            spi,
+            NoBacklight,
+            WIDTH,
+            HEIGHT,
+            CsOrder::Descending,
+            0,
+        );
+
+        let colors = [
+            (Display::D1, [0x12, 0x34]),
+            (Display::D2, [0x56, 0x78]),
+            // Past number of displays, nothing is sent
+            (Display::D3, [0x9A, 0xBC]),
+        ];
+        displays.fill_displays(0, 0, 10, 20, colors).unwrap();
+        displays.spi.done();
+    }
+
+    /// TE pin that reports blanking once it was polled a few times
+    struct TestTe(Rc<Cell<u32>>);
+
+    impl InputPin for TestTe {
+        type Error = Infallible;
+
+        fn is_high(&self) -> Result<bool, Infallible> {
+            self.0.set(self.0.get() + 1);
+            Ok(self.0.get() >= 3)
+        }
+
+        fn is_low(&self) -> Result<bool, Infallible> {
+            self.is_high().map(|is_high| !is_high)
+        }
+    }
+
+    #[test]
+    fn large_writes_to_display_with_te_wait_for_blanking() {
+        let mut expectations = Vec::new();
+        for (width, height) in [(WIDTH, HEIGHT), (WIDTH, HEIGHT), (WIDTH, 20)] {
+            expectations.extend([
+                Transaction::write(vec![Command::CASET as u8]),
+                Transaction::write([52u16, 52 + width - 1].map(u16::to_be_bytes).concat()),
+                Transaction::write(vec![Command::RASET as u8]),
+                Transaction::write([40u16, 40 + height - 1].map(u16::to_be_bytes).concat()),
+                Transaction::write(vec![Command::RAMWR as u8]),
+                Transaction::write(vec![0; 4]),
+            ]);
+        }
+        let polls = Rc::new(Cell::new(0));
+        let mut displays = ST7789VWx6::<_, _, _, 2>::new(
+            (NoPin, NoPin, NoPin, NoPin, NoPin),
+            Mock::new(&expectations),
+            NoBacklight,
+            WIDTH,
+            HEIGHT,
+            CsOrder::Descending,
+            0,
+        )
+        .with_te(TestTe(polls.clone()), Display::D1);
+
+        displays
+            .set_pixels(Display::D1, 0, 0, WIDTH, HEIGHT, &[0; 4])
+            .unwrap();
+        assert_eq!(polls.get(), 3);
+        // TE of the other display is not wired
+        displays
+            .set_pixels(Display::D2, 0, 0, WIDTH, HEIGHT, &[0; 4])
+            .unwrap();
+        assert_eq!(polls.get(), 3);
+        // Small writes don't wait
+        displays
+            .set_pixels(Display::D1, 0, 0, WIDTH, 20, &[0; 4])
+            .unwrap();
+        assert_eq!(polls.get(), 3);
+        displays.spi.done();
+    }
+}
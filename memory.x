@@ -1,6 +1,8 @@
 MEMORY {
     BOOT2 : ORIGIN = 0x10000000, LENGTH = 0x100
-    FLASH : ORIGIN = 0x10000100, LENGTH = 2048K - 0x100
+    /* Last sector is reserved for settings and 16 sectors before it for
+     * climate log, see src/flash.rs */
+    FLASH : ORIGIN = 0x10000100, LENGTH = 2048K - 0x100 - 4K - 64K
     RAM   : ORIGIN = 0x20000000, LENGTH = 256K
 }
 
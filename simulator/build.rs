@@ -0,0 +1,20 @@
+//! Images and fonts are converted by build script of firmware. It writes them
+//! to OUT_DIR of this crate, where firmware sources include them from.
+
+#[allow(dead_code)]
+mod firmware {
+    include!("../build.rs");
+
+    pub fn build() {
+        main()
+    }
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=../build.rs");
+    println!("cargo:rerun-if-changed=../misc");
+    println!("cargo:rerun-if-changed=../.git/HEAD");
+    println!("cargo:rerun-if-changed=../.git/refs/heads");
+    std::env::set_current_dir("..").unwrap();
+    firmware::build();
+}
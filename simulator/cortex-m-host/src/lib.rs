@@ -0,0 +1,26 @@
+//! Parts of cortex-m used by firmware modules the simulator runs. Cortex-m
+//! only implements them for Cortex-M processors.
+
+pub mod asm {
+    use std::time::Duration;
+
+    /// Cycles are converted to time at 125MHz, the clock of RP2040
+    pub fn delay(cycles: u32) {
+        std::thread::sleep(Duration::from_nanos(cycles as u64 * 8));
+    }
+
+    pub fn nop() {}
+}
+
+pub mod interrupt {
+    pub use bare_metal::{CriticalSection, Mutex};
+
+    /// Simulator has no interrupts, so closure just runs
+    pub fn free<F, R>(f: F) -> R
+    where
+        F: FnOnce(&CriticalSection) -> R,
+    {
+        // SAFETY: main loop is the only thread touching firmware data
+        f(unsafe { &CriticalSection::new() })
+    }
+}
@@ -0,0 +1,331 @@
+//! Emulated board. Drivers are given handles implementing embedded-hal traits,
+//! which forward everything to emulated chips on [`Board`].
+
+use core::convert::Infallible;
+use std::{cell::RefCell, rc::Rc};
+
+use embedded_hal::{
+    blocking::{i2c, spi::Write},
+    digital::v2::{InputPin, OutputPin},
+    PwmPin,
+};
+
+use crate::{
+    eeprom::Eeprom,
+    hardware::DISPLAY_COUNT,
+    led_strip::{LedOutput, LED_COUNT, RING_LED_COUNT},
+    misc::ColorRGB8,
+    panel::Panel,
+    rtc::Rtc,
+    sensor::Sensor,
+};
+
+pub type BoardRef = Rc<RefCell<Board>>;
+
+/// Addresses chips on I2C bus answer to, as in schematic
+pub const RTC_I2C_ADDR: u8 = 0x68;
+pub const SENSOR_I2C_ADDR: u8 = 0x76;
+pub const EEPROM_I2C_ADDR: u8 = 0x50;
+
+pub struct Board {
+    pub panels: [Panel; DISPLAY_COUNT],
+    /// Duty of PWM driving backlight of displays
+    pub backlight: u16,
+    pub leds: [ColorRGB8; LED_COUNT],
+    pub ring: [ColorRGB8; RING_LED_COUNT],
+    /// Are left, right and mode buttons pressed?
+    pub buttons: [bool; 3],
+    pub rtc: Rtc,
+    pub sensor: Sensor,
+    pub eeprom: Eeprom,
+    /// Has firmware reset the board? Main loop starts it over then.
+    pub reset: bool,
+    /// Levels of 3 CS lines forming number of selected display
+    cs: u8,
+    /// Data/command line, low for commands
+    dc: bool,
+}
+
+impl Board {
+    pub fn new() -> Self {
+        Self {
+            panels: Default::default(),
+            backlight: 0,
+            leds: [ColorRGB8::black(); LED_COUNT],
+            ring: [ColorRGB8::black(); RING_LED_COUNT],
+            buttons: [false; 3],
+            rtc: Rtc::new(),
+            sensor: Sensor::new(),
+            eeprom: Eeprom::new(),
+            reset: false,
+            cs: 0b111,
+            dc: false,
+        }
+    }
+
+    fn set_cs_bit(&mut self, bit: u8, is_high: bool) {
+        if is_high {
+            self.cs |= bit;
+        } else {
+            self.cs &= !bit;
+        }
+    }
+
+    /// Display selected by CS lines. CS decoder selects the last display with
+    /// 0 and D1 with DISPLAY_COUNT - 1, higher values select nothing.
+    fn selected_panel(&mut self) -> Option<&mut Panel> {
+        let index = (DISPLAY_COUNT - 1).checked_sub(self.cs as usize)?;
+        self.panels.get_mut(index)
+    }
+}
+
+/// Output pins driving displays
+#[derive(Clone, Copy)]
+pub enum PinKind {
+    Csa1,
+    Csa2,
+    Csa3,
+    Dc,
+    Rst,
+}
+
+pub struct Pin {
+    board: BoardRef,
+    kind: PinKind,
+}
+
+impl Pin {
+    pub fn new(board: &BoardRef, kind: PinKind) -> Self {
+        Self {
+            board: board.clone(),
+            kind,
+        }
+    }
+
+    fn set(&mut self, is_high: bool) {
+        let mut board = self.board.borrow_mut();
+        match self.kind {
+            PinKind::Csa1 => board.set_cs_bit(0b001, is_high),
+            PinKind::Csa2 => board.set_cs_bit(0b010, is_high),
+            PinKind::Csa3 => board.set_cs_bit(0b100, is_high),
+            PinKind::Dc => board.dc = is_high,
+            // Reset is active low and is shared by all displays
+            PinKind::Rst if !is_high => board.panels.iter_mut().for_each(Panel::reset),
+            PinKind::Rst => {}
+        }
+    }
+}
+
+impl OutputPin for Pin {
+    type Error = Infallible;
+
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        self.set(false);
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        self.set(true);
+        Ok(())
+    }
+}
+
+/// SPI bus of displays
+pub struct Spi(BoardRef);
+
+impl Spi {
+    pub fn new(board: &BoardRef) -> Self {
+        Self(board.clone())
+    }
+}
+
+impl Write<u8> for Spi {
+    type Error = Infallible;
+
+    fn write(&mut self, words: &[u8]) -> Result<(), Infallible> {
+        let mut board = self.0.borrow_mut();
+        let dc = board.dc;
+        if let Some(panel) = board.selected_panel() {
+            for &byte in words {
+                if dc {
+                    panel.data(byte);
+                } else {
+                    panel.command(byte);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// PWM channel driving backlight of displays
+pub struct Backlight(BoardRef);
+
+impl Backlight {
+    pub fn new(board: &BoardRef) -> Self {
+        Self(board.clone())
+    }
+}
+
+impl PwmPin for Backlight {
+    type Duty = u16;
+
+    fn disable(&mut self) {}
+
+    fn enable(&mut self) {}
+
+    fn get_duty(&self) -> u16 {
+        self.0.borrow().backlight
+    }
+
+    fn get_max_duty(&self) -> u16 {
+        u16::MAX
+    }
+
+    fn set_duty(&mut self, duty: u16) {
+        self.0.borrow_mut().backlight = duty;
+    }
+}
+
+#[derive(Debug)]
+pub enum I2CError {
+    /// No chip answers to address
+    NoAcknowledge,
+}
+
+/// I2C bus shared by rtc, humidity sensor and EEPROM
+#[derive(Clone)]
+pub struct I2CBus(BoardRef);
+
+impl I2CBus {
+    pub fn new(board: &BoardRef) -> Self {
+        Self(board.clone())
+    }
+}
+
+impl i2c::Write for I2CBus {
+    type Error = I2CError;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), I2CError> {
+        let mut board = self.0.borrow_mut();
+        // EEPROM has two byte addresses instead of registers
+        if address == EEPROM_I2C_ADDR {
+            board.eeprom.write(bytes);
+            return Ok(());
+        }
+
+        let (&reg, values) = bytes.split_first().ok_or(I2CError::NoAcknowledge)?;
+        match address {
+            RTC_I2C_ADDR => board.rtc.write(reg, values),
+            SENSOR_I2C_ADDR => board.sensor.write(reg, values),
+            _ => return Err(I2CError::NoAcknowledge),
+        }
+
+        Ok(())
+    }
+}
+
+impl i2c::WriteRead for I2CBus {
+    type Error = I2CError;
+
+    fn write_read(&mut self, address: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), I2CError> {
+        let mut board = self.0.borrow_mut();
+        if address == EEPROM_I2C_ADDR {
+            board.eeprom.write(bytes);
+            board.eeprom.read(buffer);
+            return Ok(());
+        }
+
+        let &[reg] = bytes else {
+            return Err(I2CError::NoAcknowledge);
+        };
+        match address {
+            RTC_I2C_ADDR => board.rtc.read(reg, buffer),
+            SENSOR_I2C_ADDR => board.sensor.read(reg, buffer),
+            _ => return Err(I2CError::NoAcknowledge),
+        }
+
+        Ok(())
+    }
+}
+
+impl i2c::Read for I2CBus {
+    type Error = I2CError;
+
+    /// Register pointer of chips is not emulated, so only acknowledge is
+    /// meaningful
+    fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), I2CError> {
+        match address {
+            RTC_I2C_ADDR | SENSOR_I2C_ADDR | EEPROM_I2C_ADDR => buffer.fill(0),
+            _ => return Err(I2CError::NoAcknowledge),
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum ButtonKind {
+    Left = 0,
+    Right = 1,
+    Mode = 2,
+}
+
+/// Input pin of button. Buttons pull pins high when pressed.
+pub struct ButtonPin {
+    board: BoardRef,
+    kind: ButtonKind,
+}
+
+impl ButtonPin {
+    pub fn new(board: &BoardRef, kind: ButtonKind) -> Self {
+        Self {
+            board: board.clone(),
+            kind,
+        }
+    }
+}
+
+impl InputPin for ButtonPin {
+    type Error = Infallible;
+
+    fn is_high(&self) -> Result<bool, Infallible> {
+        Ok(self.board.borrow().buttons[self.kind as usize])
+    }
+
+    fn is_low(&self) -> Result<bool, Infallible> {
+        self.is_high().map(|is_high| !is_high)
+    }
+}
+
+/// WS2812 LED strip or ring, with the same API as driver
+pub struct LedStrip {
+    board: BoardRef,
+    output: LedOutput,
+}
+
+impl LedStrip {
+    pub fn new(board: &BoardRef, output: LedOutput) -> Self {
+        Self {
+            board: board.clone(),
+            output,
+        }
+    }
+
+    /// LEDs are set at once, so strip is never busy
+    pub fn is_busy(&self) -> bool {
+        false
+    }
+
+    pub fn display(&mut self, colors: &[ColorRGB8]) {
+        let mut board = self.board.borrow_mut();
+        let leds = match self.output {
+            LedOutput::Strip => &mut board.leds[..],
+            LedOutput::Ring => &mut board.ring[..],
+        };
+        for (led, &color) in leds.iter_mut().zip(colors) {
+            *led = color;
+        }
+    }
+}
@@ -0,0 +1,53 @@
+//! Text console. On the clock it is USB serial port, here commands are read
+//! from standard input and replies are printed to standard output.
+
+use std::{
+    io::{BufRead, Write},
+    sync::{
+        mpsc::{self, Receiver},
+        Mutex, OnceLock,
+    },
+};
+
+use heapless::String;
+
+use crate::settings::BLOB_LEN;
+
+/// Longest line accepted, enough for `settings load` with hex of the longest
+/// blob. Longer lines are dropped.
+pub const LINE_LEN: usize = "settings load ".len() + 2 * BLOB_LEN;
+
+pub type Line = String<LINE_LEN>;
+
+static LINES: OnceLock<Mutex<Receiver<Line>>> = OnceLock::new();
+
+/// Starts thread reading standard input. Reading blocks, so it can't be done
+/// from main loop.
+pub fn init() {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            let Ok(line) = line.trim_end().parse::<Line>() else {
+                continue;
+            };
+            if !line.is_empty() && sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    LINES.set(Mutex::new(receiver)).ok();
+}
+
+/// Takes line received since last call, without line terminator
+pub fn take_line() -> Option<Line> {
+    LINES.get()?.lock().unwrap().try_recv().ok()
+}
+
+pub fn write_str(text: &str) {
+    let mut stdout = std::io::stdout().lock();
+    stdout.write_all(text.as_bytes()).ok();
+    stdout.flush().ok();
+}
@@ -0,0 +1,3 @@
+//! Drivers of firmware, except for WS2812 which needs RP2040 PIO
+
+pub use lcd_clock_drivers::*;
@@ -0,0 +1,45 @@
+//! Emulated AT24C32. Contents are kept in memory until simulator exits, write
+//! cycle finishes immediately.
+
+/// Size of AT24C32
+const CAPACITY: usize = 4 * 1024;
+const PAGE_SIZE: usize = 32;
+
+pub struct Eeprom {
+    memory: Vec<u8>,
+    /// Address of next byte read or written
+    pointer: usize,
+}
+
+impl Eeprom {
+    /// Chips are shipped erased
+    pub fn new() -> Self {
+        Self {
+            memory: vec![0xFF; CAPACITY],
+            pointer: 0,
+        }
+    }
+
+    /// First two bytes set address pointer, the rest is written from it.
+    /// Like in real chip, writes wrap around within page.
+    pub fn write(&mut self, bytes: &[u8]) {
+        let [high, low, data @ ..] = bytes else {
+            return;
+        };
+        self.pointer = u16::from_be_bytes([*high, *low]) as usize % CAPACITY;
+        let page = self.pointer - self.pointer % PAGE_SIZE;
+        for (i, &byte) in data.iter().enumerate() {
+            let offset = (self.pointer - page + i) % PAGE_SIZE;
+            self.memory[page + offset] = byte;
+        }
+    }
+
+    /// Reads sequentially from address pointer, wrapping around at the end
+    /// of memory
+    pub fn read(&mut self, buffer: &mut [u8]) {
+        for byte in buffer {
+            *byte = self.memory[self.pointer];
+            self.pointer = (self.pointer + 1) % CAPACITY;
+        }
+    }
+}
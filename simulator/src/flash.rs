@@ -0,0 +1,34 @@
+//! Emulated sectors of internal flash holding climate log. Contents are kept
+//! in memory until simulator exits, erase and program finish immediately.
+
+use crate::climate_log::{LogStorage, LOG_LEN, SECTOR_SIZE};
+
+pub struct Flash {
+    memory: Vec<u8>,
+}
+
+impl Flash {
+    /// Log sectors of fresh firmware are erased
+    pub fn new() -> Self {
+        Self {
+            memory: vec![0xFF; LOG_LEN],
+        }
+    }
+}
+
+impl LogStorage for Flash {
+    fn read(&mut self, offset: usize, buf: &mut [u8]) {
+        buf.copy_from_slice(&self.memory[offset..offset + buf.len()]);
+    }
+
+    /// Like in real flash, programming only clears bits
+    fn program(&mut self, offset: usize, bytes: &[u8]) {
+        for (byte, &new) in self.memory[offset..].iter_mut().zip(bytes) {
+            *byte &= new;
+        }
+    }
+
+    fn erase_sector(&mut self, sector: usize) {
+        self.memory[sector * SECTOR_SIZE..(sector + 1) * SECTOR_SIZE].fill(0xFF);
+    }
+}
@@ -0,0 +1,9 @@
+//! Time from GPS module. Simulated board has no module attached, so nothing
+//! is ever received.
+
+use crate::drivers::nmea::Rmc;
+
+/// Last RMC sentence received since previous call
+pub fn take() -> Option<Rmc> {
+    None
+}
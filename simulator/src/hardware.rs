@@ -0,0 +1,232 @@
+//! Board layer of simulator. Has the same API as board layer of firmware, but
+//! drivers talk to emulated chips on [`Board`].
+
+use crate::{
+    bell::Bell,
+    board::{
+        Backlight, BoardRef, ButtonKind, ButtonPin, I2CBus, LedStrip, Pin, PinKind, Spi,
+        EEPROM_I2C_ADDR, RTC_I2C_ADDR, SENSOR_I2C_ADDR,
+    },
+    diagnostics::{Diagnostics, PeripheralStatus, Power},
+    drivers::{
+        at24cxx::{self, AT24Cxx, AT24CxxState},
+        bme280::{self, BME280State, BME280},
+        buttons::{Button, ButtonEvent, Debounce},
+        ds3231::{DS3231State, SquareWave, DS3231},
+        shared_i2c::{I2CProxy, SharedI2C},
+        st7789vwx6::{self, CsOrder, ST7789VWx6},
+    },
+    flash::Flash,
+    gl::{Gl, PixelShift},
+    lcd_clock::{backlight_duty, Error},
+    led_strip::LedOutput,
+    misc::{ColorRGB565, ColorRGB8},
+    timer,
+};
+
+/// The same as in firmware
+pub const DISPLAY_COUNT: usize = 6;
+/// Emulated board is wired like revision A, see [`CsOrder`]
+pub const BOARD_REVISION: &str = "SIM";
+/// The same as in firmware
+pub const SPI_CLOCK_MHZ: u32 = 40;
+pub type ST7789VWx6Ty = ST7789VWx6<(Pin, Pin, Pin, Pin, Pin), Spi, Backlight, DISPLAY_COUNT>;
+pub type I2CProxyTy = I2CProxy<'static, I2CBus>;
+pub type DS3231Ty = DS3231<I2CProxyTy>;
+pub type BME280Ty = BME280<I2CProxyTy>;
+pub type AT24CxxTy = AT24Cxx<I2CProxyTy>;
+pub type ButtonTy = Button<ButtonPin>;
+
+/// The same as in firmware
+const BUTTON_DEBOUNCE_MS: u32 = 20;
+const BME280_CONFIG: bme280::Config = bme280::Config::new();
+
+/// Hardware of simulated clock
+pub struct LcdClockHardware {
+    board: BoardRef,
+    i2c_bus: &'static SharedI2C<I2CBus>,
+    /// Peripherals found at init
+    diagnostics: Diagnostics,
+    pub rtc: DS3231Ty,
+    pub humidity_sensor: BME280Ty,
+    /// Simulated board always has EEPROM, so settings are kept there
+    pub settings_storage: AT24CxxTy,
+    /// Climate log is kept in emulated internal flash, like in firmware
+    pub log_storage: Flash,
+    /// Offset of everything drawn on displays, see PixelShift
+    pixel_shift: PixelShift,
+    pub displays: ST7789VWx6Ty,
+    /// Always present, Option only to match firmware
+    pub led_strip: Option<LedStrip>,
+    /// Simulated board has ring fitted
+    pub led_ring: Option<LedStrip>,
+    pub left: ButtonTy,
+    pub right: ButtonTy,
+    pub mode: ButtonTy,
+    /// Simulated board has no buzzer
+    pub bell: Bell<()>,
+}
+
+impl LcdClockHardware {
+    /// Brightness is in the same steps as brightness of State
+    pub fn new(board: &BoardRef, brightness: u32) -> Self {
+        let pins = (
+            Pin::new(board, PinKind::Csa1),
+            Pin::new(board, PinKind::Csa2),
+            Pin::new(board, PinKind::Csa3),
+            Pin::new(board, PinKind::Dc),
+            Pin::new(board, PinKind::Rst),
+        );
+        let displays = ST7789VWx6::new(
+            pins,
+            Spi::new(board),
+            Backlight::new(board),
+            st7789vwx6::WIDTH,
+            st7789vwx6::HEIGHT,
+            // Emulated CS decoder is the one of LCD-Clock-A
+            CsOrder::Descending,
+            backlight_duty(brightness),
+        );
+        let button = |kind| {
+            Button::new(Debounce::new(
+                ButtonPin::new(board, kind),
+                BUTTON_DEBOUNCE_MS,
+            ))
+        };
+        // Drivers keep handles to bus for the rest of the program
+        let i2c_bus: &'static _ = Box::leak(Box::new(SharedI2C::new(I2CBus::new(board))));
+        Self {
+            board: board.clone(),
+            i2c_bus,
+            diagnostics: Default::default(),
+            rtc: DS3231::new(i2c_bus.acquire(), DS3231State::new(RTC_I2C_ADDR)),
+            humidity_sensor: BME280::new(i2c_bus.acquire(), BME280State::new(SENSOR_I2C_ADDR)),
+            settings_storage: AT24Cxx::new(
+                i2c_bus.acquire(),
+                AT24CxxState::new(EEPROM_I2C_ADDR, at24cxx::Variant::AT24C32),
+            ),
+            log_storage: Flash::new(),
+            pixel_shift: Default::default(),
+            displays,
+            led_strip: Some(LedStrip::new(board, LedOutput::Strip)),
+            led_ring: Some(LedStrip::new(board, LedOutput::Ring)),
+            left: button(ButtonKind::Left),
+            right: button(ButtonKind::Right),
+            mode: button(ButtonKind::Mode),
+            bell: Bell::new(()),
+        }
+    }
+
+    pub fn init(&mut self) -> Result<(), Error> {
+        self.diagnostics.i2c_scan = self.i2c_bus.scan();
+        self.diagnostics.rtc = self.init_rtc();
+        self.diagnostics.humidity_sensor = self.init_humidity_sensor();
+        self.displays.init().map_err(Error::Display)?;
+        self.with_gl(|gl| gl.clear_all(ColorRGB565::from(ColorRGB8::black())))?;
+
+        Ok(())
+    }
+
+    fn init_rtc(&mut self) -> PeripheralStatus {
+        if !self.diagnostics.i2c_scan.contains(RTC_I2C_ADDR) {
+            return PeripheralStatus::Missing;
+        }
+
+        // Tick is taken from host clock, but square wave is enabled anyway to
+        // run the same code as firmware
+        let result = self
+            .rtc
+            .init()
+            .and_then(|()| self.rtc.enable_square_wave(SquareWave::Hz1));
+        match result {
+            Ok(()) => PeripheralStatus::Ok,
+            Err(_) => PeripheralStatus::Failed,
+        }
+    }
+
+    fn init_humidity_sensor(&mut self) -> PeripheralStatus {
+        if !self.diagnostics.i2c_scan.contains(SENSOR_I2C_ADDR) {
+            return PeripheralStatus::Missing;
+        }
+
+        match self.humidity_sensor.init(BME280_CONFIG) {
+            Ok(()) => PeripheralStatus::Ok,
+            Err(_) => PeripheralStatus::Failed,
+        }
+    }
+
+    pub fn diagnostics(&self) -> &Diagnostics {
+        &self.diagnostics
+    }
+
+    /// Simulated board has no microphone
+    pub fn has_microphone(&self) -> bool {
+        false
+    }
+
+    pub fn read_microphone(&mut self) -> Option<u16> {
+        None
+    }
+
+    /// Simulated board runs from USB and has fresh backup cell
+    pub fn measure_power(&mut self) -> Power {
+        Power {
+            vsys: 4950,
+            backup: Some(3050),
+        }
+    }
+
+    /// Host clock can't be stopped, so simulator keeps running and buttons
+    /// are polled as usual
+    pub fn dormant(&mut self) {}
+
+    /// Main loop starts firmware over on the same board, so chips keep their
+    /// contents. Climate log is lost, as emulated flash is not on the board.
+    pub fn reboot(&mut self) {
+        self.board.borrow_mut().reset = true;
+    }
+
+    /// Simulator has no bootloader to flash, so it quits like clock
+    /// disappears from USB
+    pub fn enter_bootloader(&mut self) {
+        eprintln!("firmware has entered USB bootloader");
+        std::process::exit(0);
+    }
+
+    pub fn pixel_shift(&self) -> PixelShift {
+        self.pixel_shift
+    }
+
+    /// Moves content drawn from now on. Displays have to be redrawn.
+    pub fn set_pixel_shift(&mut self, pixel_shift: PixelShift) {
+        self.pixel_shift = pixel_shift;
+    }
+
+    /// Emulated bus has no clock, displays are only initialized again like
+    /// in firmware
+    pub fn set_spi_clock(&mut self, _mhz: u32) -> Result<(), Error> {
+        self.displays
+            .reconfigure_spi(|_| ())
+            .map_err(Error::Display)
+    }
+
+    pub fn with_gl<R>(&mut self, f: impl FnOnce(&mut Gl) -> R) -> R {
+        let mut gl = Gl::new(&mut self.displays, self.pixel_shift);
+        f(&mut gl)
+    }
+
+    pub fn update_buttons(
+        &mut self,
+    ) -> (
+        Option<ButtonEvent>,
+        Option<ButtonEvent>,
+        Option<ButtonEvent>,
+    ) {
+        let now_ms = timer::now_ms();
+        (
+            self.mode.update(now_ms),
+            self.left.update(now_ms),
+            self.right.update(now_ms),
+        )
+    }
+}
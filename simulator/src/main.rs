@@ -0,0 +1,175 @@
+//! Simulator of LCD-Clock-A. Runs firmware on desktop, showing the six
+//! displays and the LED strip in a window.
+//!
+//! Firmware crate depends on RP2040 HAL and can't be built for desktop, so
+//! modules that don't touch RP2040 peripherals are compiled as part of this
+//! crate instead. Board layer ([`hardware`]) and modules that depend on
+//! interrupts or timers ([`tick`], [`timer`], [`console`], [`gps`]) are
+//! replaced with desktop versions that have the same API. Generic drivers run
+//! unchanged on top of emulated chips ([`panel`], [`rtc`], [`sensor`],
+//! [`eeprom`], [`flash`]), so the simulator exercises the same code that runs
+//! on the clock.
+//!
+//! Keys:
+//! * Left and Right arrows - left and right buttons.
+//! * Space or Enter - mode button.
+//! * Escape - quit.
+//!
+//! Commands of [`console`] are read from standard input. Mode button can't
+//! be held before window opens, so self test is run with `--self-test`
+//! argument instead.
+
+// Firmware modules are shared with the clock, which uses parts of them that
+// simulator doesn't
+#![allow(dead_code)]
+
+mod board;
+mod console;
+mod eeprom;
+mod flash;
+mod gps;
+mod hardware;
+mod panel;
+mod rtc;
+mod sensor;
+mod tick;
+mod timer;
+mod window;
+
+// Modules of firmware
+#[path = "../../src/bell.rs"]
+mod bell;
+#[path = "../../src/climate_log.rs"]
+mod climate_log;
+#[path = "../../src/command.rs"]
+mod command;
+#[path = "../../src/crash.rs"]
+mod crash;
+#[path = "../../src/diagnostics.rs"]
+mod diagnostics;
+#[path = "../../src/dimming.rs"]
+mod dimming;
+mod drivers;
+#[path = "../../src/event_log.rs"]
+mod event_log;
+#[path = "../../src/font.rs"]
+mod font;
+#[path = "../../src/gl.rs"]
+mod gl;
+#[path = "../../src/history.rs"]
+mod history;
+#[path = "../../src/images.rs"]
+mod images;
+#[path = "../../src/lcd_clock.rs"]
+mod lcd_clock;
+#[path = "../../src/led_strip.rs"]
+mod led_strip;
+#[path = "../../src/local_time.rs"]
+mod local_time;
+#[path = "../../src/marquee.rs"]
+mod marquee;
+#[path = "../../src/microphone.rs"]
+mod microphone;
+#[path = "../../src/misc.rs"]
+mod misc;
+#[path = "../../src/qr.rs"]
+mod qr;
+#[path = "../../src/reminder.rs"]
+mod reminder;
+#[path = "../../src/rtttl.rs"]
+mod rtttl;
+#[path = "../../src/scheduler.rs"]
+mod scheduler;
+#[path = "../../src/self_test.rs"]
+mod self_test;
+#[path = "../../src/settings.rs"]
+mod settings;
+#[path = "../../src/splash.rs"]
+mod splash;
+#[path = "../../src/state.rs"]
+mod state;
+#[path = "../../src/sun.rs"]
+mod sun;
+#[path = "../../src/theme.rs"]
+mod theme;
+#[path = "../../src/time_util.rs"]
+mod time_util;
+
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    board::{Board, BoardRef},
+    hardware::LcdClockHardware,
+    lcd_clock::LcdClock,
+    misc::FloatFuncs,
+    window::Window,
+};
+
+/// Brightness clock starts with, the same as in firmware
+const BRIGHTNESS: u32 = 5;
+/// Main loop of firmware runs every millisecond, window is only redrawn at
+/// ~60 frames per second
+const WINDOW_FRAME: Duration = Duration::from_millis(16);
+
+extern "C" fn sin(x: f32) -> f32 {
+    x.sin()
+}
+
+extern "C" fn exp(x: f32) -> f32 {
+    x.exp()
+}
+
+extern "C" fn ln(x: f32) -> f32 {
+    x.ln()
+}
+
+extern "C" fn atan2(y: f32, x: f32) -> f32 {
+    y.atan2(x)
+}
+
+extern "C" fn sqrt(x: f32) -> f32 {
+    x.sqrt()
+}
+
+fn main() {
+    let board = Rc::new(RefCell::new(Board::new()));
+    let mut window = Window::new().unwrap();
+    console::init();
+
+    let mut lcd_clock = boot(&board);
+    if std::env::args().any(|arg| arg == "--self-test") {
+        lcd_clock.start_self_test();
+    }
+
+    let mut last_draw = Instant::now();
+    while window.is_open() {
+        board.borrow_mut().buttons = window.buttons();
+        lcd_clock.update();
+        if std::mem::take(&mut board.borrow_mut().reset) {
+            lcd_clock = boot(&board);
+        }
+        if last_draw.elapsed() >= WINDOW_FRAME {
+            last_draw = Instant::now();
+            window.draw(&board.borrow()).unwrap();
+        }
+    }
+}
+
+/// Starts firmware on board, like power up of clock
+fn boot(board: &BoardRef) -> LcdClock {
+    let hardware = LcdClockHardware::new(board, BRIGHTNESS);
+    let float_funcs = FloatFuncs {
+        sin,
+        exp,
+        ln,
+        atan2,
+        sqrt,
+    };
+    let mut lcd_clock = LcdClock::new(hardware, float_funcs, BRIGHTNESS);
+    lcd_clock.init().unwrap();
+    lcd_clock
+}
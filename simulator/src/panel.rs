@@ -0,0 +1,122 @@
+//! Emulated ST7789VW display. Only commands used by driver are interpreted,
+//! the rest (voltages, gamma, etc.) is accepted and ignored.
+
+use crate::drivers::st7789vwx6::{HEIGHT, WIDTH};
+
+const SLPIN: u8 = 0x10;
+const SLPOUT: u8 = 0x11;
+const DISPOFF: u8 = 0x28;
+const DISPON: u8 = 0x29;
+const CASET: u8 = 0x2A;
+const RASET: u8 = 0x2B;
+const RAMWR: u8 = 0x2C;
+
+/// Panel is a 135x240 window into 240x320 frame memory of controller
+const X_OFFSET: u16 = 52;
+const Y_OFFSET: u16 = 40;
+
+pub struct Panel {
+    /// RGB565 colors of visible pixels, row by row
+    pub pixels: Vec<u16>,
+    is_sleeping: bool,
+    is_on: bool,
+
+    /// Last command received. Data that follows are its parameters.
+    command: u8,
+    /// Parameters of CASET and RASET received so far
+    params: Vec<u8>,
+    /// Window written by RAMWR, inclusive, in frame memory coordinates
+    columns: (u16, u16),
+    rows: (u16, u16),
+    /// Next pixel written by RAMWR
+    cursor: (u16, u16),
+    /// First byte of pixel being written
+    high_byte: Option<u8>,
+}
+
+impl Default for Panel {
+    fn default() -> Self {
+        Self {
+            pixels: vec![0; WIDTH as usize * HEIGHT as usize],
+            is_sleeping: true,
+            is_on: false,
+            command: 0,
+            params: Vec::new(),
+            columns: (0, 0),
+            rows: (0, 0),
+            cursor: (0, 0),
+            high_byte: None,
+        }
+    }
+}
+
+impl Panel {
+    /// Is panel initialized and showing frame memory?
+    pub fn is_on(&self) -> bool {
+        !self.is_sleeping && self.is_on
+    }
+
+    /// Hardware reset. Frame memory keeps its content.
+    pub fn reset(&mut self) {
+        let pixels = std::mem::take(&mut self.pixels);
+        *self = Self {
+            pixels,
+            ..Default::default()
+        };
+    }
+
+    pub fn command(&mut self, command: u8) {
+        self.command = command;
+        self.params.clear();
+        match command {
+            SLPIN => self.is_sleeping = true,
+            SLPOUT => self.is_sleeping = false,
+            DISPOFF => self.is_on = false,
+            DISPON => self.is_on = true,
+            RAMWR => {
+                self.cursor = (self.columns.0, self.rows.0);
+                self.high_byte = None;
+            }
+            _ => {}
+        }
+    }
+
+    pub fn data(&mut self, byte: u8) {
+        match self.command {
+            CASET | RASET => {
+                self.params.push(byte);
+                if let &[start_high, start_low, end_high, end_low] = self.params.as_slice() {
+                    let start = u16::from_be_bytes([start_high, start_low]);
+                    let end = u16::from_be_bytes([end_high, end_low]);
+                    if self.command == CASET {
+                        self.columns = (start, end);
+                    } else {
+                        self.rows = (start, end);
+                    }
+                }
+            }
+            RAMWR => match self.high_byte.take() {
+                Some(high_byte) => self.write_pixel(u16::from_be_bytes([high_byte, byte])),
+                None => self.high_byte = Some(byte),
+            },
+            _ => {}
+        }
+    }
+
+    fn write_pixel(&mut self, color: u16) {
+        let (x, y) = self.cursor;
+        if let (Some(x), Some(y)) = (x.checked_sub(X_OFFSET), y.checked_sub(Y_OFFSET)) {
+            if x < WIDTH && y < HEIGHT {
+                self.pixels[y as usize * WIDTH as usize + x as usize] = color;
+            }
+        }
+
+        self.cursor = if x < self.columns.1 {
+            (x + 1, y)
+        } else if y < self.rows.1 {
+            (self.columns.0, y + 1)
+        } else {
+            (self.columns.0, self.rows.0)
+        };
+    }
+}
@@ -0,0 +1,150 @@
+//! Emulated DS3231. Time is kept as offset from host clock. Offset starts at
+//! zero, so clock shows UTC until time is set.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SECONDS: usize = 0x00;
+const MINUTES: usize = 0x01;
+const HOURS: usize = 0x02;
+const DATE: usize = 0x04;
+const MONTH: usize = 0x05;
+const YEAR: usize = 0x06;
+const CONTROL: usize = 0x0E;
+const TEMPERATURE_MSB: usize = 0x11;
+const TEMPERATURE_LSB: usize = 0x12;
+const REGS_LEN: usize = 0x13;
+
+/// Number of timekeeping registers
+const TIME_LEN: usize = 7;
+const CENTURY_BIT: u8 = 0x80;
+/// Hours in 24 hour mode
+const HOURS_MASK: u8 = 0x3F;
+
+/// Temperature measured by rtc, in quarters of degree
+const TEMPERATURE: u16 = 4 * 24 + 1;
+
+/// Days from 1970-01-01 to 2000-01-01
+const DAYS_TO_2000: i64 = 10957;
+
+pub struct Rtc {
+    regs: [u8; REGS_LEN],
+    /// Time kept by rtc minus time of host, in seconds
+    offset: i64,
+}
+
+impl Rtc {
+    pub fn new() -> Self {
+        let mut regs = [0; REGS_LEN];
+        // Values after power up
+        regs[CONTROL] = 0x1C;
+        regs[TEMPERATURE_MSB] = (TEMPERATURE >> 2) as u8;
+        regs[TEMPERATURE_LSB] = (TEMPERATURE << 6) as u8;
+        Self { regs, offset: 0 }
+    }
+
+    /// Seconds since unix epoch as kept by rtc
+    pub fn now(&self) -> i64 {
+        host_now() + self.offset
+    }
+
+    /// Writes consecutive registers starting from reg
+    pub fn write(&mut self, reg: u8, values: &[u8]) {
+        self.update_time_regs();
+        let reg = reg as usize;
+        for (i, &value) in values.iter().enumerate() {
+            self.regs[(reg + i) % REGS_LEN] = value;
+        }
+
+        let is_time_written = (reg..reg + values.len()).any(|reg| reg % REGS_LEN < TIME_LEN);
+        if is_time_written {
+            self.offset = self.decode_time() - host_now();
+        }
+    }
+
+    /// Reads consecutive registers starting from reg
+    pub fn read(&mut self, reg: u8, dst: &mut [u8]) {
+        self.update_time_regs();
+        for (i, byte) in dst.iter_mut().enumerate() {
+            *byte = self.regs[(reg as usize + i) % REGS_LEN];
+        }
+    }
+
+    /// Rtc is always in 24 hour mode
+    fn update_time_regs(&mut self) {
+        let now = self.now();
+        let days = now.div_euclid(86400);
+        let secs = now.rem_euclid(86400);
+        let (year, month, date) = civil_from_days(days);
+        let century_bit = if year >= 2000 { CENTURY_BIT } else { 0 };
+        self.regs[SECONDS..SECONDS + TIME_LEN].copy_from_slice(&[
+            to_bcd((secs % 60) as u8),
+            to_bcd((secs / 60 % 60) as u8),
+            to_bcd((secs / 3600) as u8),
+            // 1970-01-01 is Thursday, Sunday is 1
+            ((days + 4).rem_euclid(7) + 1) as u8,
+            to_bcd(date),
+            to_bcd(month) | century_bit,
+            to_bcd((year % 100) as u8),
+        ]);
+    }
+
+    fn decode_time(&self) -> i64 {
+        let regs = &self.regs;
+        let century = if regs[MONTH] & CENTURY_BIT != 0 {
+            2000
+        } else {
+            1900
+        };
+        let year = century + from_bcd(regs[YEAR]) as i64;
+        let month = from_bcd(regs[MONTH] & !CENTURY_BIT);
+        let days = days_from_civil(year, month, from_bcd(regs[DATE]));
+        let secs = from_bcd(regs[SECONDS]) as i64
+            + from_bcd(regs[MINUTES]) as i64 * 60
+            + from_bcd(regs[HOURS] & HOURS_MASK) as i64 * 3600;
+        days * 86400 + secs
+    }
+}
+
+fn host_now() -> i64 {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    now.as_secs() as i64
+}
+
+fn to_bcd(value: u8) -> u8 {
+    ((value / 10) << 4) | (value % 10)
+}
+
+fn from_bcd(value: u8) -> u8 {
+    (value >> 4) * 10 + (value & 0x0F)
+}
+
+/// Days since unix epoch of date in Gregorian calendar. Days of month past
+/// its end roll over to next month, like in rtc.
+fn days_from_civil(year: i64, month: u8, date: u8) -> i64 {
+    let days_before_month = [0, 31, 59, 90, 120, 151, 181, 212, 243, 273, 304, 334];
+    let month = (month as usize).clamp(1, 12);
+    let is_leap_year = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let leap_day = (is_leap_year && month > 2) as i64;
+    // Leap days before year, counted from 2000 which starts 400 year cycle
+    let years = year - 2000;
+    let leap_days =
+        (years + 3).div_euclid(4) - (years + 99).div_euclid(100) + (years + 399).div_euclid(400);
+    DAYS_TO_2000 + years * 365 + leap_days + days_before_month[month - 1] + leap_day + date as i64
+        - 1
+}
+
+/// Inverse of days_from_civil
+fn civil_from_days(days: i64) -> (i64, u8, u8) {
+    let mut year = 1970 + days.div_euclid(366);
+    while days_from_civil(year + 1, 1, 1) <= days {
+        year += 1;
+    }
+
+    let mut month = 1;
+    while month < 12 && days_from_civil(year, month + 1, 1) <= days {
+        month += 1;
+    }
+
+    let date = days - days_from_civil(year, month, 1) + 1;
+    (year, month, date as u8)
+}
@@ -0,0 +1,105 @@
+//! Emulated BME280. Measurements are ready as soon as they are requested and
+//! slowly swing around room conditions, so charts have something to show.
+
+use std::time::Instant;
+
+const CHIP_ID: usize = 0xD0;
+const CTRL_MEAS: usize = 0xF4;
+const DATA: usize = 0xF7;
+
+const BME280_CHIP_ID: u8 = 0x60;
+const MODE_MASK: u8 = 0x03;
+
+/// Calibration of temperature and pressure from example in datasheet,
+/// starting at 0x88
+const CALIBRATION_TP: [u16; 12] = [
+    27504,
+    26435,
+    -1000i16 as u16,
+    36477,
+    -10685i16 as u16,
+    3024,
+    2855,
+    140,
+    -7i16 as u16,
+    15500,
+    -14600i16 as u16,
+    6000,
+];
+/// Calibration of humidity typical for real sensors. dig_H1 is at 0xA1, the
+/// rest (dig_H2..dig_H6) starts at 0xE1. dig_H4 and dig_H5 are 12 bits and
+/// share 0xE5.
+const CALIBRATION_H1: u8 = 75;
+const CALIBRATION_H: [u8; 7] = [0x6A, 0x01, 0x00, 0x13, 0x29, 0x03, 0x1E];
+
+/// Raw readings of 25°C, 1006hPa and 45%
+const ADC_T: f32 = 519888.0;
+const ADC_P: f32 = 415148.0;
+const ADC_H: f32 = 28200.0;
+/// Raw readings swing by this much (about 1°C, 2hPa and 10%)...
+const SWING_T: f32 = 3200.0;
+const SWING_P: f32 = 2000.0;
+const SWING_H: f32 = 1800.0;
+/// ...over this many seconds
+const SWING_PERIOD: f32 = 600.0;
+
+pub struct Sensor {
+    regs: [u8; 256],
+    start: Instant,
+}
+
+impl Sensor {
+    pub fn new() -> Self {
+        let mut regs = [0; 256];
+        regs[CHIP_ID] = BME280_CHIP_ID;
+        for (i, value) in CALIBRATION_TP.iter().enumerate() {
+            regs[0x88 + 2 * i..][..2].copy_from_slice(&value.to_le_bytes());
+        }
+        regs[0xA1] = CALIBRATION_H1;
+        regs[0xE1..][..CALIBRATION_H.len()].copy_from_slice(&CALIBRATION_H);
+        Self {
+            regs,
+            start: Instant::now(),
+        }
+    }
+
+    /// Writes consecutive registers starting from reg. Measurement requested
+    /// by write to ctrl_meas is done immediately, so sensor returns to sleep
+    /// mode right away.
+    pub fn write(&mut self, reg: u8, values: &[u8]) {
+        for (i, &value) in values.iter().enumerate() {
+            let reg = reg as usize + i;
+            if reg == CTRL_MEAS && value & MODE_MASK != 0 {
+                self.measure();
+                self.regs[reg] = value & !MODE_MASK;
+            } else if let Some(old) = self.regs.get_mut(reg) {
+                *old = value;
+            }
+        }
+    }
+
+    /// Reads consecutive registers starting from reg
+    pub fn read(&mut self, reg: u8, dst: &mut [u8]) {
+        for (i, byte) in dst.iter_mut().enumerate() {
+            *byte = self.regs.get(reg as usize + i).copied().unwrap_or(0);
+        }
+    }
+
+    fn measure(&mut self) {
+        let phase = self.start.elapsed().as_secs_f32() / SWING_PERIOD * std::f32::consts::TAU;
+        let t = (ADC_T + SWING_T * phase.sin()) as u32;
+        let p = (ADC_P + SWING_P * phase.cos()) as u32;
+        let h = (ADC_H - SWING_H * phase.sin()) as u32;
+        // Pressure and temperature are 20 bits, humidity is 16 bits
+        self.regs[DATA..DATA + 8].copy_from_slice(&[
+            (p >> 12) as u8,
+            (p >> 4) as u8,
+            (p << 4) as u8,
+            (t >> 12) as u8,
+            (t >> 4) as u8,
+            (t << 4) as u8,
+            (h >> 8) as u8,
+            h as u8,
+        ]);
+    }
+}
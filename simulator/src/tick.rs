@@ -0,0 +1,18 @@
+//! Second tick. On the clock it is driven by square wave from rtc, here
+//! seconds of host clock are watched instead. Rtc is emulated on top of host
+//! clock, so both change at the same moment.
+
+use std::{
+    cell::Cell,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+thread_local! {
+    static LAST_SECOND: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Has second passed since last call?
+pub fn take() -> bool {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    LAST_SECOND.with(|last_second| last_second.replace(now.as_secs()) != now.as_secs())
+}
@@ -0,0 +1,12 @@
+//! Monotonic time. On the clock it comes from system timer of RP2040, here
+//! from host clock.
+
+use std::{sync::OnceLock, time::Instant};
+
+static START: OnceLock<Instant> = OnceLock::new();
+
+/// Milliseconds since simulator started. Wraps around after 49 days, so only
+/// differences of times should be used.
+pub fn now_ms() -> u32 {
+    START.get_or_init(Instant::now).elapsed().as_millis() as u32
+}
@@ -0,0 +1,108 @@
+//! Window showing displays side by side with LED strip and ring below them
+
+use minifb::{Key, WindowOptions};
+
+use crate::{
+    board::Board,
+    drivers::st7789vwx6::{HEIGHT, WIDTH},
+    hardware::DISPLAY_COUNT,
+    misc::ColorRGB8,
+};
+
+/// Space around displays, in pixels
+const GAP: usize = 16;
+const LED_SIZE: usize = 12;
+const PANEL_W: usize = WIDTH as usize;
+const PANEL_H: usize = HEIGHT as usize;
+const WINDOW_W: usize = DISPLAY_COUNT * PANEL_W + (DISPLAY_COUNT + 1) * GAP;
+const WINDOW_H: usize = PANEL_H + 4 * GAP + 2 * LED_SIZE;
+/// Color of enclosure around displays
+const BACKGROUND: u32 = 0x202020;
+
+pub struct Window {
+    window: minifb::Window,
+    buffer: Vec<u32>,
+}
+
+impl Window {
+    pub fn new() -> Result<Self, minifb::Error> {
+        let window =
+            minifb::Window::new("LCD-Clock-A", WINDOW_W, WINDOW_H, WindowOptions::default())?;
+        Ok(Self {
+            window,
+            buffer: vec![BACKGROUND; WINDOW_W * WINDOW_H],
+        })
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.window.is_open() && !self.window.is_key_down(Key::Escape)
+    }
+
+    /// Are left, right and mode buttons pressed?
+    pub fn buttons(&self) -> [bool; 3] {
+        [
+            self.window.is_key_down(Key::Left),
+            self.window.is_key_down(Key::Right),
+            self.window.is_key_down(Key::Space) || self.window.is_key_down(Key::Enter),
+        ]
+    }
+
+    pub fn draw(&mut self, board: &Board) -> Result<(), minifb::Error> {
+        let brightness = board.backlight as u32 * 256 / u16::MAX as u32;
+        for (i, panel) in board.panels.iter().enumerate() {
+            let left = GAP + i * (PANEL_W + GAP);
+            for (y, row) in panel.pixels.chunks(PANEL_W).enumerate() {
+                let start = (GAP + y) * WINDOW_W + left;
+                let dst = &mut self.buffer[start..start + PANEL_W];
+                for (dst, &color) in dst.iter_mut().zip(row) {
+                    // Panel that is off shows nothing regardless of backlight
+                    *dst = if panel.is_on() {
+                        dim(rgb565_to_rgb888(color), brightness)
+                    } else {
+                        0
+                    };
+                }
+            }
+        }
+
+        // LEDs are spread evenly below displays, ring is unrolled below strip
+        let led_top = 2 * GAP + PANEL_H;
+        self.draw_leds(led_top, &board.leds);
+        self.draw_leds(led_top + GAP + LED_SIZE, &board.ring);
+
+        self.window
+            .update_with_buffer(&self.buffer, WINDOW_W, WINDOW_H)
+    }
+
+    fn draw_leds(&mut self, top: usize, leds: &[ColorRGB8]) {
+        let step = WINDOW_W / leds.len();
+        for (i, &led) in leds.iter().enumerate() {
+            let left = i * step + (step - LED_SIZE) / 2;
+            for y in top..top + LED_SIZE {
+                let start = y * WINDOW_W + left;
+                self.buffer[start..start + LED_SIZE].fill(rgb8_to_rgb888(led));
+            }
+        }
+    }
+}
+
+fn rgb565_to_rgb888(color: u16) -> u32 {
+    let r = (color >> 11) as u32 & 0x1F;
+    let g = (color >> 5) as u32 & 0x3F;
+    let b = color as u32 & 0x1F;
+    // Repeat high bits in low bits, so full intensity maps to 0xFF
+    let r = (r << 3) | (r >> 2);
+    let g = (g << 2) | (g >> 4);
+    let b = (b << 3) | (b >> 2);
+    (r << 16) | (g << 8) | b
+}
+
+fn rgb8_to_rgb888(color: ColorRGB8) -> u32 {
+    ((color.r as u32) << 16) | ((color.g as u32) << 8) | color.b as u32
+}
+
+/// Scales every component by brightness out of 256
+fn dim(color: u32, brightness: u32) -> u32 {
+    let component = |shift: u32| ((color >> shift & 0xFF) * brightness / 256) << shift;
+    component(16) | component(8) | component(0)
+}
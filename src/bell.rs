@@ -1,90 +1,205 @@
-use crate::hal::pwm::Slice;
-use embedded_hal::PwmPin;
-use rp_pico::hal::pwm::{SliceId, SliceMode, ValidSliceMode};
-
-/// Frequency of Low C notes
-const CL: [u16; 8] = [0, 131, 147, 165, 175, 196, 211, 248];
-/// Frequency of Middle C notes
-const CM: [u16; 8] = [0, 262, 294, 330, 349, 392, 440, 494];
-/// Frequency of High C notes
-const CH: [u16; 8] = [0, 525, 589, 661, 700, 786, 882, 990];
-
-struct Song<const N: usize> {
-    notes: [u16; N],
-    beats: [u8; N],
-}
+//! Melodies played on buzzer.
+//!
+//! [`Bell`] plays melody note by note, advancing once per frame. Alarm can ring
+//! gently: melody starts quiet and slow and rises to full volume and tempo over
+//! a few minutes.
+
+use crate::rtttl::Melody;
+
+/// Frames of silence at the end of each note, so repeated notes are heard
+/// separately. Shorter than any note.
+const GAP_FRAMES: u32 = 1;
+/// Frames of silence before melody is repeated (~1s)
+const REPEAT_FRAMES: u32 = 60;
+/// State is updated once per frame, which takes ~16ms
+const FRAMES_PER_SECOND: u32 = 60;
+const FRAMES_PER_MINUTE: u32 = 60 * FRAMES_PER_SECOND;
+/// How long melody is previewed for (~3s)
+const PREVIEW_FRAMES: u32 = 3 * FRAMES_PER_SECOND;
+/// Volume levels go from 1 (quietest) to MAX_VOLUME. Each level below it
+/// halves duty of buzzer.
+pub const MAX_VOLUME: u8 = 5;
+/// Volume and tempo ramp starts with, in percent of full
+const RAMP_START_VOLUME: u32 = 10;
+const RAMP_START_TEMPO: u32 = 50;
+
+/// Built-in melodies in [RTTTL](crate::rtttl)
+pub const SONG1: &str = "song1:d=16,o=4,b=100:\
+    e,g,8a.,e,d,8e.,g,a,c5,a,g,c,e,d,8d.,e,g,8d.,e,e,a3,a3,a3,c,d,8e,d,b3,a3,c,g3";
+pub const SONG2: &str = "song2:d=16,o=4,b=100:\
+    c,c,8c,8g3,e,e,8e,8c,c,e,8g,8g,f,e,8d.,d,e,8f,8f,e,d,8e,8c,c,e,8d,8g3,b3,d,8c.";
+pub const SONG3: &str = "song3:d=16,o=4,b=100:\
+    8c,8d,8e,g,4g,p,4p,8e,8d,8c,d,4e,p,4p,8c,8d,8e,b,4c5,p,8c5,8c5,8b,c5,8b.,8a,4g,4p,8c,8d,\
+    8e,g,4g,p,4p,8e,8d,8c,d,8c.,4p,p,8c,8d,8e,g,4c,p,4p,8c,8b3,8a3,8b3,2c,8p";
+
+/// Short beep of self test
+pub const BEEP: &str = "beep:d=4,o=6,b=120:a";
 
-const SONG1: Song<31> = Song {
-    notes: [
-        CM[3], CM[5], CM[6], CM[3], CM[2], CM[3], CM[5], CM[6], CH[1], CM[6], CM[5], CM[1], CM[3],
-        CM[2], CM[2], CM[3], CM[5], CM[2], CM[3], CM[3], CL[6], CL[6], CL[6], CM[1], CM[2], CM[3],
-        CM[2], CL[7], CL[6], CM[1], CL[5],
-    ],
-    beats: [
-        1, 1, 3, 1, 1, 3, 1, 1, 1, 1, 1, 1, 1, 1, 3, 1, 1, 3, 1, 1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1,
-    ],
-};
-
-const SONG2: Song<30> = Song {
-    notes: [
-        CM[1], CM[1], CM[1], CL[5], CM[3], CM[3], CM[3], CM[1], CM[1], CM[3], CM[5], CM[5], CM[4],
-        CM[3], CM[2], CM[2], CM[3], CM[4], CM[4], CM[3], CM[2], CM[3], CM[1], CM[1], CM[3], CM[2],
-        CL[5], CL[7], CM[2], CM[1],
-    ],
-    beats: [
-        1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 3, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 3,
-    ],
-};
-
-const SONG3: Song<49> = Song {
-    notes: [
-        CM[1], CM[2], CM[3], CM[5], CM[5], CM[0], CM[3], CM[2], CM[1], CM[2], CM[3], CM[0], CM[1],
-        CM[2], CM[3], CM[7], CH[1], CH[1], CH[1], CM[7], CH[1], CM[7], CM[6], CM[5], CM[0], CM[1],
-        CM[2], CM[3], CM[5], CM[5], CM[0], CM[3], CM[2], CM[1], CM[2], CM[1], CM[0], CM[1], CM[2],
-        CM[3], CM[5], CM[1], CM[0], CM[1], CL[7], CL[6], CL[7], CM[1], CM[0],
-    ],
-    beats: [
-        2, 2, 2, 1, 5, 4, 2, 2, 2, 1, 5, 4, 2, 2, 2, 1, 5, 2, 2, 2, 1, 3, 2, 4, 4, 2, 2, 2, 1, 5,
-        4, 2, 2, 2, 1, 3, 5, 2, 2, 2, 1, 5, 4, 2, 2, 2, 2, 8, 2,
-    ],
-};
-
-pub struct Bell<PWM, PIN> {
-    pwm: PWM,
-    pin: PIN,
+/// Melodies alarm can be rung with
+pub const MELODIES: [&str; 3] = [SONG1, SONG2, SONG3];
+
+/// Output of bell. Piezo buzzers are driven by square wave, whose frequency
+/// sets pitch and duty sets volume.
+pub trait Buzzer {
+    /// Starts tone of given frequency in Hz. Volume is fraction of full volume
+    /// out of u16::MAX.
+    fn tone(&mut self, freq: u16, volume: u16);
+    fn silence(&mut self);
+
+    /// Is there anything to play on? Reported by self test.
+    fn is_fitted(&self) -> bool {
+        true
+    }
 }
 
-impl<PWM, PIN> Bell<PWM, PIN>
-where
-    PWM: PwmPin<Duty = u16>,
-{
-    pub fn beep(&mut self, freq: u32) {
-        // let max = set_pwm_period(&mut self.slice, self.sysclk, freq);
-        // self.pwm.set_duty(max);
-        // self.pwm.set_duty(0);
+/// Boards without buzzer
+impl Buzzer for () {
+    fn tone(&mut self, _freq: u16, _volume: u16) {}
+
+    fn silence(&mut self) {}
+
+    fn is_fitted(&self) -> bool {
+        false
     }
 }
 
-pub fn set_pwm_period<I: SliceId, M: SliceMode + ValidSliceMode<I>>(
-    slice: &mut Slice<I, M>,
-    sysclk: u32,
-    freq: u32,
-) -> u16 {
-    // div_frac = 0
-    // ph_correct = 0
-    // div_int = 125
-    // freq = [0, 1000]
-    // period = [0, 125_000]
-    // period = (top + 1) * 125
-    // (top + 1) = [0, 1000]
-    // top = period / 125 - 1
-    let period = sysclk / freq;
-    let div_int = 125;
-    let top = (period / div_int) as u16;
-    slice.set_top(top);
-    slice.clr_ph_correct();
-    slice.set_div_int(125);
-    slice.set_div_frac(0);
-
-    top
+/// Plays melodies on buzzer in a loop until stopped
+pub struct Bell<B> {
+    buzzer: B,
+    /// Melody being played, empty if bell is silent
+    melody: Melody,
+    /// Index of next note
+    note: usize,
+    /// Frames left until next note
+    note_frames: u32,
+    /// Frames of silence at the end of current note
+    rest_frames: u32,
+    /// Frames passed since bell started ringing
+    ring_frames: u32,
+    /// Frames over which volume and tempo rise to full
+    ramp_frames: u32,
+    /// Frames after which bell stops by itself, None if it rings until
+    /// stopped
+    length_frames: Option<u32>,
+    /// Volume level, from 1 to MAX_VOLUME
+    volume: u8,
+}
+
+impl<B: Buzzer> Bell<B> {
+    pub fn new(buzzer: B) -> Self {
+        Self {
+            buzzer,
+            melody: Melody::new(),
+            note: 0,
+            note_frames: 0,
+            rest_frames: 0,
+            ring_frames: 0,
+            ramp_frames: 0,
+            length_frames: None,
+            volume: MAX_VOLUME,
+        }
+    }
+
+    /// Starts playing melody from the beginning. It starts quiet and slow and
+    /// reaches full volume and tempo in given number of minutes, 0 plays it
+    /// at full at once.
+    pub fn ring(&mut self, melody: Melody, ramp_mins: u8) {
+        self.melody = melody;
+        self.note = 0;
+        self.note_frames = 0;
+        self.ring_frames = 0;
+        self.ramp_frames = ramp_mins as u32 * FRAMES_PER_MINUTE;
+        self.length_frames = None;
+    }
+
+    /// Plays beginning of melody without ramp, e.g. while it is chosen
+    pub fn preview(&mut self, melody: Melody) {
+        self.ring(melody, 0);
+        self.length_frames = Some(PREVIEW_FRAMES);
+    }
+
+    pub fn stop(&mut self) {
+        self.melody.clear();
+        self.buzzer.silence();
+    }
+
+    pub fn volume(&self) -> u8 {
+        self.volume
+    }
+
+    /// Sets volume level notes are played with from next one on. Levels out
+    /// of 1 to MAX_VOLUME are ignored.
+    pub fn set_volume(&mut self, volume: u8) {
+        if (1..=MAX_VOLUME).contains(&volume) {
+            self.volume = volume;
+        }
+    }
+
+    /// Stops preview, alarm keeps ringing
+    pub fn stop_preview(&mut self) {
+        if self.length_frames.is_some() {
+            self.stop();
+        }
+    }
+
+    pub fn has_buzzer(&self) -> bool {
+        self.buzzer.is_fitted()
+    }
+
+    pub fn is_ringing(&self) -> bool {
+        !self.melody.is_empty()
+    }
+
+    /// Plays next note once current one is over. Has to be called every
+    /// frame.
+    pub fn update(&mut self) {
+        let Some(&note) = self.melody.get(self.note) else {
+            return;
+        };
+
+        self.ring_frames = self.ring_frames.saturating_add(1);
+        if self
+            .length_frames
+            .is_some_and(|length| self.ring_frames > length)
+        {
+            self.stop();
+            return;
+        }
+        if self.note_frames > 0 {
+            self.note_frames -= 1;
+            if self.note_frames == self.rest_frames {
+                self.buzzer.silence();
+            }
+            if self.note_frames > 0 {
+                return;
+            }
+        }
+
+        if note.freq == 0 {
+            self.buzzer.silence();
+        } else {
+            let full = u16::MAX as u32 >> (MAX_VOLUME - self.volume);
+            let volume = full * self.ramp(RAMP_START_VOLUME) / 100;
+            self.buzzer.tone(note.freq, volume as u16);
+        }
+        let frames = note.millis as u32 * FRAMES_PER_SECOND / 1000;
+        let frames = frames * 100 / self.ramp(RAMP_START_TEMPO);
+        let sound_frames = frames.saturating_sub(GAP_FRAMES).max(1);
+        self.note = (self.note + 1) % self.melody.len();
+        self.rest_frames = if self.note == 0 {
+            GAP_FRAMES + REPEAT_FRAMES
+        } else {
+            GAP_FRAMES
+        };
+        self.note_frames = sound_frames + self.rest_frames;
+    }
+
+    /// Percentage rising linearly from start to 100 over ramp
+    fn ramp(&self, start: u32) -> u32 {
+        if self.ring_frames >= self.ramp_frames {
+            return 100;
+        }
+
+        start + (100 - start) * self.ring_frames / self.ramp_frames
+    }
 }
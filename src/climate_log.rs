@@ -0,0 +1,303 @@
+//! Log of room climate kept in flash over weeks.
+//!
+//! Every [`PERIOD_MINS`] minutes a sample of humidity sensor is appended to
+//! the log together with date and time it was taken at. Unlike
+//! [`history`](crate::history) log survives power cycles, and it is exported
+//! over USB console as CSV with `log dump`.
+//!
+//! Log is a ring of flash sectors split into slots. Each slot holds an entry
+//! serialized with [postcard](https://docs.rs/postcard), prefixed by its
+//! length. Bits of flash can only be cleared until sector is erased, so
+//! entries are programmed one by one into erased slots, and a sector is
+//! erased right before log wraps around into it, dropping the oldest entries.
+//! Slot to write next is found at init as the first empty slot that follows
+//! a written one.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    drivers::ds3231::{Date, Time},
+    history::Sample,
+};
+
+/// Sensor is logged every this many minutes
+pub const PERIOD_MINS: u8 = 10;
+/// Smallest erasable unit of flash
+pub const SECTOR_SIZE: usize = 4096;
+/// Sectors taken by log. With 256 slots in each they cover 4 weeks.
+pub const SECTOR_COUNT: usize = 16;
+/// Bytes of flash taken by log
+pub const LOG_LEN: usize = SECTOR_COUNT * SECTOR_SIZE;
+/// Length byte followed by entry, enough for its longest encoding
+const SLOT_LEN: usize = 16;
+const SLOTS_PER_SECTOR: usize = SECTOR_SIZE / SLOT_LEN;
+const SLOT_COUNT: usize = SECTOR_COUNT * SLOTS_PER_SECTOR;
+/// Length byte of erased slot
+const EMPTY: u8 = 0xFF;
+
+/// Region of flash holding log. Offsets are from its start.
+pub trait LogStorage {
+    fn read(&mut self, offset: usize, buf: &mut [u8]);
+    /// Programs bytes that have been erased. They never cross page of flash.
+    fn program(&mut self, offset: usize, bytes: &[u8]);
+    /// Sets all bytes of sector to 0xFF
+    fn erase_sector(&mut self, sector: usize);
+}
+
+/// Sample taken at given date and time
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    pub year: u16,
+    pub month: u8,
+    pub date: u8,
+    pub hours: u8,
+    pub mins: u8,
+    pub sample: Sample,
+}
+
+impl Entry {
+    pub fn new(date: Date, time: Time, sample: Sample) -> Self {
+        Self {
+            year: date.year,
+            month: date.month,
+            date: date.date,
+            hours: time.hours,
+            mins: time.mins,
+            sample,
+        }
+    }
+}
+
+/// Slot of log as read from flash
+enum Slot {
+    Empty,
+    Entry(Entry),
+    /// Neither erased nor holding entry, e.g. left by other firmware
+    Corrupted,
+}
+
+pub struct ClimateLog {
+    /// Slot next entry is written to
+    next: usize,
+    /// Minute last entry was written at. Used to write only one entry during
+    /// that minute.
+    last_entry_mins: Option<u8>,
+}
+
+impl ClimateLog {
+    pub fn new() -> Self {
+        Self {
+            next: 0,
+            last_entry_mins: None,
+        }
+    }
+
+    /// Finds where log left off. Log that has corrupted slots is erased, as
+    /// its slots can't be told apart.
+    pub fn init(&mut self, storage: &mut impl LogStorage) {
+        let mut is_prev_written = matches!(read_slot(storage, SLOT_COUNT - 1), Slot::Entry(_));
+        self.next = 0;
+        for index in 0..SLOT_COUNT {
+            match read_slot(storage, index) {
+                Slot::Empty => {
+                    if is_prev_written {
+                        self.next = index;
+                    }
+                    is_prev_written = false;
+                }
+                Slot::Entry(_) => is_prev_written = true,
+                Slot::Corrupted => {
+                    for sector in 0..SECTOR_COUNT {
+                        storage.erase_sector(sector);
+                    }
+                    self.next = 0;
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Should new entry be written at given time?
+    pub fn is_due(&self, time: Time) -> bool {
+        time.mins.is_multiple_of(PERIOD_MINS) && self.last_entry_mins != Some(time.mins)
+    }
+
+    pub fn push(&mut self, storage: &mut impl LogStorage, entry: Entry) {
+        self.last_entry_mins = Some(entry.mins);
+
+        let mut slot = [EMPTY; SLOT_LEN];
+        let Ok(len) = postcard::to_slice(&entry, &mut slot[1..]).map(|bytes| bytes.len()) else {
+            return;
+        };
+        slot[0] = len as u8;
+
+        // Sector is erased before log wraps around into it, so there is
+        // always an empty slot between the newest and the oldest entry
+        if self.next.is_multiple_of(SLOTS_PER_SECTOR) {
+            storage.erase_sector(self.next / SLOTS_PER_SECTOR);
+        }
+        storage.program(self.next * SLOT_LEN, &slot);
+        self.next = (self.next + 1) % SLOT_COUNT;
+    }
+
+    /// Reads entries from the oldest to the newest a few at a time, see
+    /// [`Dump`]
+    pub fn dump(&self) -> Dump {
+        Dump {
+            index: self.next,
+            remaining: SLOT_COUNT,
+        }
+    }
+}
+
+impl Default for ClimateLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Position of reading log. Console can only take a few lines at a time, so
+/// entries are read over many frames.
+pub struct Dump {
+    /// Slot read next
+    index: usize,
+    /// Slots not read yet
+    remaining: usize,
+}
+
+impl Dump {
+    /// Returns the next entry, None once all of them have been read
+    pub fn next_entry(&mut self, storage: &mut impl LogStorage) -> Option<Entry> {
+        while self.remaining > 0 {
+            let slot = read_slot(storage, self.index);
+            self.index = (self.index + 1) % SLOT_COUNT;
+            self.remaining -= 1;
+            if let Slot::Entry(entry) = slot {
+                return Some(entry);
+            }
+        }
+        None
+    }
+}
+
+fn read_slot(storage: &mut impl LogStorage, index: usize) -> Slot {
+    let mut slot = [0; SLOT_LEN];
+    storage.read(index * SLOT_LEN, &mut slot);
+    let len = slot[0] as usize;
+    if len == EMPTY as usize {
+        return Slot::Empty;
+    }
+
+    match slot.get(1..1 + len).map(postcard::from_bytes) {
+        Some(Ok(entry)) => Slot::Entry(entry),
+        _ => Slot::Corrupted,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Erased flash in memory, programming clears bits like real one
+    struct Memory([u8; LOG_LEN]);
+
+    impl LogStorage for Memory {
+        fn read(&mut self, offset: usize, buf: &mut [u8]) {
+            buf.copy_from_slice(&self.0[offset..offset + buf.len()]);
+        }
+
+        fn program(&mut self, offset: usize, bytes: &[u8]) {
+            for (byte, &new) in self.0[offset..].iter_mut().zip(bytes) {
+                *byte &= new;
+            }
+        }
+
+        fn erase_sector(&mut self, sector: usize) {
+            self.0[sector * SECTOR_SIZE..(sector + 1) * SECTOR_SIZE].fill(EMPTY);
+        }
+    }
+
+    fn entry(mins: u16) -> Entry {
+        Entry {
+            year: 2026,
+            month: 10,
+            date: 16,
+            hours: (mins / 60) as u8,
+            mins: (mins % 60) as u8,
+            sample: Sample {
+                temperature: -1234,
+                humidity: 4567,
+                pressure: 10132,
+            },
+        }
+    }
+
+    fn entries(log: &ClimateLog, storage: &mut Memory) -> Vec<Entry> {
+        let mut dump = log.dump();
+        core::iter::from_fn(|| dump.next_entry(storage)).collect()
+    }
+
+    #[test]
+    fn test_push_and_dump() {
+        let mut storage = Memory([EMPTY; LOG_LEN]);
+        let mut log = ClimateLog::new();
+        log.init(&mut storage);
+        assert!(entries(&log, &mut storage).is_empty());
+
+        log.push(&mut storage, entry(0));
+        log.push(&mut storage, entry(10));
+        assert_eq!(entries(&log, &mut storage), [entry(0), entry(10)]);
+
+        // Log continues where it left off after reboot
+        let mut log = ClimateLog::new();
+        log.init(&mut storage);
+        log.push(&mut storage, entry(20));
+        assert_eq!(
+            entries(&log, &mut storage),
+            [entry(0), entry(10), entry(20)]
+        );
+    }
+
+    #[test]
+    fn test_wrap_around() {
+        let mut storage = Memory([EMPTY; LOG_LEN]);
+        let mut log = ClimateLog::new();
+        log.init(&mut storage);
+        for i in 0..SLOT_COUNT + 1 {
+            log.push(&mut storage, entry(i as u16));
+        }
+
+        // The oldest sector was erased to make room for the last entry
+        let expected: Vec<_> = (SLOTS_PER_SECTOR..SLOT_COUNT + 1)
+            .map(|i| entry(i as u16))
+            .collect();
+        assert_eq!(entries(&log, &mut storage), expected);
+
+        let mut log = ClimateLog::new();
+        log.init(&mut storage);
+        assert_eq!(entries(&log, &mut storage), expected);
+    }
+
+    #[test]
+    fn test_corrupted() {
+        let mut storage = Memory([0; LOG_LEN]);
+        let mut log = ClimateLog::new();
+        log.init(&mut storage);
+        assert!(storage.0.iter().all(|&byte| byte == EMPTY));
+    }
+
+    #[test]
+    fn test_is_due() {
+        let mut storage = Memory([EMPTY; LOG_LEN]);
+        let mut log = ClimateLog::new();
+        let time = |mins| Time {
+            hours: 12,
+            mins,
+            secs: 0,
+        };
+        assert!(log.is_due(time(30)));
+        assert!(!log.is_due(time(35)));
+        log.push(&mut storage, entry(12 * 60 + 30));
+        assert!(!log.is_due(time(30)));
+    }
+}
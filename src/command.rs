@@ -0,0 +1,548 @@
+//! Commands received over [`console`](crate::console).
+//!
+//! Each command is a single line: name followed by arguments separated by
+//! spaces. Supported commands:
+//! * `settime <iso8601>` - sets date and time, e.g.
+//!   `settime 2023-04-01T12:30:00`. Host clock can be synced with
+//!   `echo "settime $(date -Iseconds)" > /dev/ttyACM0`.
+//! * `timezone <+HH:MM>` - sets offset of local time from UTC, which is
+//!   applied to time received from GPS, e.g. `timezone -03:30`. Minutes must
+//!   be a multiple of 15.
+//! * `night <HH> <HH>` - sets hours between which clock sleeps, e.g.
+//!   `night 23 07`. `night off` keeps clock awake all the time. `night sun`
+//!   makes night last from the hour after sunset to the hour of sunrise,
+//!   which needs location.
+//! * `location <latitude> <longitude>` - sets location for sunrise and
+//!   sunset in degrees with up to 2 decimals, north and east positive, e.g.
+//!   `location 52.52 13.40`. `location off` forgets it. Sunrise and sunset
+//!   are in local time given by `timezone`.
+//! * `reminder add <MM-DD>` - celebrates day of year, e.g. birthday with
+//!   `reminder add 10-16`. Up to 4 reminders are kept. `reminder remove
+//!   <MM-DD>` forgets one and `reminder list` prints all of them.
+//! * `brightness <screen> <level>` - sets brightness of `time` screens,
+//!   `menu` or `night` from 0 to 9, e.g. `brightness night 1`. Menu and night
+//!   follow brightness of time screens again after `unset`.
+//! * `dimming <HH> <HH> <percent>` - dims backlight of regular screens and
+//!   LEDs over an hour from the first hour to percent of their brightness,
+//!   and brings them back over an hour from the second one, e.g.
+//!   `dimming 20 07 30`. `dimming off` keeps brightness the same all day.
+//! * `tint <HH> <HH> <percent>` - blends colors of LEDs towards warm amber
+//!   by percent between two hours, e.g. `tint 21 07 80`. `tint off` keeps
+//!   colors as they are.
+//! * `spi [night] <MHz>` - sets clock of display SPI by day or at night
+//!   from 1 to 62 MHz, e.g. `spi 20` for panels that can't keep up with
+//!   default 40 MHz or `spi night 10`. Displays are initialized again on
+//!   change. `unset` restores default clock, or clock by day at night.
+//! * `idle <seconds>` - sets seconds of inactivity after which menus and
+//!   settings go back to time screen, from 1 to 3600, e.g. `idle 120`.
+//!   `idle off` keeps them open until mode is pressed.
+//! * `alarm ramp <minutes>` - sets minutes alarm takes to rise from quiet and
+//!   slow to full volume and tempo, from 0 to 30, e.g. `alarm ramp 5`. `alarm
+//!   ramp 0` rings at full at once.
+//! * `alarm sunrise <minutes>` - lights LED strip like sunrise, from deep red
+//!   to warm white, and raises backlight over minutes ahead of alarm, from 1
+//!   to 60, e.g. `alarm sunrise 20`. `alarm sunrise off` turns it off.
+//! * `settings dump` - prints all settings as hex encoded
+//!   [blob](crate::settings::Settings::to_blob), e.g. to back them up with
+//!   `head -n 1 /dev/ttyACM0 > backup & echo "settings dump" > /dev/ttyACM0`.
+//! * `settings load <hex>` - replaces all settings with dumped ones, e.g.
+//!   `echo "settings load $(cat backup)" > /dev/ttyACM0`.
+//! * `log dump` - prints [climate log](crate::climate_log) as CSV with
+//!   time, temperature, humidity and pressure, e.g.
+//!   `cat /dev/ttyACM0 > climate.csv & echo "log dump" > /dev/ttyACM0`.
+//!   Dump ends with `ok`.
+//! * `log events` - prints recent [events and errors](crate::event_log),
+//!   oldest first, with uptime in seconds they last happened at and number
+//!   of times they happened in a row.
+//! * `log crash` - prints [report](crate::crash) of crash that has reset the
+//!   clock: panic or fault, address of faulting instruction and message of
+//!   panic.
+//! * `message <text>` - scrolls ASCII text of up to 64 characters across
+//!   displays a few times, e.g. `message Dinner is ready`. Any button
+//!   dismisses it.
+//! * `bootloader` - restarts into USB bootloader of RP2040, which shows up as
+//!   a drive new firmware is copied to, the same as if BOOTSEL was held.
+
+use heapless::String;
+
+use crate::{
+    dimming::Dimming,
+    drivers::ds3231::{days_in_month, Date, Time},
+    led_strip::NightTint,
+    marquee::MARQUEE_LEN,
+    reminder::Reminder,
+    settings::{Settings, BLOB_LEN},
+    state::{
+        BrightnessScreen, MAX_ALARM_RAMP_MINS, MAX_BRIGHTNESS, MAX_IDLE_TIMEOUT_SECS,
+        MAX_SPI_CLOCK, MAX_SUNRISE_MINS,
+    },
+    sun::Location,
+};
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Command {
+    SetTime(Date, Time),
+    /// Offset from UTC in quarters of hour
+    SetTimezone(i8),
+    /// Start and end hour of night, None if clock never sleeps
+    Night(Option<(u8, u8)>),
+    /// Night lasts from sunset to sunrise
+    NightFromSun,
+    /// Location for sunrise and sunset, None to forget it
+    Location(Option<Location>),
+    AddReminder(Reminder),
+    RemoveReminder(Reminder),
+    ListReminders,
+    /// Brightness level of screen, None to follow time screens
+    Brightness(BrightnessScreen, Option<u32>),
+    /// Curve of dimming over day, None to turn it off
+    Dimming(Option<Dimming>),
+    /// Hours and strength of warm tint of LEDs, None to turn it off
+    NightTint(Option<NightTint>),
+    /// Clock of display SPI in MHz, at night if flag is set. None restores
+    /// default clock, or clock by day at night.
+    SpiClock(bool, Option<u8>),
+    /// Seconds of inactivity after which menus are left, None if they never
+    /// are
+    IdleTimeout(Option<u16>),
+    /// Minutes bell takes to reach full volume and tempo
+    AlarmRamp(u8),
+    /// Minutes of sunrise ahead of alarm, None to turn it off
+    Sunrise(Option<u8>),
+    DumpSettings,
+    LoadSettings(Settings),
+    DumpLog,
+    ListEvents,
+    ShowCrash,
+    /// Text scrolled across displays
+    Message(String<MARQUEE_LEN>),
+    Bootloader,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ParseError {
+    UnknownCommand,
+    MissingArgument,
+    InvalidDateTime,
+    InvalidTimezone,
+    InvalidNight,
+    InvalidLocation,
+    InvalidReminder,
+    InvalidBrightness,
+    InvalidDimming,
+    InvalidTint,
+    InvalidSpiClock,
+    InvalidIdleTimeout,
+    InvalidAlarm,
+    InvalidSettings,
+    InvalidLog,
+    InvalidMessage,
+}
+
+impl ParseError {
+    pub fn message(self) -> &'static str {
+        match self {
+            Self::UnknownCommand => "unknown command",
+            Self::MissingArgument => "missing argument",
+            Self::InvalidDateTime => "invalid date and time, expected YYYY-MM-DDTHH:MM:SS",
+            Self::InvalidTimezone => "invalid timezone, expected +HH:MM",
+            Self::InvalidNight => "invalid night, expected two different hours HH HH, off or sun",
+            Self::InvalidLocation => {
+                "invalid location, expected latitude and longitude in degrees or off"
+            }
+            Self::InvalidReminder => "invalid reminder, expected add or remove and MM-DD, or list",
+            Self::InvalidBrightness => {
+                "invalid brightness, expected time, menu or night and 0-9 or unset"
+            }
+            Self::InvalidDimming => {
+                "invalid dimming, expected two different hours HH HH and 1-100 percent or off"
+            }
+            Self::InvalidTint => {
+                "invalid tint, expected two different hours HH HH and 1-100 percent or off"
+            }
+            Self::InvalidSpiClock => "invalid spi clock, expected night or 1-62 MHz or unset",
+            Self::InvalidIdleTimeout => "invalid idle timeout, expected 1-3600 seconds or off",
+            Self::InvalidAlarm => {
+                "invalid alarm, expected ramp and 0-30 or sunrise and 1-60 minutes or off"
+            }
+            Self::InvalidSettings => "invalid settings, expected dump or load and hex of dump",
+            Self::InvalidLog => "invalid log, expected dump, events or crash",
+            Self::InvalidMessage => "invalid message, expected ASCII text up to 64 characters",
+        }
+    }
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Self, ParseError> {
+        let mut words = line.split_ascii_whitespace();
+        match words.next() {
+            Some("settime") => {
+                let arg = words.next().ok_or(ParseError::MissingArgument)?;
+                let (date, time) = parse_iso8601(arg).ok_or(ParseError::InvalidDateTime)?;
+                Ok(Self::SetTime(date, time))
+            }
+            Some("timezone") => {
+                let arg = words.next().ok_or(ParseError::MissingArgument)?;
+                let offset = parse_utc_offset(arg).ok_or(ParseError::InvalidTimezone)?;
+                Ok(Self::SetTimezone(offset))
+            }
+            Some("night") => {
+                let start = words.next().ok_or(ParseError::MissingArgument)?;
+                match start {
+                    "off" => return Ok(Self::Night(None)),
+                    "sun" => return Ok(Self::NightFromSun),
+                    _ => {}
+                }
+                let end = words.next().ok_or(ParseError::MissingArgument)?;
+                let start = parse_hour(start).ok_or(ParseError::InvalidNight)?;
+                let end = parse_hour(end).ok_or(ParseError::InvalidNight)?;
+                if start == end {
+                    return Err(ParseError::InvalidNight);
+                }
+                Ok(Self::Night(Some((start, end))))
+            }
+            Some("location") => {
+                let latitude = words.next().ok_or(ParseError::MissingArgument)?;
+                if latitude == "off" {
+                    return Ok(Self::Location(None));
+                }
+                let longitude = words.next().ok_or(ParseError::MissingArgument)?;
+                let latitude = parse_degrees(latitude).ok_or(ParseError::InvalidLocation)?;
+                let longitude = parse_degrees(longitude).ok_or(ParseError::InvalidLocation)?;
+                let location =
+                    Location::new(latitude, longitude).ok_or(ParseError::InvalidLocation)?;
+                Ok(Self::Location(Some(location)))
+            }
+            Some("reminder") => {
+                let action = words.next().ok_or(ParseError::MissingArgument)?;
+                if action == "list" {
+                    return Ok(Self::ListReminders);
+                }
+                let arg = words.next().ok_or(ParseError::MissingArgument)?;
+                let reminder = parse_reminder(arg).ok_or(ParseError::InvalidReminder)?;
+                match action {
+                    "add" => Ok(Self::AddReminder(reminder)),
+                    "remove" => Ok(Self::RemoveReminder(reminder)),
+                    _ => Err(ParseError::InvalidReminder),
+                }
+            }
+            Some("brightness") => {
+                let screen = words.next().ok_or(ParseError::MissingArgument)?;
+                let level = words.next().ok_or(ParseError::MissingArgument)?;
+                let screen = match screen {
+                    "time" => BrightnessScreen::Time,
+                    "menu" => BrightnessScreen::Menu,
+                    "night" => BrightnessScreen::Night,
+                    _ => return Err(ParseError::InvalidBrightness),
+                };
+                let level = match level {
+                    "unset" if screen != BrightnessScreen::Time => None,
+                    _ => Some(parse_brightness(level).ok_or(ParseError::InvalidBrightness)?),
+                };
+                Ok(Self::Brightness(screen, level))
+            }
+            Some("dimming") => {
+                let start = words.next().ok_or(ParseError::MissingArgument)?;
+                if start == "off" {
+                    return Ok(Self::Dimming(None));
+                }
+                let end = words.next().ok_or(ParseError::MissingArgument)?;
+                let level = words.next().ok_or(ParseError::MissingArgument)?;
+                let start = parse_hour(start).ok_or(ParseError::InvalidDimming)?;
+                let end = parse_hour(end).ok_or(ParseError::InvalidDimming)?;
+                let level = parse_percent(level).ok_or(ParseError::InvalidDimming)?;
+                let dimming = Dimming::new(start, end, level).ok_or(ParseError::InvalidDimming)?;
+                Ok(Self::Dimming(Some(dimming)))
+            }
+            Some("tint") => {
+                let start = words.next().ok_or(ParseError::MissingArgument)?;
+                if start == "off" {
+                    return Ok(Self::NightTint(None));
+                }
+                let end = words.next().ok_or(ParseError::MissingArgument)?;
+                let strength = words.next().ok_or(ParseError::MissingArgument)?;
+                let start = parse_hour(start).ok_or(ParseError::InvalidTint)?;
+                let end = parse_hour(end).ok_or(ParseError::InvalidTint)?;
+                let strength = parse_percent(strength).ok_or(ParseError::InvalidTint)?;
+                let tint = NightTint::new(start, end, strength).ok_or(ParseError::InvalidTint)?;
+                Ok(Self::NightTint(Some(tint)))
+            }
+            Some("spi") => {
+                let mut clock = words.next().ok_or(ParseError::MissingArgument)?;
+                let is_night = clock == "night";
+                if is_night {
+                    clock = words.next().ok_or(ParseError::MissingArgument)?;
+                }
+                let mhz = match clock {
+                    "unset" => None,
+                    _ => Some(parse_spi_clock(clock).ok_or(ParseError::InvalidSpiClock)?),
+                };
+                Ok(Self::SpiClock(is_night, mhz))
+            }
+            Some("idle") => {
+                let secs = words.next().ok_or(ParseError::MissingArgument)?;
+                let secs = match secs {
+                    "off" => None,
+                    _ => Some(parse_idle_timeout(secs).ok_or(ParseError::InvalidIdleTimeout)?),
+                };
+                Ok(Self::IdleTimeout(secs))
+            }
+            Some("alarm") => match words.next().ok_or(ParseError::MissingArgument)? {
+                "ramp" => {
+                    let mins = words.next().ok_or(ParseError::MissingArgument)?;
+                    let mins = parse_alarm_ramp(mins).ok_or(ParseError::InvalidAlarm)?;
+                    Ok(Self::AlarmRamp(mins))
+                }
+                "sunrise" => {
+                    let mins = words.next().ok_or(ParseError::MissingArgument)?;
+                    let mins = match mins {
+                        "off" => None,
+                        _ => Some(parse_sunrise(mins).ok_or(ParseError::InvalidAlarm)?),
+                    };
+                    Ok(Self::Sunrise(mins))
+                }
+                _ => Err(ParseError::InvalidAlarm),
+            },
+            Some("settings") => match words.next().ok_or(ParseError::MissingArgument)? {
+                "dump" => Ok(Self::DumpSettings),
+                "load" => {
+                    let arg = words.next().ok_or(ParseError::MissingArgument)?;
+                    let mut blob = [0; BLOB_LEN];
+                    let blob = parse_hex(arg, &mut blob).ok_or(ParseError::InvalidSettings)?;
+                    let settings = Settings::from_blob(blob).ok_or(ParseError::InvalidSettings)?;
+                    Ok(Self::LoadSettings(settings))
+                }
+                _ => Err(ParseError::InvalidSettings),
+            },
+            Some("log") => match words.next().ok_or(ParseError::MissingArgument)? {
+                "dump" => Ok(Self::DumpLog),
+                "events" => Ok(Self::ListEvents),
+                "crash" => Ok(Self::ShowCrash),
+                _ => Err(ParseError::InvalidLog),
+            },
+            Some("message") => {
+                // Text is the rest of line with its spaces
+                let text = line
+                    .trim()
+                    .split_once(char::is_whitespace)
+                    .map_or("", |(_, text)| text.trim_start());
+                if text.is_empty() {
+                    return Err(ParseError::MissingArgument);
+                }
+                if !text.is_ascii() {
+                    return Err(ParseError::InvalidMessage);
+                }
+                let text = text.parse().map_err(|_| ParseError::InvalidMessage)?;
+                Ok(Self::Message(text))
+            }
+            Some("bootloader") => Ok(Self::Bootloader),
+            _ => Err(ParseError::UnknownCommand),
+        }
+    }
+}
+
+/// Parses date and time in ISO 8601 extended format:
+/// `YYYY-MM-DDTHH:MM:SS`, optionally followed by fraction of second and
+/// offset from UTC (`Z` or `+HH:MM`). Both are ignored, as clock shows time
+/// as it is written, i.e. local time of host.
+pub fn parse_iso8601(text: &str) -> Option<(Date, Time)> {
+    let (date, time) = text.split_once(['T', 't'])?;
+
+    let mut fields = date.split('-');
+    let year = parse_number(fields.next()?, 4)?;
+    let month = parse_number(fields.next()?, 2)? as u8;
+    let date = parse_number(fields.next()?, 2)? as u8;
+    if fields.next().is_some() {
+        return None;
+    }
+
+    // Seconds end where fraction or offset starts
+    let time = time.split(['.', ',', 'Z', 'z', '+', '-']).next()?;
+    let mut fields = time.split(':');
+    let hours = parse_number(fields.next()?, 2)? as u8;
+    let mins = parse_number(fields.next()?, 2)? as u8;
+    let secs = parse_number(fields.next()?, 2)? as u8;
+    if fields.next().is_some() {
+        return None;
+    }
+
+    let is_valid = (1..=12).contains(&month)
+        && (1..=days_in_month(year, month)).contains(&date)
+        && hours < 24
+        && mins < 60
+        && secs < 60;
+    is_valid.then_some((Date { year, month, date }, Time { hours, mins, secs }))
+}
+
+/// Parses offset from UTC `+HH:MM` or `-HH:MM` into quarters of hour
+fn parse_utc_offset(text: &str) -> Option<i8> {
+    let sign = match text.get(..1)? {
+        "+" => 1,
+        "-" => -1,
+        _ => return None,
+    };
+    let (hours, mins) = text[1..].split_once(':')?;
+    let hours = parse_number(hours, 2)?;
+    let mins = parse_number(mins, 2)?;
+    if hours > 14 || mins >= 60 || !mins.is_multiple_of(15) {
+        return None;
+    }
+
+    Some(sign * (hours * 4 + mins / 15) as i8)
+}
+
+/// Parses hour of day `HH`
+fn parse_hour(text: &str) -> Option<u8> {
+    let hour = parse_number(text, 2)?;
+    (hour < 24).then_some(hour as u8)
+}
+
+/// Parses degrees `[-]D.DD` with up to 3 integer digits and 2 decimals into
+/// hundredths of degree
+fn parse_degrees(text: &str) -> Option<i16> {
+    let (sign, text) = match text.strip_prefix('-') {
+        Some(text) => (-1, text),
+        None => (1, text),
+    };
+    let (degrees, decimals) = text.split_once('.').unwrap_or((text, ""));
+    if degrees.is_empty() || degrees.len() > 3 || decimals.len() > 2 {
+        return None;
+    }
+    let degrees = parse_number(degrees, degrees.len())?;
+    let hundredths = match decimals.len() {
+        0 => 0,
+        1 => parse_number(decimals, 1)? * 10,
+        _ => parse_number(decimals, 2)?,
+    };
+    let value = degrees.checked_mul(100)?.checked_add(hundredths)?;
+    Some(sign * i16::try_from(value).ok()?)
+}
+
+/// Parses day of year `MM-DD`
+fn parse_reminder(text: &str) -> Option<Reminder> {
+    let (month, day) = text.split_once('-')?;
+    Reminder::new(parse_number(month, 2)? as u8, parse_number(day, 2)? as u8)
+}
+
+/// Parses single digit brightness level
+fn parse_brightness(text: &str) -> Option<u32> {
+    let level = parse_number(text, 1)? as u32;
+    (level <= MAX_BRIGHTNESS).then_some(level)
+}
+
+/// Parses clock in MHz of up to 2 digits
+fn parse_spi_clock(text: &str) -> Option<u8> {
+    if text.len() > 2 {
+        return None;
+    }
+    let mhz = parse_number(text, text.len())? as u8;
+    (1..=MAX_SPI_CLOCK).contains(&mhz).then_some(mhz)
+}
+
+/// Parses idle timeout in seconds of up to 4 digits
+fn parse_idle_timeout(text: &str) -> Option<u16> {
+    if text.len() > 4 {
+        return None;
+    }
+    let secs = parse_number(text, text.len())?;
+    (1..=MAX_IDLE_TIMEOUT_SECS).contains(&secs).then_some(secs)
+}
+
+/// Parses minutes of ramp of up to 2 digits
+fn parse_alarm_ramp(text: &str) -> Option<u8> {
+    if text.len() > 2 {
+        return None;
+    }
+    let mins = parse_number(text, text.len())? as u8;
+    (mins <= MAX_ALARM_RAMP_MINS).then_some(mins)
+}
+
+/// Parses minutes of sunrise of up to 2 digits
+fn parse_sunrise(text: &str) -> Option<u8> {
+    if text.len() > 2 {
+        return None;
+    }
+    let mins = parse_number(text, text.len())? as u8;
+    (1..=MAX_SUNRISE_MINS).contains(&mins).then_some(mins)
+}
+
+/// Parses percent of up to 3 digits
+fn parse_percent(text: &str) -> Option<u8> {
+    if text.len() > 3 {
+        return None;
+    }
+    let percent = parse_number(text, text.len())?;
+    (percent <= 100).then_some(percent as u8)
+}
+
+/// Parses hex string into bytes, returning part of buffer they took
+fn parse_hex<'a>(text: &str, buf: &'a mut [u8]) -> Option<&'a [u8]> {
+    if !text.len().is_multiple_of(2) || text.len() / 2 > buf.len() {
+        return None;
+    }
+
+    let len = text.len() / 2;
+    for (i, byte) in buf[..len].iter_mut().enumerate() {
+        *byte = u8::from_str_radix(text.get(2 * i..2 * i + 2)?, 16).ok()?;
+    }
+    Some(&buf[..len])
+}
+
+/// Parses decimal number of exactly given number of digits
+fn parse_number(text: &str, digits: usize) -> Option<u16> {
+    if text.len() != digits || !text.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+
+    text.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use core::fmt::Write;
+
+    use super::*;
+    use crate::{console::LINE_LEN, reminder::MAX_REMINDERS};
+
+    #[test]
+    fn test_load_longest_settings() {
+        let settings = Settings {
+            theme: u8::MAX,
+            digit_style: Some(u8::MAX),
+            rotation: Some(u8::MAX),
+            utc_offset: i8::MIN,
+            night: Some((u8::MAX, u8::MAX)),
+            brightness: u8::MAX,
+            menu_brightness: Some(u8::MAX),
+            night_brightness: Some(u8::MAX),
+            melody: u8::MAX,
+            volume: u8::MAX,
+            is_fahrenheit: true,
+            date_format: u8::MAX,
+            hide_seconds: true,
+            alarm_armed: true,
+            alarm_time: (u8::MAX, u8::MAX),
+            alarm_ramp: u8::MAX,
+            sunrise_mins: Some(u8::MAX),
+            time_layout: u8::MAX,
+            led_hue: u16::MAX,
+            ring_hue: u16::MAX,
+            location: Location::new(-9000, -18000),
+            night_from_sun: true,
+            dimming: Dimming::new(22, 23, 100),
+            night_tint: NightTint::new(22, 23, 100),
+            spi_clock: Some(u8::MAX),
+            night_spi_clock: Some(u8::MAX),
+            station_altitude: i16::MIN,
+            idle_timeout: Some(u16::MAX),
+            reminders: [Reminder::new(12, 31); MAX_REMINDERS],
+        };
+
+        let mut line = String::<LINE_LEN>::new();
+        line.push_str("settings load ").unwrap();
+        for byte in settings.to_blob() {
+            write!(line, "{byte:02x}").unwrap();
+        }
+        assert_eq!(Command::parse(&line), Ok(Command::LoadSettings(settings)));
+    }
+}
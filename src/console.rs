@@ -0,0 +1,200 @@
+//! Text console over USB CDC (virtual serial port).
+//!
+//! USB has to be serviced within milliseconds, much faster than main loop
+//! runs, so device is polled from USB interrupt. Handler collects received
+//! characters into lines, which main loop takes with [`take_line`] and
+//! executes as [`command`](crate::command)s. Replies are written with
+//! [`write_str`].
+//!
+//! Builds with `uart-console` feature mirror console on UART1 (GPIO20 TX,
+//! GPIO21 RX) at 115200 baud, so a USB-UART adapter is enough for
+//! diagnostics when USB port is out of reach. Lines are taken from both and
+//! replies are written to both. UART is drained from its interrupt like
+//! [`gps`](crate::gps) does.
+
+use core::cell::RefCell;
+
+use cortex_m::interrupt::{self, Mutex};
+use heapless::String;
+use usb_device::{class_prelude::UsbBusAllocator, prelude::*};
+use usbd_serial::{SerialPort, USB_CLASS_CDC};
+
+#[cfg(feature = "uart-console")]
+use crate::hardware::ConsoleUartTy;
+use crate::{
+    hal::{pac, usb::UsbBus},
+    settings::BLOB_LEN,
+};
+
+/// Longest line accepted, enough for `settings load` with hex of the longest
+/// blob. Longer lines are dropped.
+pub const LINE_LEN: usize = "settings load ".len() + 2 * BLOB_LEN;
+
+pub type Line = String<LINE_LEN>;
+
+/// Collects received characters into lines
+#[derive(Default)]
+struct LineReader {
+    /// Line being received
+    line: Line,
+    /// Is line being received too long? It is dropped once it ends.
+    is_overflown: bool,
+    /// Last received line not yet taken by main loop
+    pending: Option<Line>,
+}
+
+struct Console {
+    device: UsbDevice<'static, UsbBus>,
+    serial: SerialPort<'static, UsbBus>,
+    reader: LineReader,
+}
+
+#[cfg(feature = "uart-console")]
+struct UartConsole {
+    uart: ConsoleUartTy,
+    reader: LineReader,
+}
+
+static CONSOLE: Mutex<RefCell<Option<Console>>> = Mutex::new(RefCell::new(None));
+#[cfg(feature = "uart-console")]
+static UART_CONSOLE: Mutex<RefCell<Option<UartConsole>>> = Mutex::new(RefCell::new(None));
+
+/// Registers serial port on USB bus and enables USB interrupt
+pub fn init(usb_bus: &'static UsbBusAllocator<UsbBus>) {
+    let serial = SerialPort::new(usb_bus);
+    let device = UsbDeviceBuilder::new(usb_bus, UsbVidPid(0x16c0, 0x27dd))
+        .manufacturer("Waveshare")
+        .product("LCD-Clock-A")
+        .serial_number("0")
+        .device_class(USB_CLASS_CDC)
+        .build();
+    let console = Console {
+        device,
+        serial,
+        reader: LineReader::default(),
+    };
+    interrupt::free(|cs| CONSOLE.borrow(cs).replace(Some(console)));
+    // SAFETY: handler only touches data behind critical section
+    unsafe {
+        pac::NVIC::unmask(pac::Interrupt::USBCTRL_IRQ);
+    }
+}
+
+/// Has to be called from USBCTRL_IRQ handler
+pub fn on_interrupt() {
+    interrupt::free(|cs| {
+        let mut console = CONSOLE.borrow(cs).borrow_mut();
+        let Some(console) = console.as_mut() else {
+            return;
+        };
+        if !console.device.poll(&mut [&mut console.serial]) {
+            return;
+        }
+
+        let mut buf = [0u8; 64];
+        let Ok(count) = console.serial.read(&mut buf) else {
+            return;
+        };
+        for &byte in &buf[..count] {
+            console.reader.receive(byte);
+        }
+    });
+}
+
+/// Hands UART over to interrupt handler and enables receive interrupt
+#[cfg(feature = "uart-console")]
+pub fn init_uart(mut uart: ConsoleUartTy) {
+    uart.enable_rx_interrupt();
+    let console = UartConsole {
+        uart,
+        reader: LineReader::default(),
+    };
+    interrupt::free(|cs| UART_CONSOLE.borrow(cs).replace(Some(console)));
+    // SAFETY: handler only touches data behind critical section
+    unsafe {
+        pac::NVIC::unmask(pac::Interrupt::UART1_IRQ);
+    }
+}
+
+/// Has to be called from UART1_IRQ handler
+#[cfg(feature = "uart-console")]
+pub fn on_uart_interrupt() {
+    use embedded_hal::serial::Read;
+
+    interrupt::free(|cs| {
+        let mut console = UART_CONSOLE.borrow(cs).borrow_mut();
+        let Some(console) = console.as_mut() else {
+            return;
+        };
+        loop {
+            match console.uart.read() {
+                Ok(byte) => console.reader.receive(byte),
+                Err(nb::Error::WouldBlock) => break,
+                // Overrun or framing error, line is garbled
+                Err(nb::Error::Other(_)) => console.reader.is_overflown = true,
+            }
+        }
+    });
+}
+
+impl LineReader {
+    fn receive(&mut self, byte: u8) {
+        match byte {
+            b'\r' | b'\n' => {
+                let line = core::mem::take(&mut self.line);
+                if !core::mem::take(&mut self.is_overflown) && !line.is_empty() {
+                    // Main loop handles commands much faster than they can
+                    // be typed, so at most one is ever pending
+                    self.pending = Some(line);
+                }
+            }
+            _ => {
+                let is_pushed = byte.is_ascii() && self.line.push(byte as char).is_ok();
+                self.is_overflown |= !is_pushed;
+            }
+        }
+    }
+}
+
+/// Takes line received since last call, without line terminator
+pub fn take_line() -> Option<Line> {
+    let line = interrupt::free(|cs| {
+        let mut console = CONSOLE.borrow(cs).borrow_mut();
+        console.as_mut()?.reader.pending.take()
+    });
+    #[cfg(feature = "uart-console")]
+    let line = line.or_else(|| {
+        interrupt::free(|cs| {
+            let mut console = UART_CONSOLE.borrow(cs).borrow_mut();
+            console.as_mut()?.reader.pending.take()
+        })
+    });
+    line
+}
+
+/// Writes text to serial port. Text that doesn't fit into USB buffers (e.g.
+/// because nothing on host reads the port) is dropped. UART console, if any,
+/// gets all of it.
+pub fn write_str(text: &str) {
+    #[cfg(feature = "uart-console")]
+    interrupt::free(|cs| {
+        if let Some(console) = UART_CONSOLE.borrow(cs).borrow_mut().as_mut() {
+            console.uart.write_full_blocking(text.as_bytes());
+        }
+    });
+
+    interrupt::free(|cs| {
+        let mut console = CONSOLE.borrow(cs).borrow_mut();
+        let Some(console) = console.as_mut() else {
+            return;
+        };
+
+        let mut bytes = text.as_bytes();
+        while !bytes.is_empty() {
+            match console.serial.write(bytes) {
+                Ok(count) if count > 0 => bytes = &bytes[count..],
+                _ => break,
+            }
+        }
+    });
+}
@@ -0,0 +1,176 @@
+//! Report of the last crash, kept in RAM across reset.
+//!
+//! Clock in the field can't be attached to debugger, so panic and hard fault
+//! handlers of firmware write what has happened into [`Record`] placed in RAM
+//! that isn't initialized at boot, and reset the chip. On the next boot the
+//! record is taken: clock shows crash report until a button is pressed, and
+//! `log crash` over USB console prints it. Record doesn't survive loss of
+//! power.
+
+use core::fmt;
+
+use heapless::String;
+
+/// Longest message of panic kept, the rest is cut off
+pub const MESSAGE_LEN: usize = 96;
+/// Marks record as written by handler, "CRSH"
+const MAGIC: u32 = 0x4352_5348;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Kind {
+    Panic,
+    HardFault,
+}
+
+impl Kind {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Panic => "PANIC",
+            Self::HardFault => "FAULT",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CrashReport {
+    pub kind: Kind,
+    /// Program counter fault happened at, 0 for panics
+    pub pc: u32,
+    /// Message and location of panic in printable ASCII, empty for faults
+    pub message: String<MESSAGE_LEN>,
+}
+
+/// Crash report as it lies in RAM. Any content is valid, so RAM left over
+/// from before reset can be read as record.
+#[repr(C)]
+pub struct Record {
+    magic: u32,
+    kind: u32,
+    pc: u32,
+    len: u32,
+    message: [u8; MESSAGE_LEN],
+    checksum: u32,
+}
+
+impl Record {
+    /// Starts writing report. Message is written with [`fmt::Write`], then
+    /// [`finish`](Self::finish) marks record as valid.
+    pub fn start(&mut self, kind: Kind, pc: u32) {
+        self.magic = 0;
+        self.kind = kind as u32;
+        self.pc = pc;
+        self.len = 0;
+    }
+
+    pub fn finish(&mut self) {
+        self.checksum = self.checksum();
+        self.magic = MAGIC;
+    }
+
+    /// Takes report written before reset, None if there is none. Record is
+    /// cleared, so report is only taken once.
+    pub fn take(&mut self) -> Option<CrashReport> {
+        let is_valid = self.magic == MAGIC && self.checksum == self.checksum();
+        self.magic = 0;
+        if !is_valid {
+            return None;
+        }
+
+        let kind = match self.kind {
+            0 => Kind::Panic,
+            1 => Kind::HardFault,
+            _ => return None,
+        };
+        let len = (self.len as usize).min(MESSAGE_LEN);
+        let message = core::str::from_utf8(&self.message[..len]).ok()?;
+        Some(CrashReport {
+            kind,
+            pc: self.pc,
+            message: message.into(),
+        })
+    }
+
+    fn checksum(&self) -> u32 {
+        let len = (self.len as usize).min(MESSAGE_LEN);
+        self.message[..len]
+            .iter()
+            .fold(self.kind ^ self.pc ^ self.len, |sum, &byte| {
+                sum.rotate_left(5) ^ byte as u32
+            })
+    }
+}
+
+/// Appends to message, cutting off what doesn't fit. Characters that can't
+/// be shown, e.g. line breaks, are replaced with spaces.
+impl fmt::Write for Record {
+    fn write_str(&mut self, text: &str) -> fmt::Result {
+        for byte in text.bytes() {
+            let len = self.len as usize;
+            if len >= MESSAGE_LEN {
+                break;
+            }
+            // Bytes of multibyte characters are replaced one by one
+            self.message[len] = if byte.is_ascii_graphic() { byte } else { b' ' };
+            self.len += 1;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::fmt::Write;
+
+    fn garbage() -> Record {
+        Record {
+            magic: 0xDEAD_BEEF,
+            kind: 7,
+            pc: 0x1234,
+            len: 1000,
+            message: [0xAA; MESSAGE_LEN],
+            checksum: 42,
+        }
+    }
+
+    #[test]
+    fn test_take() {
+        let mut record = garbage();
+        assert_eq!(record.take(), None);
+
+        record.start(Kind::Panic, 0);
+        write!(
+            record,
+            "panicked at src/state.rs:10:5:\nindex out of bounds"
+        )
+        .unwrap();
+        record.finish();
+        let report = record.take().unwrap();
+        assert_eq!(report.kind, Kind::Panic);
+        assert_eq!(
+            report.message,
+            "panicked at src/state.rs:10:5: index out of bounds"
+        );
+        // Report is only taken once
+        assert_eq!(record.take(), None);
+
+        record.start(Kind::HardFault, 0x1000_2345);
+        record.finish();
+        // Bit flipped while power was lost
+        record.pc ^= 0x100;
+        assert_eq!(record.take(), None);
+    }
+
+    #[test]
+    fn test_long_message() {
+        let mut record = garbage();
+        record.start(Kind::Panic, 0);
+        for _ in 0..MESSAGE_LEN {
+            write!(record, "ab").unwrap();
+        }
+        record.finish();
+        let report = record.take().unwrap();
+        assert_eq!(report.message.len(), MESSAGE_LEN);
+        assert!(report.message.starts_with("abab"));
+    }
+}
@@ -0,0 +1,88 @@
+//! Results of probing peripherals at init.
+//!
+//! Missing or broken chips don't stop the clock from starting. They are
+//! recorded here and shown on diagnostics screen instead.
+
+use crate::drivers::shared_i2c::I2CScan;
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum PeripheralStatus {
+    #[default]
+    Ok,
+    /// Nothing acknowledges address of peripheral
+    Missing,
+    /// Peripheral answers, but couldn't be initialized
+    Failed,
+}
+
+impl PeripheralStatus {
+    pub fn is_ok(self) -> bool {
+        self == Self::Ok
+    }
+
+    /// Short name shown on diagnostics screen
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Ok => "OK",
+            Self::Missing => "MISSING",
+            Self::Failed => "FAILED",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Diagnostics {
+    /// Devices that answered on I2C bus, including unknown ones
+    pub i2c_scan: I2CScan,
+    pub rtc: PeripheralStatus,
+    pub humidity_sensor: PeripheralStatus,
+    /// LEDs don't answer, so only failure to set up PIO is detected
+    pub led_strip: PeripheralStatus,
+}
+
+impl Diagnostics {
+    pub fn is_ok(&self) -> bool {
+        self.rtc.is_ok() && self.humidity_sensor.is_ok() && self.led_strip.is_ok()
+    }
+}
+
+/// State of optional GPS module, updated as its sentences arrive
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum GpsStatus {
+    /// Nothing is received, module is probably not attached
+    #[default]
+    None,
+    /// Module is attached, but doesn't know time yet
+    NoFix,
+    Fix,
+}
+
+impl GpsStatus {
+    /// Short name shown on diagnostics screen
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::None => "NONE",
+            Self::NoFix => "NO FIX",
+            Self::Fix => "FIX",
+        }
+    }
+}
+
+/// Supply voltages in millivolts
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Power {
+    /// Supply of the board, from USB or external source
+    pub vsys: u16,
+    /// Backup cell of rtc, None if it is not wired to ADC
+    pub backup: Option<u16>,
+}
+
+impl Power {
+    /// DS3231 keeps time down to 2.3V. Cell is reported a bit earlier, so
+    /// there is time to replace it.
+    pub const BACKUP_LOW: u16 = 2500;
+
+    pub fn is_backup_low(&self) -> bool {
+        self.backup.is_some_and(|backup| backup < Self::BACKUP_LOW)
+    }
+}
@@ -0,0 +1,95 @@
+//! Brightness following time of day.
+//!
+//! Night mode switches brightness at whole hours. Dimming instead ramps
+//! backlight of regular screens and LEDs down over an hour from the start
+//! hour, keeps them at its level until the end hour and ramps them back up
+//! over the next hour. Level is recomputed every minute, so steps are too
+//! small to notice.
+
+use serde::{Deserialize, Serialize};
+
+/// Level of brightness that is not dimmed at all, in percent
+pub const FULL: u32 = 100;
+/// Minutes ramps down and up take
+const RAMP_MINS: u32 = 60;
+const MINUTES_PER_DAY: u32 = 24 * 60;
+
+/// Curve of brightness over day
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Dimming {
+    /// Hour ramp down starts at
+    start: u8,
+    /// Hour ramp up starts at
+    end: u8,
+    /// Percent of brightness between ramps
+    level: u8,
+}
+
+impl Dimming {
+    /// Returns None if hours are out of range or the same, or level is not
+    /// from 1 to [`FULL`]
+    pub fn new(start: u8, end: u8, level: u8) -> Option<Self> {
+        let is_valid =
+            start < 24 && end < 24 && start != end && (1..=FULL).contains(&(level as u32));
+        is_valid.then_some(Self { start, end, level })
+    }
+
+    pub fn start(self) -> u8 {
+        self.start
+    }
+
+    pub fn end(self) -> u8 {
+        self.end
+    }
+
+    pub fn level(self) -> u8 {
+        self.level
+    }
+
+    /// Percent of brightness at given minute since midnight, from level to
+    /// [`FULL`]
+    pub fn percent(self, minutes: u16) -> u32 {
+        let level = self.level as u32;
+        let start = self.start as u32 * 60;
+        let dimmed_mins = (self.end as u32 * 60 + MINUTES_PER_DAY - start) % MINUTES_PER_DAY;
+        let since_start =
+            (minutes as u32 % MINUTES_PER_DAY + MINUTES_PER_DAY - start) % MINUTES_PER_DAY;
+        if since_start < dimmed_mins {
+            let ramp = since_start.min(RAMP_MINS);
+            FULL - (FULL - level) * ramp / RAMP_MINS
+        } else {
+            let ramp = (since_start - dimmed_mins).min(RAMP_MINS);
+            level + (FULL - level) * ramp / RAMP_MINS
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percent() {
+        let dimming = Dimming::new(20, 7, 30).unwrap();
+        assert_eq!(dimming.percent(12 * 60), FULL);
+        assert_eq!(dimming.percent(20 * 60), FULL);
+        assert_eq!(dimming.percent(20 * 60 + 30), 65);
+        assert_eq!(dimming.percent(21 * 60), 30);
+        assert_eq!(dimming.percent(0), 30);
+        assert_eq!(dimming.percent(7 * 60), 30);
+        assert_eq!(dimming.percent(7 * 60 + 30), 65);
+        assert_eq!(dimming.percent(8 * 60), FULL);
+        assert_eq!(dimming.percent(24 * 60 - 1), 30);
+
+        // Ramp down takes the whole hour of short dimming
+        let dimming = Dimming::new(13, 14, 50).unwrap();
+        assert_eq!(dimming.percent(13 * 60 + 30), 75);
+        assert_eq!(dimming.percent(14 * 60), 50);
+        assert_eq!(dimming.percent(14 * 60 + 30), 75);
+
+        assert_eq!(Dimming::new(7, 7, 30), None);
+        assert_eq!(Dimming::new(20, 24, 30), None);
+        assert_eq!(Dimming::new(20, 7, 0), None);
+        assert_eq!(Dimming::new(20, 7, 101), None);
+    }
+}
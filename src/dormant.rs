@@ -0,0 +1,85 @@
+//! Dormant state of RP2040.
+//!
+//! In dormant state crystal oscillator is stopped and with it every clock, so
+//! chip draws well under a milliamp until level on one of wake pins changes.
+//! PLLs lose lock once their reference stops, so system clock is switched to
+//! reference clock (crystal) before and back to PLL once it has locked again.
+//! Peripherals clocked from USB PLL (USB, ADC) are stopped meanwhile and host
+//! is likely to drop USB connection.
+
+use core::ptr::{read_volatile, write_volatile};
+
+const CLOCKS_BASE: u32 = 0x4000_8000;
+const CLK_SYS_CTRL: u32 = CLOCKS_BASE + 0x3C;
+const CLK_SYS_SELECTED: u32 = CLOCKS_BASE + 0x44;
+/// Glitchless mux of system clock selects PLL (through auxiliary mux)
+const CLK_SYS_SRC_AUX: u32 = 1 << 0;
+/// Bits of CLK_SYS_SELECTED for reference and auxiliary source
+const CLK_SYS_SELECTED_REF: u32 = 1 << 0;
+const CLK_SYS_SELECTED_AUX: u32 = 1 << 1;
+
+const IO_BANK0_BASE: u32 = 0x4001_4000;
+/// Four registers, each has 4 bits for each of 8 GPIOs
+const DORMANT_WAKE_INTE0: u32 = IO_BANK0_BASE + 0x160;
+
+const XOSC_BASE: u32 = 0x4002_4000;
+const XOSC_STATUS: u32 = XOSC_BASE + 0x04;
+const XOSC_DORMANT: u32 = XOSC_BASE + 0x08;
+const XOSC_STABLE: u32 = 1 << 31;
+/// Value written to XOSC_DORMANT to stop oscillator, "coma" in ASCII
+const XOSC_DORMANT_VALUE: u32 = 0x636f_6d61;
+
+const PLL_SYS_CS: u32 = 0x4002_8000;
+const PLL_LOCK: u32 = 1 << 31;
+
+/// Registers have aliases that atomically set or clear written bits
+const SET_ALIAS: u32 = 0x2000;
+const CLEAR_ALIAS: u32 = 0x3000;
+
+/// Level of wake pin that wakes chip up
+#[derive(Clone, Copy)]
+pub enum WakeLevel {
+    Low,
+    High,
+}
+
+/// Stops all clocks until one of pins is at its wake level. Pins have to be
+/// inputs. Returns right away if one of them is at wake level already.
+pub fn sleep(wake_pins: &[(u8, WakeLevel)]) {
+    cortex_m::interrupt::free(|_| {
+        // SAFETY: registers are only accessed here and by clock setup at
+        // start, interrupts can't observe system clock being switched
+        unsafe {
+            for &(pin, level) in wake_pins {
+                let (reg, bit) = wake_bit(pin, level);
+                write_volatile((reg | SET_ALIAS) as *mut u32, bit);
+            }
+
+            write_volatile((CLK_SYS_CTRL | CLEAR_ALIAS) as *mut u32, CLK_SYS_SRC_AUX);
+            while read_volatile(CLK_SYS_SELECTED as *const u32) != CLK_SYS_SELECTED_REF {}
+
+            // Core stops here until wake pin reaches its level
+            write_volatile(XOSC_DORMANT as *mut u32, XOSC_DORMANT_VALUE);
+            while read_volatile(XOSC_STATUS as *const u32) & XOSC_STABLE == 0 {}
+            while read_volatile(PLL_SYS_CS as *const u32) & PLL_LOCK == 0 {}
+
+            write_volatile((CLK_SYS_CTRL | SET_ALIAS) as *mut u32, CLK_SYS_SRC_AUX);
+            while read_volatile(CLK_SYS_SELECTED as *const u32) != CLK_SYS_SELECTED_AUX {}
+
+            for &(pin, level) in wake_pins {
+                let (reg, bit) = wake_bit(pin, level);
+                write_volatile((reg | CLEAR_ALIAS) as *mut u32, bit);
+            }
+        }
+    })
+}
+
+/// Register and bit enabling wake up from dormant on given level of GPIO
+fn wake_bit(pin: u8, level: WakeLevel) -> (u32, u32) {
+    let reg = DORMANT_WAKE_INTE0 + (pin as u32 / 8) * 4;
+    let level_bit = match level {
+        WakeLevel::Low => 0,
+        WakeLevel::High => 1,
+    };
+    (reg, 1 << ((pin as u32 % 8) * 4 + level_bit))
+}
@@ -1,5 +1,7 @@
-pub mod bme280;
-pub mod buttons;
-pub mod ds3231;
-pub mod st7789vwx6;
+//! Drivers for chips found on the board. Generic ones live in
+//! [`lcd_clock_drivers`] crate and are reexported here, WS2812 uses RP2040
+//! PIO and stays with firmware.
+
+pub use lcd_clock_drivers::*;
+
 pub mod ws2812;
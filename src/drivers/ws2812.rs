@@ -1,16 +1,60 @@
 //! WS2812 PIO
+//!
+//! Colors are fed to TX FIFO of PIO by DMA, so sending a frame doesn't block
+//! main loop. Frame is encoded into one of two buffers while DMA reads the
+//! other one, so frame being sent is never torn.
+//!
+//! Several strips can be driven at once, each by its own state machine and
+//! DMA channel. Frames equal to the last one sent are skipped, so LEDs that
+//! don't change cost nothing.
+
+use core::sync::atomic::{compiler_fence, Ordering};
 
 use crate::{
     hal::{
         self,
         gpio::{Function, FunctionConfig, Pin, PinId, ValidPinMode},
+        pac::{dma::CH, DMA, RESETS},
         pio::{PIOExt, StateMachineIndex, Tx, UninitStateMachine, PIO},
     },
+    led_strip::LED_COUNT,
     misc::ColorRGB8,
 };
 use fugit::HertzU32;
 
-pub struct WS2812<P, SM, I>
+/// Colors of all LEDs encoded as words of PIO program
+pub type Frame<const N: usize = LED_COUNT> = [u32; N];
+
+/// DMA channel feeding PIO of one strip
+pub struct DmaChannel(usize);
+
+impl DmaChannel {
+    fn regs(&self) -> &CH {
+        // SAFETY: channel is owned by this handle, DMA is taken by
+        // DmaChannels, so nothing else touches its registers
+        unsafe { &(*DMA::ptr()).ch[self.0] }
+    }
+}
+
+/// DMA channels used by strips, nothing else uses DMA
+pub struct DmaChannels {
+    pub ch0: DmaChannel,
+    pub ch1: DmaChannel,
+}
+
+impl DmaChannels {
+    pub fn new(_dma: DMA, resets: &mut RESETS) -> Self {
+        resets.reset.modify(|_, w| w.dma().clear_bit());
+        while resets.reset_done.read().dma().bit_is_clear() {}
+
+        Self {
+            ch0: DmaChannel(0),
+            ch1: DmaChannel(1),
+        }
+    }
+}
+
+pub struct WS2812<P, SM, I, const N: usize = LED_COUNT>
 where
     I: PinId,
     P: PIOExt + FunctionConfig,
@@ -19,9 +63,18 @@ where
 {
     tx: Tx<(P, SM)>,
     _pin: Pin<I, Function<P>>,
+    dma: DmaChannel,
+    frames: &'static mut [Frame<N>; 2],
+    /// Index of frame DMA reads from, the other one is written
+    front: usize,
+    /// Number of LEDs in back frame waiting to be sent
+    pending: Option<usize>,
+    /// Number of LEDs in front frame, None until the first frame is sent.
+    /// LEDs keep colors across reset, so it is sent even if it is black.
+    sent: Option<usize>,
 }
 
-impl<P, SM, I> WS2812<P, SM, I>
+impl<P, SM, I, const N: usize> WS2812<P, SM, I, N>
 where
     I: PinId,
     P: PIOExt + FunctionConfig,
@@ -32,6 +85,8 @@ where
         pin: Pin<I, Function<P>>,
         pio: &mut PIO<P>,
         sm: UninitStateMachine<(P, SM)>,
+        dma: DmaChannel,
+        frames: &'static mut [Frame<N>; 2],
         clock_freq: fugit::HertzU32,
     ) -> Result<Self, Error> {
         const T1: u8 = 2; // start bit
@@ -93,16 +148,84 @@ where
         sm.set_pindirs([(I::DYN.num, hal::pio::PinDir::Output)]);
         sm.start();
 
-        Ok(Self { tx, _pin: pin })
+        Ok(Self {
+            tx,
+            _pin: pin,
+            dma,
+            frames,
+            front: 0,
+            pending: None,
+            sent: None,
+        })
+    }
+
+    /// Is DMA still sending previous frame?
+    pub fn is_busy(&self) -> bool {
+        self.dma.regs().ch_ctrl_trig.read().busy().bit_is_set()
     }
 
+    /// Encodes colors into back frame and starts sending it in background.
+    /// If previous frame is still being sent, this one waits for the next
+    /// call, being replaced by newer colors if there are any.
     pub fn display(&mut self, colors: &[ColorRGB8]) {
-        for &color in colors.iter() {
+        let len = colors.len().min(N);
+        let frame = &mut self.frames[1 - self.front];
+        for (word, &color) in frame.iter_mut().zip(colors) {
             let (r, g, b) = color.into();
-            let word = (u32::from(g) << 24) | (u32::from(r) << 16) | (u32::from(b) << 8);
-            while !self.tx.write(word) {
-                cortex_m::asm::nop();
-            }
+            *word = (u32::from(g) << 24) | (u32::from(r) << 16) | (u32::from(b) << 8);
+        }
+        // LEDs already show these colors, frame waiting to be sent is
+        // outdated too
+        let front = &self.frames[self.front];
+        let back = &self.frames[1 - self.front];
+        if self.sent == Some(len) && front[..len] == back[..len] {
+            self.pending = None;
+            return;
+        }
+        self.pending = Some(len);
+
+        self.flush();
+    }
+
+    /// Starts sending back frame if there is one and DMA is done with front
+    /// frame
+    fn flush(&mut self) {
+        if self.is_busy() {
+            return;
+        }
+        let Some(len) = self.pending.take() else {
+            return;
+        };
+        self.sent = Some(len);
+
+        self.front = 1 - self.front;
+        let frame = &self.frames[self.front];
+        // Frame has to be in memory before DMA reads it
+        compiler_fence(Ordering::SeqCst);
+        let ch = self.dma.regs();
+        // SAFETY: frame is 'static and isn't written until it becomes back
+        // frame again, FIFO address and DREQ come from state machine owned by
+        // driver
+        unsafe {
+            ch.ch_read_addr.write(|w| w.bits(frame.as_ptr() as u32));
+            ch.ch_write_addr
+                .write(|w| w.bits(self.tx.fifo_address() as u32));
+            ch.ch_trans_count.write(|w| w.bits(len as u32));
+            ch.ch_ctrl_trig.write(|w| {
+                w.data_size()
+                    .size_word()
+                    .incr_read()
+                    .set_bit()
+                    .incr_write()
+                    .clear_bit()
+                    .treq_sel()
+                    .bits(self.tx.dreq_value())
+                    // Chaining to itself disables chaining
+                    .chain_to()
+                    .bits(self.dma.0 as u8)
+                    .en()
+                    .set_bit()
+            });
         }
     }
 }
@@ -0,0 +1,154 @@
+//! Recent events and errors kept in RAM.
+//!
+//! Errors of peripherals used to stop the clock. Now main loop logs them and
+//! carries on, so failures that come and go, e.g. noise on I2C or SPI, can be
+//! found later on hidden diagnostics screen or with `log events` over USB
+//! console. Events are stamped with milliseconds of [`timer`](crate::timer)
+//! since boot. The same event repeated in a row takes one entry, which counts
+//! the repeats, so a peripheral failing every frame doesn't push everything
+//! else out.
+
+use heapless::Deque;
+
+/// Number of events kept
+pub const LEN: usize = 32;
+
+/// What has happened
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Code {
+    Boot,
+    /// Rtc was set from menu, console or GPS
+    TimeSet,
+    /// Clock went to sleep for the night
+    Sleep,
+    /// Clock woke up from sleep
+    Wake,
+    /// Armed alarm started ringing
+    Alarm,
+    /// Clock was reset by panic or fault, see [`crash`](crate::crash)
+    Crash,
+    DisplayError,
+    SensorError,
+    RtcError,
+    SettingsError,
+}
+
+impl Code {
+    /// Short name that fits line of diagnostics screen
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Boot => "BOOT",
+            Self::TimeSet => "TIME SET",
+            Self::Sleep => "SLEEP",
+            Self::Wake => "WAKE",
+            Self::Alarm => "ALARM",
+            Self::Crash => "CRASH",
+            Self::DisplayError => "LCD ERR",
+            Self::SensorError => "SENS ERR",
+            Self::RtcError => "RTC ERR",
+            Self::SettingsError => "SETT ERR",
+        }
+    }
+
+    pub fn is_error(self) -> bool {
+        matches!(
+            self,
+            Self::Crash
+                | Self::DisplayError
+                | Self::SensorError
+                | Self::RtcError
+                | Self::SettingsError
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Event {
+    pub code: Code,
+    /// Timer when event last happened
+    pub ms: u32,
+    /// Times event happened in a row
+    pub count: u16,
+}
+
+pub struct EventLog {
+    events: Deque<Event, LEN>,
+}
+
+impl EventLog {
+    pub fn new() -> Self {
+        Self {
+            events: Deque::new(),
+        }
+    }
+
+    /// Logs event, dropping the oldest one if log is full
+    pub fn push(&mut self, code: Code, ms: u32) {
+        if let Some(last) = self.events.back_mut() {
+            if last.code == code {
+                last.ms = ms;
+                last.count = last.count.saturating_add(1);
+                return;
+            }
+        }
+
+        if self.events.is_full() {
+            self.events.pop_front();
+        }
+        self.events.push_back(Event { code, ms, count: 1 }).ok();
+    }
+
+    /// Events from the oldest to the newest
+    pub fn events(&self) -> impl DoubleEndedIterator<Item = &Event> {
+        self.events.iter()
+    }
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn codes(log: &EventLog) -> Vec<(Code, u32, u16)> {
+        log.events()
+            .map(|event| (event.code, event.ms, event.count))
+            .collect()
+    }
+
+    #[test]
+    fn test_repeats() {
+        let mut log = EventLog::new();
+        log.push(Code::Boot, 0);
+        log.push(Code::RtcError, 100);
+        log.push(Code::RtcError, 116);
+        log.push(Code::Sleep, 200);
+        log.push(Code::RtcError, 300);
+        assert_eq!(
+            codes(&log),
+            [
+                (Code::Boot, 0, 1),
+                (Code::RtcError, 116, 2),
+                (Code::Sleep, 200, 1),
+                (Code::RtcError, 300, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_full() {
+        let mut log = EventLog::new();
+        for i in 0..LEN as u32 + 2 {
+            let code = if i % 2 == 0 { Code::Sleep } else { Code::Wake };
+            log.push(code, i);
+        }
+        let events = codes(&log);
+        assert_eq!(events.len(), LEN);
+        assert_eq!(events[0], (Code::Sleep, 2, 1));
+        assert_eq!(events[LEN - 1], (Code::Wake, LEN as u32 + 1, 1));
+    }
+}
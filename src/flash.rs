@@ -0,0 +1,135 @@
+//! Storage of settings and climate log in internal flash.
+//!
+//! Settings are kept in the last sector of flash and climate log in sectors
+//! right before it, all of them are excluded from program memory in
+//! memory.x. Flash can't be read while it is written to, so writing is done
+//! by bootrom functions called from RAM with interrupts disabled.
+
+use crate::{
+    climate_log::{LogStorage, LOG_LEN},
+    hal::rom_data,
+    settings::{Error, Record, Storage, RECORD_LEN},
+};
+
+/// Address flash is mapped to
+const XIP_BASE: u32 = 0x1000_0000;
+const FLASH_SIZE: u32 = 2048 * 1024;
+/// Smallest erasable unit of flash
+const SECTOR_SIZE: u32 = 4096;
+/// Smallest programmable unit of flash
+const PAGE_SIZE: usize = 256;
+/// Offset of settings sector from start of flash
+const SETTINGS_OFFSET: u32 = FLASH_SIZE - SECTOR_SIZE;
+/// Offset of climate log from start of flash
+const LOG_OFFSET: u32 = SETTINGS_OFFSET - LOG_LEN as u32;
+/// Command used by bootrom to erase single sector
+const SECTOR_ERASE_CMD: u8 = 0x20;
+
+/// Reserved sectors at the end of internal flash
+#[derive(Default)]
+pub struct Flash;
+
+impl Flash {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Storage for Flash {
+    fn read(&mut self, record: &mut Record) -> Result<(), Error> {
+        // SAFETY: settings sector is mapped and never written to while read
+        let sector = unsafe {
+            core::slice::from_raw_parts((XIP_BASE + SETTINGS_OFFSET) as *const u8, RECORD_LEN)
+        };
+        record.copy_from_slice(sector);
+        Ok(())
+    }
+
+    /// Record is programmed at the start of page, the rest of page is left
+    /// erased
+    fn write(&mut self, record: &Record) -> Result<(), Error> {
+        let mut page = [0xFF; PAGE_SIZE];
+        page[..RECORD_LEN].copy_from_slice(record);
+        write_flash(SETTINGS_OFFSET, true, Some(&page));
+        Ok(())
+    }
+}
+
+impl LogStorage for Flash {
+    fn read(&mut self, offset: usize, buf: &mut [u8]) {
+        // SAFETY: log sectors are mapped and never written to while read
+        let log =
+            unsafe { core::slice::from_raw_parts((XIP_BASE + LOG_OFFSET) as *const u8, LOG_LEN) };
+        buf.copy_from_slice(&log[offset..offset + buf.len()]);
+    }
+
+    /// Only whole pages can be programmed, so bytes around the given ones
+    /// are programmed as 0xFF, which leaves them as they are
+    fn program(&mut self, offset: usize, bytes: &[u8]) {
+        let start = offset % PAGE_SIZE;
+        let mut page = [0xFF; PAGE_SIZE];
+        page[start..start + bytes.len()].copy_from_slice(bytes);
+        write_flash(LOG_OFFSET + (offset - start) as u32, false, Some(&page));
+    }
+
+    fn erase_sector(&mut self, sector: usize) {
+        write_flash(LOG_OFFSET + sector as u32 * SECTOR_SIZE, true, None);
+    }
+}
+
+/// Erases sector at offset if asked to, then programs page at the same
+/// offset if one is given
+fn write_flash(offset: u32, erase: bool, page: Option<&[u8; PAGE_SIZE]>) {
+    cortex_m::interrupt::free(|_| {
+        // Second stage bootloader configures flash for fast reads and has to
+        // be rerun after flash is written. Flash is not accessible by then,
+        // so it is copied to RAM.
+        let mut boot2 = [0u32; 64];
+        // SAFETY: boot2 occupies first 256 bytes of flash
+        unsafe {
+            core::ptr::copy_nonoverlapping(XIP_BASE as *const u32, boot2.as_mut_ptr(), 64);
+        }
+
+        let funcs = FlashFuncs {
+            connect_internal_flash: rom_data::connect_internal_flash::ptr(),
+            flash_exit_xip: rom_data::flash_exit_xip::ptr(),
+            flash_range_erase: rom_data::flash_range_erase::ptr(),
+            flash_range_program: rom_data::flash_range_program::ptr(),
+            flash_flush_cache: rom_data::flash_flush_cache::ptr(),
+            // SAFETY: boot2 is thumb code, thus lowest bit of address is set
+            boot2: unsafe { core::mem::transmute((boot2.as_ptr() as *const u8).add(1)) },
+        };
+        let page = page.map_or(core::ptr::null(), |page| page.as_ptr());
+        // SAFETY: interrupts are disabled and second core is not running, so
+        // nothing executes from flash while it is written
+        unsafe { write_sector(&funcs, offset, erase, page) };
+    });
+}
+
+/// Pointers to bootrom flash functions. They are looked up beforehand, as
+/// lookup itself runs from flash.
+struct FlashFuncs {
+    connect_internal_flash: unsafe extern "C" fn(),
+    flash_exit_xip: unsafe extern "C" fn(),
+    flash_range_erase: unsafe extern "C" fn(u32, usize, u32, u8),
+    flash_range_program: unsafe extern "C" fn(u32, *const u8, usize),
+    flash_flush_cache: unsafe extern "C" fn(),
+    boot2: unsafe extern "C" fn(),
+}
+
+/// Erases sector at offset if asked to and programs page at offset unless it
+/// is null. Placed in RAM and must not call anything that lives in flash.
+#[inline(never)]
+#[link_section = ".data.ram_func"]
+unsafe fn write_sector(funcs: &FlashFuncs, offset: u32, erase: bool, page: *const u8) {
+    (funcs.connect_internal_flash)();
+    (funcs.flash_exit_xip)();
+    if erase {
+        (funcs.flash_range_erase)(offset, SECTOR_SIZE as usize, SECTOR_SIZE, SECTOR_ERASE_CMD);
+    }
+    if !page.is_null() {
+        (funcs.flash_range_program)(offset, page, PAGE_SIZE);
+    }
+    (funcs.flash_flush_cache)();
+    (funcs.boot2)();
+}
@@ -0,0 +1,89 @@
+//! Bitmap fonts used for rendering text on LCD's.
+//! These are embedded directly in executable using include_bytes!.
+//! Glyph bitmaps and table of fonts are generated using build script
+//! (build.rs) by rasterizing TTF files in misc/font.
+
+/// Every font contains printable ASCII characters from FIRST_CHAR to LAST_CHAR
+const FIRST_CHAR: char = ' ';
+const LAST_CHAR: char = '~';
+/// Drawn in place of characters missing from font
+const REPLACEMENT_CHAR: char = '?';
+
+/// Monospace font. All glyphs have the same size.
+pub struct Font {
+    glyph_width: u32,
+    glyph_height: u32,
+    /// Glyph bitmaps 1 bit per pixel, row by row, msb first
+    glyphs: &'static [u8],
+}
+
+impl Font {
+    pub const fn glyph_width(&self) -> u32 {
+        self.glyph_width
+    }
+
+    pub const fn glyph_height(&self) -> u32 {
+        self.glyph_height
+    }
+
+    /// Width of text in pixels when drawn in single line
+    pub fn text_width(&self, text: &str) -> u32 {
+        text.chars().count() as u32 * self.glyph_width()
+    }
+
+    /// Pixels of glyph row by row, true where glyph is drawn
+    pub fn glyph_pixels(&self, c: char) -> impl Iterator<Item = bool> + '_ {
+        let bitmap = self.glyph_bitmap(c);
+        let pixel_count = (self.glyph_width * self.glyph_height) as usize;
+        (0..pixel_count).map(move |bit| bitmap[bit / 8] & (0x80 >> (bit % 8)) != 0)
+    }
+
+    /// Is glyph drawn at column x and row y of it?
+    pub fn glyph_pixel(&self, c: char, x: u32, y: u32) -> bool {
+        let bit = (y * self.glyph_width + x) as usize;
+        self.glyph_bitmap(c)[bit / 8] & (0x80 >> (bit % 8)) != 0
+    }
+
+    fn glyph_bitmap(&self, c: char) -> &'static [u8] {
+        let c = if (FIRST_CHAR..=LAST_CHAR).contains(&c) {
+            c
+        } else {
+            REPLACEMENT_CHAR
+        };
+        let pixel_count = (self.glyph_width * self.glyph_height) as usize;
+        let glyph_size = (pixel_count + 7) / 8;
+        let offset = (c as usize - FIRST_CHAR as usize) * glyph_size;
+        &self.glyphs[offset..offset + glyph_size]
+    }
+}
+
+/// Digits of large time layout, each fills a display. Hours and minutes are
+/// drawn on two displays each.
+pub struct LargeDigits {
+    width: u32,
+    height: u32,
+    /// Bitmaps of digits laid out the same way as glyphs of [`Font`]
+    glyphs: &'static [u8],
+}
+
+impl LargeDigits {
+    pub const fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub const fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Pixels of digit row by row, true where digit is drawn
+    pub fn digit_pixels(&self, digit: u8) -> impl Iterator<Item = bool> + '_ {
+        let pixel_count = (self.width * self.height) as usize;
+        let glyph_size = pixel_count.div_ceil(8);
+        let offset = (digit as usize % 10) * glyph_size;
+        let bitmap = &self.glyphs[offset..offset + glyph_size];
+        (0..pixel_count).map(move |bit| bitmap[bit / 8] & (0x80 >> (bit % 8)) != 0)
+    }
+}
+
+// FONT_LARGE, FONT_SMALL and LARGE_DIGITS, all are DejaVu Sans Mono Bold
+include!(concat!(env!("OUT_DIR"), "/fonts_gen.rs"));
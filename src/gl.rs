@@ -1,40 +1,247 @@
+//! Drawing primitives on top of display driver.
+
+use core::ops::Range;
+
 use crate::{
-    drivers::st7789vwx6::Display, hardware::ST7789VWx6Ty, images::Image, lcd_clock::Error,
+    drivers::st7789vwx6::{self, Display},
+    font::{Font, LARGE_DIGITS},
+    hardware::ST7789VWx6Ty,
+    images::Image,
+    lcd_clock::Error,
     misc::ColorRGB565,
+    qr::QrCode,
 };
 
+/// Font and colors used to draw text
+#[derive(Clone, Copy)]
+pub struct TextStyle {
+    pub font: &'static Font,
+    pub color: ColorRGB565,
+    pub background: ColorRGB565,
+}
+
+impl TextStyle {
+    pub const fn new(font: &'static Font, color: ColorRGB565, background: ColorRGB565) -> Self {
+        Self {
+            font,
+            color,
+            background,
+        }
+    }
+}
+
+/// Colors of progress bar
+#[derive(Clone, Copy)]
+pub struct BarStyle {
+    /// Color of outline and filled part
+    pub color: ColorRGB565,
+    pub background: ColorRGB565,
+}
+
+impl BarStyle {
+    pub const fn new(color: ColorRGB565, background: ColorRGB565) -> Self {
+        Self { color, background }
+    }
+}
+
+/// Space between progress bar and edges of display
+const BAR_MARGIN: u16 = 16;
+/// Thickness of outline of progress bar
+const BAR_OUTLINE: u16 = 3;
+/// Space between outline and filled part of progress bar
+const BAR_PADDING: u16 = 3;
+
+/// Direction colors of gradient change in
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum GradientDirection {
+    TopToBottom,
+    LeftToRight,
+}
+
+/// Light modules around QR code, readers need them to find it
+const QR_QUIET_ZONE: u16 = 4;
+
+/// Offset of everything drawn on displays. Static digits burn into LCDs if
+/// they stay in place for too long, so content is moved by a couple of pixels
+/// once in a while. Pixels moved off display are not drawn, pixels uncovered
+/// keep what was drawn there before.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct PixelShift {
+    pub x: u16,
+    pub y: u16,
+}
+
+impl PixelShift {
+    /// Shifts content goes through, none is more than 2 pixels away from
+    /// original position
+    const CYCLE: [PixelShift; 6] = [
+        PixelShift { x: 0, y: 0 },
+        PixelShift { x: 1, y: 0 },
+        PixelShift { x: 2, y: 1 },
+        PixelShift { x: 2, y: 2 },
+        PixelShift { x: 1, y: 2 },
+        PixelShift { x: 0, y: 1 },
+    ];
+
+    pub fn next(self) -> Self {
+        let index = Self::CYCLE.iter().position(|&shift| shift == self);
+        let index = index.map_or(0, |index| (index + 1) % Self::CYCLE.len());
+        Self::CYCLE[index]
+    }
+}
+
 /// Helper structure containing functions for drawing on displays. (Thus the
 /// name - graphics library).
 pub struct Gl<'a> {
     displays: &'a mut ST7789VWx6Ty,
+    shift: PixelShift,
 }
 
 impl<'a> Gl<'a> {
-    pub fn new(displays: &'a mut ST7789VWx6Ty) -> Self {
-        Self { displays }
+    pub fn new(displays: &'a mut ST7789VWx6Ty, shift: PixelShift) -> Self {
+        Self { displays, shift }
     }
 
-    pub fn fill(&mut self, display: Display, color: ColorRGB565) -> Result<(), Error> {
+    /// Sets pixels of region moved by pixel shift. Part of region that is
+    /// moved off display is dropped.
+    fn set_pixels_iter(
+        &mut self,
+        display: Display,
+        x_start: u16,
+        y_start: u16,
+        x_end: u16,
+        y_end: u16,
+        colors: impl IntoIterator<Item = u8>,
+    ) -> Result<(), Error> {
+        let x_start = x_start + self.shift.x;
+        let y_start = y_start + self.shift.y;
+        let x_end = x_end + self.shift.x;
+        let y_end = y_end + self.shift.y;
         let w = self.displays.width();
         let h = self.displays.height();
+        if x_start >= w || y_start >= h {
+            return Ok(());
+        }
+
+        let visible_x_end = x_end.min(w);
+        let visible_y_end = y_end.min(h);
+        if visible_x_end == x_end && visible_y_end == y_end {
+            return self
+                .displays
+                .set_pixels_iter(display, x_start, y_start, x_end, y_end, colors)
+                .map_err(Error::Display);
+        }
+
+        let width = x_end - x_start;
+        let visible_width = visible_x_end - x_start;
+        let visible_len = 2 * width as usize * (visible_y_end - y_start) as usize;
+        // Drop columns that are off display. Colors are 2 bytes per pixel.
+        let mut col = 0;
+        let mut is_second_byte = false;
+        let colors = colors.into_iter().take(visible_len).filter(move |_| {
+            let is_visible = col < visible_width;
+            if is_second_byte {
+                col += 1;
+                if col == width {
+                    col = 0;
+                }
+            }
+            is_second_byte = !is_second_byte;
+            is_visible
+        });
         self.displays
             .set_pixels_iter(
                 display,
-                0,
-                0,
-                w,
-                h,
-                (0..(w * h)).flat_map(|_| color.to_be()),
+                x_start,
+                y_start,
+                visible_x_end,
+                visible_y_end,
+                colors,
             )
             .map_err(Error::Display)
     }
 
-    pub fn clear_all(&mut self, color: ColorRGB565) -> Result<(), Error> {
-        for display in Display::all() {
-            self.fill(display, color)?;
+    /// The same as set_pixels_iter, but faster when nothing is dropped
+    fn set_pixels(
+        &mut self,
+        display: Display,
+        x_start: u16,
+        y_start: u16,
+        x_end: u16,
+        y_end: u16,
+        colors: &[u8],
+    ) -> Result<(), Error> {
+        let x_end_shifted = x_end + self.shift.x;
+        let y_end_shifted = y_end + self.shift.y;
+        if x_end_shifted > self.displays.width() || y_end_shifted > self.displays.height() {
+            return self.set_pixels_iter(
+                display,
+                x_start,
+                y_start,
+                x_end,
+                y_end,
+                colors.iter().copied(),
+            );
         }
 
-        Ok(())
+        self.displays
+            .set_pixels(
+                display,
+                x_start + self.shift.x,
+                y_start + self.shift.y,
+                x_end_shifted,
+                y_end_shifted,
+                colors,
+            )
+            .map_err(Error::Display)
+    }
+
+    pub fn fill(&mut self, display: Display, color: ColorRGB565) -> Result<(), Error> {
+        self.fill_each([(display, color)])
+    }
+
+    /// Fills several displays, each with a color of its own, in one go
+    pub fn fill_each(
+        &mut self,
+        colors: impl IntoIterator<Item = (Display, ColorRGB565)>,
+    ) -> Result<(), Error> {
+        // Region starts at pixel shift and is cut by edges of display
+        let w = self.displays.width();
+        let h = self.displays.height();
+        let colors = colors
+            .into_iter()
+            .map(|(display, color)| (display, color.to_be()));
+        self.displays
+            .fill_displays(self.shift.x, self.shift.y, w, h, colors)
+            .map_err(Error::Display)
+    }
+
+    /// Fills display with gradient going from one color to the other. Top to
+    /// bottom gradient computes color once per row.
+    pub fn fill_gradient(
+        &mut self,
+        display: Display,
+        from: ColorRGB565,
+        to: ColorRGB565,
+        direction: GradientDirection,
+    ) -> Result<(), Error> {
+        let w = self.displays.width();
+        let h = self.displays.height();
+        let pixels = (0..h).flat_map(move |y| {
+            let row = from.lerp(to, y as u32, h as u32 - 1);
+            (0..w).flat_map(move |x| {
+                let color = match direction {
+                    GradientDirection::TopToBottom => row,
+                    GradientDirection::LeftToRight => from.lerp(to, x as u32, w as u32 - 1),
+                };
+                color.to_be()
+            })
+        });
+        self.set_pixels_iter(display, 0, 0, w, h, pixels)
+    }
+
+    pub fn clear_all(&mut self, color: ColorRGB565) -> Result<(), Error> {
+        self.fill_each(ST7789VWx6Ty::displays().map(|display| (display, color)))
     }
 
     pub fn draw_rect(
@@ -59,12 +266,275 @@ impl<'a> Gl<'a> {
     }
 
     pub fn draw_pic(&mut self, display: Display, pic: &Image) -> Result<(), Error> {
+        self.draw_pic_at(display, 0, 0, pic, None)
+    }
+
+    /// Draws pic with its top left corner at (x, y). Pic has to fit on
+    /// display. Transparent pixels of pic and pixels of key color, if it is
+    /// given, are left untouched. Display can't be read back, so this is done
+    /// by drawing each opaque run of pixels separately. Thus transparent pics
+    /// should be small.
+    pub fn draw_pic_at(
+        &mut self,
+        display: Display,
+        x: u16,
+        y: u16,
+        pic: &Image,
+        key: Option<ColorRGB565>,
+    ) -> Result<(), Error> {
         let w = pic.width() as u16;
         let h = pic.height() as u16;
-        let pix = pic.pixels();
-        self.displays
-            .set_pixels(display, 0, 0, w, h, pix)
-            .map_err(Error::Display)
+        let mut pixels = pic.pixels();
+        if key.is_none() && !pic.is_transparent() {
+            return self.set_pixels_iter(
+                display,
+                x,
+                y,
+                x + w,
+                y + h,
+                pixels.flat_map(|pixel| pixel.unwrap_or_default()),
+            );
+        }
+
+        let key = key.map(ColorRGB565::to_be);
+        let mut run = [0u8; 2 * st7789vwx6::WIDTH as usize];
+        for y in y..y + h {
+            let mut run_start = 0;
+            let mut run_len = 0;
+            for col in 0..w {
+                let pixel = pixels.next().flatten().filter(|&pixel| Some(pixel) != key);
+                if let Some(pixel) = pixel {
+                    if run_len == 0 {
+                        run_start = col;
+                    }
+                    run[run_len * 2..][..2].copy_from_slice(&pixel);
+                    run_len += 1;
+                }
+
+                if run_len != 0 && (pixel.is_none() || col + 1 == w) {
+                    let run_end = run_start + run_len as u16;
+                    self.set_pixels(
+                        display,
+                        x + run_start,
+                        y,
+                        x + run_end,
+                        y + 1,
+                        &run[..run_len * 2],
+                    )?;
+                    run_len = 0;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Draws single line of text with top left corner at (x, y). Characters
+    /// that don't fit on display are not drawn.
+    pub fn draw_text(
+        &mut self,
+        display: Display,
+        x: u16,
+        y: u16,
+        text: &str,
+        style: TextStyle,
+    ) -> Result<(), Error> {
+        let w = style.font.glyph_width() as u16;
+        let h = style.font.glyph_height() as u16;
+        if y + h > self.displays.height() {
+            return Ok(());
+        }
+
+        let mut x = x;
+        for c in text.chars() {
+            if x + w > self.displays.width() {
+                break;
+            }
+
+            let pixels = style.font.glyph_pixels(c).flat_map(|is_set| {
+                if is_set {
+                    style.color.to_be()
+                } else {
+                    style.background.to_be()
+                }
+            });
+            self.set_pixels_iter(display, x, y, x + w, y + h, pixels)?;
+            x += w;
+        }
+
+        Ok(())
+    }
+
+    /// Width of canvas made of all displays side by side
+    pub fn canvas_width(&self) -> u16 {
+        self.displays.width() * ST7789VWx6Ty::displays().count() as u16
+    }
+
+    /// Draws region of canvas made of all displays side by side, split
+    /// between displays it spans. Color of pixel is given by its column and
+    /// row on canvas. The rest of canvas is left untouched.
+    pub fn draw_canvas(
+        &mut self,
+        columns: Range<i32>,
+        rows: Range<u16>,
+        color: impl Fn(i32, u16) -> ColorRGB565 + Copy,
+    ) -> Result<(), Error> {
+        let w = self.displays.width() as i32;
+        for display in ST7789VWx6Ty::displays() {
+            let left = display as i32 * w;
+            let start = columns.start.max(left);
+            let end = columns.end.min(left + w);
+            if start >= end || rows.is_empty() {
+                continue;
+            }
+
+            let pixels = rows
+                .clone()
+                .flat_map(move |y| (start..end).flat_map(move |x| color(x, y).to_be()));
+            let (start, end) = ((start - left) as u16, (end - left) as u16);
+            self.set_pixels_iter(display, start, rows.start, end, rows.end, pixels)?;
+        }
+
+        Ok(())
+    }
+
+    /// Draws single line of text on canvas made of all displays side by side.
+    /// Text starts at column x of canvas and may stick out of it on either
+    /// side. Only given columns of canvas are drawn, so text scrolled by a few
+    /// pixels redraws only columns it covers or has just left. Text is drawn
+    /// byte by byte, so it should be ASCII.
+    pub fn draw_marquee(
+        &mut self,
+        text: &str,
+        x: i32,
+        y: u16,
+        columns: Range<i32>,
+        style: TextStyle,
+    ) -> Result<(), Error> {
+        let glyph_w = style.font.glyph_width() as i32;
+        let glyph_h = style.font.glyph_height() as u16;
+        let text = text.as_bytes();
+        self.draw_canvas(columns, y..y + glyph_h, move |col, row| {
+            let text_x = col - x;
+            let is_set = text_x >= 0
+                && text.get((text_x / glyph_w) as usize).is_some_and(|&c| {
+                    style
+                        .font
+                        .glyph_pixel(c as char, (text_x % glyph_w) as u32, (row - y) as u32)
+                });
+            if is_set {
+                style.color
+            } else {
+                style.background
+            }
+        })
+    }
+
+    /// Draws digit of large time layout covering the whole display
+    pub fn draw_large_digit(
+        &mut self,
+        display: Display,
+        digit: u8,
+        color: ColorRGB565,
+        background: ColorRGB565,
+    ) -> Result<(), Error> {
+        let w = LARGE_DIGITS.width() as u16;
+        let h = LARGE_DIGITS.height() as u16;
+        let pixels = LARGE_DIGITS.digit_pixels(digit).flat_map(|is_set| {
+            if is_set {
+                color.to_be()
+            } else {
+                background.to_be()
+            }
+        });
+        self.set_pixels_iter(display, 0, 0, w, h, pixels)
+    }
+
+    /// Draws area chart. Column x is filled with color from the bottom of
+    /// display up to heights[x] pixels, the rest is filled with background.
+    /// Missing heights are treated as zero.
+    pub fn draw_area_chart(
+        &mut self,
+        display: Display,
+        heights: &[u16],
+        color: ColorRGB565,
+        background: ColorRGB565,
+    ) -> Result<(), Error> {
+        let w = self.displays.width();
+        let h = self.displays.height();
+        let pixels = (0..h).flat_map(move |y| {
+            (0..w).flat_map(move |x| {
+                let height = heights.get(x as usize).copied().unwrap_or(0);
+                if h - y <= height {
+                    color.to_be()
+                } else {
+                    background.to_be()
+                }
+            })
+        });
+        self.set_pixels_iter(display, 0, 0, w, h, pixels)
+    }
+
+    /// Draws vertical progress bar covering the whole display. Bar is
+    /// outlined and filled from the bottom in proportion of value to max.
+    pub fn draw_progress_bar(
+        &mut self,
+        display: Display,
+        value: u32,
+        max: u32,
+        style: BarStyle,
+    ) -> Result<(), Error> {
+        let w = self.displays.width();
+        let h = self.displays.height();
+        let inset = BAR_MARGIN + BAR_OUTLINE + BAR_PADDING;
+        let inner_h = h.saturating_sub(2 * inset);
+        let filled = (inner_h as u32 * value.min(max) / max.max(1)) as u16;
+        let pixels = (0..h).flat_map(move |y| {
+            (0..w).flat_map(move |x| {
+                let dx = x.min(w - 1 - x);
+                let dy = y.min(h - 1 - y);
+                let d = dx.min(dy);
+                let is_outline = (BAR_MARGIN..BAR_MARGIN + BAR_OUTLINE).contains(&d);
+                let is_filled = d >= inset && y >= h - inset - filled;
+                if is_outline || is_filled {
+                    style.color.to_be()
+                } else {
+                    style.background.to_be()
+                }
+            })
+        });
+        self.set_pixels_iter(display, 0, 0, w, h, pixels)
+    }
+
+    /// Draws QR code covering the whole display. Modules are scaled to
+    /// largest whole number of pixels that fits width of display together
+    /// with quiet zone, code is centered.
+    pub fn draw_qr(
+        &mut self,
+        display: Display,
+        qr: &QrCode,
+        color: ColorRGB565,
+        background: ColorRGB565,
+    ) -> Result<(), Error> {
+        let w = self.displays.width();
+        let h = self.displays.height();
+        let size = qr.size() as u16;
+        let scale = (w.min(h) / (size + 2 * QR_QUIET_ZONE)).max(1);
+        let left = w.saturating_sub(size * scale) / 2;
+        let top = h.saturating_sub(size * scale) / 2;
+        let pixels = (0..h).flat_map(move |y| {
+            (0..w).flat_map(move |x| {
+                let is_dark = x >= left
+                    && y >= top
+                    && qr.get(((x - left) / scale) as usize, ((y - top) / scale) as usize);
+                if is_dark {
+                    color.to_be()
+                } else {
+                    background.to_be()
+                }
+            })
+        });
+        self.set_pixels_iter(display, 0, 0, w, h, pixels)
     }
 
     pub fn draw_bounding_rect(
@@ -0,0 +1,46 @@
+//! Time from GPS module attached to UART0 (GPIO0 TX, GPIO1 RX).
+//!
+//! Module sends NMEA sentences every second whether anyone listens or not, so
+//! UART is drained from its interrupt before receive FIFO overflows. Handler
+//! keeps only the last RMC sentence, which main loop takes with [`take`].
+//! Without module attached interrupt never fires and [`take`] always returns
+//! None.
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::{self, Mutex};
+
+use crate::{
+    drivers::nmea::{Nmea, Rmc},
+    hal::pac,
+    hardware::GpsUartTy,
+};
+
+static NMEA: Mutex<RefCell<Option<Nmea<GpsUartTy>>>> = Mutex::new(RefCell::new(None));
+static RMC: Mutex<Cell<Option<Rmc>>> = Mutex::new(Cell::new(None));
+
+/// Hands UART over to interrupt handler and enables receive interrupt
+pub fn init(mut uart: GpsUartTy) {
+    uart.enable_rx_interrupt();
+    interrupt::free(|cs| NMEA.borrow(cs).replace(Some(Nmea::new(uart))));
+    // SAFETY: handler only touches data behind critical section
+    unsafe {
+        pac::NVIC::unmask(pac::Interrupt::UART0_IRQ);
+    }
+}
+
+/// Must be called from UART0_IRQ interrupt handler
+pub fn on_interrupt() {
+    interrupt::free(|cs| {
+        if let Some(nmea) = NMEA.borrow(cs).borrow_mut().as_mut() {
+            if let Some(rmc) = nmea.poll() {
+                RMC.borrow(cs).set(Some(rmc));
+            }
+        }
+    })
+}
+
+/// Last RMC sentence received since previous call
+pub fn take() -> Option<Rmc> {
+    interrupt::free(|cs| RMC.borrow(cs).take())
+}
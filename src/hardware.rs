@@ -1,58 +1,161 @@
+//! Board layer. Binds generic drivers to concrete pins and peripherals of
+//! LCD-Clock-A. Ports to other boards are expected to replace this module.
+//!
+//! [`setup`] brings the board up from peripherals of RP2040, so the binary
+//! doesn't touch pins or clocks itself.
+
+use embedded_hal::{
+    adc::{Channel, OneShot},
+    digital::v2::InputPin,
+    spi::MODE_0,
+};
+use fugit::{HertzU32, RateExtU32};
+use rp_pico::Pins;
+use usb_device::class_prelude::UsbBusAllocator;
+
 use crate::{
+    bell::Bell,
+    console,
+    diagnostics::{Diagnostics, PeripheralStatus, Power},
+    dormant::{self, WakeLevel},
     drivers::{
-        bme280::{BME280State, BME280},
-        buttons::{Button, ButtonEvent},
-        ds3231::{DS3231State, DS3231},
-        st7789vwx6::ST7789VWx6,
-        ws2812::WS2812,
+        at24cxx::{self, AT24Cxx, AT24CxxState},
+        bme280::{self, BME280State, BME280},
+        buttons::{Button, ButtonEvent, Debounce},
+        ds3231::{DS3231State, SquareWave, DS3231},
+        shared_i2c::{I2CProxy, SharedI2C},
+        st7789vwx6::{self, ST7789VWx6},
+        ws2812::{self, DmaChannels, WS2812},
     },
-    gl::Gl,
-    lcd_clock::Error,
+    flash::Flash,
+    gl::{Gl, PixelShift},
+    gps,
+    lcd_clock::{backlight_duty, Error},
+    led_strip::RING_LED_COUNT,
     misc::{ColorRGB565, ColorRGB8},
+    settings::{self, Record, Storage},
+    tick, timer,
 };
 
 use crate::hal::{
+    adc::Adc,
+    clocks::{init_clocks_and_plls, Clock},
     gpio::{
-        bank0::{Gpio12, Gpio15, Gpio16, Gpio17, Gpio2, Gpio22, Gpio3, Gpio4, Gpio6, Gpio7, Gpio8},
-        FunctionI2C, Pin, PullDownInput, PushPullOutput,
+        FloatingInput, FunctionI2C, FunctionSpi, FunctionUart, Pin, PinId, PullDownInput,
+        PullUpInput, PushPullOutput,
     },
     i2c::I2C,
-    pac::{I2C1, PIO0, SPI1},
-    pio::SM0,
-    pwm::{self, Pwm6},
+    pac::{Peripherals, PIO0},
+    pio::{PIOExt, SM0, SM1},
+    pwm::{self, Slice, SliceId, SliceMode, Slices, ValidSliceMode},
     spi::{self, Spi},
+    uart::{self, DataBits, StopBits, UartConfig, UartPeripheral},
+    usb::UsbBus,
+    watchdog::Watchdog,
+    Sio, Timer,
 };
+#[cfg(feature = "display-te")]
+use crate::pinout::DisplayTe;
+use crate::pinout::{
+    self, Backlight, BacklightChannel, BacklightSlice, Backup, DisplayCsa1, DisplayCsa2,
+    DisplayCsa3, DisplayDc, DisplayRst, DisplaySpi, GpsRx, GpsTx, GpsUart, I2CBlock, I2CScl,
+    I2CSda, LedRing, LedStrip, LeftButton, Microphone, ModeButton, Pinout, RightButton, RtcSqw,
+    Vsys, REVISION,
+};
+#[cfg(feature = "uart-console")]
+use crate::pinout::{ConsoleRx, ConsoleTx, ConsoleUart};
 
-pub type I2CBusTy = I2C<I2C1, (Pin<Gpio6, FunctionI2C>, Pin<Gpio7, FunctionI2C>)>;
+pub type I2CBusTy = I2C<I2CBlock, (Pin<I2CSda, FunctionI2C>, Pin<I2CScl, FunctionI2C>)>;
+/// Displays of the kit. LCD-Clock-A has 6, variants of the kit have 4 or 8.
+pub const DISPLAY_COUNT: usize = 6;
+/// Brightness backlight is lit with until settings are loaded
+pub const BOOT_BRIGHTNESS: u32 = 5;
+/// Clock of display SPI in MHz unless set over console. RP2040 divides
+/// peripheral clock, so it runs at the fastest clock up to this one.
+pub const SPI_CLOCK_MHZ: u32 = 40;
+/// Revision of the kit, see [`pinout`](crate::pinout)
+pub const BOARD_REVISION: &str = REVISION;
 pub type ST7789VWx6Ty = ST7789VWx6<
     (
-        Pin<Gpio2, PushPullOutput>,
-        Pin<Gpio3, PushPullOutput>,
-        Pin<Gpio4, PushPullOutput>,
-        Pin<Gpio8, PushPullOutput>,
-        Pin<Gpio12, PushPullOutput>,
+        Pin<DisplayCsa1, PushPullOutput>,
+        Pin<DisplayCsa2, PushPullOutput>,
+        Pin<DisplayCsa3, PushPullOutput>,
+        Pin<DisplayDc, PushPullOutput>,
+        Pin<DisplayRst, PushPullOutput>,
     ),
-    Spi<spi::Enabled, SPI1, 8>,
-    pwm::Channel<Pwm6, pwm::FreeRunning, pwm::B>,
+    Spi<spi::Enabled, DisplaySpi, 8>,
+    pwm::Channel<BacklightSlice, pwm::FreeRunning, BacklightChannel>,
+    DISPLAY_COUNT,
+    DisplayTeTy,
 >;
-pub type WS2812Ty = WS2812<PIO0, SM0, Gpio22>;
-pub type DS3231Ty = DS3231<I2CBusTy>;
-pub type BME280Ty = BME280<I2CBusTy>;
+/// TE output of a display, see [`st7789vwx6`](crate::drivers::st7789vwx6)
+#[cfg(feature = "display-te")]
+pub type DisplayTeTy = Pin<DisplayTe, FloatingInput>;
+#[cfg(not(feature = "display-te"))]
+pub type DisplayTeTy = st7789vwx6::NoTe;
+pub type WS2812Ty = WS2812<PIO0, SM0, LedStrip>;
+pub type WS2812RingTy = WS2812<PIO0, SM1, LedRing, RING_LED_COUNT>;
+/// Handle to I2C bus shared by rtc and humidity sensor
+pub type I2CProxyTy = I2CProxy<'static, I2CBusTy>;
+pub type DS3231Ty = DS3231<I2CProxyTy>;
+pub type BME280Ty = BME280<I2CProxyTy>;
+pub type AT24CxxTy = AT24Cxx<I2CProxyTy>;
 
-pub type LeftBtnTy = Button<Pin<Gpio15, PullDownInput>>;
-pub type RightBtnTy = Button<Pin<Gpio16, PullDownInput>>;
-pub type ModeBtnTy = Button<Pin<Gpio17, PullDownInput>>;
+/// Builds with touch pads instead of buttons can use
+/// `Button<Electrode<I2CProxyTy>>`, see [`mpr121`](crate::drivers::mpr121)
+pub type LeftBtnTy = Button<Pin<LeftButton, PullDownInput>>;
+pub type RightBtnTy = Button<Pin<RightButton, PullDownInput>>;
+pub type ModeBtnTy = Button<Pin<ModeButton, PullDownInput>>;
+/// Buzzer is not fitted, see [`bell::Buzzer`](crate::bell::Buzzer)
 pub type BuzzerTy = ();
+/// INT/SQW output of ds3231. It is open drain, so pull up is needed.
+pub type SqwPinTy = Pin<RtcSqw, PullUpInput>;
+/// VSYS through 200k/100k divider on Pico
+pub type VsysPinTy = Pin<Vsys, FloatingInput>;
+/// Optional backup cell of rtc through 100k/100k divider
+pub type BackupPinTy = Pin<Backup, FloatingInput>;
+/// Optional microphone module with output biased to half of supply
+pub type MicPinTy = Pin<Microphone, FloatingInput>;
+/// Optional GPS module on spare pins, TX and RX of the board respectively
+pub type GpsUartTy =
+    UartPeripheral<uart::Enabled, GpsUart, (Pin<GpsTx, FunctionUart>, Pin<GpsRx, FunctionUart>)>;
+/// Optional command console on spare pins, see [`console`](crate::console)
+#[cfg(feature = "uart-console")]
+pub type ConsoleUartTy = UartPeripheral<
+    uart::Enabled,
+    ConsoleUart,
+    (Pin<ConsoleTx, FunctionUart>, Pin<ConsoleRx, FunctionUart>),
+>;
 
 /// Hardware of clock. It is basically a collection of drivers. Its
 /// functionality loosely corresponds to Model in MVC.
 pub struct LcdClockHardware {
-    i2c_bus: Option<I2CBusTy>,
-    rtc: Option<DS3231State>,
-    humidity_sensor: Option<BME280State>,
+    i2c_bus: &'static SharedI2C<I2CBusTy>,
+    /// Peripherals found at init
+    diagnostics: Diagnostics,
+    /// Offset of everything drawn on displays, see PixelShift
+    pixel_shift: PixelShift,
+    /// Was mode held while clock was powering up?
+    mode_held_at_boot: bool,
+    pub rtc: DS3231Ty,
+    pub humidity_sensor: BME280Ty,
+    pub settings_storage: SettingsStorage,
+    /// Climate log is always kept in internal flash
+    pub log_storage: Flash,
+    adc: Adc,
+    vsys_pin: VsysPinTy,
+    /// None if backup cell is not wired to ADC
+    backup_pin: Option<BackupPinTy>,
+    /// None if microphone is not wired to ADC
+    mic_pin: Option<MicPinTy>,
     pub displays: ST7789VWx6Ty,
-    pub led_strip: WS2812Ty,
-    pub buzzer: BuzzerTy,
+    /// Clock display SPI divides its baud rate from, as configured at setup
+    peripheral_clock: HertzU32,
+    /// None if PIO program couldn't be installed
+    pub led_strip: Option<WS2812Ty>,
+    /// None if ring is not fitted or its PIO program couldn't be installed
+    pub led_ring: Option<WS2812RingTy>,
+    pub bell: Bell<BuzzerTy>,
     pub left: LeftBtnTy,
     pub right: RightBtnTy,
     pub mode: ModeBtnTy,
@@ -60,83 +163,176 @@ pub struct LcdClockHardware {
 
 impl LcdClockHardware {
     pub fn new(
-        i2c_bus: I2CBusTy,
+        i2c_bus: &'static SharedI2C<I2CBusTy>,
         displays: ST7789VWx6Ty,
-        led_strip: WS2812Ty,
+        peripheral_clock: HertzU32,
+        led_strip: Option<WS2812Ty>,
+        led_ring: Option<WS2812RingTy>,
         left: LeftBtnTy,
         right: RightBtnTy,
         mode: ModeBtnTy,
         buzzer: BuzzerTy,
+        adc: Adc,
+        vsys_pin: VsysPinTy,
+        backup_pin: Option<BackupPinTy>,
+        mic_pin: Option<MicPinTy>,
     ) -> Self {
         Self {
-            i2c_bus: Some(i2c_bus),
-            rtc: None,
-            humidity_sensor: None,
+            i2c_bus,
+            diagnostics: Default::default(),
+            rtc: DS3231::new(i2c_bus.acquire(), DS3231State::new(DS3231_I2C_ADDR)),
+            humidity_sensor: BME280::new(i2c_bus.acquire(), BME280State::new(BME280_I2C_ADDR)),
+            settings_storage: SettingsStorage::Flash(Flash::new()),
+            log_storage: Flash::new(),
+            adc,
+            vsys_pin,
+            backup_pin,
+            mic_pin,
+            pixel_shift: Default::default(),
+            mode_held_at_boot: false,
             displays,
+            peripheral_clock,
             led_strip,
+            led_ring,
             left,
             right,
             mode,
-            buzzer,
+            bell: Bell::new(buzzer),
         }
     }
 
+    /// Chips on I2C bus that are missing or fail to initialize are only
+    /// recorded in diagnostics, their drivers must not be used then. The same
+    /// goes for LED strip. Peripherals may still be powering up after cold
+    /// boot, so failing ones are retried a few times before that.
     pub fn init(&mut self) -> Result<(), Error> {
-        self.rtc.replace(DS3231State::new(DS3231_I2C_ADDR));
-        self.humidity_sensor
-            .replace(BME280State::new(BME280_I2C_ADDR));
-        self.with_rtc(DS3231Ty::init)?.map_err(Error::Rtc)?;
-        self.with_humidity_sensor(BME280Ty::init)?
-            .map_err(Error::HumiditySensor)?;
+        self.diagnostics.i2c_scan = self.i2c_bus.scan();
+        self.diagnostics.rtc = self.with_retries(Self::init_rtc);
+        self.diagnostics.humidity_sensor = self.with_retries(Self::init_humidity_sensor);
+        if self.led_strip.is_none() {
+            self.diagnostics.led_strip = PeripheralStatus::Failed;
+        }
+        if self.diagnostics.i2c_scan.contains(EEPROM_I2C_ADDR) {
+            let state = AT24CxxState::new(EEPROM_I2C_ADDR, EEPROM_VARIANT);
+            let eeprom = AT24Cxx::new(self.i2c_bus.acquire(), state);
+            self.settings_storage = SettingsStorage::Eeprom(eeprom);
+        }
         self.displays.init().map_err(Error::Display)?;
         self.with_gl(|gl| gl.clear_all(ColorRGB565::from(ColorRGB8::black())))?;
 
         Ok(())
     }
 
-    /// Calls f on instance of ds3231. I2C bus is shared between ds3231 and
-    /// bme280 drivers and rust type system forbids us from using two
-    /// drivers simultaneosly. Thus i2c_bus field acts like a mutex.
-    pub fn with_rtc<R>(&mut self, f: impl FnOnce(&mut DS3231Ty) -> R) -> Result<R, Error> {
-        if self.i2c_bus.is_none() || self.rtc.is_none() {
-            return Err(Error::I2CClaim);
+    /// Runs init of chip on I2C bus until it succeeds or retries run out
+    fn with_retries(&mut self, init: fn(&mut Self) -> PeripheralStatus) -> PeripheralStatus {
+        let mut status = init(self);
+        for delay_ms in retry_delays() {
+            if status.is_ok() {
+                break;
+            }
+            cortex_m::asm::delay(delay_ms * CYCLES_PER_MS);
+            // Chip that was missing may have started to answer
+            self.diagnostics.i2c_scan = self.i2c_bus.scan();
+            status = init(self);
         }
+        status
+    }
 
-        let (Some(i2c_bus), Some(ds3231_state)) = (self.i2c_bus.take(), self.rtc.take()) else {
-            return Err(Error::I2CClaim);
-        };
+    fn init_rtc(&mut self) -> PeripheralStatus {
+        if !self.diagnostics.i2c_scan.contains(DS3231_I2C_ADDR) {
+            return PeripheralStatus::Missing;
+        }
 
-        let mut ds3231 = DS3231Ty::new(i2c_bus, ds3231_state);
-        let result = f(&mut ds3231);
-        let (i2c_bus, ds3231_state) = ds3231.release();
-        self.i2c_bus.replace(i2c_bus);
-        self.rtc.replace(ds3231_state);
-        Ok(result)
+        // Square wave drives second tick, see tick module
+        let result = self
+            .rtc
+            .init()
+            .and_then(|()| self.rtc.enable_square_wave(SquareWave::Hz1));
+        match result {
+            Ok(()) => PeripheralStatus::Ok,
+            Err(_) => PeripheralStatus::Failed,
+        }
     }
 
-    /// Calls f on instance of bme280. For details see with_ds3231.
-    pub fn with_humidity_sensor<R>(
-        &mut self,
-        f: impl FnOnce(&mut BME280Ty) -> R,
-    ) -> Result<R, Error> {
-        if self.i2c_bus.is_none() || self.humidity_sensor.is_none() {
-            return Err(Error::I2CClaim);
+    fn init_humidity_sensor(&mut self) -> PeripheralStatus {
+        if !self.diagnostics.i2c_scan.contains(BME280_I2C_ADDR) {
+            return PeripheralStatus::Missing;
         }
 
-        let (Some(i2c_bus), Some(bme280_state)) = (self.i2c_bus.take(), self.humidity_sensor.take()) else {
-            return Err(Error::I2CClaim);
-        };
+        match self.humidity_sensor.init(BME280_CONFIG) {
+            Ok(()) => PeripheralStatus::Ok,
+            Err(_) => PeripheralStatus::Failed,
+        }
+    }
+
+    pub fn diagnostics(&self) -> &Diagnostics {
+        &self.diagnostics
+    }
+
+    /// Samples supply voltages with ADC
+    pub fn measure_power(&mut self) -> Power {
+        let vsys = read_millivolts(&mut self.adc, &mut self.vsys_pin) * VSYS_DIVIDER;
+        let backup = self
+            .backup_pin
+            .as_mut()
+            .map(|pin| read_millivolts(&mut self.adc, pin) * BACKUP_DIVIDER);
+        Power { vsys, backup }
+    }
+
+    pub fn has_microphone(&self) -> bool {
+        self.mic_pin.is_some()
+    }
+
+    /// Raw 12 bit sample of microphone, None if there is none
+    pub fn read_microphone(&mut self) -> Option<u16> {
+        let pin = self.mic_pin.as_mut()?;
+        nb::block!(self.adc.read(pin)).ok()
+    }
+
+    /// Stops clocks until button is pressed or rtc pulls INT/SQW low on
+    /// alarm. Displays and LEDs are left as they are.
+    pub fn dormant(&mut self) {
+        dormant::sleep(&DORMANT_WAKE_PINS);
+    }
+
+    /// Resets RP2040, firmware starts over as if it was powered up
+    pub fn reboot(&mut self) {
+        cortex_m::peripheral::SCB::sys_reset();
+    }
+
+    /// Restarts RP2040 into USB bootloader in its ROM, the same as if BOOTSEL
+    /// was held while plugging the clock in. Both mass storage and PICOBOOT
+    /// interfaces are exposed, no pin signals activity.
+    pub fn enter_bootloader(&mut self) {
+        crate::hal::rom_data::reset_to_usb_boot(0, 0);
+    }
 
-        let mut bme280 = BME280Ty::new(i2c_bus, bme280_state);
-        let result = f(&mut bme280);
-        let (i2c_bus, bme280_state) = bme280.release();
-        self.i2c_bus.replace(i2c_bus);
-        self.humidity_sensor.replace(bme280_state);
-        Ok(result)
+    /// Mode held while clock boots runs self test, see [`setup`]
+    pub fn is_mode_held_at_boot(&self) -> bool {
+        self.mode_held_at_boot
+    }
+
+    pub fn pixel_shift(&self) -> PixelShift {
+        self.pixel_shift
+    }
+
+    /// Moves content drawn from now on. Displays have to be redrawn.
+    pub fn set_pixel_shift(&mut self, pixel_shift: PixelShift) {
+        self.pixel_shift = pixel_shift;
+    }
+
+    /// Changes clock of display SPI, displays are initialized again and
+    /// have to be redrawn
+    pub fn set_spi_clock(&mut self, mhz: u32) -> Result<(), Error> {
+        self.displays
+            .reconfigure_spi(|spi| {
+                spi.set_baudrate(self.peripheral_clock, (mhz * 1_000_000).Hz());
+            })
+            .map_err(Error::Display)
     }
 
     pub fn with_gl<R>(&mut self, f: impl FnOnce(&mut Gl) -> R) -> R {
-        let mut gl = Gl::new(&mut self.displays);
+        let mut gl = Gl::new(&mut self.displays, self.pixel_shift);
         f(&mut gl)
     }
 
@@ -147,10 +343,303 @@ impl LcdClockHardware {
         Option<ButtonEvent>,
         Option<ButtonEvent>,
     ) {
-        (self.mode.update(), self.left.update(), self.right.update())
+        let now_ms = timer::now_ms();
+        (
+            self.mode.update(now_ms),
+            self.left.update(now_ms),
+            self.right.update(now_ms),
+        )
     }
 }
 
+/// Settings are kept in EEPROM on builds that have one and in internal flash
+/// otherwise. Storage is chosen at init by scanning I2C bus.
+pub enum SettingsStorage {
+    Flash(Flash),
+    Eeprom(AT24CxxTy),
+}
+
+impl Storage for SettingsStorage {
+    fn read(&mut self, record: &mut Record) -> Result<(), settings::Error> {
+        match self {
+            Self::Flash(flash) => Storage::read(flash, record),
+            Self::Eeprom(eeprom) => Storage::read(eeprom, record),
+        }
+    }
+
+    fn write(&mut self, record: &Record) -> Result<(), settings::Error> {
+        match self {
+            Self::Flash(flash) => Storage::write(flash, record),
+            Self::Eeprom(eeprom) => Storage::write(eeprom, record),
+        }
+    }
+}
+
+/// Brings up the board: clocks, pins of [`Pinout`] and peripherals they are
+/// routed to, and drivers built on top of them. Timer, second tick, USB and
+/// UART are handed over to modules that own them, so the binary has to route
+/// their interrupts there. Displays start with backlight of
+/// [`BOOT_BRIGHTNESS`].
+pub fn setup(mut dp: Peripherals) -> LcdClockHardware {
+    let mut wdg = Watchdog::new(dp.WATCHDOG);
+    let sio = Sio::new(dp.SIO);
+
+    let clocks = init_clocks_and_plls(
+        rp_pico::XOSC_CRYSTAL_FREQ,
+        dp.XOSC,
+        dp.CLOCKS,
+        dp.PLL_SYS,
+        dp.PLL_USB,
+        &mut dp.RESETS,
+        &mut wdg,
+    )
+    .ok()
+    .unwrap();
+
+    let pins = Pins::new(dp.IO_BANK0, dp.PADS_BANK0, sio.gpio_bank0, &mut dp.RESETS);
+    let pwm_slices = Slices::new(dp.PWM, &mut dp.RESETS);
+    let pinout = Pinout::new(pins, pwm_slices);
+
+    let i2c_bus = {
+        let sda = pinout.i2c_sda.into_mode::<FunctionI2C>();
+        let scl = pinout.i2c_scl.into_mode::<FunctionI2C>();
+        I2C::i2c1(
+            dp.I2C1,
+            sda,
+            scl,
+            100u32.kHz(),
+            &mut dp.RESETS,
+            &clocks.peripheral_clock,
+        )
+    };
+
+    let st7789vw = {
+        let csa1 = pinout.display_csa1.into_push_pull_output();
+        let csa2 = pinout.display_csa2.into_push_pull_output();
+        let csa3 = pinout.display_csa3.into_push_pull_output();
+        let dc = pinout.display_dc.into_push_pull_output();
+        let rst = pinout.display_rst.into_push_pull_output();
+        let _csn = pinout.display_csn.into_mode::<FunctionSpi>();
+        let _clk = pinout.display_clk.into_mode::<FunctionSpi>();
+        let _mosi = pinout.display_mosi.into_mode::<FunctionSpi>();
+        let bl = pinout.backlight.into_push_pull_output();
+
+        let mut pwm = pinout.backlight_slice;
+        pwm.set_ph_correct();
+        pwm.enable();
+
+        let mut channel = pinout::backlight_channel(pwm);
+        channel.output_to(bl);
+
+        let spi = Spi::<_, _, 8>::new(dp.SPI1);
+        let spi = spi.init(
+            &mut dp.RESETS,
+            clocks.peripheral_clock.freq(),
+            (SPI_CLOCK_MHZ * 1_000_000).Hz(),
+            &MODE_0,
+        );
+
+        ST7789VWx6::new(
+            (csa1, csa2, csa3, dc, rst),
+            spi,
+            channel,
+            st7789vwx6::WIDTH,
+            st7789vwx6::HEIGHT,
+            pinout::CS_ORDER,
+            backlight_duty(BOOT_BRIGHTNESS),
+        )
+    };
+    #[cfg(feature = "display-te")]
+    let st7789vw = st7789vw.with_te(pinout.display_te.into_floating_input(), pinout::TE_DISPLAY);
+
+    let (mut pio, sm0, _sm1, _, _) = dp.PIO0.split(&mut dp.RESETS);
+    let dma = DmaChannels::new(dp.DMA, &mut dp.RESETS);
+    let ws2812 = {
+        let rgb = pinout.led_strip.into_mode();
+        // DMA reads frames while main loop goes on
+        let frames = cortex_m::singleton!(: [ws2812::Frame; 2] = Default::default()).unwrap();
+        // Clock is usable without LEDs, failure is shown on diagnostics screen
+        WS2812::new(
+            rgb,
+            &mut pio,
+            sm0,
+            dma.ch0,
+            frames,
+            clocks.peripheral_clock.freq(),
+        )
+        .ok()
+    };
+    #[cfg(feature = "led-ring")]
+    let ws2812_ring = {
+        let rgb = pinout.led_ring.into_mode();
+        let frames = cortex_m::singleton!(
+            : [ws2812::Frame<RING_LED_COUNT>; 2] = [[0; RING_LED_COUNT]; 2]
+        )
+        .unwrap();
+        WS2812::new(
+            rgb,
+            &mut pio,
+            _sm1,
+            dma.ch1,
+            frames,
+            clocks.peripheral_clock.freq(),
+        )
+        .ok()
+    };
+    #[cfg(not(feature = "led-ring"))]
+    let ws2812_ring = None;
+
+    timer::init(Timer::new(dp.TIMER, &mut dp.RESETS));
+
+    let button_debounce_ms = 20;
+    let button_left = Button::new(Debounce::new(
+        pinout.left_button.into_pull_down_input(),
+        button_debounce_ms,
+    ));
+    let button_right = Button::new(Debounce::new(
+        pinout.right_button.into_pull_down_input(),
+        button_debounce_ms,
+    ));
+    let mode_pin = pinout.mode_button.into_pull_down_input();
+    let mode_held_at_boot = mode_pin.is_high().unwrap();
+    let button_mode = Button::new(Debounce::new(mode_pin, button_debounce_ms));
+
+    tick::init(pinout.rtc_sqw.into_pull_up_input());
+
+    let usb_bus = UsbBusAllocator::new(UsbBus::new(
+        dp.USBCTRL_REGS,
+        dp.USBCTRL_DPRAM,
+        clocks.usb_clock,
+        true,
+        &mut dp.RESETS,
+    ));
+    // Bus has to outlive serial port, which is used from interrupt
+    let usb_bus = cortex_m::singleton!(: UsbBusAllocator<UsbBus> = usb_bus).unwrap();
+    console::init(usb_bus);
+
+    let gps_uart = {
+        let tx = pinout.gps_tx.into_mode::<FunctionUart>();
+        let rx = pinout.gps_rx.into_mode::<FunctionUart>();
+        UartPeripheral::new(dp.UART0, (tx, rx), &mut dp.RESETS)
+            .enable(
+                UartConfig::new(9600.Hz(), DataBits::Eight, None, StopBits::One),
+                clocks.peripheral_clock.freq(),
+            )
+            .unwrap()
+    };
+    gps::init(gps_uart);
+
+    #[cfg(feature = "uart-console")]
+    {
+        let tx = pinout.console_tx.into_mode::<FunctionUart>();
+        let rx = pinout.console_rx.into_mode::<FunctionUart>();
+        let console_uart = UartPeripheral::new(dp.UART1, (tx, rx), &mut dp.RESETS)
+            .enable(
+                UartConfig::new(115200.Hz(), DataBits::Eight, None, StopBits::One),
+                clocks.peripheral_clock.freq(),
+            )
+            .unwrap();
+        console::init_uart(console_uart);
+    }
+
+    // Rtc and humidity sensor keep handles to the bus for the whole program
+    let i2c_bus = cortex_m::singleton!(: SharedI2C<I2CBusTy> = SharedI2C::new(i2c_bus)).unwrap();
+
+    let adc = Adc::new(dp.ADC, &mut dp.RESETS);
+    let vsys_pin = pinout.vsys.into_floating_input();
+    // Boards with backup cell wired through divider pass
+    // Some(pinout.backup.into_floating_input())
+    let backup_pin = None;
+    // Boards with microphone module pass
+    // Some(pinout.microphone.into_floating_input())
+    let mic_pin = None;
+    let mut hardware = LcdClockHardware::new(
+        i2c_bus,
+        st7789vw,
+        clocks.peripheral_clock.freq(),
+        ws2812,
+        ws2812_ring,
+        button_left,
+        button_right,
+        button_mode,
+        (),
+        adc,
+        vsys_pin,
+        backup_pin,
+        mic_pin,
+    );
+    hardware.mode_held_at_boot = mode_held_at_boot;
+    hardware
+}
+
+/// Sets PWM slice to run at given frequency, returns its top value. Used to
+/// drive buzzer.
+pub fn set_pwm_period<I: SliceId, M: SliceMode + ValidSliceMode<I>>(
+    slice: &mut Slice<I, M>,
+    sysclk: u32,
+    freq: u32,
+) -> u16 {
+    // div_frac = 0
+    // ph_correct = 0
+    // div_int = 125
+    // freq = [0, 1000]
+    // period = [0, 125_000]
+    // period = (top + 1) * 125
+    // (top + 1) = [0, 1000]
+    // top = period / 125 - 1
+    let period = sysclk / freq;
+    let div_int = 125;
+    let top = (period / div_int) as u16;
+    slice.set_top(top);
+    slice.clr_ph_correct();
+    slice.set_div_int(125);
+    slice.set_div_frac(0);
+
+    top
+}
+
+/// Delays before retries of failed init in milliseconds, each one is twice
+/// as long as previous
+fn retry_delays() -> impl Iterator<Item = u32> {
+    (0..INIT_RETRIES).map(|retry| INIT_RETRY_DELAY_MS << retry)
+}
+
+/// Voltage on ADC pin in millivolts. ADC is 12 bit with 3.3V reference.
+fn read_millivolts<PIN>(adc: &mut Adc, pin: &mut PIN) -> u16
+where
+    PIN: Channel<Adc, ID = u8>,
+    Adc: OneShot<Adc, u16, PIN>,
+{
+    let raw: u16 = nb::block!(adc.read(pin)).unwrap_or(0);
+    (raw as u32 * ADC_REFERENCE_MV / 4096) as u16
+}
+
+/// Clock shows measurements at most once a second, so there is no need for
+/// filtering. Temperature and pressure are sampled once, humidity 16 times.
+pub const BME280_CONFIG: bme280::Config = bme280::Config::new();
+
 /// This addresses are specified in schematic for product.
 pub const BME280_I2C_ADDR: u8 = 0x76;
 pub const DS3231_I2C_ADDR: u8 = 0x68;
+const ADC_REFERENCE_MV: u32 = 3300;
+/// Cycles of 125MHz system clock in a millisecond
+const CYCLES_PER_MS: u32 = 125 * 1000;
+/// Retries of peripheral that fails to initialize, with delays of 2, 4, 8
+/// and 16ms they take about 30ms at most
+const INIT_RETRIES: u32 = 4;
+const INIT_RETRY_DELAY_MS: u32 = 2;
+/// Buttons are active high, rtc alarm is active low
+const DORMANT_WAKE_PINS: [(u8, WakeLevel); 4] = [
+    (<LeftButton as PinId>::DYN.num, WakeLevel::High),
+    (<RightButton as PinId>::DYN.num, WakeLevel::High),
+    (<ModeButton as PinId>::DYN.num, WakeLevel::High),
+    (<RtcSqw as PinId>::DYN.num, WakeLevel::Low),
+];
+/// Ratios of voltage dividers in front of ADC pins
+const VSYS_DIVIDER: u16 = 3;
+const BACKUP_DIVIDER: u16 = 2;
+
+/// Optional EEPROM with all address pins pulled low
+pub const EEPROM_I2C_ADDR: u8 = 0x50;
+/// The smallest supported EEPROM, larger ones work with it as well
+pub const EEPROM_VARIANT: at24cxx::Variant = at24cxx::Variant::AT24C32;
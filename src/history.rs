@@ -0,0 +1,141 @@
+//! History of humidity sensor measurements over last 24 hours.
+
+use crate::drivers::{bme280::Measurement, ds3231::Time};
+use heapless::HistoryBuffer;
+use serde::{Deserialize, Serialize};
+
+/// Measurements are sampled every this many minutes
+pub const PERIOD_MINS: u8 = 5;
+/// Number of samples covering 24 hours
+pub const LEN: usize = 24 * 60 / PERIOD_MINS as usize;
+
+/// Measurement stored in compact form
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Sample {
+    /// In 0.01 degrees of celcius
+    pub temperature: i16,
+    /// In 0.01 percents
+    pub humidity: u16,
+    /// In 0.1 hectopascals
+    pub pressure: u16,
+}
+
+impl From<Measurement> for Sample {
+    fn from(value: Measurement) -> Self {
+        Self {
+            temperature: (value.temperature.as_celcius() * 100.0) as i16,
+            humidity: value
+                .humidity
+                .map_or(0, |humidity| (humidity.as_percent() * 100.0) as u16),
+            pressure: (value.pressure.as_pas() / 10.0) as u16,
+        }
+    }
+}
+
+/// Picks single quantity out of sample
+pub type SampleValue = fn(&Sample) -> i32;
+
+pub struct History {
+    samples: HistoryBuffer<Sample, LEN>,
+    /// Minute last sample was taken at. Used to take only one sample during
+    /// that minute.
+    last_sample_mins: Option<u8>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self {
+            samples: HistoryBuffer::new(),
+            last_sample_mins: None,
+        }
+    }
+
+    /// Should new sample be taken at given time?
+    pub fn is_due(&self, time: Time) -> bool {
+        time.mins.is_multiple_of(PERIOD_MINS) && self.last_sample_mins != Some(time.mins)
+    }
+
+    pub fn push(&mut self, time: Time, sample: Sample) {
+        self.samples.write(sample);
+        self.last_sample_mins = Some(time.mins);
+    }
+
+    /// Samples from oldest to newest
+    pub fn samples(&self) -> impl Iterator<Item = &Sample> {
+        self.samples.oldest_ordered()
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.len() == 0
+    }
+
+    /// Scales history of single quantity to heights of chart columns, so that
+    /// minimal value is 1 and maximal is `height`. Chart is stretched over
+    /// available samples, and is flat at 0 while there are none.
+    pub fn chart<const WIDTH: usize>(&self, value: SampleValue, height: u16) -> [u16; WIDTH] {
+        let mut heights = [0; WIDTH];
+        if self.is_empty() {
+            return heights;
+        }
+
+        let (min, max) = self
+            .samples()
+            .map(value)
+            .fold((i32::MAX, i32::MIN), |(min, max), v| {
+                (min.min(v), max.max(v))
+            });
+        let range = core::cmp::max(max - min, 1);
+        let max_height = height as i32 - 1;
+
+        let len = self.len();
+        for (i, sample) in self.samples().enumerate() {
+            let from = i * WIDTH / len;
+            let to = (i + 1) * WIDTH / len;
+            let height = (value(sample) - min) * max_height / range + 1;
+            for column in &mut heights[from..core::cmp::max(to, from + 1)] {
+                *column = height as u16;
+            }
+        }
+
+        heights
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temperature(sample: &Sample) -> i32 {
+        sample.temperature as i32
+    }
+
+    #[test]
+    fn test_chart() {
+        let mut history = History::new();
+        assert_eq!(history.chart::<4>(temperature, 10), [0; 4]);
+
+        for (mins, temperature) in [(0, 2000), (5, 2500)] {
+            let time = Time {
+                hours: 12,
+                mins,
+                secs: 0,
+            };
+            let sample = Sample {
+                temperature,
+                ..Default::default()
+            };
+            history.push(time, sample);
+        }
+        assert_eq!(history.chart::<4>(temperature, 10), [1, 1, 10, 10]);
+    }
+}
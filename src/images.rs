@@ -1,32 +1,143 @@
 //! Images used for displaying time and menu options on LCD's.
 //! These are embedded directly in executable using include_bytes!.
-//! Images are generated using build script (build.rs).
-
-use crate::state::MenuOption;
+//! Images are converted by build script (build.rs), which also generates a
+//! constant for each of them, so assets are added without editing this
+//! module. Their pixels are run length encoded, see [`Pixels`]. Sets of
+//! images are grouped into themes, see [`theme`](crate::theme). Icons in
+//! misc/icon are shared by all themes, each is `ICON_<FILE NAME>`.
+//!
+//! Pictures of themes cover the whole display. Build script resizes them to
+//! [`PICTURE_WIDTH`]x[`PICTURE_HEIGHT`] and sets of pictures check it at
+//! compile time, so picture of other size fails the build instead of being
+//! drawn as garbage.
+
+use crate::{
+    drivers::st7789vwx6,
+    state::{MenuOption, TemperatureUnit},
+};
+
+// PICTURE_WIDTH, PICTURE_HEIGHT, ICON_* constants of icons and NUMPIC_* and
+// MENUPIC_* constants of themes
+include!(concat!(env!("OUT_DIR"), "/images_gen.rs"));
+
+const _: () = assert!(
+    PICTURE_WIDTH == st7789vwx6::WIDTH as u32 && PICTURE_HEIGHT == st7789vwx6::HEIGHT as u32,
+    "pictures made by build.rs don't match size of display"
+);
 
 pub struct Image(&'static [u8]);
 
 impl Image {
-    pub fn width(&self) -> u32 {
+    pub const fn new(data: &'static [u8]) -> Self {
+        Self(data)
+    }
+
+    pub const fn width(&self) -> u32 {
         u32::from_le_bytes([self.0[0], self.0[1], self.0[2], self.0[3]])
     }
 
-    pub fn height(&self) -> u32 {
+    pub const fn height(&self) -> u32 {
         u32::from_le_bytes([self.0[4], self.0[5], self.0[6], self.0[7]])
     }
 
-    pub fn pixels(&self) -> &[u8] {
-        &self.0[8..]
+    /// Does image cover the whole display?
+    const fn is_picture(&self) -> bool {
+        self.width() == PICTURE_WIDTH && self.height() == PICTURE_HEIGHT
+    }
+
+    /// Does image have transparent pixels?
+    pub fn is_transparent(&self) -> bool {
+        self.0[8] != 0
+    }
+
+    /// Pixels row by row
+    pub fn pixels(&self) -> Pixels<'_> {
+        Pixels {
+            data: &self.0[12..],
+            packet: PACKET_LITERAL,
+            remaining: 0,
+            color: None,
+        }
     }
 }
 
-const fn make_image(data: &'static [u8]) -> Image {
-    Image(data)
+/// Small transparent icon of temperature unit
+pub fn unit_icon(unit: TemperatureUnit) -> &'static Image {
+    match unit {
+        TemperatureUnit::Celsius => &ICON_CELSIUS,
+        TemperatureUnit::Fahrenheit => &ICON_FAHRENHEIT,
+    }
+}
+
+/// Big endian RGB565 color of pixel, None if pixel is transparent
+pub type Pixel = Option<[u8; 2]>;
+
+const PACKET_TYPE_MASK: u8 = 0xC0;
+const PACKET_LITERAL: u8 = 0x00;
+const PACKET_REPEAT: u8 = 0x80;
+const PACKET_TRANSPARENT: u8 = 0x40;
+
+/// Decoder of run length encoded pixels. Each packet starts with header byte.
+/// Its 2 high bits are type of packet and the rest is number of pixels in
+/// packet minus 1. Pixels of literal packet follow it as is, repeat packet is
+/// followed by single pixel and transparent packet has no pixels.
+pub struct Pixels<'a> {
+    data: &'a [u8],
+    /// Type of current packet
+    packet: u8,
+    /// Pixels left in current packet
+    remaining: u8,
+    /// Color of current repeat or transparent packet
+    color: Pixel,
+}
+
+impl Pixels<'_> {
+    fn take_pixel(&mut self) -> Option<[u8; 2]> {
+        if self.data.len() < 2 {
+            return None;
+        }
+
+        let pixel = [self.data[0], self.data[1]];
+        self.data = &self.data[2..];
+        Some(pixel)
+    }
+}
+
+impl Iterator for Pixels<'_> {
+    type Item = Pixel;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            let (&header, rest) = self.data.split_first()?;
+            self.data = rest;
+            self.packet = header & PACKET_TYPE_MASK;
+            self.remaining = (header & !PACKET_TYPE_MASK) + 1;
+            self.color = match self.packet {
+                PACKET_REPEAT => Some(self.take_pixel()?),
+                _ => None,
+            };
+        }
+
+        self.remaining -= 1;
+        match self.packet {
+            PACKET_LITERAL => self.take_pixel().map(Some),
+            _ => Some(self.color),
+        }
+    }
 }
 
 pub struct Numpic([Image; 10]);
 
 impl Numpic {
+    pub const fn new(digits: [Image; 10]) -> Self {
+        let mut i = 0;
+        while i < digits.len() {
+            assert!(digits[i].is_picture(), "digit doesn't cover display");
+            i += 1;
+        }
+        Self(digits)
+    }
+
     pub fn get_digit(&self, digit: u8) -> Option<&Image> {
         let digit = digit as usize;
         if digit < self.0.len() {
@@ -37,79 +148,43 @@ impl Numpic {
     }
 }
 
-macro_rules! make_numpic_img {
-    ($letter:literal, $num:literal) => {
-        make_image(include_bytes!(concat!(
-            "../target/img/numpic/",
-            $letter,
-            "/",
-            $num,
-            ".bin"
-        )))
-    };
-}
-
-macro_rules! make_numpic {
-    ($name:ident, $letter:literal) => {
-        pub const $name: Numpic = Numpic([
-            make_numpic_img!($letter, 0),
-            make_numpic_img!($letter, 1),
-            make_numpic_img!($letter, 2),
-            make_numpic_img!($letter, 3),
-            make_numpic_img!($letter, 4),
-            make_numpic_img!($letter, 5),
-            make_numpic_img!($letter, 6),
-            make_numpic_img!($letter, 7),
-            make_numpic_img!($letter, 8),
-            make_numpic_img!($letter, 9),
-        ]);
-    };
-}
-
-make_numpic!(NUMPIC_A, "A");
-// make_numpic!(NUMPIC_B, "B");
-// make_numpic!(NUMPIC_C, "C");
-// make_numpic!(NUMPIC_D, "D");
-
 pub struct Menupic([Image; 6]);
 
 impl Menupic {
-    pub fn get_pic(&self, opt: MenuOption) -> &Image {
-        match opt {
-            MenuOption::SetTime => &self.0[0],
-            MenuOption::SetAlarm => &self.0[1],
-            MenuOption::SetRgb => &self.0[2],
-            MenuOption::SetBrightness => &self.0[3],
-            MenuOption::TempHumidity => &self.0[4],
-            MenuOption::Return => &self.0[5],
+    pub const fn new(pics: [Image; 6]) -> Self {
+        let mut i = 0;
+        while i < pics.len() {
+            assert!(pics[i].is_picture(), "menu picture doesn't cover display");
+            i += 1;
         }
+        Self(pics)
     }
-}
-
-macro_rules! make_menupic_img {
-    ($letter:literal, $num:literal) => {
-        make_image(include_bytes!(concat!(
-            "../target/img/menupic/",
-            $letter,
-            "/",
-            $num,
-            ".bin"
-        )))
-    };
-}
 
-macro_rules! make_menupic {
-    ($name:ident, $letter:literal) => {
-        pub const $name: Menupic = Menupic([
-            make_menupic_img!($letter, 1),
-            make_menupic_img!($letter, 2),
-            make_menupic_img!($letter, 3),
-            make_menupic_img!($letter, 4),
-            make_menupic_img!($letter, 5),
-            make_menupic_img!($letter, 6),
-        ]);
-    };
+    /// Options added after original pictures were drawn have none and are
+    /// shown as text instead
+    pub fn get_pic(&self, opt: MenuOption) -> Option<&Image> {
+        match opt {
+            MenuOption::SetTime => Some(&self.0[0]),
+            MenuOption::SetAlarm => Some(&self.0[1]),
+            MenuOption::SetRgb => Some(&self.0[2]),
+            MenuOption::SetBrightness => Some(&self.0[3]),
+            MenuOption::TempHumidity => Some(&self.0[4]),
+            MenuOption::Return => Some(&self.0[5]),
+            MenuOption::SetArmed
+            | MenuOption::SetMelody
+            | MenuOption::SetVolume
+            | MenuOption::SetTheme
+            | MenuOption::SetDigits
+            | MenuOption::SetRotation
+            | MenuOption::SetUnit
+            | MenuOption::SetDateFormat
+            | MenuOption::SetSeconds
+            | MenuOption::SetLayout
+            | MenuOption::SetReminders
+            | MenuOption::Diagnostics
+            | MenuOption::About
+            | MenuOption::Open(_)
+            | MenuOption::Back => None,
+        }
+    }
 }
-
-make_menupic!(MENUPIC_A, "A");
-// make_menupic!(MENUPIC_B, "B");
@@ -1,53 +1,352 @@
 //! General project-wide functionality
 
+use core::{
+    f32::consts::{FRAC_PI_2, PI},
+    fmt::Write,
+};
+
+use heapless::{String, Vec};
+
 use crate::{
+    bell::{BEEP, MAX_VOLUME},
+    climate_log::{ClimateLog, Dump, Entry},
+    command::Command,
+    console,
+    crash::{self, CrashReport},
+    diagnostics::{GpsStatus, Power},
+    dimming,
     drivers::{
-        bme280, ds3231,
+        bme280::{self, Variant},
+        buttons::ButtonEvent,
+        ds3231,
         ds3231::{Date, Time},
         st7789vwx6,
         st7789vwx6::Display,
     },
-    hardware::LcdClockHardware,
-    images::{MENUPIC_A, NUMPIC_A},
-    led_strip::{LedMode, LED_COUNT},
-    misc::{ColorRGB565, ColorRGB8, Sin},
-    state::{AppMode, MenuOption, State, TimeDateScreen},
+    event_log::{Code, EventLog},
+    font::{FONT_LARGE, FONT_SMALL},
+    gl::{BarStyle, Gl, GradientDirection, TextStyle},
+    gps,
+    hardware::{LcdClockHardware, ST7789VWx6Ty, BOARD_REVISION, SPI_CLOCK_MHZ},
+    history::{History, Sample, SampleValue},
+    images::{self, Image},
+    led_strip::{self, FlashPattern, LedMode, LedOutput, Notification, LED_COUNT, RING_LED_COUNT},
+    local_time::LocalTime,
+    marquee::Marquee,
+    microphone::Envelope,
+    misc::{hsv2rgb_u8, ColorRGB565, ColorRGB8, FloatFuncs},
+    qr::{self, QrCode},
+    rtttl,
+    scheduler::{Scheduler, Task},
+    self_test::{SelfTest, Step},
+    settings,
+    splash::{self, Splash},
+    state::{
+        AppMode, DateFormat, DatePart, DiagnosticsPage, MenuOption, SensorScreen, Source, State,
+        TemperatureUnit, TimeDateScreen, TimeLayout, MAX_BRIGHTNESS,
+    },
+    sun::{self, Daylight},
+    theme::Theme,
+    tick, time_util, timer,
 };
 
+/// Displays menu options are shown on. Edge displays have arrows showing that
+/// menu scrolls.
+const MENU_DISPLAYS: [Display; 4] = [Display::D2, Display::D3, Display::D4, Display::D5];
+/// Index in MENU_DISPLAYS of display with selected option
+const MENU_SELECTED: usize = 1;
+
+/// How often rtc is set from GPS while it has fix (~1h). Rtc drifts only a
+/// couple of seconds a month, so there is no need to do it more often.
+const GPS_SYNC_FRAMES: u32 = 60 * 60 * 60;
+/// Lines of climate log written to console each frame. More would overflow
+/// buffers of USB serial port.
+const LOG_DUMP_LINES: usize = 2;
+/// GPS module is considered detached if nothing is received for this long
+/// (~5s). Modules send RMC every second.
+const GPS_TIMEOUT_FRAMES: u32 = 60 * 5;
+/// How long clock stays up after waking from dormant before it sleeps again
+/// (~1s). Button that woke it up has to be debounced in the meantime.
+const DORMANT_WAKE_FRAMES: u32 = 60;
+/// Corners of dot marking armed alarm on time screen, inside of cursor of
+/// time setting
+const ALARM_DOT: (u16, u16) = (16, 32);
+/// Gift icon marking day of reminder is this far from the top right corner of
+/// the first display, inside of cursor of time setting
+const REMINDER_ICON_MARGIN: u16 = 16;
+/// Frames rainbow of reminder plays for, at the start of day and then every
+/// hour (~30s)
+const CELEBRATION_FRAMES: u32 = 60 * 30;
+/// Frames rainbow of reminder takes to go around once (~2s)
+const RAINBOW_FRAMES: u16 = 120;
+/// Displays showing seconds on time screen
+const SECONDS_DISPLAYS: [Display; 2] = [Display::D5, Display::D6];
+/// Dot of large time layout goes around this point of canvas made of all
+/// displays, which is the middle of displays of seconds
+const ORBIT_CENTER: (i32, i32) = (5 * st7789vwx6::WIDTH as i32, st7789vwx6::HEIGHT as i32 / 2);
+const ORBIT_RADIUS: i32 = 90;
+const ORBIT_DOT_RADIUS: i32 = 12;
+/// Arc of seconds of arc time layout goes along orbit with this thickness
+const ARC_WIDTH: i32 = 16;
+/// Backgrounds of menu and sensor screens fade from theme background at the
+/// top to this share of the way to accent at the bottom
+const GRADIENT_ACCENT: (u32, u32) = (1, 4);
+/// Main loop waits between tasks in steps of millisecond at 125MHz
+const CYCLES_PER_MS: u32 = 125 * 1000;
+/// Backlight PWM duty for each brightness level, see backlight_duty
+const BACKLIGHT_DUTIES: [u16; 10] = [0, 837, 2344, 5041, 9272, 15383, 23719, 34626, 48449, 65535];
+
 /// Main application. Its functionality loosely corresponds to View in MVC.
 pub struct LcdClock {
     hardware: LcdClockHardware,
     state: State,
+    float_funcs: FloatFuncs,
 
     /// Used as comparator value needed to decide which displays we want to
     /// update
     last_time: Time,
+    /// Position of orbiting dot of large time layout on canvas, None if it
+    /// has to be drawn together with its orbit
+    orbit_dot: Option<(i32, i32)>,
+    /// Angle of end of seconds arc of arc time layout, None if it has to be
+    /// drawn together with its track
+    arc_angle: Option<f32>,
     last_date: Date,
     last_brightness: u32,
+    /// Percent of brightness backlight was last dimmed to
+    last_dimming: u32,
+    /// Clock of display SPI in MHz it was last set to
+    last_spi_clock: u32,
+    /// Time counted by system timer, so rtc is rarely read
+    local_time: LocalTime,
+    scheduler: Scheduler,
+    /// Events of mode, left and right buttons polled since the last frame
+    button_events: (
+        Option<ButtonEvent>,
+        Option<ButtonEvent>,
+        Option<ButtonEvent>,
+    ),
+    last_sensor_values: [Option<u8>; 6],
+    history: History,
+    climate_log: ClimateLog,
+    /// Climate log being written to console, if any
+    log_dump: Option<Dump>,
+    event_log: EventLog,
+    /// Index of the next event being written to console, if any
+    event_dump: Option<usize>,
+    gps_status: GpsStatus,
+    /// Frames passed since last RMC sentence was received
+    gps_frames: u32,
+    /// Frames passed since rtc was last set from GPS
+    gps_sync_frames: u32,
+    power: Power,
+    /// Loudness picked up by microphone
+    envelope: Envelope,
+    /// Message being shown, if any
+    marquee: Option<Marquee>,
+    /// Self test being run, if any
+    self_test: Option<SelfTest>,
+    /// Animation shown after boot, until it ends
+    splash: Option<Splash>,
+    /// Crash that has reset clock before this boot, if any
+    crash_report: Option<CrashReport>,
+    /// Are displays asleep and rtc set to raise alarm at the end of night?
+    is_dormant: bool,
+    /// Frames passed since clock has woken up from dormant
+    dormant_frames: u32,
+    /// Is cursor on digit being set shown? It blinks in step with seconds,
+    /// shown in the first half of each one.
+    is_cursor_shown: bool,
+    /// Is any reminder due today? Time screen is marked with gift icon then.
+    is_reminder_day: bool,
+    /// Is it the minute armed alarm goes off at? Alarm rings as it starts.
+    is_alarm_minute: bool,
 }
 
 impl LcdClock {
-    pub fn new(hardware: LcdClockHardware, sin: Sin, brightness: u32) -> Self {
-        let state = State::new(sin, brightness);
+    pub fn new(hardware: LcdClockHardware, float_funcs: FloatFuncs, brightness: u32) -> Self {
+        let state = State::new(float_funcs.sin, brightness);
         let last_brightness = brightness;
         Self {
             hardware,
             state,
+            float_funcs,
             last_time: Default::default(),
+            orbit_dot: None,
+            arc_angle: None,
             last_date: Default::default(),
             last_brightness,
+            last_dimming: dimming::FULL,
+            last_spi_clock: SPI_CLOCK_MHZ,
+            local_time: LocalTime::new(),
+            scheduler: Scheduler::new(timer::now_ms()),
+            button_events: Default::default(),
+            last_sensor_values: Default::default(),
+            history: History::new(),
+            climate_log: ClimateLog::new(),
+            log_dump: None,
+            event_log: EventLog::new(),
+            event_dump: None,
+            gps_status: GpsStatus::None,
+            gps_frames: 0,
+            // First fix is applied right away
+            gps_sync_frames: GPS_SYNC_FRAMES,
+            power: Power::default(),
+            envelope: Envelope::new(),
+            marquee: None,
+            self_test: None,
+            splash: None,
+            crash_report: None,
+            is_dormant: false,
+            dormant_frames: 0,
+            is_cursor_shown: false,
+            is_reminder_day: false,
+            is_alarm_minute: false,
         }
     }
 
     pub fn init(&mut self) -> Result<(), Error> {
+        self.event_log.push(Code::Boot, timer::now_ms());
         self.hardware.init()?;
+        self.power = self.hardware.measure_power();
+
+        match settings::load(&mut self.hardware.settings_storage) {
+            Some(settings) => self.state.apply_settings(settings),
+            None => self.state.show_first_boot(),
+        }
+        self.hardware.bell.set_volume(self.state.volume());
+        self.climate_log.init(&mut self.hardware.log_storage);
+
+        let diagnostics = *self.hardware.diagnostics();
+        // Variant is only known if sensor has been initialized
+        let variant = self.hardware.humidity_sensor.variant();
+        self.state
+            .set_has_sensor(diagnostics.humidity_sensor.is_ok());
+        self.state
+            .set_has_humidity(variant.is_some_and(Variant::has_humidity));
+        self.state.set_has_led_strip(diagnostics.led_strip.is_ok());
+        self.state
+            .set_has_led_ring(self.hardware.led_ring.is_some());
+        self.state
+            .set_has_microphone(self.hardware.has_microphone());
+
+        if diagnostics.rtc.is_ok() {
+            let oscillator_stopped = self
+                .hardware
+                .rtc
+                .has_oscillator_stopped()
+                .map_err(Error::Rtc)?;
+            if oscillator_stopped {
+                self.state.set_time_invalid();
+            }
+        }
+
+        // Clock can't work without rtc, but can without sensor
+        if !diagnostics.is_ok() {
+            self.state.show_diagnostics(!diagnostics.rtc.is_ok());
+        }
+        self.splash = Some(Splash::new());
+
         Ok(())
     }
 
-    pub fn update(&mut self) -> Result<(), Error> {
+    /// Shows report of crash taken from RAM after reset, see
+    /// [`crash`](crate::crash). Has to be called after init.
+    pub fn show_crash_report(&mut self, report: CrashReport) {
+        self.event_log.push(Code::Crash, timer::now_ms());
+        self.crash_report = Some(report);
+        self.state.show_crash_report();
+    }
+
+    /// Runs power-on self test, e.g. when mode button is held during boot.
+    /// Has to be called after init.
+    pub fn start_self_test(&mut self) {
+        self.self_test = Some(SelfTest::new());
+        self.splash = None;
+        self.state.show_self_test();
+    }
+
+    /// Runs tasks that are due, then waits until the next one is, see
+    /// [`scheduler`](crate::scheduler). Errors of peripherals are logged
+    /// (see [`event_log`](crate::event_log)) and failed task runs again once it is due.
+    pub fn update(&mut self) {
+        let now_ms = timer::now_ms();
+        if self.scheduler.take(Task::Input, now_ms) {
+            self.update_input();
+        }
+        if self.scheduler.take(Task::Sensor, now_ms) {
+            self.state.mark_dirty(Source::Sensor);
+            let result = self.update_history();
+            self.log_error(result);
+        }
+        if self.scheduler.take(Task::Power, now_ms) {
+            self.update_power();
+        }
+        if self.scheduler.take(Task::PixelShift, now_ms) {
+            self.update_pixel_shift();
+        }
+        if self.scheduler.take(Task::Frame, now_ms) {
+            let result = self.update_frame();
+            self.log_error(result);
+        }
+
+        let wait_ms = self.scheduler.ms_until_due(timer::now_ms());
+        cortex_m::asm::delay(wait_ms * CYCLES_PER_MS);
+    }
+
+    fn log_error(&mut self, result: Result<(), Error>) {
+        if let Err(error) = result {
+            self.event_log.push(error.event_code(), timer::now_ms());
+        }
+    }
+
+    /// Handles buttons and commands, draws current screen and animates LEDs
+    fn update_frame(&mut self) -> Result<(), Error> {
+        // Asked for during previous frame, which has sent reply to console
+        // and saved settings since
+        if self.state.take_bootloader() {
+            self.hardware.enter_bootloader();
+        }
+        // Time is read for the first time once splash has ended
+        if self.splash.is_some() {
+            return self.update_splash();
+        }
         self.update_buttons();
+        self.update_console()?;
+        self.update_log_dump();
+        self.update_event_dump();
+        self.update_gps()?;
+        // Preview ends once melody or volume is chosen, beep of self test
+        // plays on
+        if !matches!(
+            self.state.mode(),
+            AppMode::SetMelody | AppMode::SetVolume | AppMode::SelfTest
+        ) {
+            self.hardware.bell.stop_preview();
+        }
+        if self.state.take_alarm_stopped() {
+            self.hardware.bell.stop();
+        }
+        self.hardware.bell.update();
+        if tick::take() {
+            self.state.mark_dirty(Source::Time);
+            self.update_local_time()?;
+            self.update_night()?;
+            self.update_dimming()?;
+            self.update_tint()?;
+            self.update_reminders()?;
+            self.update_alarm()?;
+        }
+        if self.is_dormant && self.state.mode() != AppMode::Dormant {
+            self.leave_dormant()?;
+        }
+        if self.state.mode() != AppMode::SelfTest {
+            self.self_test = None;
+        }
+        self.update_spi_clock()?;
 
-        let brightness = self.state.brightness();
+        let brightness = self.state.screen_brightness();
+        let dimming = self.state.screen_dimming();
         let transition = self.state.eat_transition();
         match self.state.mode() {
             AppMode::Regular(screen) => match screen {
@@ -57,36 +356,185 @@ impl LcdClock {
                 TimeDateScreen::Date => {
                     self.mode_date(transition)?;
                 }
+                TimeDateScreen::FullDate => {
+                    self.mode_full_date(transition)?;
+                }
+                TimeDateScreen::Sun => {
+                    self.mode_sun(transition)?;
+                }
+                TimeDateScreen::Temperature => {
+                    self.mode_temp_humidity(SensorScreen::Temperature, transition)?;
+                }
+                TimeDateScreen::Humidity => {
+                    self.mode_temp_humidity(SensorScreen::Humidity, transition)?;
+                }
             },
             AppMode::Menu(menu) => self.mode_menu(menu, transition)?,
             AppMode::SetTime(screen_index) => self.mode_set_time(screen_index, transition)?,
-            AppMode::SetAlarm(screen_index) => self.mode_set_time(screen_index, transition)?,
-            AppMode::SetRgb => self.mode_rgb(transition)?,
-            AppMode::SetBrightness => self.mode_brightness(transition, brightness)?,
-            _ => {}
+            AppMode::SetAlarm(index) => self.mode_set_alarm(index, transition)?,
+            AppMode::SetArmed => self.mode_armed(transition)?,
+            AppMode::SetMelody => self.mode_melody(transition)?,
+            AppMode::SetVolume => self.mode_volume(transition)?,
+            AppMode::SetRgb(output) => self.mode_rgb(output, transition)?,
+            AppMode::SetBrightness => self.mode_brightness(transition)?,
+            AppMode::TempHumidity(screen) => self.mode_temp_humidity(screen, transition)?,
+            AppMode::SetTheme | AppMode::SetDigits => self.mode_preview(transition)?,
+            AppMode::SetRotation => self.mode_rotation(transition)?,
+            AppMode::SetUnit => self.mode_unit(transition)?,
+            // Format is previewed on current date
+            AppMode::SetDateFormat => self.mode_date(transition)?,
+            AppMode::SetSeconds | AppMode::SetLayout => self.mode_time(transition)?,
+            AppMode::SetReminders(field) => self.mode_set_reminders(field, transition)?,
+            AppMode::Diagnostics(page) => self.mode_diagnostics(page, transition)?,
+            AppMode::About => self.mode_about(transition)?,
+            AppMode::Message => self.mode_message(transition)?,
+            AppMode::SelfTest => self.mode_self_test(transition)?,
+            AppMode::CrashReport => self.mode_crash_report(transition)?,
+            AppMode::Dormant => self.mode_dormant(transition)?,
         }
 
-        if let Some(time_delta) = self.state.take_time_delta() {
-            let (index, change) = time_delta;
-            if matches!(self.state.mode(), AppMode::SetTime(..)) {
-                self.change_time(index, change)?;
-            } else {
-                // self.change_alarm(index, change)?;
+        // Displays are turned on only after new screen has been drawn on
+        // them, so old content doesn't flash
+        let hides_seconds = self.state.hide_seconds()
+            && matches!(
+                self.state.mode(),
+                AppMode::Regular(TimeDateScreen::Time) | AppMode::SetSeconds
+            );
+        for display in SECONDS_DISPLAYS {
+            self.hardware
+                .displays
+                .set_display_on(display, !hides_seconds)
+                .map_err(Error::Display)?;
+        }
+
+        if self.state.take_factory_reset() {
+            self.factory_reset()?;
+        }
+        if self.state.take_settings_changed() {
+            settings::save(&mut self.hardware.settings_storage, self.state.settings())
+                .map_err(Error::Settings)?;
+        }
+
+        if let Some((index, change)) = self.state.take_time_delta() {
+            self.change_time(index, change)?;
+            if self.state.is_time_invalid() {
+                self.hardware
+                    .rtc
+                    .clear_oscillator_stop_flag()
+                    .map_err(Error::Rtc)?;
+                self.state.clear_time_invalid();
             }
         }
 
-        if brightness != self.last_brightness {
+        if brightness != self.last_brightness || dimming != self.last_dimming {
+            // Panels turned off at brightness 0 are back before fade in
+            if self.last_brightness == 0 {
+                self.hardware.displays.wake().map_err(Error::Display)?;
+            }
             self.last_brightness = brightness;
-            let brightness_mapped = (u16::MAX / 10) * brightness as u16;
-            self.hardware.displays.set_brightness(brightness_mapped);
+            self.last_dimming = dimming;
+            let duty = backlight_duty(brightness) as u32 * dimming / dimming::FULL;
+            self.hardware.displays.fade_brightness(duty as u16);
+        }
+        self.hardware.displays.update_fade();
+        // Backlight alone still lets panels glow, so they are turned off too
+        // once it has faded out
+        if brightness == 0 && self.hardware.displays.is_faded() {
+            self.hardware.displays.sleep().map_err(Error::Display)?;
         }
 
-        // TODO: dynamic update time (using rtc or system timer)
-        cortex_m::asm::delay(125 * 1000 * 16);
+        self.state.set_loudness(self.envelope.loudness());
         self.state.update();
-        self.hardware
-            .led_strip
-            .display(self.state.led_strip().colors());
+
+        // Report dead displays by lighting LED under them red, as they can't
+        // show anything themselves
+        // Dimming only applies to animations, status below is shown as is
+        let led_dimming = self.state.led_dimming();
+        let mut colors = self
+            .state
+            .led_strip()
+            .colors()
+            .map(|color| color.scale(led_dimming, dimming::FULL));
+        // Invalid time is reported on all LEDs until user sets it
+        if self.state.is_time_invalid() {
+            colors = [ColorRGB8::yellow(); LED_COUNT];
+        }
+        // Low backup cell is shown under D6, where voltages are shown on
+        // diagnostics screen
+        if self.power.is_backup_low() {
+            colors[Display::D6 as usize] = ColorRGB8::orange();
+        }
+        // Self test lights LEDs with colors of displays
+        let test_color = self
+            .self_test
+            .as_ref()
+            .and_then(|self_test| step_color(self_test.step()));
+        if let Some(color) = test_color {
+            colors = [color; LED_COUNT];
+        }
+        // Nothing is lit at night
+        if self.is_dormant {
+            colors = [ColorRGB8::black(); LED_COUNT];
+        }
+        if let Some(led_strip) = &mut self.hardware.led_strip {
+            led_strip.display(&colors);
+        }
+
+        // Ring only shows its own animation, status is reported by strip
+        let mut ring_colors = self
+            .state
+            .led_ring()
+            .colors()
+            .map(|color| color.scale(led_dimming, dimming::FULL));
+        if let Some(color) = test_color {
+            ring_colors = [color; RING_LED_COUNT];
+        }
+        if self.is_dormant {
+            ring_colors = [ColorRGB8::black(); RING_LED_COUNT];
+        }
+        if let Some(led_ring) = &mut self.hardware.led_ring {
+            led_ring.display(&ring_colors);
+        }
+
+        Ok(())
+    }
+
+    /// Fades in letters of splash and LEDs under them. Buttons pressed
+    /// meanwhile are ignored.
+    fn update_splash(&mut self) -> Result<(), Error> {
+        let Some(mut splash) = self.splash else {
+            return Ok(());
+        };
+        splash.update();
+        self.splash = (!splash.is_done()).then_some(splash);
+        self.button_events = Default::default();
+        if splash.is_done() {
+            self.state.request_redraw();
+        }
+
+        let accent = self.state.theme().accent;
+        let black = ColorRGB8::black();
+        let x = (st7789vwx6::WIDTH - FONT_LARGE.glyph_width() as u16) / 2;
+        let y = (st7789vwx6::HEIGHT - FONT_LARGE.glyph_height() as u16) / 2;
+        let mut colors = [black; LED_COUNT];
+        for (index, color) in colors.iter_mut().enumerate() {
+            let fade = splash.fade(index);
+            *color = accent.scale(fade, splash::FADE_FRAMES);
+            let (Some(display), Some(letter)) = (
+                Display::from_index(index),
+                SPLASH_TEXT.get(index..index + 1),
+            ) else {
+                continue;
+            };
+            if splash.is_fading(index) {
+                let style = TextStyle::new(&FONT_LARGE, (*color).into(), black.into());
+                self.hardware
+                    .with_gl(|gl| gl.draw_text(display, x, y, letter, style))?;
+            }
+        }
+        if let Some(led_strip) = &mut self.hardware.led_strip {
+            led_strip.display(&colors);
+        }
 
         Ok(())
     }
@@ -96,26 +544,61 @@ impl LcdClock {
             return Ok(());
         }
 
-        let last_mode = self.state.last_mode();
-        let last_mode = match last_mode {
-            AppMode::Menu(menu) => Some(menu),
-            _ => None,
-        };
+        let theme = self.state.theme();
 
-        for (mode, display) in MenuOption::all().zip(Display::all()) {
-            // avoid redrawing screens that did not change
-            if let Some(last_mode) = last_mode {
-                if last_mode != mode && mode != selected_mode {
-                    continue;
-                }
-            }
+        // arrows don't change while scrolling
+        if !matches!(self.state.last_mode(), AppMode::Menu(_)) {
+            let x = (st7789vwx6::WIDTH - FONT_LARGE.glyph_width() as u16) / 2;
+            let y = (st7789vwx6::HEIGHT - FONT_LARGE.glyph_height() as u16) / 2;
+            let background = gradient_at(theme, y + FONT_LARGE.glyph_height() as u16 / 2);
+            let style = TextStyle::new(&FONT_LARGE, theme.accent.into(), background);
+            self.hardware.with_gl(|gl| {
+                fill_gradient(gl, Display::D1, theme)?;
+                gl.draw_text(Display::D1, x, y, "<", style)?;
+                fill_gradient(gl, Display::D6, theme)?;
+                gl.draw_text(Display::D6, x, y, ">", style)
+            })?;
+        }
+
+        let options = self.state.menu_options();
+        let selected = options
+            .iter()
+            .position(|&o| o == selected_mode)
+            .unwrap_or(0);
+        let count = options.len() as isize;
+        for (slot, display) in MENU_DISPLAYS.into_iter().enumerate() {
+            let position = (selected + slot) as isize - MENU_SELECTED as isize;
+            // Long menus wrap around, so selected option always has
+            // neighbours. Short ones would show options twice.
+            let position = if options.len() >= MENU_DISPLAYS.len() {
+                Some(position.rem_euclid(count))
+            } else {
+                (0..count).contains(&position).then_some(position)
+            };
+            let Some(position) = position else {
+                self.hardware
+                    .with_gl(|gl| fill_gradient(gl, display, theme))?;
+                continue;
+            };
 
-            let pic = MENUPIC_A.get_pic(mode);
-            self.hardware.with_gl(|gl| gl.draw_pic(display, pic))?;
+            let mode = options[position as usize];
+            if let Some(pic) = theme.menupic.get_pic(mode) {
+                self.hardware.with_gl(|gl| gl.draw_pic(display, pic))?;
+            } else {
+                let label = mode.label();
+                let x = st7789vwx6::WIDTH.saturating_sub(FONT_SMALL.text_width(label) as u16) / 2;
+                let y = (st7789vwx6::HEIGHT - FONT_SMALL.glyph_height() as u16) / 2;
+                let background = gradient_at(theme, y + FONT_SMALL.glyph_height() as u16 / 2);
+                let style = TextStyle::new(&FONT_SMALL, ColorRGB8::white().into(), background);
+                self.hardware.with_gl(|gl| {
+                    fill_gradient(gl, display, theme)?;
+                    gl.draw_text(display, x, y, label, style)
+                })?;
+            }
 
             if mode == selected_mode {
                 let thickness = 8;
-                let color = ColorRGB565::from(ColorRGB8::red());
+                let color = ColorRGB565::from(theme.accent);
                 self.hardware
                     .with_gl(|gl| gl.draw_bounding_rect(display, thickness, color))?;
             }
@@ -133,64 +616,361 @@ impl LcdClock {
             self.mode_date(force_update)?;
         }
 
-        let display = match screen_index % 6 {
-            0 => Display::D1,
-            1 => Display::D2,
-            2 => Display::D3,
-            3 => Display::D4,
-            4 => Display::D5,
-            5 => Display::D6,
-            _ => Display::D1,
+        let display = Display::from_index(screen_index % 6).unwrap_or(Display::D1);
+
+        let (_, _, millis) = self.now()?;
+        let was_cursor_shown = self.is_cursor_shown;
+        self.is_cursor_shown = millis < 500;
+        if self.is_cursor_shown {
+            let thickness = 8;
+            let color = ColorRGB565::from(self.state.theme().accent);
+            self.hardware
+                .with_gl(|gl| gl.draw_bounding_rect(display, thickness, color))?;
+        } else if was_cursor_shown && !force_update {
+            // Cursor is hidden by drawing digit under it again
+            let digits = if screen_index < 6 {
+                time_to_display_values(self.last_time)
+            } else {
+                date_to_display_values(self.last_date, self.state.date_format())
+            };
+            if let Some(pic) = self.state.numpic().get_digit(digits[screen_index % 6]) {
+                self.hardware.with_gl(|gl| gl.draw_pic(display, pic))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Shows alarm time as hours and minutes with cursor blinking over the
+    /// digit being set, seconds are zero
+    fn mode_set_alarm(&mut self, index: usize, force_update: bool) -> Result<(), Error> {
+        let (hours, mins) = self.state.alarm_time();
+        let alarm = Time {
+            hours,
+            mins,
+            secs: 0,
         };
-        let thickness = 8;
-        let color = ColorRGB565::from(ColorRGB8::red());
-        self.hardware
-            .with_gl(|gl| gl.draw_bounding_rect(display, thickness, color))?;
+        let digits = time_to_display_values(alarm);
+        let numpic = self.state.numpic();
+        if force_update {
+            for (display, digit) in ST7789VWx6Ty::displays().zip(digits) {
+                if let Some(pic) = numpic.get_digit(digit) {
+                    self.hardware.with_gl(|gl| gl.draw_pic(display, pic))?;
+                }
+            }
+        }
+
+        let display = Display::from_index(index).unwrap_or(Display::D1);
+        let (_, _, millis) = self.now()?;
+        let was_cursor_shown = self.is_cursor_shown;
+        self.is_cursor_shown = millis < 500;
+        if self.is_cursor_shown {
+            let thickness = 8;
+            let color = ColorRGB565::from(self.state.theme().accent);
+            self.hardware
+                .with_gl(|gl| gl.draw_bounding_rect(display, thickness, color))?;
+        } else if was_cursor_shown && !force_update {
+            if let Some(pic) = numpic.get_digit(digits[index]) {
+                self.hardware.with_gl(|gl| gl.draw_pic(display, pic))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Previews selected theme and digits on current time
+    fn mode_preview(&mut self, force_update: bool) -> Result<(), Error> {
+        if !force_update {
+            return Ok(());
+        }
+
+        let (_, time, _) = self.now()?;
+        let numpic = self.state.numpic();
+        let thickness = 4;
+        let color = ColorRGB565::from(self.state.theme().accent);
+        for (display, digit) in ST7789VWx6Ty::displays().zip(time_to_display_values(time)) {
+            if let Some(pic) = numpic.get_digit(digit) {
+                self.hardware.with_gl(|gl| {
+                    gl.draw_pic(display, pic)?;
+                    gl.draw_bounding_rect(display, thickness, color)
+                })?;
+            }
+        }
 
         Ok(())
     }
 
     fn mode_time(&mut self, force_update: bool) -> Result<(), Error> {
-        let time = self
-            .hardware
-            .with_rtc(|rtc| rtc.get_time())?
-            .map_err(Error::Rtc)?;
+        let layout = self.state.time_layout();
+        if layout == TimeLayout::Large {
+            return self.mode_time_large(force_update);
+        }
 
-        let time_displays = time_to_display_values(time);
-        let prev_time_displays = time_to_display_values(self.last_time);
+        let is_time_dirty = self.state.take_dirty(Source::Time);
+        if force_update || is_time_dirty {
+            let (_, time, _) = self.now()?;
 
-        for ((display, &time), &prev) in Display::all()
-            .into_iter()
-            .zip(time_displays.iter())
-            .zip(prev_time_displays.iter())
-        {
-            if let Some(pic) = NUMPIC_A.get_digit(time) {
-                if time != prev || force_update {
-                    self.hardware.with_gl(|gl| gl.draw_pic(display, pic))?;
+            let time_displays = time_to_display_values(time);
+            let prev_time_displays = time_to_display_values(self.last_time);
+
+            // Digits of seconds are replaced with arc in arc layout
+            let has_seconds = !self.state.hide_seconds() && layout == TimeLayout::Pictures;
+            let numpic = self.state.numpic();
+            for ((display, &time), &prev) in ST7789VWx6Ty::displays()
+                .into_iter()
+                .zip(time_displays.iter())
+                .zip(prev_time_displays.iter())
+            {
+                // Displays of seconds are off, they are redrawn when turned on
+                if !has_seconds && SECONDS_DISPLAYS.contains(&display) {
+                    continue;
+                }
+                if let Some(pic) = numpic.get_digit(time) {
+                    if time != prev || force_update {
+                        self.hardware.with_gl(|gl| gl.draw_pic(display, pic))?;
+                    }
+                }
+            }
+
+            if time_displays[0] != prev_time_displays[0] || force_update {
+                self.draw_alarm_dot()?;
+                self.draw_reminder_icon()?;
+            }
+
+            self.last_time = time;
+        }
+
+        if layout != TimeLayout::Arc || self.state.hide_seconds() {
+            return Ok(());
+        }
+        if force_update {
+            self.clear_seconds_displays()?;
+            self.arc_angle = None;
+        }
+        self.draw_seconds_arc()
+    }
+
+    /// Draws hours and minutes with large digits on two displays each. A dot
+    /// goes around displays of seconds once a minute.
+    fn mode_time_large(&mut self, force_update: bool) -> Result<(), Error> {
+        let is_time_dirty = self.state.take_dirty(Source::Time);
+        if force_update || is_time_dirty {
+            let (_, time, _) = self.now()?;
+            let time_displays = time_to_display_values(time);
+            let prev_time_displays = time_to_display_values(self.last_time);
+
+            let color = ColorRGB565::from(self.state.theme().accent);
+            let background = ColorRGB565::from(self.state.theme().background);
+            // Hours and minutes take the first four displays
+            for ((display, &digit), &prev) in ST7789VWx6Ty::displays()
+                .zip(time_displays.iter())
+                .zip(prev_time_displays.iter())
+                .filter(|((display, _), _)| !SECONDS_DISPLAYS.contains(display))
+            {
+                if digit != prev || force_update {
+                    self.hardware
+                        .with_gl(|gl| gl.draw_large_digit(display, digit, color, background))?;
                 }
             }
+            if time_displays[0] != prev_time_displays[0] || force_update {
+                self.draw_alarm_dot()?;
+                self.draw_reminder_icon()?;
+            }
+
+            self.last_time = time;
+        }
+
+        // Displays of seconds are off, they are redrawn when turned on
+        if self.state.hide_seconds() {
+            return Ok(());
+        }
+        if force_update {
+            self.clear_seconds_displays()?;
+            self.orbit_dot = None;
+        }
+        self.draw_orbit()
+    }
+
+    fn clear_seconds_displays(&mut self) -> Result<(), Error> {
+        let background = ColorRGB565::from(self.state.theme().background);
+        self.hardware.with_gl(|gl| {
+            SECONDS_DISPLAYS
+                .into_iter()
+                .try_for_each(|display| gl.fill(display, background))
+        })
+    }
+
+    /// Seconds with fraction counted by system timer, so animations of
+    /// seconds move smoothly
+    fn seconds_fraction(&self) -> f32 {
+        match self.local_time.now(timer::now_ms()) {
+            Some((_, time, millis)) => time.secs as f32 + millis as f32 / 1000.0,
+            None => self.last_time.secs as f32,
+        }
+    }
+
+    /// Draws dot going around displays of seconds, together with its orbit
+    /// if it isn't there yet. Otherwise only area around the dot is redrawn
+    /// once it moves by a pixel.
+    fn draw_orbit(&mut self) -> Result<(), Error> {
+        let angle = self.seconds_fraction() / 60.0 * 2.0 * PI;
+        let sin = self.float_funcs.sin;
+        let (cx, cy) = ORBIT_CENTER;
+        let radius = ORBIT_RADIUS as f32;
+        let dot = (
+            cx + (radius * sin(angle)) as i32,
+            cy - (radius * sin(angle + FRAC_PI_2)) as i32,
+        );
+        if self.orbit_dot == Some(dot) {
+            return Ok(());
+        }
+
+        let accent = self.state.theme().accent;
+        let color = ColorRGB565::from(accent);
+        let orbit = track_color(accent);
+        let background = ColorRGB565::from(self.state.theme().background);
+        let pixel = move |x: i32, y: u16| {
+            let (dx, dy) = (x - dot.0, y as i32 - dot.1);
+            let (ox, oy) = (x - cx, y as i32 - cy);
+            let orbit_dist = ox * ox + oy * oy;
+            if dx * dx + dy * dy <= ORBIT_DOT_RADIUS * ORBIT_DOT_RADIUS {
+                color
+            } else if ((ORBIT_RADIUS - 1).pow(2)..=(ORBIT_RADIUS + 1).pow(2)).contains(&orbit_dist)
+            {
+                orbit
+            } else {
+                background
+            }
+        };
+
+        // Area of dot and wherever it was before, or the whole orbit
+        let r = ORBIT_DOT_RADIUS + 1;
+        let (from, to) = match self.orbit_dot {
+            Some(prev) => (
+                (prev.0.min(dot.0) - r, prev.1.min(dot.1) - r),
+                (prev.0.max(dot.0) + r + 1, prev.1.max(dot.1) + r + 1),
+            ),
+            None => {
+                let r = ORBIT_RADIUS + r;
+                ((cx - r, cy - r), (cx + r + 1, cy + r + 1))
+            }
+        };
+        let rows = from.1.max(0) as u16..to.1.max(0) as u16;
+        self.hardware
+            .with_gl(|gl| gl.draw_canvas(from.0..to.0, rows, pixel))?;
+        self.orbit_dot = Some(dot);
+        Ok(())
+    }
+
+    /// Draws arc on displays of seconds that fills up along orbit over a
+    /// minute, together with its track if it isn't there yet. Otherwise only
+    /// area around the end of arc is redrawn once it moves by a pixel.
+    fn draw_seconds_arc(&mut self) -> Result<(), Error> {
+        let angle = self.seconds_fraction() / 60.0 * 2.0 * PI;
+        let sin = self.float_funcs.sin;
+        let (cx, cy) = ORBIT_CENTER;
+        let inner = ORBIT_RADIUS - ARC_WIDTH / 2;
+        let outer = ORBIT_RADIUS + ARC_WIDTH / 2;
+        // Point at given radius on line from center at given angle
+        let point = |radius: i32, angle: f32| {
+            let radius = radius as f32;
+            (
+                cx + (radius * sin(angle)) as i32,
+                cy - (radius * sin(angle + FRAC_PI_2)) as i32,
+            )
+        };
+        let end = point(outer, angle);
+        if self.arc_angle.map(|prev| point(outer, prev)) == Some(end) {
+            return Ok(());
         }
 
-        self.last_time = time;
+        let accent = self.state.theme().accent;
+        let color = ColorRGB565::from(accent);
+        let track = track_color(accent);
+        let background = ColorRGB565::from(self.state.theme().background);
+        let limit = pseudo_angle(sin(angle), sin(angle + FRAC_PI_2));
+        let pixel = move |x: i32, y: u16| {
+            let (ox, oy) = (x - cx, y as i32 - cy);
+            if !(inner * inner..=outer * outer).contains(&(ox * ox + oy * oy)) {
+                background
+            } else if pseudo_angle(ox as f32, -oy as f32) <= limit {
+                color
+            } else {
+                track
+            }
+        };
 
+        // Arc only grows a little each frame, except when minute starts
+        // over. Then the whole track is redrawn.
+        let (from, to) = match self.arc_angle {
+            Some(prev) if (prev..prev + FRAC_PI_2).contains(&angle) => {
+                let points = [
+                    point(inner, prev),
+                    point(outer, prev),
+                    point(inner, angle),
+                    end,
+                ];
+                let (xs, ys) = (points.map(|p| p.0), points.map(|p| p.1));
+                let min = |v: [i32; 4]| v.into_iter().min().unwrap_or(0);
+                let max = |v: [i32; 4]| v.into_iter().max().unwrap_or(0);
+                ((min(xs) - 2, min(ys) - 2), (max(xs) + 3, max(ys) + 3))
+            }
+            _ => (
+                (cx - outer - 1, cy - outer - 1),
+                (cx + outer + 2, cy + outer + 2),
+            ),
+        };
+        let rows = from.1.max(0) as u16..to.1.max(0) as u16;
+        self.hardware
+            .with_gl(|gl| gl.draw_canvas(from.0..to.0, rows, pixel))?;
+        self.arc_angle = Some(angle);
         Ok(())
     }
 
+    /// Armed alarm is marked with dot in the corner of the first display,
+    /// which is drawn again whenever digit under it is
+    fn draw_alarm_dot(&mut self) -> Result<(), Error> {
+        if !self.state.is_alarm_armed() {
+            return Ok(());
+        }
+
+        let color = ColorRGB565::from(self.state.theme().accent);
+        let (from, to) = ALARM_DOT;
+        self.hardware
+            .with_gl(|gl| gl.draw_rect(Display::D1, from, from, to, to, color))
+    }
+
+    /// Day of reminder is marked with gift icon in the corner of the first
+    /// display, drawn again whenever digit under it is
+    fn draw_reminder_icon(&mut self) -> Result<(), Error> {
+        if !self.is_reminder_day {
+            return Ok(());
+        }
+
+        let icon = &images::ICON_GIFT;
+        let x = st7789vwx6::WIDTH - icon.width() as u16 - REMINDER_ICON_MARGIN;
+        self.hardware
+            .with_gl(|gl| gl.draw_pic_at(Display::D1, x, REMINDER_ICON_MARGIN, icon, None))
+    }
+
     fn mode_date(&mut self, force_update: bool) -> Result<(), Error> {
-        let date = self
-            .hardware
-            .with_rtc(|rtc| rtc.get_calendar())?
-            .map_err(Error::Rtc)?;
+        if !self.state.take_dirty(Source::Time) && !force_update {
+            return Ok(());
+        }
+
+        let (date, _, _) = self.now()?;
 
-        let date_displays = date_to_display_values(date);
-        let prev_date_displays = date_to_display_values(self.last_date);
-        for ((display, &cur), &prev) in Display::all()
+        let format = self.state.date_format();
+        let date_displays = date_to_display_values(date, format);
+        let prev_date_displays = date_to_display_values(self.last_date, format);
+        let numpic = self.state.numpic();
+        for ((display, &cur), &prev) in ST7789VWx6Ty::displays()
             .into_iter()
             .zip(date_displays.iter())
             .zip(prev_date_displays.iter())
         {
             if cur != prev || force_update {
-                if let Some(pic) = NUMPIC_A.get_digit(cur) {
+                if let Some(pic) = numpic.get_digit(cur) {
                     self.hardware.with_gl(|gl| gl.draw_pic(display, pic))?;
                 }
             }
@@ -201,115 +981,1609 @@ impl LcdClock {
         Ok(())
     }
 
-    fn mode_rgb(&mut self, force_update: bool) -> Result<(), Error> {
-        let colors = match self.state.led_strip().mode() {
-            LedMode::Sin => [
-                ColorRGB8::red(),
-                ColorRGB8::green(),
-                ColorRGB8::blue(),
-                ColorRGB8::cyan(),
-                ColorRGB8::yellow(),
-                ColorRGB8::pink(),
-            ],
-            LedMode::Off => [ColorRGB8::black(); LED_COUNT],
-            LedMode::Red => [ColorRGB8::red(); LED_COUNT],
-            LedMode::Green => [ColorRGB8::green(); LED_COUNT],
-            LedMode::Blue => [ColorRGB8::blue(); LED_COUNT],
-            LedMode::Cyan => [ColorRGB8::cyan(); LED_COUNT],
-            LedMode::Yellow => [ColorRGB8::yellow(); LED_COUNT],
-            LedMode::Pink => [ColorRGB8::pink(); LED_COUNT],
-        };
+    /// Draws year with all four digits, followed by two letters of weekday
+    /// on the last two displays. ISO week number is written under weekday and
+    /// phase of moon above it.
+    fn mode_full_date(&mut self, force_update: bool) -> Result<(), Error> {
+        if !self.state.take_dirty(Source::Time) && !force_update {
+            return Ok(());
+        }
 
-        if force_update {
-            for (display, color) in Display::all().zip(colors) {
-                self.hardware.with_gl(|gl| gl.fill(display, color.into()))?;
+        let (date, _, _) = self.now()?;
+        if date == self.last_date && !force_update {
+            return Ok(());
+        }
+
+        let year = date.year;
+        let digits = [year / 1000 % 10, year / 100 % 10, year / 10 % 10, year % 10];
+        let numpic = self.state.numpic();
+        for (display, digit) in ST7789VWx6Ty::displays().zip(digits) {
+            if let Some(pic) = numpic.get_digit(digit as u8) {
+                self.hardware.with_gl(|gl| gl.draw_pic(display, pic))?;
             }
         }
 
+        let weekday = WEEKDAYS[time_util::weekday(date) as usize];
+        let mut week: String<2> = String::new();
+        write!(week, "{}", time_util::iso_week(date)).ok();
+        let theme = self.state.theme();
+        let background = ColorRGB565::from(theme.background);
+        let style = TextStyle::new(&FONT_LARGE, theme.accent.into(), background);
+        let x = (st7789vwx6::WIDTH - FONT_LARGE.glyph_width() as u16) / 2;
+        let y = (st7789vwx6::HEIGHT - FONT_LARGE.glyph_height() as u16) / 2;
+        let small = TextStyle::new(&FONT_SMALL, ColorRGB8::white().into(), background);
+        let week_y = st7789vwx6::HEIGHT - 2 * FONT_SMALL.glyph_height() as u16;
+        for (i, (display, week_part)) in [(Display::D5, "WEEK"), (Display::D6, week.as_str())]
+            .into_iter()
+            .enumerate()
+        {
+            let letter = &weekday[i..i + 1];
+            let week_x =
+                st7789vwx6::WIDTH.saturating_sub(FONT_SMALL.text_width(week_part) as u16) / 2;
+            self.hardware.with_gl(|gl| {
+                gl.fill(display, background)?;
+                gl.draw_text(display, x, y, letter, style)?;
+                gl.draw_text(display, week_x, week_y, week_part, small)
+            })?;
+        }
+
+        let phase = time_util::moon_phase(date) as usize;
+        if let Some(icon) = images::MOON_PHASES.get(phase) {
+            let icon_x = (st7789vwx6::WIDTH - icon.width() as u16) / 2;
+            let icon_y = y.saturating_sub(icon.height() as u16) / 2;
+            self.hardware
+                .with_gl(|gl| gl.draw_pic_at(Display::D6, icon_x, icon_y, icon, None))?;
+        }
+
+        self.last_date = date;
+
         Ok(())
     }
 
-    fn mode_brightness(&mut self, force_update: bool, brightness: u32) -> Result<(), Error> {
-        if force_update {
-            for display in Display::all() {
-                if let Some(pic) = NUMPIC_A.get_digit(brightness as u8) {
-                    self.hardware.with_gl(|gl| gl.draw_pic(display, pic))?;
+    /// Draws sunrise on the first three displays and sunset on the other
+    /// three: label followed by hours and minutes. Times are dashed if sun
+    /// doesn't rise or set.
+    fn mode_sun(&mut self, force_update: bool) -> Result<(), Error> {
+        if !self.state.take_dirty(Source::Time) && !force_update {
+            return Ok(());
+        }
+
+        let (date, _, _) = self.now()?;
+        if date == self.last_date && !force_update {
+            return Ok(());
+        }
+
+        let daylight = self.state.location().map(|location| {
+            sun::daylight(
+                date,
+                location,
+                self.state.utc_offset_mins(),
+                &self.float_funcs,
+            )
+        });
+        let (sunrise, sunset) = match daylight {
+            Some(Daylight::Sun { sunrise, sunset }) => (Some(sunrise), Some(sunset)),
+            _ => (None, None),
+        };
+
+        let theme = self.state.theme();
+        let background = ColorRGB565::from(theme.background);
+        let label = TextStyle::new(&FONT_SMALL, ColorRGB8::white().into(), background);
+        let style = TextStyle::new(&FONT_LARGE, theme.accent.into(), background);
+        let rows = [
+            ([Display::D1, Display::D2, Display::D3], "SUNRISE", sunrise),
+            ([Display::D4, Display::D5, Display::D6], "SUNSET", sunset),
+        ];
+        for (displays, name, minutes) in rows {
+            let mut hours: String<2> = String::new();
+            let mut mins: String<2> = String::new();
+            match minutes {
+                Some(minutes) => {
+                    write!(hours, "{:02}", minutes / 60).ok();
+                    write!(mins, "{:02}", minutes % 60).ok();
+                }
+                None => {
+                    hours.push_str("--").ok();
+                    mins.push_str("--").ok();
                 }
             }
+
+            let [name_display, hours_display, mins_display] = displays;
+            self.hardware.with_gl(|gl| {
+                gl.fill(name_display, background)?;
+                let x = st7789vwx6::WIDTH.saturating_sub(FONT_SMALL.text_width(name) as u16) / 2;
+                let y = (st7789vwx6::HEIGHT - FONT_SMALL.glyph_height() as u16) / 2;
+                gl.draw_text(name_display, x, y, name, label)?;
+
+                for (display, text) in [(hours_display, &hours), (mins_display, &mins)] {
+                    gl.fill(display, background)?;
+                    let x =
+                        st7789vwx6::WIDTH.saturating_sub(FONT_LARGE.text_width(text) as u16) / 2;
+                    let y = (st7789vwx6::HEIGHT - FONT_LARGE.glyph_height() as u16) / 2;
+                    gl.draw_text(display, x, y, text, style)?;
+                }
+                Ok(())
+            })?;
         }
 
+        self.last_date = date;
+
         Ok(())
     }
 
-    fn update_buttons(&mut self) {
-        let (mode_button_transition, left_button_transition, right_button_transition) =
-            self.hardware.update_buttons();
-        self.state.handle_buttons(
-            mode_button_transition,
-            left_button_transition,
-            right_button_transition,
-        );
-    }
+    /// Draws reminder with given field selected: its number on the first
+    /// display, month and day in order of date format and gift icon if it is
+    /// set. Unset reminder is dashed.
+    fn mode_set_reminders(&mut self, field: usize, force_update: bool) -> Result<(), Error> {
+        if !force_update {
+            return Ok(());
+        }
 
-    fn change_time(&mut self, index: usize, change: i8) -> Result<(), Error> {
-        if index < 6 {
-            let time = self
-                .hardware
-                .with_rtc(|rtc| rtc.get_time())?
-                .map_err(Error::Rtc)?;
-            let mut new_time = time;
-            match index {
-                0 => new_time.hours = time.hours.saturating_add_signed(change * 10),
-                1 => new_time.hours = time.hours.saturating_add_signed(change * 1),
-                2 => new_time.mins = time.mins.saturating_add_signed(change * 10),
-                3 => new_time.mins = time.mins.saturating_add_signed(change * 1),
-                4 => new_time.secs = time.secs.saturating_add_signed(change * 10),
-                5 => new_time.secs = time.secs.saturating_add_signed(change * 1),
-                _ => {}
-            }
-            new_time.hours %= 24;
-            new_time.mins %= 60;
-            new_time.secs %= 60;
-            if new_time.hours != time.hours {
-                self.hardware
-                    .with_rtc(|rtc| rtc.set_hours(new_time.hours))?
-                    .map_err(Error::Rtc)?;
-            } else if new_time.mins != time.mins {
-                self.hardware
-                    .with_rtc(|rtc| rtc.set_mins(new_time.mins))?
-                    .map_err(Error::Rtc)?;
-            } else {
-                self.hardware
-                    .with_rtc(|rtc| rtc.set_secs(new_time.secs))?
-                    .map_err(Error::Rtc)?;
-            }
+        let reminder = self.state.reminders()[field / 2];
+        let parts = self.state.date_format().parts();
+        let month_first = parts.iter().position(|&part| part == DatePart::Month)
+            < parts.iter().position(|&part| part == DatePart::Day);
+        let is_month_selected = field.is_multiple_of(2);
+        let month = (reminder.map(|reminder| reminder.month()), is_month_selected);
+        let day = (reminder.map(|reminder| reminder.day()), !is_month_selected);
+        let (first, second) = if month_first {
+            (month, day)
         } else {
-            let date = self
-                .hardware
-                .with_rtc(|rtc| rtc.get_calendar())?
-                .map_err(Error::Rtc)?;
-            let mut new_date = date;
-            match index % 6 {
-                0 => new_date.year = date.year.saturating_add_signed(change as i16 * 10),
-                1 => new_date.year = date.year.saturating_add_signed(change as i16 * 1),
-                2 => new_date.month = date.month.saturating_add_signed(change * 10),
-                3 => new_date.month = date.month.saturating_add_signed(change * 1),
-                4 => new_date.date = date.date.saturating_add_signed(change * 10),
-                5 => new_date.date = date.date.saturating_add_signed(change * 1),
-                _ => {}
+            (day, month)
+        };
+
+        let theme = self.state.theme();
+        let background = ColorRGB565::from(theme.background);
+        let label = TextStyle::new(&FONT_SMALL, ColorRGB8::white().into(), background);
+        let style = TextStyle::new(&FONT_LARGE, theme.accent.into(), background);
+        let numpic = self.state.numpic();
+        let mut number: String<1> = String::new();
+        write!(number, "{}", field / 2 + 1).ok();
+        self.hardware.with_gl(|gl| {
+            gl.fill(Display::D1, background)?;
+            draw_lines(gl, Display::D1, &[("REMIND", label)])?;
+            let x = st7789vwx6::WIDTH.saturating_sub(FONT_LARGE.text_width(&number) as u16) / 2;
+            let y = (st7789vwx6::HEIGHT - FONT_LARGE.glyph_height() as u16) / 2;
+            gl.draw_text(Display::D1, x, y, &number, style)?;
+
+            let pairs = [
+                ([Display::D2, Display::D3], first),
+                ([Display::D4, Display::D5], second),
+            ];
+            for (displays, (value, is_selected)) in pairs {
+                let digits = value.map_or([None; 2], |value| [Some(value / 10), Some(value % 10)]);
+                for (display, digit) in displays.into_iter().zip(digits) {
+                    match digit.and_then(|digit| numpic.get_digit(digit)) {
+                        Some(pic) => gl.draw_pic(display, pic)?,
+                        None => {
+                            gl.fill(display, background)?;
+                            let x = st7789vwx6::WIDTH
+                                .saturating_sub(FONT_LARGE.text_width("-") as u16)
+                                / 2;
+                            gl.draw_text(display, x, y, "-", style)?;
+                        }
+                    }
+                    if is_selected {
+                        gl.draw_bounding_rect(display, 8, theme.accent.into())?;
+                    }
+                }
             }
-            if new_date.year != date.year {
+
+            gl.fill(Display::D6, background)?;
+            if reminder.is_some() {
+                draw_icon(gl, Display::D6, &images::ICON_GIFT)?;
+            }
+            Ok(())
+        })
+    }
+
+    fn mode_armed(&mut self, force_update: bool) -> Result<(), Error> {
+        if !force_update {
+            return Ok(());
+        }
+
+        let background = ColorRGB565::from(self.state.theme().background);
+        let label = TextStyle::new(&FONT_SMALL, ColorRGB8::white().into(), background);
+        let state = TextStyle::new(&FONT_LARGE, self.state.theme().accent.into(), background);
+        let text = if self.state.is_alarm_armed() {
+            "ON"
+        } else {
+            "OFF"
+        };
+        self.hardware.with_gl(|gl| {
+            gl.clear_all(background)?;
+            draw_lines(gl, Display::D3, &[("ALARM", label)])?;
+            draw_lines(gl, Display::D4, &[(text, state)])
+        })
+    }
+
+    /// Shows number of selected alarm melody and plays its beginning
+    fn mode_melody(&mut self, force_update: bool) -> Result<(), Error> {
+        if !force_update {
+            return Ok(());
+        }
+
+        // Built-in melodies are always valid
+        if let Ok(melody) = rtttl::parse(self.state.melody()) {
+            self.hardware.bell.preview(melody);
+        }
+
+        let numpic = self.state.numpic();
+        let background = ColorRGB565::from(self.state.theme().background);
+        let number = self.state.melody_index() as u32 + 1;
+        for (display, digit) in ST7789VWx6Ty::displays().zip(integer_to_display_values(number)) {
+            match digit.and_then(|digit| numpic.get_digit(digit)) {
+                Some(pic) => self.hardware.with_gl(|gl| gl.draw_pic(display, pic))?,
+                None => self.hardware.with_gl(|gl| gl.fill(display, background))?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Shows volume level and plays alarm melody with it
+    fn mode_volume(&mut self, force_update: bool) -> Result<(), Error> {
+        if !force_update {
+            return Ok(());
+        }
+
+        self.hardware.bell.set_volume(self.state.volume());
+        if let Ok(melody) = rtttl::parse(self.state.melody()) {
+            self.hardware.bell.preview(melody);
+        }
+
+        self.draw_level(self.state.volume() as u32, MAX_VOLUME as u32)
+    }
+
+    fn mode_rgb(&mut self, output: LedOutput, force_update: bool) -> Result<(), Error> {
+        let mode = match output {
+            LedOutput::Strip => self.state.led_strip().mode(),
+            LedOutput::Ring => self.state.led_ring().mode(),
+        };
+        let colors = match mode {
+            LedMode::Sin => [
+                ColorRGB8::red(),
+                ColorRGB8::green(),
+                ColorRGB8::blue(),
+                ColorRGB8::cyan(),
+                ColorRGB8::yellow(),
+                ColorRGB8::pink(),
+            ],
+            LedMode::Candle => [ColorRGB8::orange(); LED_COUNT],
+            LedMode::Sound => [
+                ColorRGB8::green(),
+                ColorRGB8::green(),
+                ColorRGB8::yellow(),
+                ColorRGB8::yellow(),
+                ColorRGB8::orange(),
+                ColorRGB8::red(),
+            ],
+            LedMode::Chase => {
+                let mut colors = [ColorRGB8::black(); LED_COUNT];
+                colors[0] = ColorRGB8::white();
+                colors
+            }
+            LedMode::Off => [ColorRGB8::black(); LED_COUNT],
+            LedMode::Solid(hue) => [hsv2rgb_u8(hue as f32, 1.0, 1.0).into(); LED_COUNT],
+            LedMode::Sunrise(percent) => [led_strip::sunrise_color(percent); LED_COUNT],
+        };
+
+        if force_update {
+            let colors = ST7789VWx6Ty::displays().zip(colors.map(ColorRGB565::from));
+            self.hardware.with_gl(|gl| gl.fill_each(colors))?;
+            // Preview of ring looks the same as the one of strip
+            if output == LedOutput::Ring {
+                let label = "RING";
+                let style = TextStyle::new(
+                    &FONT_SMALL,
+                    ColorRGB8::white().into(),
+                    ColorRGB8::black().into(),
+                );
+                let x = st7789vwx6::WIDTH.saturating_sub(FONT_SMALL.text_width(label) as u16) / 2;
+                let y = (st7789vwx6::HEIGHT - FONT_SMALL.glyph_height() as u16) / 2;
                 self.hardware
-                    .with_rtc(|rtc| rtc.set_year(new_date.year))?
-                    .ok();
-            } else if new_date.month != date.month {
+                    .with_gl(|gl| gl.draw_text(Display::D1, x, y, label, style))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn mode_brightness(&mut self, force_update: bool) -> Result<(), Error> {
+        if !force_update {
+            return Ok(());
+        }
+
+        self.draw_level(self.state.brightness(), MAX_BRIGHTNESS)
+    }
+
+    /// Shows level of setting as digit on D1 and as meter on the other
+    /// displays. Meter is made of progress bars, each one filling up after
+    /// the one to the left of it.
+    fn draw_level(&mut self, value: u32, max: u32) -> Result<(), Error> {
+        let theme = self.state.theme();
+        let style = BarStyle::new(theme.accent.into(), theme.background.into());
+        let bars = ST7789VWx6Ty::displays().count() as u32 - 1;
+        match self.state.numpic().get_digit(value as u8) {
+            Some(pic) => self.hardware.with_gl(|gl| gl.draw_pic(Display::D1, pic))?,
+            None => self
+                .hardware
+                .with_gl(|gl| gl.fill(Display::D1, style.background))?,
+        }
+        for (bar, display) in ST7789VWx6Ty::displays().skip(1).enumerate() {
+            // Every bar covers max / bars of value, in units of max / bars
+            let filled = (value * bars).saturating_sub(bar as u32 * max).min(max);
+            self.hardware
+                .with_gl(|gl| gl.draw_progress_bar(display, filled, max, style))?;
+        }
+
+        Ok(())
+    }
+
+    fn mode_rotation(&mut self, force_update: bool) -> Result<(), Error> {
+        if !force_update {
+            return Ok(());
+        }
+
+        let numpic = self.state.numpic();
+        let background = ColorRGB565::from(self.state.theme().background);
+        let Some(rotation) = self.state.rotation() else {
+            let style = TextStyle::new(&FONT_LARGE, ColorRGB8::white().into(), background);
+            let x = st7789vwx6::WIDTH.saturating_sub(FONT_LARGE.text_width("OFF") as u16) / 2;
+            let y = (st7789vwx6::HEIGHT - FONT_LARGE.glyph_height() as u16) / 2;
+            return self.hardware.with_gl(|gl| {
+                gl.clear_all(background)?;
+                gl.draw_text(Display::D6, x, y, "OFF", style)
+            });
+        };
+
+        for (display, digit) in
+            ST7789VWx6Ty::displays().zip(integer_to_display_values(rotation as u32))
+        {
+            match digit.and_then(|digit| numpic.get_digit(digit)) {
+                Some(pic) => self.hardware.with_gl(|gl| gl.draw_pic(display, pic))?,
+                None => self.hardware.with_gl(|gl| gl.fill(display, background))?,
+            }
+        }
+
+        Ok(())
+    }
+
+    fn mode_unit(&mut self, force_update: bool) -> Result<(), Error> {
+        if !force_update {
+            return Ok(());
+        }
+
+        let background = ColorRGB565::from(self.state.theme().background);
+        let icon = images::unit_icon(self.state.temperature_unit());
+        self.hardware.with_gl(|gl| {
+            for display in ST7789VWx6Ty::displays() {
+                gl.fill(display, background)?;
+                draw_icon(gl, display, icon)?;
+            }
+            Ok(())
+        })
+    }
+
+    fn mode_temp_humidity(
+        &mut self,
+        screen: SensorScreen,
+        force_update: bool,
+    ) -> Result<(), Error> {
+        // Without sensor temperature of rtc is shown. It is measured inside
+        // rtc, which is warmed up by the board, so it is only approximate.
+        let has_sensor = self.hardware.diagnostics().humidity_sensor.is_ok();
+        if !has_sensor && screen != SensorScreen::Temperature {
+            return self.mode_sensor_missing(force_update);
+        }
+
+        if screen == SensorScreen::History {
+            return self.mode_history(force_update);
+        }
+
+        // Sensor is only woken up while this screen is visible
+        if !self.state.take_dirty(Source::Sensor) && !force_update {
+            return Ok(());
+        }
+
+        if !has_sensor {
+            let temperature = self.hardware.rtc.get_temperature().map_err(Error::Rtc)?;
+            let value = match self.state.temperature_unit() {
+                TemperatureUnit::Celsius => temperature.as_celcius() * 100.0,
+                TemperatureUnit::Fahrenheit => temperature.as_fahrenheit() * 100.0,
+            };
+            return self.draw_sensor_value(screen, value, true, force_update);
+        }
+
+        let measurement = self
+            .hardware
+            .humidity_sensor
+            .measure_once()
+            .map_err(Error::HumiditySensor)?;
+
+        // Degrees, percents and hectopascals with 2 fractional digits
+        let temperature = measurement.temperature;
+        let value = match screen {
+            SensorScreen::Temperature => match self.state.temperature_unit() {
+                TemperatureUnit::Celsius => temperature.as_celcius() * 100.0,
+                TemperatureUnit::Fahrenheit => temperature.as_fahrenheit() * 100.0,
+            },
+            SensorScreen::Humidity => measurement
+                .humidity
+                .map_or(0.0, |humidity| humidity.as_percent() * 100.0),
+            SensorScreen::Pressure => {
+                let altitude = self.state.station_altitude() as f32;
+                let powf = |base, exp| self.float_funcs.powf(base, exp);
+                measurement.pressure.sea_level(altitude, powf).as_pas()
+            }
+            SensorScreen::History => unreachable!(),
+        };
+        self.draw_sensor_value(screen, value, false, force_update)
+    }
+
+    /// Draws value with 2 fractional digits, redrawing only changed digits.
+    /// Temperature is followed by its unit, approximate one is marked so.
+    fn draw_sensor_value(
+        &mut self,
+        screen: SensorScreen,
+        value: f32,
+        is_approximate: bool,
+        force_update: bool,
+    ) -> Result<(), Error> {
+        let values = fixed_to_display_values(value as i32);
+
+        let numpic = self.state.numpic();
+        let theme = self.state.theme();
+        for ((display, &cur), &prev) in ST7789VWx6Ty::displays()
+            .zip(values.iter())
+            .zip(self.last_sensor_values.iter())
+        {
+            if cur == prev && !force_update {
+                continue;
+            }
+
+            if cur == Some(MINUS_SIGN) {
                 self.hardware
-                    .with_rtc(|rtc| rtc.set_month(new_date.month))?
-                    .ok();
+                    .with_gl(|gl| draw_minus_sign(gl, display, theme))?;
+                continue;
+            }
+            match cur.and_then(|digit| numpic.get_digit(digit)) {
+                Some(pic) => self.hardware.with_gl(|gl| gl.draw_pic(display, pic))?,
+                None => self
+                    .hardware
+                    .with_gl(|gl| fill_gradient(gl, display, theme))?,
+            }
+        }
+
+        self.last_sensor_values = values;
+
+        // First display is free unless temperature has 3 integer digits
+        if screen == SensorScreen::Temperature && force_update && values[0].is_none() {
+            let icon = images::unit_icon(self.state.temperature_unit());
+            let background = gradient_at(theme, FONT_SMALL.glyph_height() as u16 / 2);
+            let style = TextStyle::new(&FONT_SMALL, ColorRGB8::white().into(), background);
+            self.hardware.with_gl(|gl| {
+                draw_icon(gl, Display::D1, icon)?;
+                if is_approximate {
+                    draw_lines(gl, Display::D1, &[("APPROX", style)])?;
+                }
+                Ok(())
+            })?;
+        }
+
+        Ok(())
+    }
+
+    fn mode_history(&mut self, force_update: bool) -> Result<(), Error> {
+        if !self.state.take_dirty(Source::History) && !force_update {
+            return Ok(());
+        }
+
+        // Each quantity is charted on a pair of displays
+        let charts: [(SampleValue, ColorRGB8, bool); 3] = [
+            (|s| s.temperature as i32, ColorRGB8::red(), true),
+            (
+                |s| s.humidity as i32,
+                ColorRGB8::cyan(),
+                self.state.has_humidity(),
+            ),
+            (|s| s.pressure as i32, ColorRGB8::green(), true),
+        ];
+        let background = ColorRGB565::from(self.state.theme().background);
+        let mut displays = ST7789VWx6Ty::displays();
+        for (value, color, is_shown) in charts {
+            let (Some(left), Some(right)) = (displays.next(), displays.next()) else {
+                break;
+            };
+
+            let heights = if is_shown {
+                self.history.chart(value, st7789vwx6::HEIGHT)
             } else {
-                self.hardware
-                    .with_rtc(|rtc| rtc.set_date(new_date.date))?
-                    .ok();
+                [0; HISTORY_CHART_WIDTH]
+            };
+            let (left_heights, right_heights) = heights.split_at(st7789vwx6::WIDTH as usize);
+            let color = ColorRGB565::from(color);
+            self.hardware.with_gl(|gl| {
+                gl.draw_area_chart(left, left_heights, color, background)?;
+                gl.draw_area_chart(right, right_heights, color, background)
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Shown instead of sensor screens if sensor hasn't been found
+    fn mode_sensor_missing(&mut self, force_update: bool) -> Result<(), Error> {
+        if !force_update {
+            return Ok(());
+        }
+
+        let background = ColorRGB565::from(self.state.theme().background);
+        let style = TextStyle::new(&FONT_SMALL, ColorRGB8::white().into(), background);
+        self.hardware.with_gl(|gl| {
+            gl.clear_all(background)?;
+            draw_lines(gl, Display::D3, &[("SENSOR", style)])?;
+            draw_lines(gl, Display::D4, &[("MISSING", style)])
+        })
+    }
+
+    fn mode_diagnostics(&mut self, page: DiagnosticsPage, force_update: bool) -> Result<(), Error> {
+        if !force_update {
+            return Ok(());
+        }
+
+        match page {
+            DiagnosticsPage::Status => self.draw_diagnostics_status(),
+            DiagnosticsPage::Info => self.draw_diagnostics_info(),
+            DiagnosticsPage::Events => self.draw_diagnostics_events(),
+        }
+    }
+
+    /// Lists peripherals found at init and their status
+    fn draw_diagnostics_status(&mut self) -> Result<(), Error> {
+        let diagnostics = *self.hardware.diagnostics();
+        let background = ColorRGB565::from(self.state.theme().background);
+        let style = |color: ColorRGB8| TextStyle::new(&FONT_SMALL, color.into(), background);
+        let title = style(self.state.theme().accent);
+        let status_style = |is_ok| {
+            if is_ok {
+                style(ColorRGB8::green())
+            } else {
+                style(ColorRGB8::red())
+            }
+        };
+
+        // Addresses are listed below title, the ones that don't fit are cut
+        let mut addresses: Vec<String<DIAGNOSTICS_LINE_LEN>, { DIAGNOSTICS_LINES - 1 }> =
+            Vec::new();
+        for addr in diagnostics.i2c_scan.addresses() {
+            let mut line = String::new();
+            write!(line, "0x{addr:02X}").ok();
+            if addresses.push(line).is_err() {
+                break;
+            }
+        }
+
+        // Displays can't be read back, so only the clock they are driven at
+        // is shown
+        let mut spi_clock: String<DIAGNOSTICS_LINE_LEN> = String::new();
+        let mhz = self.state.spi_clock().map_or(SPI_CLOCK_MHZ, u32::from);
+        write!(spi_clock, "{mhz}MHZ").ok();
+
+        let rtc = [
+            ("RTC", title),
+            ("DS3231", style(ColorRGB8::white())),
+            (
+                diagnostics.rtc.label(),
+                status_style(diagnostics.rtc.is_ok()),
+            ),
+        ];
+        let sensor = [
+            ("SENSOR", title),
+            ("BME280", style(ColorRGB8::white())),
+            (
+                diagnostics.humidity_sensor.label(),
+                status_style(diagnostics.humidity_sensor.is_ok()),
+            ),
+            ("LEDS", title),
+            ("WS2812", style(ColorRGB8::white())),
+            (
+                diagnostics.led_strip.label(),
+                status_style(diagnostics.led_strip.is_ok()),
+            ),
+        ];
+        let mut i2c: Vec<(&str, TextStyle), DIAGNOSTICS_LINES> = Vec::new();
+        i2c.push(("I2C", title)).ok();
+        for address in &addresses {
+            i2c.push((address, style(ColorRGB8::white()))).ok();
+        }
+        let mut utc_offset: String<DIAGNOSTICS_LINE_LEN> = String::new();
+        let offset_mins = self.state.utc_offset_mins();
+        let sign = if offset_mins < 0 { '-' } else { '+' };
+        let offset_mins = offset_mins.unsigned_abs();
+        write!(
+            utc_offset,
+            "UTC{sign}{:02}:{:02}",
+            offset_mins / 60,
+            offset_mins % 60
+        )
+        .ok();
+        let gps = [
+            ("GPS", title),
+            (
+                self.gps_status.label(),
+                status_style(self.gps_status == GpsStatus::Fix),
+            ),
+            (utc_offset.as_str(), style(ColorRGB8::white())),
+        ];
+        let mut vsys: String<DIAGNOSTICS_LINE_LEN> = String::new();
+        write_volts(&mut vsys, self.power.vsys);
+        let mut backup: String<DIAGNOSTICS_LINE_LEN> = String::new();
+        match self.power.backup {
+            Some(millivolts) => write_volts(&mut backup, millivolts),
+            None => backup.push_str("NONE").unwrap(),
+        }
+        let power = [
+            ("POWER", title),
+            ("VSYS", style(ColorRGB8::white())),
+            (vsys.as_str(), style(ColorRGB8::white())),
+            ("BACKUP", style(ColorRGB8::white())),
+            (backup.as_str(), status_style(!self.power.is_backup_low())),
+        ];
+        let displays = [
+            ("LCD", title),
+            ("ST7789VW", style(ColorRGB8::white())),
+            ("SPI", style(ColorRGB8::white())),
+            (spi_clock.as_str(), style(ColorRGB8::white())),
+        ];
+
+        self.hardware.with_gl(|gl| {
+            gl.clear_all(background)?;
+            draw_lines(gl, Display::D1, &rtc)?;
+            draw_lines(gl, Display::D2, &sensor)?;
+            draw_lines(gl, Display::D3, &i2c)?;
+            draw_lines(gl, Display::D4, &displays)?;
+            draw_lines(gl, Display::D5, &gps)?;
+            draw_lines(gl, Display::D6, &power)
+        })
+    }
+
+    /// Shows firmware version and link to manual, both as text and QR code
+    fn draw_diagnostics_info(&mut self) -> Result<(), Error> {
+        let mut version: String<{ qr::MAX_DATA_LEN }> = String::new();
+        write!(version, "lcd-clock-rs {FIRMWARE_VERSION}").ok();
+        let version_qr = QrCode::encode(version.as_bytes());
+        let manual_qr = QrCode::encode(MANUAL_URL.as_bytes());
+
+        let background = ColorRGB565::from(self.state.theme().background);
+        let title = TextStyle::new(&FONT_SMALL, self.state.theme().accent.into(), background);
+        let text = TextStyle::new(&FONT_SMALL, ColorRGB8::white().into(), background);
+        // Readers expect dark modules on light background whatever the theme
+        let (dark, light) = (ColorRGB8::black().into(), ColorRGB8::white().into());
+        self.hardware.with_gl(|gl| {
+            gl.clear_all(background)?;
+            draw_lines(
+                gl,
+                Display::D1,
+                &[("FIRMWARE", title), (FIRMWARE_VERSION, text)],
+            )?;
+            if let Some(qr) = &version_qr {
+                gl.draw_qr(Display::D2, qr, dark, light)?;
+            }
+            draw_lines(gl, Display::D3, &[("MANUAL", title), ("SCAN", text)])?;
+            if let Some(qr) = &manual_qr {
+                gl.draw_qr(Display::D4, qr, dark, light)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Shows kind of crash and address of faulting instruction, message of
+    /// panic wraps over the displays that follow
+    fn mode_crash_report(&mut self, force_update: bool) -> Result<(), Error> {
+        if !force_update {
+            return Ok(());
+        }
+        let Some(report) = &self.crash_report else {
+            return Ok(());
+        };
+
+        let mut pc: String<DIAGNOSTICS_LINE_LEN> = String::new();
+        write!(pc, "{:08X}", report.pc).ok();
+        // Message is ASCII, so it is split between any bytes
+        let mut message = report
+            .message
+            .as_bytes()
+            .chunks(DIAGNOSTICS_LINE_LEN)
+            .map(|line| core::str::from_utf8(line).unwrap_or(""));
+
+        let background = ColorRGB565::from(self.state.theme().background);
+        let style = |color: ColorRGB8| TextStyle::new(&FONT_SMALL, color.into(), background);
+        let title = style(self.state.theme().accent);
+        let text = style(ColorRGB8::white());
+        let kind = (report.kind.label(), style(ColorRGB8::red()));
+        self.hardware.with_gl(|gl| {
+            gl.clear_all(background)?;
+            draw_lines(
+                gl,
+                Display::D1,
+                &[("CRASH", title), kind, ("PC", title), (&pc, text)],
+            )?;
+            for display in ST7789VWx6Ty::displays().skip(1) {
+                let lines: Vec<_, DIAGNOSTICS_LINES> = message
+                    .by_ref()
+                    .take(DIAGNOSTICS_LINES)
+                    .map(|line| (line, text))
+                    .collect();
+                draw_lines(gl, display, &lines)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Shows version, commit and date of build and revision of the kit
+    fn mode_about(&mut self, force_update: bool) -> Result<(), Error> {
+        if !force_update {
+            return Ok(());
+        }
+
+        // Whole date doesn't fit on a line, year goes on a line of its own
+        let (year, month_day) = BUILD_DATE.split_once('-').unwrap_or((BUILD_DATE, ""));
+        let background = ColorRGB565::from(self.state.theme().background);
+        let title = TextStyle::new(&FONT_SMALL, self.state.theme().accent.into(), background);
+        let text = TextStyle::new(&FONT_SMALL, ColorRGB8::white().into(), background);
+        self.hardware.with_gl(|gl| {
+            gl.clear_all(background)?;
+            draw_lines(
+                gl,
+                Display::D2,
+                &[("VERSION", title), (FIRMWARE_VERSION, text)],
+            )?;
+            draw_lines(gl, Display::D3, &[("COMMIT", title), (GIT_HASH, text)])?;
+            draw_lines(
+                gl,
+                Display::D4,
+                &[("BUILT", title), (year, text), (month_day, text)],
+            )?;
+            draw_lines(gl, Display::D5, &[("BOARD", title), (BOARD_REVISION, text)])
+        })
+    }
+
+    /// Shows uptime and the newest events, with time of uptime they last
+    /// happened at. Errors are red.
+    fn draw_diagnostics_events(&mut self) -> Result<(), Error> {
+        let mut uptime: String<DIAGNOSTICS_LINE_LEN> = String::new();
+        write_uptime(&mut uptime, timer::now_ms());
+        let mut times: [String<DIAGNOSTICS_LINE_LEN>; EVENTS_SHOWN] = Default::default();
+        let events = self.event_log.events().rev().take(EVENTS_SHOWN);
+        let events: Vec<_, EVENTS_SHOWN> = events.copied().collect();
+        for (time, event) in times.iter_mut().zip(&events) {
+            write_uptime(time, event.ms);
+        }
+
+        let background = ColorRGB565::from(self.state.theme().background);
+        let style = |color: ColorRGB8| TextStyle::new(&FONT_SMALL, color.into(), background);
+        let title = style(self.state.theme().accent);
+        let text = style(ColorRGB8::white());
+        let mut lines: Vec<(&str, TextStyle), { 2 * EVENTS_SHOWN }> = Vec::new();
+        for (time, event) in times.iter().zip(&events) {
+            let label_style = if event.code.is_error() {
+                style(ColorRGB8::red())
+            } else {
+                text
+            };
+            lines.push((event.code.label(), label_style)).ok();
+            lines.push((time.as_str(), text)).ok();
+        }
+
+        self.hardware.with_gl(|gl| {
+            gl.clear_all(background)?;
+            draw_lines(
+                gl,
+                Display::D1,
+                &[("EVENTS", title), ("UPTIME", text), (uptime.as_str(), text)],
+            )?;
+            for (display, lines) in ST7789VWx6Ty::displays()
+                .skip(1)
+                .zip(lines.chunks(2 * EVENTS_PER_DISPLAY))
+            {
+                draw_lines(gl, display, lines)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Fills displays with test patterns, then beeps and reports each
+    /// peripheral
+    fn mode_self_test(&mut self, force_update: bool) -> Result<(), Error> {
+        let Some(self_test) = &mut self.self_test else {
+            return Ok(());
+        };
+
+        let step = match self_test.update() {
+            Some(step) => {
+                if step == Step::Report {
+                    if let Ok(melody) = rtttl::parse(BEEP) {
+                        self.hardware.bell.preview(melody);
+                    }
+                }
+                step
+            }
+            None if force_update => self_test.step(),
+            None => return Ok(()),
+        };
+
+        match step_color(step) {
+            Some(color) => self
+                .hardware
+                .with_gl(|gl| gl.clear_all(ColorRGB565::from(color))),
+            None => self.draw_self_test_report(),
+        }
+    }
+
+    /// Reports rtc, sensor and LEDs as passed or failed. Rtc and sensor are
+    /// read once more, and sensor is named by its chip ID. Displays and buzzer
+    /// can only be seen or heard, so buzzer is reported as fitted or not.
+    fn draw_self_test_report(&mut self) -> Result<(), Error> {
+        let diagnostics = *self.hardware.diagnostics();
+        let rtc_temperature = if diagnostics.rtc.is_ok() {
+            self.hardware.rtc.get_temperature().ok()
+        } else {
+            None
+        };
+        let measurement = if diagnostics.humidity_sensor.is_ok() {
+            self.hardware.humidity_sensor.measure_once().ok()
+        } else {
+            None
+        };
+        let sensor_name = match self.hardware.humidity_sensor.variant() {
+            Some(Variant::BMP280) => "BMP280",
+            Some(Variant::BME280) => "BME280",
+            Some(Variant::BME680) => "BME680",
+            None => "NONE",
+        };
+
+        let mut rtc_line: String<DIAGNOSTICS_LINE_LEN> = String::new();
+        let mut sensor_line: String<DIAGNOSTICS_LINE_LEN> = String::new();
+        for (line, celcius) in [
+            (&mut rtc_line, rtc_temperature.map(|it| it.as_celcius())),
+            (
+                &mut sensor_line,
+                measurement.map(|it| it.temperature.as_celcius()),
+            ),
+        ] {
+            match celcius {
+                Some(celcius) => write!(line, "{celcius:.1}C").ok(),
+                None => write!(line, "-").ok(),
+            };
+        }
+
+        let rtc_pass = rtc_temperature.is_some();
+        let sensor_pass = measurement.is_some();
+        let leds_pass = diagnostics.led_strip.is_ok();
+        let is_pass = rtc_pass && sensor_pass && leds_pass;
+
+        let background = ColorRGB565::from(self.state.theme().background);
+        let style = |color: ColorRGB8| TextStyle::new(&FONT_SMALL, color.into(), background);
+        let title = style(self.state.theme().accent);
+        let text = style(ColorRGB8::white());
+        let result = |is_pass| {
+            if is_pass {
+                ("PASS", style(ColorRGB8::green()))
+            } else {
+                ("FAIL", style(ColorRGB8::red()))
+            }
+        };
+
+        // Displays can't be read back, colors they showed are checked by eye
+        let displays = [("LCD", title), ("CHECK", text), ("BY EYE", text)];
+        let rtc = [
+            ("RTC", title),
+            ("DS3231", text),
+            (rtc_line.as_str(), text),
+            result(rtc_pass),
+        ];
+        let sensor = [
+            ("SENSOR", title),
+            (sensor_name, text),
+            (sensor_line.as_str(), text),
+            result(sensor_pass),
+        ];
+        let ring = if self.hardware.led_ring.is_some() {
+            result(true)
+        } else {
+            ("NONE", text)
+        };
+        let leds = [
+            ("LEDS", title),
+            ("STRIP", text),
+            result(leds_pass),
+            ("RING", text),
+            ring,
+        ];
+        let buzzer = if self.hardware.bell.has_buzzer() {
+            "FITTED"
+        } else {
+            "NONE"
+        };
+        let buzzer = [("BUZZER", title), (buzzer, text)];
+        let summary = [
+            ("RESULT", title),
+            result(is_pass),
+            ("", text),
+            ("L OR R", text),
+            ("TO EXIT", text),
+        ];
+
+        self.hardware.with_gl(|gl| {
+            gl.clear_all(background)?;
+            draw_lines(gl, Display::D1, &displays)?;
+            draw_lines(gl, Display::D2, &rtc)?;
+            draw_lines(gl, Display::D3, &sensor)?;
+            draw_lines(gl, Display::D4, &leds)?;
+            draw_lines(gl, Display::D5, &buzzer)?;
+            draw_lines(gl, Display::D6, &summary)
+        })
+    }
+
+    /// Scrolls message across displays, then goes back to time
+    fn mode_message(&mut self, force_update: bool) -> Result<(), Error> {
+        let Some(marquee) = &mut self.marquee else {
+            self.state.hide_message();
+            return Ok(());
+        };
+
+        let background = ColorRGB565::from(self.state.theme().background);
+        let style = TextStyle::new(&FONT_LARGE, self.state.theme().accent.into(), background);
+        let y = (st7789vwx6::HEIGHT - FONT_LARGE.glyph_height() as u16) / 2;
+        if force_update {
+            self.hardware.with_gl(|gl| gl.clear_all(background))?;
+            marquee.redraw();
+        }
+        self.hardware.with_gl(|gl| marquee.draw(gl, y, style))?;
+
+        if marquee.is_done() {
+            self.marquee = None;
+            self.state.hide_message();
+        }
+        Ok(())
+    }
+
+    /// Sleeps until button is pressed, armed alarm rings or night ends
+    fn mode_dormant(&mut self, force_update: bool) -> Result<(), Error> {
+        if force_update && !self.is_dormant {
+            self.enter_dormant()?;
+        }
+
+        self.dormant_frames += 1;
+        if self.dormant_frames < DORMANT_WAKE_FRAMES {
+            return Ok(());
+        }
+        self.dormant_frames = 0;
+        // Backlight has faded out by now
+        self.hardware.displays.sleep().map_err(Error::Display)?;
+        self.hardware.dormant();
+        // System timer has stopped with other clocks
+        self.local_time.invalidate();
+        // Woken up either by button, which is handled as usual, or by rtc
+        // alarm at time of armed alarm or at the end of night
+        self.update_alarm()?;
+        self.update_night()?;
+        // Night goes on, so rtc wakes clock up again at its end
+        if self.state.mode() == AppMode::Dormant {
+            self.set_wake_alarm()?;
+        }
+
+        Ok(())
+    }
+
+    /// Sets rtc alarm to wake clock up, see
+    /// [`set_wake_alarm`](Self::set_wake_alarm). Backlight fades out
+    /// meanwhile, as brightness of dormant mode is zero.
+    fn enter_dormant(&mut self) -> Result<(), Error> {
+        self.set_wake_alarm()?;
+        self.is_dormant = true;
+        self.dormant_frames = 0;
+        self.event_log.push(Code::Sleep, timer::now_ms());
+
+        Ok(())
+    }
+
+    /// Sets rtc alarm to the end of night or time of armed alarm or its
+    /// sunrise, whichever comes first. Alarm is signaled on the same pin as second tick, which
+    /// is not needed until then.
+    fn set_wake_alarm(&mut self) -> Result<(), Error> {
+        const MINS_PER_DAY: i16 = 24 * 60;
+        let (_, now, _) = self.now()?;
+        let now = now.hours as i16 * 60 + now.mins as i16;
+        let end = self.state.night().map(|(_, end)| (end, 0));
+        // Clock wakes up for sunrise ahead of alarm
+        let alarm = self.state.is_alarm_armed().then(|| {
+            let (hours, mins) = self.state.alarm_time();
+            let sunrise_mins = self.state.sunrise_mins().unwrap_or(0) as i16;
+            let mins = (hours as i16 * 60 + mins as i16 - sunrise_mins).rem_euclid(MINS_PER_DAY);
+            ((mins / 60) as u8, (mins % 60) as u8)
+        });
+        // Minute that has started already comes again tomorrow
+        let wake = [end, alarm]
+            .into_iter()
+            .flatten()
+            .min_by_key(|&(hours, mins)| {
+                (hours as i16 * 60 + mins as i16 - now - 1).rem_euclid(MINS_PER_DAY)
+            });
+        let Some((hours, mins)) = wake else {
+            return Ok(());
+        };
+
+        let wake = Time {
+            hours,
+            mins,
+            secs: 0,
+        };
+        let rtc = &mut self.hardware.rtc;
+        rtc.set_daily_alarm1(wake)
+            .and_then(|()| rtc.clear_alarm1_flag())
+            .and_then(|()| rtc.set_alarm1_interrupt(true))
+            .and_then(|()| rtc.disable_square_wave())
+            .map_err(Error::Rtc)
+    }
+
+    /// Wipes settings and configuration of rtc, then reboots into first boot
+    /// setup. Time kept by rtc stays.
+    fn factory_reset(&mut self) -> Result<(), Error> {
+        settings::erase(&mut self.hardware.settings_storage).map_err(Error::Settings)?;
+        self.hardware.rtc.reset_config().map_err(Error::Rtc)?;
+        self.hardware.reboot();
+
+        Ok(())
+    }
+
+    fn leave_dormant(&mut self) -> Result<(), Error> {
+        let rtc = &mut self.hardware.rtc;
+        rtc.set_alarm1_interrupt(false)
+            .and_then(|()| rtc.clear_alarm1_flag())
+            .and_then(|()| rtc.enable_square_wave(ds3231::SquareWave::Hz1))
+            .map_err(Error::Rtc)?;
+        // Backlight fades in as brightness of new mode differs
+        self.hardware.displays.wake().map_err(Error::Display)?;
+        self.is_dormant = false;
+        self.state.mark_dirty(Source::Time);
+        self.event_log.push(Code::Wake, timer::now_ms());
+
+        Ok(())
+    }
+
+    /// Date, time and milliseconds into second, counted by system timer
+    /// between reads of rtc. Rtc is read right away if time is not known.
+    fn now(&mut self) -> Result<(Date, Time, u16), Error> {
+        let now_ms = timer::now_ms();
+        if let Some(now) = self.local_time.now(now_ms) {
+            return Ok(now);
+        }
+
+        let (date, time) = self.hardware.rtc.get_date_time().map_err(Error::Rtc)?;
+        self.local_time.sync(date, time, now_ms, false);
+        Ok((date, time, 0))
+    }
+
+    /// Reads rtc on tick when time counted by system timer is due to be
+    /// corrected, otherwise only restarts fraction of second
+    fn update_local_time(&mut self) -> Result<(), Error> {
+        if !self.hardware.diagnostics().rtc.is_ok() {
+            return Ok(());
+        }
+
+        let now_ms = timer::now_ms();
+        if self.local_time.is_sync_due(now_ms) {
+            let (date, time) = self.hardware.rtc.get_date_time().map_err(Error::Rtc)?;
+            self.local_time.sync(date, time, now_ms, true);
+        } else {
+            self.local_time.tick(now_ms);
+        }
+
+        Ok(())
+    }
+
+    /// Lets state decide whether clock should sleep at current hour
+    fn update_night(&mut self) -> Result<(), Error> {
+        if self.state.night().is_none() && !self.state.night_from_sun() && !self.is_dormant {
+            return Ok(());
+        }
+        if !self.hardware.diagnostics().rtc.is_ok() {
+            return Ok(());
+        }
+
+        if let (true, Some(location)) = (self.state.night_from_sun(), self.state.location()) {
+            let (date, _, _) = self.now()?;
+            let utc_offset = self.state.utc_offset_mins();
+            // Night starts with the first whole hour after sunset and ends
+            // with the hour of sunrise. Clock doesn't sleep through polar
+            // days and nights.
+            let night = match sun::daylight(date, location, utc_offset, &self.float_funcs) {
+                Daylight::Sun { sunrise, sunset } => {
+                    let start = (sunset.div_ceil(60) % 24) as u8;
+                    let end = (sunrise / 60) as u8;
+                    (start != end).then_some((start, end))
+                }
+                Daylight::PolarDay | Daylight::PolarNight => None,
+            };
+            self.state.set_sun_night(night);
+        }
+
+        let (_, time, _) = self.now()?;
+        self.state.update_night(time.hours);
+
+        Ok(())
+    }
+
+    /// Follows curve of dimming with minute of rtc
+    fn update_dimming(&mut self) -> Result<(), Error> {
+        if self.state.dimming().is_none() || !self.hardware.diagnostics().rtc.is_ok() {
+            return Ok(());
+        }
+
+        let (_, time, _) = self.now()?;
+        self.state
+            .update_dimming(time.hours as u16 * 60 + time.mins as u16);
+
+        Ok(())
+    }
+
+    /// Switches clock of display SPI once it is set over console or night
+    /// starts or ends. Displays are initialized again, so screen is redrawn.
+    fn update_spi_clock(&mut self) -> Result<(), Error> {
+        let spi_clock = self.state.spi_clock().map_or(SPI_CLOCK_MHZ, u32::from);
+        if spi_clock == self.last_spi_clock {
+            return Ok(());
+        }
+
+        self.last_spi_clock = spi_clock;
+        self.hardware.set_spi_clock(spi_clock)?;
+        self.state.request_redraw();
+
+        Ok(())
+    }
+
+    /// Tints LEDs warm during hours of tint
+    fn update_tint(&mut self) -> Result<(), Error> {
+        if self.state.night_tint().is_none() || !self.hardware.diagnostics().rtc.is_ok() {
+            return Ok(());
+        }
+
+        let (_, time, _) = self.now()?;
+        self.state.update_tint(time.hours);
+
+        Ok(())
+    }
+
+    /// Tracks whether any reminder is due today. Rainbow plays on LED strip
+    /// once the day starts and then every hour, unless clock sleeps.
+    fn update_reminders(&mut self) -> Result<(), Error> {
+        let has_reminders = self.state.reminders().iter().any(Option::is_some);
+        if !has_reminders || !self.hardware.diagnostics().rtc.is_ok() {
+            if self.is_reminder_day {
+                self.is_reminder_day = false;
+                self.state.request_redraw();
+            }
+            return Ok(());
+        }
+
+        let (date, time, _) = self.now()?;
+        let is_reminder_day = self.state.is_reminder_day(date);
+        let is_hour_start = time.mins == 0 && time.secs == 0;
+        let celebrates = is_reminder_day && (!self.is_reminder_day || is_hour_start);
+        if is_reminder_day != self.is_reminder_day {
+            self.is_reminder_day = is_reminder_day;
+            self.state.request_redraw();
+        }
+        if celebrates && self.state.mode() != AppMode::Dormant {
+            self.state.led_strip_mut().notify(Notification {
+                pattern: FlashPattern::Rainbow(RAINBOW_FRAMES),
+                color: ColorRGB8::white(),
+                frames: Some(CELEBRATION_FRAMES),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Rings armed alarm as its minute starts. Bell rises to full volume and
+    /// tempo over ramp and rings with LED strip until a button is pressed.
+    /// Sunrise ahead of alarm lasts until then too.
+    fn update_alarm(&mut self) -> Result<(), Error> {
+        if !self.state.is_alarm_armed() || !self.hardware.diagnostics().rtc.is_ok() {
+            self.is_alarm_minute = false;
+            self.state.update_sunrise(None);
+            return Ok(());
+        }
+
+        let (_, time, _) = self.now()?;
+        let (hours, mins) = self.state.alarm_time();
+        if !self.state.is_alarm_ringing() {
+            const SECS_PER_DAY: i32 = 24 * 60 * 60;
+            let now = time.hours as i32 * 60 * 60 + time.mins as i32 * 60 + time.secs as i32;
+            let alarm = hours as i32 * 60 * 60 + mins as i32 * 60;
+            let secs_left = (alarm - now).rem_euclid(SECS_PER_DAY);
+            let sunrise = self.state.sunrise_mins().and_then(|sunrise_mins| {
+                let secs = sunrise_mins as i32 * 60;
+                (secs_left > 0 && secs_left <= secs).then(|| (100 - secs_left * 100 / secs) as u8)
+            });
+            self.state.update_sunrise(sunrise);
+        }
+
+        let is_alarm_minute = (time.hours, time.mins) == (hours, mins);
+        if is_alarm_minute && !self.is_alarm_minute && !self.state.is_alarm_ringing() {
+            if self.state.sunrise_mins().is_some() {
+                self.state.update_sunrise(Some(100));
+            }
+            self.state.start_alarm();
+            self.hardware.bell.set_volume(self.state.volume());
+            // Built-in melodies are always valid
+            if let Ok(melody) = rtttl::parse(self.state.melody()) {
+                self.hardware.bell.ring(melody, self.state.alarm_ramp());
+            }
+            self.event_log.push(Code::Alarm, timer::now_ms());
+        }
+        self.is_alarm_minute = is_alarm_minute;
+
+        Ok(())
+    }
+
+    /// Samples humidity sensor into history and climate log every few minutes.
+    /// Log is written only while time is valid, as entries are stamped with
+    /// it.
+    fn update_history(&mut self) -> Result<(), Error> {
+        let diagnostics = self.hardware.diagnostics();
+        if !diagnostics.rtc.is_ok() || !diagnostics.humidity_sensor.is_ok() {
+            return Ok(());
+        }
+
+        let (date, time, _) = self.now()?;
+        let is_history_due = self.history.is_due(time);
+        let is_log_due = self.climate_log.is_due(time) && !self.state.is_time_invalid();
+        if !is_history_due && !is_log_due {
+            return Ok(());
+        }
+
+        let measurement = self
+            .hardware
+            .humidity_sensor
+            .measure_once()
+            .map_err(Error::HumiditySensor)?;
+        let sample = Sample::from(measurement);
+        if is_history_due {
+            self.history.push(time, sample);
+            self.state.mark_dirty(Source::History);
+        }
+        if is_log_due {
+            let entry = Entry::new(date, time, sample);
+            self.climate_log.push(&mut self.hardware.log_storage, entry);
+        }
+
+        Ok(())
+    }
+
+    /// Executes command received over USB, if there is one
+    fn update_console(&mut self) -> Result<(), Error> {
+        let Some(line) = console::take_line() else {
+            return Ok(());
+        };
+
+        let command = match Command::parse(&line) {
+            Ok(command) => command,
+            Err(err) => {
+                console::write_str("error: ");
+                console::write_str(err.message());
+                console::write_str("\r\n");
+                return Ok(());
+            }
+        };
+
+        match command {
+            Command::SetTime(..) if !self.hardware.diagnostics().rtc.is_ok() => {
+                console::write_str("error: rtc is not working\r\n");
+                return Ok(());
+            }
+            Command::SetTime(date, time) => self.set_date_time(date, time)?,
+            Command::SetTimezone(utc_offset) => self.state.set_utc_offset(utc_offset),
+            Command::Night(night) => self.state.set_night(night),
+            Command::NightFromSun if self.state.location().is_none() => {
+                console::write_str("error: location is not set\r\n");
+                return Ok(());
+            }
+            Command::NightFromSun => self.state.set_night_from_sun(),
+            Command::Location(location) => self.state.set_location(location),
+            Command::Dimming(dimming) => self.state.set_dimming(dimming),
+            Command::NightTint(tint) => self.state.set_night_tint(tint),
+            Command::AddReminder(reminder) => {
+                if !self.state.add_reminder(reminder) {
+                    console::write_str("error: all reminders are set\r\n");
+                    return Ok(());
+                }
+            }
+            Command::RemoveReminder(reminder) => {
+                if !self.state.remove_reminder(reminder) {
+                    console::write_str("error: reminder is not set\r\n");
+                    return Ok(());
+                }
+            }
+            Command::ListReminders => {
+                for reminder in self.state.reminders().iter().flatten() {
+                    let mut line = String::<8>::new();
+                    write!(line, "{:02}-{:02}\r\n", reminder.month(), reminder.day()).ok();
+                    console::write_str(&line);
+                }
+            }
+            Command::Brightness(screen, brightness) => {
+                self.state.set_screen_brightness(screen, brightness)
+            }
+            Command::SpiClock(is_night, mhz) => self.state.set_spi_clock(is_night, mhz),
+            Command::IdleTimeout(secs) => self.state.set_idle_timeout(secs),
+            Command::AlarmRamp(mins) => self.state.set_alarm_ramp(mins),
+            Command::Sunrise(mins) => self.state.set_sunrise_mins(mins),
+            Command::DumpSettings => {
+                let mut line = String::<{ 2 * settings::BLOB_LEN + 2 }>::new();
+                for byte in self.state.settings().to_blob() {
+                    write!(line, "{byte:02x}").ok();
+                }
+                line.push_str("\r\n").ok();
+                console::write_str(&line);
+            }
+            Command::LoadSettings(settings) => self.state.load_settings(settings),
+            Command::DumpLog => {
+                // Entries follow over next frames, ok ends them
+                console::write_str("time,temperature,humidity,pressure\r\n");
+                self.log_dump = Some(self.climate_log.dump());
+                return Ok(());
+            }
+            Command::ListEvents => {
+                // Events follow over next frames, ok ends them
+                self.event_dump = Some(0);
+                return Ok(());
+            }
+            Command::ShowCrash => {
+                let Some(report) = &self.crash_report else {
+                    console::write_str("error: no crash since power up\r\n");
+                    return Ok(());
+                };
+                let mut line = String::<{ crash::MESSAGE_LEN + 24 }>::new();
+                write!(
+                    line,
+                    "{} {:#010x} {}\r\n",
+                    report.kind.label(),
+                    report.pc,
+                    report.message
+                )
+                .ok();
+                console::write_str(&line);
+            }
+            Command::Message(text) => {
+                self.marquee = Some(Marquee::new(text, MESSAGE_PASSES));
+                self.state.show_message();
+            }
+            Command::Bootloader => self.state.request_bootloader(),
+        }
+        console::write_str("ok\r\n");
+
+        Ok(())
+    }
+
+    /// Writes a few lines of climate log being dumped as CSV. Temperature is
+    /// in degrees of celsius, humidity in percents and pressure in
+    /// hectopascals.
+    fn update_log_dump(&mut self) {
+        let Some(dump) = self.log_dump.as_mut() else {
+            return;
+        };
+
+        for _ in 0..LOG_DUMP_LINES {
+            let Some(entry) = dump.next_entry(&mut self.hardware.log_storage) else {
+                console::write_str("ok\r\n");
+                self.log_dump = None;
+                return;
+            };
+
+            let Entry { sample, .. } = entry;
+            let sign = if sample.temperature < 0 { "-" } else { "" };
+            let temperature = sample.temperature.unsigned_abs();
+            let mut line = String::<48>::new();
+            write!(
+                line,
+                "{:04}-{:02}-{:02}T{:02}:{:02}:00,{sign}{}.{:02},{}.{:02},{}.{}\r\n",
+                entry.year,
+                entry.month,
+                entry.date,
+                entry.hours,
+                entry.mins,
+                temperature / 100,
+                temperature % 100,
+                sample.humidity / 100,
+                sample.humidity % 100,
+                sample.pressure / 10,
+                sample.pressure % 10,
+            )
+            .ok();
+            console::write_str(&line);
+        }
+    }
+
+    /// Writes a few lines of events being listed, each with uptime in
+    /// seconds it last happened at and times it happened in a row
+    fn update_event_dump(&mut self) {
+        let Some(index) = self.event_dump.as_mut() else {
+            return;
+        };
+
+        for _ in 0..LOG_DUMP_LINES {
+            let Some(event) = self.event_log.events().nth(*index) else {
+                console::write_str("ok\r\n");
+                self.event_dump = None;
+                return;
+            };
+
+            *index += 1;
+            let mut line = String::<32>::new();
+            write!(
+                line,
+                "{}.{:03} {} {}\r\n",
+                event.ms / 1000,
+                event.ms % 1000,
+                event.code.label(),
+                event.count
+            )
+            .ok();
+            console::write_str(&line);
+        }
+    }
+
+    /// Tracks fix of GPS module and sets rtc from it every GPS_SYNC_FRAMES
+    fn update_gps(&mut self) -> Result<(), Error> {
+        self.gps_frames = self.gps_frames.saturating_add(1);
+        self.gps_sync_frames = self.gps_sync_frames.saturating_add(1);
+
+        let status = match gps::take() {
+            Some(rmc) => {
+                self.gps_frames = 0;
+                match rmc.date_time {
+                    Some((date, time)) => {
+                        let is_due = self.gps_sync_frames >= GPS_SYNC_FRAMES;
+                        if is_due && self.hardware.diagnostics().rtc.is_ok() {
+                            let utc_offset = self.state.utc_offset_mins();
+                            let (date, time) = ds3231::offset_date_time(date, time, utc_offset);
+                            self.set_date_time(date, time)?;
+                            self.gps_sync_frames = 0;
+                        }
+                        GpsStatus::Fix
+                    }
+                    None => GpsStatus::NoFix,
+                }
+            }
+            None if self.gps_frames >= GPS_TIMEOUT_FRAMES => GpsStatus::None,
+            None => self.gps_status,
+        };
+
+        if status != self.gps_status {
+            self.gps_status = status;
+            if matches!(self.state.mode(), AppMode::Diagnostics(_)) {
+                self.state.request_redraw();
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Polls buttons and samples microphone. Button events wait for the
+    /// next frame, which handles them and draws the result.
+    fn update_input(&mut self) {
+        // Debounce takes longer than a frame, so button rarely has more than
+        // one event per frame. The latest one is kept then.
+        let (mode, left, right) = self.hardware.update_buttons();
+        let (last_mode, last_left, last_right) = self.button_events;
+        self.button_events = (mode.or(last_mode), left.or(last_left), right.or(last_right));
+
+        if let Some(raw) = self.hardware.read_microphone() {
+            self.envelope.sample(raw);
+        }
+    }
+
+    fn update_power(&mut self) {
+        self.power = self.hardware.measure_power();
+        if matches!(self.state.mode(), AppMode::Diagnostics(_)) {
+            self.state.request_redraw();
+        }
+    }
+
+    /// Sets time kept by rtc, which makes it valid again if oscillator has
+    /// stopped before
+    fn set_date_time(&mut self, date: Date, time: Time) -> Result<(), Error> {
+        self.hardware
+            .rtc
+            .set_date_time(date, time)
+            .map_err(Error::Rtc)?;
+        if self.state.is_time_invalid() {
+            self.hardware
+                .rtc
+                .clear_oscillator_stop_flag()
+                .map_err(Error::Rtc)?;
+            self.state.clear_time_invalid();
+        }
+        self.state.mark_dirty(Source::Time);
+        self.local_time.invalidate();
+        self.event_log.push(Code::TimeSet, timer::now_ms());
+
+        Ok(())
+    }
+
+    fn update_pixel_shift(&mut self) {
+        let pixel_shift = self.hardware.pixel_shift().next();
+        self.hardware.set_pixel_shift(pixel_shift);
+        self.state.request_redraw();
+    }
+
+    fn update_buttons(&mut self) {
+        let (mode_button_transition, left_button_transition, right_button_transition) =
+            core::mem::take(&mut self.button_events);
+        self.state.handle_buttons(
+            mode_button_transition,
+            left_button_transition,
+            right_button_transition,
+        );
+    }
+
+    /// Changes digit of time or date shown on display of given index. Digits
+    /// wrap around and carry into each other, e.g. minute after 59 is 00 of
+    /// next hour. Only fields that have changed are written, so second
+    /// isn't restarted by changing hours.
+    fn change_time(&mut self, index: usize, change: i8) -> Result<(), Error> {
+        self.state.mark_dirty(Source::Time);
+        self.local_time.invalidate();
+        self.event_log.push(Code::TimeSet, timer::now_ms());
+        let rtc = &mut self.hardware.rtc;
+        if index < 6 {
+            const SECS_PER_DAY: i32 = 24 * 60 * 60;
+            const DIGIT_SECS: [i32; 6] = [10 * 60 * 60, 60 * 60, 10 * 60, 60, 10, 1];
+            let time = rtc.get_time().map_err(Error::Rtc)?;
+            let secs = time.hours as i32 * 60 * 60 + time.mins as i32 * 60 + time.secs as i32;
+            let secs = (secs + change as i32 * DIGIT_SECS[index]).rem_euclid(SECS_PER_DAY);
+            let new_time = Time {
+                hours: (secs / 60 / 60) as u8,
+                mins: (secs / 60 % 60) as u8,
+                secs: (secs % 60) as u8,
+            };
+            if new_time.hours != time.hours {
+                rtc.set_hours(new_time.hours).map_err(Error::Rtc)?;
+            }
+            if new_time.mins != time.mins {
+                rtc.set_mins(new_time.mins).map_err(Error::Rtc)?;
+            }
+            if new_time.secs != time.secs {
+                rtc.set_secs(new_time.secs).map_err(Error::Rtc)?;
+            }
+        } else {
+            let date = rtc.get_calendar().map_err(Error::Rtc)?;
+            // Each part of date is shown on pair of displays, tens first
+            let part = self.state.date_format().parts()[index % 6 / 2];
+            let change = match index % 2 {
+                0 => change as i16 * 10,
+                _ => change as i16,
+            };
+            let mut new_date = match part {
+                DatePart::Year => ds3231::add_months(date, change * 12),
+                DatePart::Month => ds3231::add_months(date, change),
+                DatePart::Day => ds3231::add_days(date, change),
+            };
+            // Only two digits of year are shown, so it wraps within century
+            if !(2000..=2099).contains(&new_date.year) {
+                new_date.year = 2000 + (new_date.year as i16 - 2000).rem_euclid(100) as u16;
+            }
+            if new_date.year != date.year {
+                rtc.set_year(new_date.year).map_err(Error::Rtc)?;
+            }
+            if new_date.month != date.month {
+                rtc.set_month(new_date.month).map_err(Error::Rtc)?;
+            }
+            if new_date.date != date.date {
+                rtc.set_date(new_date.date).map_err(Error::Rtc)?;
             }
         }
 
@@ -322,8 +2596,149 @@ pub enum Error {
     Display(st7789vwx6::Error),
     HumiditySensor(bme280::Error),
     Rtc(ds3231::Error),
+    Settings(settings::Error),
+}
+
+impl Error {
+    /// Code error is logged with
+    pub fn event_code(&self) -> Code {
+        match self {
+            Self::Display(_) => Code::DisplayError,
+            Self::HumiditySensor(_) => Code::SensorError,
+            Self::Rtc(_) => Code::RtcError,
+            Self::Settings(_) => Code::SettingsError,
+        }
+    }
+}
+
+/// Lines of small font that fit on display
+const DIAGNOSTICS_LINES: usize = st7789vwx6::HEIGHT as usize / FONT_SMALL.glyph_height() as usize;
+/// Characters of small font that fit in single line
+const DIAGNOSTICS_LINE_LEN: usize = st7789vwx6::WIDTH as usize / FONT_SMALL.glyph_width() as usize;
+/// Events on each display of events screen, two lines each
+const EVENTS_PER_DISPLAY: usize = DIAGNOSTICS_LINES / 2;
+/// Events shown on events screen, on all displays but the first one
+const EVENTS_SHOWN: usize = 5 * EVENTS_PER_DISPLAY;
+/// Times message scrolls across displays
+const MESSAGE_PASSES: u8 = 3;
+/// Letters displays come up with after boot, one per display
+const SPLASH_TEXT: &str = "LCD-RS";
+/// Version of firmware shown on diagnostics and about screens
+const FIRMWARE_VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Commit firmware was built from, set by build script
+const GIT_HASH: &str = env!("GIT_HASH");
+/// Date firmware was built on as YYYY-MM-DD, set by build script
+const BUILD_DATE: &str = env!("BUILD_DATE");
+/// Manual of the clock, linked from diagnostics screen
+const MANUAL_URL: &str = "https://www.waveshare.com/wiki/LCD-Clock-A";
+
+/// Duty of backlight PWM for brightness of state. Eye perceives lightness
+/// roughly as cube root of luminance, so levels follow CIE 1931 lightness
+/// curve to look evenly spaced.
+pub fn backlight_duty(brightness: u32) -> u16 {
+    BACKLIGHT_DUTIES[(brightness as usize).min(BACKLIGHT_DUTIES.len() - 1)]
+}
 
-    I2CClaim,
+/// Color displays and LEDs are filled with at given step of self test, None
+/// for report
+fn step_color(step: Step) -> Option<ColorRGB8> {
+    match step {
+        Step::Red => Some(ColorRGB8::red()),
+        Step::Green => Some(ColorRGB8::green()),
+        Step::Blue => Some(ColorRGB8::blue()),
+        Step::White => Some(ColorRGB8::white()),
+        Step::Report => None,
+    }
+}
+
+/// Writes milliseconds of timer as hours, minutes and seconds of uptime
+fn write_uptime(text: &mut String<DIAGNOSTICS_LINE_LEN>, ms: u32) {
+    let secs = ms / 1000;
+    write!(
+        text,
+        "{}:{:02}:{:02}",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+    .ok();
+}
+
+/// Writes millivolts as volts with two decimal places
+fn write_volts(text: &mut String<DIAGNOSTICS_LINE_LEN>, millivolts: u16) {
+    write!(text, "{}.{:02}V", millivolts / 1000, millivolts % 1000 / 10).ok();
+}
+
+/// Dim accent color of orbit and track of seconds arc
+fn track_color(accent: ColorRGB8) -> ColorRGB565 {
+    ColorRGB565::from(ColorRGB8 {
+        r: accent.r / 4,
+        g: accent.g / 4,
+        b: accent.b / 4,
+    })
+}
+
+/// Stand-in for angle of vector (x, y) clockwise from up, going from 0 to 4
+/// over full turn. Unlike angle itself it needs no atan2, and it is enough to
+/// compare angles.
+fn pseudo_angle(x: f32, y: f32) -> f32 {
+    let (abs_x, abs_y) = (if x < 0.0 { -x } else { x }, if y < 0.0 { -y } else { y });
+    if abs_x + abs_y == 0.0 {
+        return 0.0;
+    }
+    let t = abs_x / (abs_x + abs_y);
+    match (x >= 0.0, y > 0.0) {
+        (true, true) => t,
+        (true, false) => 2.0 - t,
+        (false, false) => 2.0 + t,
+        (false, true) => 4.0 - t,
+    }
+}
+
+/// Fills display with background gradient of menu and sensor screens
+fn fill_gradient(gl: &mut Gl, display: Display, theme: &Theme) -> Result<(), Error> {
+    let (from, to) = gradient_colors(theme);
+    gl.fill_gradient(display, from, to, GradientDirection::TopToBottom)
+}
+
+/// Color of background gradient at row y, used behind text drawn on it
+fn gradient_at(theme: &Theme, y: u16) -> ColorRGB565 {
+    let (from, to) = gradient_colors(theme);
+    from.lerp(to, y as u32, st7789vwx6::HEIGHT as u32 - 1)
+}
+
+fn gradient_colors(theme: &Theme) -> (ColorRGB565, ColorRGB565) {
+    let background = ColorRGB565::from(theme.background);
+    let (share, of) = GRADIENT_ACCENT;
+    let to = background.lerp(theme.accent.into(), share, of);
+    (background, to)
+}
+
+/// Draws transparent icon in the middle of display
+fn draw_icon(gl: &mut Gl, display: Display, icon: &Image) -> Result<(), Error> {
+    let x = (st7789vwx6::WIDTH - icon.width() as u16) / 2;
+    let y = (st7789vwx6::HEIGHT - icon.height() as u16) / 2;
+    gl.draw_pic_at(display, x, y, icon, None)
+}
+
+/// Draws minus sign of sensor value in the middle of display
+fn draw_minus_sign(gl: &mut Gl, display: Display, theme: &Theme) -> Result<(), Error> {
+    let (w, h) = MINUS_SIGN_SIZE;
+    let x = (st7789vwx6::WIDTH - w) / 2;
+    let y = (st7789vwx6::HEIGHT - h) / 2;
+    fill_gradient(gl, display, theme)?;
+    gl.draw_rect(display, x, y, x + w, y + h, ColorRGB8::white().into())
+}
+
+/// Draws lines of text from the top of display, centered horizontally
+fn draw_lines(gl: &mut Gl, display: Display, lines: &[(&str, TextStyle)]) -> Result<(), Error> {
+    let line_height = FONT_SMALL.glyph_height() as u16;
+    for (i, &(text, style)) in lines.iter().enumerate() {
+        let x = st7789vwx6::WIDTH.saturating_sub(style.font.text_width(text) as u16) / 2;
+        gl.draw_text(display, x, i as u16 * line_height, text, style)?;
+    }
+
+    Ok(())
 }
 
 fn time_to_display_values(time: Time) -> [u8; 6] {
@@ -337,13 +2752,60 @@ fn time_to_display_values(time: Time) -> [u8; 6] {
     [houra, hourb, mina, minb, seca, secb]
 }
 
-fn date_to_display_values(date: Date) -> [u8; 6] {
-    let yeara = (date.year % 100) / 10;
-    let yearb = date.year % 10;
-    let montha = date.month / 10;
-    let monthb = date.month % 10;
-    let datea = date.date / 10;
-    let dateb = date.date % 10;
+/// Charts span two displays
+const HISTORY_CHART_WIDTH: usize = 2 * st7789vwx6::WIDTH as usize;
+
+/// Stands in place of digit for minus sign of negative sensor value
+const MINUS_SIGN: u8 = 10;
+/// Minus sign is a bar across the middle of display
+const MINUS_SIGN_SIZE: (u16, u16) = (st7789vwx6::WIDTH / 2, 16);
+
+/// Splits value with 2 fractional digits into digits aligned to the right.
+/// Leading zeroes of integer part are left blank, negative value is preceded
+/// by [`MINUS_SIGN`].
+fn fixed_to_display_values(value: i32) -> [Option<u8>; 6] {
+    let mut result = [None; 6];
+    let mut magnitude = value.unsigned_abs();
+    let mut len = 0;
+    for (i, digit) in result.iter_mut().rev().enumerate() {
+        if magnitude == 0 && i > 2 {
+            break;
+        }
+
+        *digit = Some((magnitude % 10) as u8);
+        magnitude /= 10;
+        len = i + 1;
+    }
+    if value < 0 && len < result.len() {
+        result[result.len() - 1 - len] = Some(MINUS_SIGN);
+    }
+
+    result
+}
+
+/// Right aligned digits of value without leading zeroes
+fn integer_to_display_values(mut value: u32) -> [Option<u8>; 6] {
+    let mut result = [None; 6];
+    for digit in result.iter_mut().rev() {
+        *digit = Some((value % 10) as u8);
+        value /= 10;
+        if value == 0 {
+            break;
+        }
+    }
+
+    result
+}
+
+/// Two letters of weekdays starting from Sunday
+const WEEKDAYS: [&str; 7] = ["SU", "MO", "TU", "WE", "TH", "FR", "SA"];
+
+fn date_to_display_values(date: Date, format: DateFormat) -> [u8; 6] {
+    let [a, b, c] = format.parts().map(|part| match part {
+        DatePart::Year => (date.year % 100) as u8,
+        DatePart::Month => date.month,
+        DatePart::Day => date.date,
+    });
 
-    [yeara as u8, yearb as u8, montha, monthb, datea, dateb]
+    [a / 10, a % 10, b / 10, b % 10, c / 10, c % 10]
 }
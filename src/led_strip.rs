@@ -1,52 +1,183 @@
+//! Animations of ambient LED strip and optional LED ring, each running its
+//! own mode.
+//!
+//! Notifications (e.g. ringing alarm) temporarily override animation chosen
+//! by user, which keeps running underneath and is shown again once
+//! notification ends.
+//!
+//! At night colors can be tinted warm to cut blue light in bedrooms, see
+//! [`NightTint`].
+
+use serde::{Deserialize, Serialize};
+
 use crate::misc::{hsv2rgb_u8, ColorRGB8, Sin};
 
+/// LEDs of strip under displays
 pub const LED_COUNT: usize = 6;
+/// LEDs of optional ring, see [`pinout`](crate::pinout)
+pub const RING_LED_COUNT: usize = 12;
 const DEFAULT_BRIGHTNESS: u8 = 0x40;
+/// Hue candle flickers around, amber
+const CANDLE_HUE: f32 = 30.0;
+/// How far hue of candle drifts towards red as flame dims
+const CANDLE_HUE_SPREAD: f32 = 15.0;
+/// Flame never gets darker than this
+const CANDLE_MIN_LEVEL: f32 = 0.4;
+/// Sweeps of chase per period of animation
+const CHASE_SWEEPS: f32 = 4.0;
+/// LEDs lit behind head of chase
+const CHASE_TAIL: f32 = 3.0;
+/// Hue of silence in sound mode, green. Loud sound is red.
+const SOUND_QUIET_HUE: f32 = 120.0;
+/// Step of hue in solid mode, in degrees
+pub const HUE_STEP: i16 = 10;
+/// Strength of tint that replaces colors with amber entirely, in percent
+pub const MAX_TINT: u8 = 100;
+/// Color sunrise starts with, dim deep red
+const SUNRISE_RED: ColorRGB8 = ColorRGB8 {
+    r: 0x20,
+    g: 0x00,
+    b: 0x00,
+};
+/// Color sunrise ends with, bright warm white
+const SUNRISE_WHITE: ColorRGB8 = ColorRGB8 {
+    r: 0xff,
+    g: 0xc8,
+    b: 0x8c,
+};
 
 #[derive(Clone, Copy, Debug, Default)]
 pub enum LedMode {
     Off,
     #[default]
     Sin,
-    Red,
-    Green,
-    Blue,
-    Yellow,
-    Cyan,
-    Pink,
+    /// Warm flicker of candle
+    Candle,
+    /// Bright LED sweeping across strip with fading tail
+    Chase,
+    /// Brightness and color follow loudness picked up by microphone
+    Sound,
+    /// All LEDs have the same color of given hue, in degrees
+    Solid(u16),
+    /// Light of sunrise ahead of alarm after given percent of it, see
+    /// [`sunrise_color`]. Not chosen by user.
+    Sunrise(u8),
 }
 
 impl LedMode {
-    fn right(self) -> Self {
+    /// Solid mode gets given hue
+    fn right(self, hue: u16) -> Self {
         match self {
-            Self::Off => Self::Sin,
-            Self::Sin => Self::Red,
-            Self::Red => Self::Green,
-            Self::Green => Self::Blue,
-            Self::Blue => Self::Yellow,
-            Self::Yellow => Self::Cyan,
-            Self::Cyan => Self::Pink,
-            Self::Pink => Self::Off,
+            Self::Off | Self::Sunrise(_) => Self::Sin,
+            Self::Sin => Self::Candle,
+            Self::Candle => Self::Chase,
+            Self::Chase => Self::Sound,
+            Self::Sound => Self::Solid(hue),
+            Self::Solid(_) => Self::Off,
         }
     }
 
-    fn left(self) -> Self {
+    /// Solid mode gets given hue
+    fn left(self, hue: u16) -> Self {
         match self {
-            Self::Off => Self::Pink,
+            Self::Off | Self::Sunrise(_) => Self::Solid(hue),
             Self::Sin => Self::Off,
-            Self::Red => Self::Sin,
-            Self::Green => Self::Red,
-            Self::Blue => Self::Green,
-            Self::Yellow => Self::Blue,
-            Self::Cyan => Self::Yellow,
-            Self::Pink => Self::Cyan,
+            Self::Candle => Self::Sin,
+            Self::Chase => Self::Candle,
+            Self::Sound => Self::Chase,
+            Self::Solid(_) => Self::Sound,
+        }
+    }
+}
+
+/// LEDs driven by their own WS2812 output
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum LedOutput {
+    #[default]
+    Strip,
+    Ring,
+}
+
+/// How LEDs are lit during notification
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FlashPattern {
+    /// Lit all the time
+    Steady,
+    /// Lit and dark for given number of frames each
+    Blink(u16),
+    /// Fade in and out over given number of frames
+    Pulse(u16),
+    /// Hues of rainbow run along LEDs, going around once over given number
+    /// of frames. Color of notification is not used.
+    Rainbow(u16),
+}
+
+#[derive(Clone, Copy)]
+pub struct Notification {
+    pub pattern: FlashPattern,
+    pub color: ColorRGB8,
+    /// Frames notification lasts for, None if it lasts until dismissed
+    pub frames: Option<u32>,
+}
+
+/// Hours colors are blended towards amber during
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct NightTint {
+    /// Hour tint starts at
+    start: u8,
+    /// Hour tint ends at, may be on next day
+    end: u8,
+    /// Percent of the way to amber colors are blended
+    strength: u8,
+}
+
+impl NightTint {
+    /// Returns None if hours are out of range or the same, or strength is
+    /// not from 1 to [`MAX_TINT`]
+    pub fn new(start: u8, end: u8, strength: u8) -> Option<Self> {
+        let is_valid = start < 24 && end < 24 && start != end && (1..=MAX_TINT).contains(&strength);
+        is_valid.then_some(Self {
+            start,
+            end,
+            strength,
+        })
+    }
+
+    pub fn start(self) -> u8 {
+        self.start
+    }
+
+    pub fn end(self) -> u8 {
+        self.end
+    }
+
+    pub fn strength(self) -> u8 {
+        self.strength
+    }
+
+    /// Strength of tint at given hour, 0 outside of its hours
+    pub fn strength_at(self, hours: u8) -> u8 {
+        let is_active = match self.start < self.end {
+            true => (self.start..self.end).contains(&hours),
+            false => hours >= self.start || hours < self.end,
+        };
+        if is_active {
+            self.strength
+        } else {
+            0
         }
     }
 }
 
-pub struct LedStripState {
-    colors: [ColorRGB8; LED_COUNT],
+pub struct LedStripState<const N: usize = LED_COUNT> {
+    /// Colors of animation
+    colors: [ColorRGB8; N],
+    /// Colors shown, either of animation or of notification
+    shown: [ColorRGB8; N],
     mode: LedMode,
+    notification: Option<Notification>,
+    /// Frames passed since notification was shown
+    notification_frames: u32,
     transition: bool,
 
     sin: Sin,
@@ -54,18 +185,39 @@ pub struct LedStripState {
     brightness: u8,
     t: f32,
     animation_speed: f32,
+    /// Level of flame of every LED in candle mode, from 0 to 1
+    flame: [f32; N],
+    rng: Rng,
+    /// Is there microphone? If not, sound mode is skipped
+    has_microphone: bool,
+    /// Loudness picked up by microphone, from 0 to 1
+    loudness: f32,
+    /// Hue of solid mode, kept while other modes are shown
+    hue: u16,
+    /// Percent of the way to amber shown colors are blended, 0 if they are
+    /// not tinted
+    tint: u8,
 }
 
-impl LedStripState {
+impl<const N: usize> LedStripState<N> {
     pub fn new(sin: Sin) -> Self {
         Self {
-            colors: [Default::default(); LED_COUNT],
+            colors: [Default::default(); N],
+            shown: [Default::default(); N],
             mode: Default::default(),
+            notification: None,
+            notification_frames: 0,
             transition: false,
             sin,
             brightness: DEFAULT_BRIGHTNESS,
             t: 0.0,
             animation_speed: 0.1,
+            flame: [1.0; N],
+            rng: Rng::new(),
+            has_microphone: false,
+            loudness: 0.0,
+            hue: 0,
+            tint: 0,
         }
     }
 
@@ -73,35 +225,101 @@ impl LedStripState {
         self.mode
     }
 
+    pub fn set_mode(&mut self, mode: LedMode) {
+        if let LedMode::Solid(hue) = mode {
+            self.hue = hue;
+        }
+        self.mode = mode;
+        self.transition = true;
+    }
+
     pub fn left(&mut self) {
-        self.mode = self.mode.left();
+        self.mode = self.mode.left(self.hue);
+        if matches!(self.mode, LedMode::Sound) && !self.has_microphone {
+            self.mode = self.mode.left(self.hue);
+        }
         self.transition = true;
     }
 
     pub fn right(&mut self) {
-        self.mode = self.mode.right();
+        self.mode = self.mode.right(self.hue);
+        if matches!(self.mode, LedMode::Sound) && !self.has_microphone {
+            self.mode = self.mode.right(self.hue);
+        }
         self.transition = true;
     }
 
-    pub fn colors(&self) -> &[ColorRGB8; LED_COUNT] {
-        &self.colors
+    /// Hue of solid mode in degrees, even if other mode is shown
+    pub fn hue(&self) -> u16 {
+        self.hue
+    }
+
+    /// Sets hue of solid mode in degrees, hues past 360 wrap around
+    pub fn set_hue(&mut self, hue: u16) {
+        self.hue = hue % 360;
+        if let LedMode::Solid(_) = self.mode {
+            self.set_mode(LedMode::Solid(self.hue));
+        }
+    }
+
+    /// Moves hue of solid mode by given number of degrees. Does nothing if
+    /// other mode is shown.
+    pub fn shift_hue(&mut self, delta: i16) {
+        if let LedMode::Solid(hue) = self.mode {
+            self.set_hue((hue as i16 + delta).rem_euclid(360) as u16);
+        }
+    }
+
+    pub fn set_has_microphone(&mut self, has_microphone: bool) {
+        self.has_microphone = has_microphone;
+    }
+
+    /// Sets loudness shown in sound mode, from 0 to 1
+    pub fn set_loudness(&mut self, loudness: f32) {
+        self.loudness = loudness;
+    }
+
+    /// Sets strength of warm tint, from 0 (none) to [`MAX_TINT`]
+    pub fn set_tint(&mut self, tint: u8) {
+        self.tint = tint.min(MAX_TINT);
+    }
+
+    /// Colors shown, either of animation or of notification
+    #[allow(clippy::misnamed_getters)]
+    pub fn colors(&self) -> &[ColorRGB8; N] {
+        &self.shown
+    }
+
+    /// Shows notification instead of animation, replacing previous one
+    pub fn notify(&mut self, notification: Notification) {
+        self.notification = Some(notification);
+        self.notification_frames = 0;
+    }
+
+    /// Ends notification before its time, animation is shown again
+    pub fn dismiss(&mut self) {
+        self.notification = None;
+    }
+
+    pub fn notification(&self) -> Option<&Notification> {
+        self.notification.as_ref()
     }
 
     pub fn update(&mut self) {
         if self.transition {
             self.transition = false;
             let colors = match self.mode {
-                LedMode::Sin => {
+                LedMode::Sin | LedMode::Chase => {
                     self.t = 0.0;
-                    [Default::default(); LED_COUNT]
+                    [Default::default(); N]
+                }
+                LedMode::Candle => {
+                    self.flame = [1.0; N];
+                    [Default::default(); N]
                 }
-                LedMode::Off => [Default::default(); LED_COUNT],
-                LedMode::Red => [ColorRGB8::red(); LED_COUNT],
-                LedMode::Green => [ColorRGB8::green(); LED_COUNT],
-                LedMode::Blue => [ColorRGB8::blue(); LED_COUNT],
-                LedMode::Cyan => [ColorRGB8::cyan(); LED_COUNT],
-                LedMode::Yellow => [ColorRGB8::yellow(); LED_COUNT],
-                LedMode::Pink => [ColorRGB8::pink(); LED_COUNT],
+                LedMode::Sound | LedMode::Sunrise(_) => [Default::default(); N],
+                LedMode::Off => [Default::default(); N],
+                LedMode::Solid(hue) => [hsv2rgb_u8(hue as f32, 1.0, 1.0).into(); N],
             };
 
             self.colors = colors.map(|color| adjust_brightness(color, self.brightness));
@@ -128,12 +346,134 @@ impl LedStripState {
                 let rgb = hsv2rgb_u8(hue, sat, val);
                 *led = adjust_brightness(rgb.into(), self.brightness);
             }
+        }
+
+        if let LedMode::Chase = self.mode {
+            // f32::fract needs std
+            let sweep = self.t * CHASE_SWEEPS;
+            let head = (sweep - sweep as u32 as f32) * N as f32;
+            for (i, led) in self.colors.iter_mut().enumerate() {
+                // Distance behind head, wrapping around the end of strip
+                let mut distance = head - i as f32;
+                if distance < 0.0 {
+                    distance += N as f32;
+                }
+                let level = (1.0 - distance / CHASE_TAIL).max(0.0);
+                // Tail fades faster than linearly
+                let level = (level * level * 255.0) as u8;
+                let brightness = (self.brightness as u16 * level as u16 / 0xff) as u8;
+                *led = adjust_brightness(ColorRGB8::white(), brightness);
+            }
+        }
+
+        if let LedMode::Sound = self.mode {
+            let hue = SOUND_QUIET_HUE * (1.0 - self.loudness);
+            let rgb = hsv2rgb_u8(hue, 1.0, self.loudness);
+            self.colors = [adjust_brightness(rgb.into(), self.brightness); N];
+        }
 
+        // Sunrise is as bright as it gets, whatever brightness is
+        if let LedMode::Sunrise(percent) = self.mode {
+            self.colors = [sunrise_color(percent); N];
+        }
+
+        if matches!(self.mode, LedMode::Sin | LedMode::Chase) {
             self.t += (16.0 / 1000.0) * self.animation_speed;
             while self.t > 1.0 {
                 self.t -= 1.0;
             }
         }
+
+        if let LedMode::Candle = self.mode {
+            for (led, flame) in self.colors.iter_mut().zip(self.flame.iter_mut()) {
+                // Flame flares up now and then and settles down slowly, so
+                // that it doesn't look like noise
+                let target = CANDLE_MIN_LEVEL + (1.0 - CANDLE_MIN_LEVEL) * self.rng.next_f32();
+                let speed = if target > *flame { 0.5 } else { 0.1 };
+                *flame += (target - *flame) * speed;
+
+                // Dim flame is redder
+                let hue = CANDLE_HUE - CANDLE_HUE_SPREAD * (1.0 - *flame);
+                let rgb = hsv2rgb_u8(hue, 1.0, *flame);
+                *led = adjust_brightness(rgb.into(), self.brightness);
+            }
+        }
+
+        self.shown = self.colors;
+        if let Some(notification) = self.notification {
+            let frame = self.notification_frames;
+            let brightness = match notification.pattern {
+                FlashPattern::Steady | FlashPattern::Rainbow(_) => self.brightness,
+                FlashPattern::Blink(frames) => match frame / frames.max(1) as u32 % 2 {
+                    0 => self.brightness,
+                    _ => 0,
+                },
+                FlashPattern::Pulse(frames) => {
+                    let phase = frame as f32 / frames.max(1) as f32 * core::f32::consts::TAU;
+                    // Shifted by quarter of period, so that pulse starts dark
+                    let level = (1.0 - (self.sin)(phase + core::f32::consts::FRAC_PI_2)) * 0.5;
+                    (self.brightness as f32 * level) as u8
+                }
+            };
+            self.shown = match notification.pattern {
+                FlashPattern::Rainbow(frames) => {
+                    let frames = frames.max(1) as u32;
+                    let start = 360.0 * (frame % frames) as f32 / frames as f32;
+                    core::array::from_fn(|i| {
+                        let hue = (start + 360.0 * i as f32 / N as f32) % 360.0;
+                        adjust_brightness(hsv2rgb_u8(hue, 1.0, 1.0).into(), brightness)
+                    })
+                }
+                _ => [adjust_brightness(notification.color, brightness); N],
+            };
+
+            self.notification_frames += 1;
+            if notification
+                .frames
+                .is_some_and(|frames| self.notification_frames >= frames)
+            {
+                self.notification = None;
+            }
+        }
+
+        if self.tint > 0 {
+            self.shown = self.shown.map(|color| tint(color, self.tint));
+        }
+    }
+}
+
+/// Xorshift PRNG, good enough for animations
+struct Rng(u32);
+
+impl Rng {
+    fn new() -> Self {
+        // Any seed but 0 works
+        Self(0x2545_f491)
+    }
+
+    fn next_u32(&mut self) -> u32 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.0 = x;
+        x
+    }
+
+    /// Random number from 0 to 1
+    fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1 << 24) as f32
+    }
+}
+
+/// Color of sunrise after given percent of it, deep red turning orange and
+/// then bright warm white
+pub fn sunrise_color(percent: u8) -> ColorRGB8 {
+    let percent = percent.min(100) as u32;
+    if percent < 50 {
+        SUNRISE_RED.lerp(ColorRGB8::orange(), percent, 50)
+    } else {
+        ColorRGB8::orange().lerp(SUNRISE_WHITE, percent - 50, 50)
     }
 }
 
@@ -146,3 +486,11 @@ fn adjust_brightness(color: ColorRGB8, brightness: u8) -> ColorRGB8 {
 
     rgb.into()
 }
+
+/// Blends color towards amber of the same brightness, so tinted animations
+/// don't get brighter or darker
+fn tint(color: ColorRGB8, tint: u8) -> ColorRGB8 {
+    let level = color.r.max(color.g).max(color.b);
+    let amber = ColorRGB8::amber().scale(level as u32, 0xff);
+    color.lerp(amber, tint as u32, MAX_TINT as u32)
+}
@@ -0,0 +1,89 @@
+//! Firmware for [LCD-Nixie-Clock](https://www.waveshare.com/lcd-clock-a.htm)
+//! as a library.
+//!
+//! The binary is only a thin shell that brings up the board with
+//! [`hardware::setup`] and runs [`LcdClock`](lcd_clock::LcdClock) in a loop.
+//! Everything else lives here, so forks (different enclosures, display
+//! counts, wiring) can reuse most of the code and replace only the parts that
+//! differ.
+//!
+//! The crate is split into following layers:
+//! * [`drivers`] - drivers for chips found on the board. Generic
+//!   embedded-hal ones come from `lcd-clock-drivers` crate.
+//! * [`hardware`] - board layer. Concrete pin and peripheral types and
+//!   [`LcdClockHardware`](hardware::LcdClockHardware) that owns all drivers,
+//!   built by [`setup`](hardware::setup).
+//!   This is the module to swap when porting to other board.
+//! * [`pinout`] - GPIO assignment, the only module to change for a rewired
+//!   board.
+//! * [`diagnostics`] - peripherals found at init.
+//! * [`self_test`] - power-on self test of displays, LEDs and chips.
+//! * [`splash`] - animation displays come up with after boot.
+//! * [`event_log`] - recent events and errors of peripherals in RAM.
+//! * [`crash`] - report of panic or fault kept in RAM across reset.
+//! * [`state`] - state machine driven by buttons (Controller in MVC).
+//! * [`lcd_clock`] - screens drawn for each state (View in MVC).
+//! * [`scheduler`] - rates buttons, screens, LEDs and sensors are updated at.
+//! * [`gl`], [`images`], [`font`], [`qr`] - drawing helpers, embedded images,
+//!   fonts and QR codes.
+//! * [`marquee`] - text scrolled across all displays.
+//! * [`bell`], [`rtttl`] - melodies played on buzzer and their text format.
+//! * [`tick`] - second tick from rtc delivered by interrupt.
+//! * [`timer`] - monotonic milliseconds from system timer, e.g. for debounce.
+//! * [`local_time`] - time counted by system timer between reads of rtc.
+//! * [`time_util`] - calendar calculations, e.g. weekday and ISO week.
+//! * [`sun`] - sunrise and sunset at location of clock.
+//! * [`dimming`] - brightness of backlight and LEDs following time of day.
+//! * [`reminder`] - birthdays and anniversaries celebrated on their day.
+//! * [`dormant`] - deep sleep of RP2040 during night.
+//! * [`console`], [`command`] - text commands from host over USB serial.
+//! * [`gps`] - time from optional GPS module over UART.
+//! * [`microphone`] - loudness from optional microphone for LEDs.
+//! * [`settings`], [`flash`], [`theme`] - user settings persisted in flash or
+//!   EEPROM and color themes.
+//! * [`history`], [`climate_log`] - measurements of humidity sensor over last
+//!   day in RAM and over weeks in flash.
+//!
+//! Most commonly used items are reexported in [`prelude`].
+#![no_std]
+
+pub use rp_pico::hal;
+
+pub mod bell;
+pub mod climate_log;
+pub mod command;
+pub mod console;
+pub mod crash;
+pub mod diagnostics;
+pub mod dimming;
+pub mod dormant;
+pub mod drivers;
+pub mod event_log;
+pub mod flash;
+pub mod font;
+pub mod gl;
+pub mod gps;
+pub mod hardware;
+pub mod history;
+pub mod images;
+pub mod lcd_clock;
+pub mod led_strip;
+pub mod local_time;
+pub mod marquee;
+pub mod microphone;
+pub mod misc;
+pub mod pinout;
+pub mod prelude;
+pub mod qr;
+pub mod reminder;
+pub mod rtttl;
+pub mod scheduler;
+pub mod self_test;
+pub mod settings;
+pub mod splash;
+pub mod state;
+pub mod sun;
+pub mod theme;
+pub mod tick;
+pub mod time_util;
+pub mod timer;
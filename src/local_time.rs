@@ -0,0 +1,154 @@
+//! Time kept between reads of rtc by system timer.
+//!
+//! Rtc is read over I2C only once in a while, see [`SYNC_PERIOD_MS`]. In
+//! between, time is counted on from milliseconds of
+//! [`timer`](crate::timer), which also gives fraction of second for smooth
+//! animations. Ticks of rtc mark exact second boundaries, so they keep
+//! fraction in step with rtc without reading it. Rtc doesn't tell fraction
+//! of second it is read at, so time read at other moments is taken as
+//! unaligned and rtc is read again on the next tick.
+
+use crate::drivers::ds3231::{self, Date, Time};
+
+/// Rtc is read again this often to correct drift of system timer (10min)
+pub const SYNC_PERIOD_MS: u32 = 10 * 60 * 1000;
+const SECS_PER_DAY: u32 = 24 * 60 * 60;
+
+pub struct LocalTime {
+    /// Date and time at base_ms, None if rtc has to be read
+    base: Option<(Date, Time)>,
+    /// Timer at the start of second of base
+    base_ms: u32,
+    /// Was base read right at the start of second, i.e. on tick?
+    is_aligned: bool,
+    /// Timer when rtc was last read
+    synced_ms: u32,
+}
+
+impl LocalTime {
+    pub fn new() -> Self {
+        Self {
+            base: None,
+            base_ms: 0,
+            is_aligned: false,
+            synced_ms: 0,
+        }
+    }
+
+    /// Should rtc be read on tick at given time?
+    pub fn is_sync_due(&self, now_ms: u32) -> bool {
+        self.base.is_none()
+            || !self.is_aligned
+            || now_ms.wrapping_sub(self.synced_ms) >= SYNC_PERIOD_MS
+    }
+
+    /// Takes date and time read from rtc at given time. On tick second has
+    /// just started, otherwise its fraction is unknown and taken as zero.
+    pub fn sync(&mut self, date: Date, time: Time, now_ms: u32, on_tick: bool) {
+        self.base = Some((date, time));
+        self.base_ms = now_ms;
+        self.is_aligned = on_tick;
+        self.synced_ms = now_ms;
+    }
+
+    /// Forgets time, e.g. after rtc has been set
+    pub fn invalidate(&mut self) {
+        self.base = None;
+    }
+
+    /// Restarts fraction of second on tick of rtc. Timer may have drifted a
+    /// bit from rtc since the last tick, so whole seconds are rounded.
+    pub fn tick(&mut self, now_ms: u32) {
+        let Some((date, time)) = self.base else {
+            return;
+        };
+        let secs = (now_ms.wrapping_sub(self.base_ms) + 500) / 1000;
+        self.base = Some(add_secs(date, time, secs));
+        self.base_ms = now_ms;
+    }
+
+    /// Date, time and milliseconds into second at given time, None if rtc
+    /// has to be read first
+    pub fn now(&self, now_ms: u32) -> Option<(Date, Time, u16)> {
+        let (date, time) = self.base?;
+        let elapsed_ms = now_ms.wrapping_sub(self.base_ms);
+        let (date, time) = add_secs(date, time, elapsed_ms / 1000);
+        Some((date, time, (elapsed_ms % 1000) as u16))
+    }
+}
+
+impl Default for LocalTime {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Moves date and time by given number of seconds, carrying into days
+fn add_secs(date: Date, time: Time, secs: u32) -> (Date, Time) {
+    let secs_of_day = time.hours as u32 * 60 * 60 + time.mins as u32 * 60 + time.secs as u32;
+    let secs = secs_of_day + secs;
+    let days = (secs / SECS_PER_DAY).min(i16::MAX as u32) as i16;
+    let secs = secs % SECS_PER_DAY;
+    let time = Time {
+        hours: (secs / 60 / 60) as u8,
+        mins: (secs / 60 % 60) as u8,
+        secs: (secs % 60) as u8,
+    };
+    (ds3231::add_days(date, days), time)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DATE: Date = Date {
+        year: 2026,
+        month: 12,
+        date: 31,
+    };
+
+    fn time(hours: u8, mins: u8, secs: u8) -> Time {
+        Time { hours, mins, secs }
+    }
+
+    #[test]
+    fn test_now() {
+        let mut local_time = LocalTime::new();
+        assert!(local_time.now(0).is_none());
+        assert!(local_time.is_sync_due(0));
+
+        local_time.sync(DATE, time(23, 59, 58), 1000, true);
+        assert!(!local_time.is_sync_due(1000));
+        assert_eq!(local_time.now(1250), Some((DATE, time(23, 59, 58), 250)));
+
+        let next_year = Date {
+            year: 2027,
+            month: 1,
+            date: 1,
+        };
+        assert_eq!(local_time.now(3100), Some((next_year, time(0, 0, 0), 100)));
+        assert!(local_time.is_sync_due(1000 + SYNC_PERIOD_MS));
+    }
+
+    #[test]
+    fn test_tick() {
+        let mut local_time = LocalTime::new();
+        local_time.sync(DATE, time(12, 0, 0), 0, true);
+        // Timer runs a bit fast, tick restarts second
+        local_time.tick(990);
+        assert_eq!(local_time.now(990), Some((DATE, time(12, 0, 1), 0)));
+        local_time.tick(2000);
+        assert_eq!(local_time.now(2500), Some((DATE, time(12, 0, 2), 500)));
+    }
+
+    #[test]
+    fn test_unaligned() {
+        let mut local_time = LocalTime::new();
+        local_time.sync(DATE, time(12, 0, 0), u32::MAX - 100, false);
+        assert!(local_time.is_sync_due(u32::MAX));
+        assert_eq!(local_time.now(899), Some((DATE, time(12, 0, 1), 0)));
+
+        local_time.invalidate();
+        assert!(local_time.now(1000).is_none());
+    }
+}
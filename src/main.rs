@@ -1,11 +1,18 @@
+//! Entry point of the firmware. Board is brought up by
+//! [`hardware::setup`](lcd_clock::hardware::setup), the binary only routes
+//! interrupts and crashes and hands the board over to the library.
 #![no_std]
 #![no_main]
 
-use drivers::buttons::{Button, Debounce};
-use hardware::LcdClockHardware;
-use lcd_clock::LcdClock;
-#[cfg(not(feature = "semihosting"))]
-use panic_halt as _;
+use core::{fmt::Write, mem::MaybeUninit, ptr::addr_of_mut};
+
+use cortex_m::peripheral::SCB;
+use cortex_m_rt::{exception, ExceptionFrame};
+use lcd_clock::{
+    crash,
+    hardware::{self, BOOT_BRIGHTNESS},
+    prelude::*,
+};
 #[cfg(feature = "semihosting")]
 use panic_semihosting as _;
 
@@ -13,148 +20,93 @@ use panic_semihosting as _;
 #[macro_use]
 extern crate cortex_m_semihosting;
 
-use embedded_hal::spi::MODE_0;
-use fugit::*;
 use rp_pico::{
     entry,
     hal::{
         self,
-        clocks::{init_clocks_and_plls, Clock},
-        gpio,
-        pac::Peripherals,
-        pio::PIOExt,
-        spi::Spi,
-        watchdog::Watchdog,
-        Sio,
+        pac::{interrupt, Peripherals},
     },
-    Pins,
-};
-
-mod bell;
-mod drivers;
-mod gl;
-mod hardware;
-mod images;
-mod lcd_clock;
-mod led_strip;
-mod misc;
-mod state;
-
-use crate::drivers::{
-    st7789vwx6::{self, ST7789VWx6},
-    ws2812::WS2812,
 };
 
 #[entry]
 fn main() -> ! {
-    let mut dp = Peripherals::take().unwrap();
-
-    let mut wdg = Watchdog::new(dp.WATCHDOG);
-    let sio = Sio::new(dp.SIO);
-
-    let clocks = init_clocks_and_plls(
-        rp_pico::XOSC_CRYSTAL_FREQ,
-        dp.XOSC,
-        dp.CLOCKS,
-        dp.PLL_SYS,
-        dp.PLL_USB,
-        &mut dp.RESETS,
-        &mut wdg,
-    )
-    .ok()
-    .unwrap();
-
-    let pins = Pins::new(dp.IO_BANK0, dp.PADS_BANK0, sio.gpio_bank0, &mut dp.RESETS);
-    let pwm_slices = hal::pwm::Slices::new(dp.PWM, &mut dp.RESETS);
-
-    let i2c_bus = {
-        let sda = pins.gpio6.into_mode::<gpio::FunctionI2C>();
-        let scl = pins.gpio7.into_mode::<gpio::FunctionI2C>();
-        hal::I2C::i2c1(
-            dp.I2C1,
-            sda,
-            scl,
-            100u32.kHz(),
-            &mut dp.RESETS,
-            &clocks.peripheral_clock,
-        )
-    };
-
-    let brightness = 5;
-    let st7789vw = {
-        let csa1 = pins.gpio2.into_push_pull_output();
-        let csa2 = pins.gpio3.into_push_pull_output();
-        let csa3 = pins.gpio4.into_push_pull_output();
-        let dc = pins.gpio8.into_push_pull_output();
-        let rst = pins.gpio12.into_push_pull_output();
-        let _clk = pins.gpio9.into_mode::<gpio::FunctionSpi>();
-        let _miso = pins.gpio10.into_mode::<gpio::FunctionSpi>();
-        let _mosi = pins.gpio11.into_mode::<gpio::FunctionSpi>();
-        let bl = pins.gpio13.into_push_pull_output();
-
-        let mut pwm = pwm_slices.pwm6;
-        pwm.set_ph_correct();
-        pwm.enable();
-
-        let mut channel = pwm.channel_b;
-        channel.output_to(bl);
-
-        let spi = Spi::<_, _, 8>::new(dp.SPI1);
-        let spi = spi.init(
-            &mut dp.RESETS,
-            clocks.peripheral_clock.freq(),
-            40_000_000u32.Hz(),
-            &MODE_0,
-        );
-
-        ST7789VWx6::new(
-            (csa1, csa2, csa3, dc, rst),
-            spi,
-            channel,
-            st7789vwx6::WIDTH,
-            st7789vwx6::HEIGHT,
-            u16::MAX / 10 * brightness,
-        )
+    let dp = Peripherals::take().unwrap();
+    let hardware = hardware::setup(dp);
+    // Mode held while clock boots runs self test
+    let self_test = hardware.is_mode_held_at_boot();
+
+    let float_funcs = FloatFuncs {
+        sin: hal::rom_data::float_funcs::fsin::ptr(),
+        exp: hal::rom_data::float_funcs::fexp::ptr(),
+        ln: hal::rom_data::float_funcs::fln::ptr(),
+        atan2: hal::rom_data::float_funcs::fatan2::ptr(),
+        sqrt: hal::rom_data::float_funcs::fsqrt::ptr(),
     };
+    let mut lcd_clock = LcdClock::new(hardware, float_funcs, BOOT_BRIGHTNESS);
 
-    let ws2812 = {
-        let (mut pio, sm0, _, _, _) = dp.PIO0.split(&mut dp.RESETS);
-        let rgb = pins.gpio22.into_mode();
-        WS2812::new(rgb, &mut pio, sm0, clocks.peripheral_clock.freq()).unwrap()
-    };
-
-    let button_debounce_integrator = 2;
-    let button_right = Button::new(Debounce::new(
-        pins.gpio15.into_pull_down_input(),
-        button_debounce_integrator,
-    ));
-    let button_left = Button::new(Debounce::new(
-        pins.gpio16.into_pull_down_input(),
-        button_debounce_integrator,
-    ));
-    let button_mode = Button::new(Debounce::new(
-        pins.gpio17.into_pull_down_input(),
-        button_debounce_integrator,
-    ));
-
-    let hardware = LcdClockHardware::new(
-        i2c_bus,
-        st7789vw,
-        ws2812,
-        button_right,
-        button_left,
-        button_mode,
-        (),
-    );
-
-    let sin = hal::rom_data::float_funcs::fsin::ptr();
-    let mut lcd_clock = LcdClock::new(hardware, sin, brightness as u32);
-
-    // delay for 2ms so displays are initialized
-    cortex_m::asm::delay(125 * 1000 * 20);
+    // Peripherals that are slow to power up are retried by init
     lcd_clock.init().unwrap();
+    if let Some(report) = crash_record().take() {
+        lcd_clock.show_crash_report(report);
+    }
+    if self_test {
+        lcd_clock.start_self_test();
+    }
 
     loop {
-        lcd_clock.update().unwrap();
+        lcd_clock.update();
     }
 }
+
+/// Report of crash written before reset, see [`crash`]. RAM of `.uninit`
+/// isn't initialized at boot, so the record survives reset.
+#[link_section = ".uninit.CRASH"]
+static mut CRASH: MaybeUninit<crash::Record> = MaybeUninit::uninit();
+
+fn crash_record() -> &'static mut crash::Record {
+    // SAFETY: any content of RAM is valid record. It is only touched by main
+    // before the loop and by handlers that reset the chip.
+    unsafe { &mut *addr_of_mut!(CRASH).cast() }
+}
+
+/// Keeps message and location of panic for the next boot and resets
+#[cfg(not(feature = "semihosting"))]
+#[panic_handler]
+fn panic(info: &core::panic::PanicInfo) -> ! {
+    cortex_m::interrupt::disable();
+    let record = crash_record();
+    record.start(crash::Kind::Panic, 0);
+    write!(record, "{info}").ok();
+    record.finish();
+    SCB::sys_reset()
+}
+
+/// Keeps address of faulting instruction for the next boot and resets
+#[exception]
+unsafe fn HardFault(frame: &ExceptionFrame) -> ! {
+    let record = crash_record();
+    record.start(crash::Kind::HardFault, frame.pc());
+    record.finish();
+    SCB::sys_reset()
+}
+
+#[interrupt]
+fn IO_IRQ_BANK0() {
+    lcd_clock::tick::on_interrupt();
+}
+
+#[interrupt]
+fn USBCTRL_IRQ() {
+    lcd_clock::console::on_interrupt();
+}
+
+#[interrupt]
+fn UART0_IRQ() {
+    lcd_clock::gps::on_interrupt();
+}
+
+#[cfg(feature = "uart-console")]
+#[interrupt]
+fn UART1_IRQ() {
+    lcd_clock::console::on_uart_interrupt();
+}
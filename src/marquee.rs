@@ -0,0 +1,74 @@
+//! Text scrolled across all displays, e.g. messages sent over USB console.
+//!
+//! Displays are treated as one wide canvas, see
+//! [`Gl::draw_marquee`](crate::gl::Gl::draw_marquee). Text enters at the right
+//! edge of it and leaves at the left one.
+
+use core::ops::Range;
+
+use heapless::String;
+
+use crate::{
+    gl::{Gl, TextStyle},
+    lcd_clock::Error,
+};
+
+/// Longest text shown
+pub const MARQUEE_LEN: usize = 64;
+/// Pixels text moves by each frame
+const SPEED: i32 = 4;
+
+pub struct Marquee {
+    text: String<MARQUEE_LEN>,
+    /// Pixels scrolled since text entered canvas
+    scrolled: i32,
+    /// Columns of canvas covered by text when it was last drawn
+    drawn: Range<i32>,
+    passes_left: u8,
+}
+
+impl Marquee {
+    /// Text scrolls across canvas given number of times
+    pub fn new(text: String<MARQUEE_LEN>, passes: u8) -> Self {
+        Self {
+            text,
+            scrolled: 0,
+            drawn: 0..0,
+            passes_left: passes,
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.passes_left == 0
+    }
+
+    /// Forgets what was drawn, so the next frame is drawn in full. Canvas
+    /// has to be cleared before.
+    pub fn redraw(&mut self) {
+        self.drawn = 0..0;
+    }
+
+    /// Draws text on band of canvas starting at row y and moves it for the
+    /// next frame
+    pub fn draw(&mut self, gl: &mut Gl, y: u16, style: TextStyle) -> Result<(), Error> {
+        let canvas_width = gl.canvas_width() as i32;
+        let text_width = style.font.text_width(&self.text) as i32;
+        let x = canvas_width - self.scrolled;
+        let text = x..x + text_width;
+        // Columns text has just left are cleared
+        let columns = if self.drawn.is_empty() {
+            text.clone()
+        } else {
+            text.start.min(self.drawn.start)..text.end.max(self.drawn.end)
+        };
+        gl.draw_marquee(&self.text, x, y, columns, style)?;
+        self.drawn = text;
+
+        self.scrolled += SPEED;
+        if self.scrolled >= canvas_width + text_width {
+            self.scrolled = 0;
+            self.passes_left = self.passes_left.saturating_sub(1);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,62 @@
+//! Loudness picked up by optional microphone (e.g. MAX4466 module) on ADC.
+//!
+//! Microphone is sampled every millisecond together with buttons, see
+//! [`scheduler`](crate::scheduler). Samples go through envelope
+//! follower, which gives loudness that rises quickly with sound and decays
+//! slowly, so LEDs don't flicker with every wave of it.
+
+/// How fast envelope follows rising sound, per sample
+const ATTACK: f32 = 0.36;
+/// How fast envelope decays with silence, per sample (~0.25s)
+const RELEASE: f32 = 0.004;
+/// How fast DC level tracks mid-supply bias of microphone, per sample
+const DC_TRACKING: f32 = 0.002;
+/// Amplitude of raw sample treated as full loudness. Module outputs up to
+/// half of 12 bit range, but only shouting gets close to it.
+const FULL_SCALE: f32 = 1024.0;
+
+pub struct Envelope {
+    /// Bias of microphone output, removed from samples
+    dc: f32,
+    level: f32,
+}
+
+impl Envelope {
+    pub fn new() -> Self {
+        Self {
+            // Module is biased to half of supply
+            dc: 2048.0,
+            level: 0.0,
+        }
+    }
+
+    /// Feeds raw 12 bit ADC sample
+    pub fn sample(&mut self, raw: u16) {
+        let raw = raw as f32;
+        self.dc += (raw - self.dc) * DC_TRACKING;
+        let amplitude = raw - self.dc;
+        let amplitude = if amplitude < 0.0 {
+            -amplitude
+        } else {
+            amplitude
+        };
+
+        let speed = if amplitude > self.level {
+            ATTACK
+        } else {
+            RELEASE
+        };
+        self.level += (amplitude - self.level) * speed;
+    }
+
+    /// Loudness from 0 to 1
+    pub fn loudness(&self) -> f32 {
+        (self.level / FULL_SCALE).min(1.0)
+    }
+}
+
+impl Default for Envelope {
+    fn default() -> Self {
+        Self::new()
+    }
+}
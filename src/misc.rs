@@ -1,4 +1,36 @@
+//! Colors and other small helpers shared across modules.
+
 pub type Sin = extern "C" fn(f32) -> f32;
+pub type Exp = extern "C" fn(f32) -> f32;
+pub type Ln = extern "C" fn(f32) -> f32;
+pub type Atan2 = extern "C" fn(f32, f32) -> f32;
+pub type Sqrt = extern "C" fn(f32) -> f32;
+
+/// Float functions that are not available in core. On RP2040 they are taken
+/// from bootrom.
+#[derive(Clone, Copy)]
+pub struct FloatFuncs {
+    pub sin: Sin,
+    pub exp: Exp,
+    pub ln: Ln,
+    /// Angle of point (x, y), called with y first
+    pub atan2: Atan2,
+    pub sqrt: Sqrt,
+}
+
+impl FloatFuncs {
+    pub fn powf(&self, base: f32, exp: f32) -> f32 {
+        (self.exp)(exp * (self.ln)(base))
+    }
+
+    pub fn cos(&self, x: f32) -> f32 {
+        (self.sin)(x + core::f32::consts::FRAC_PI_2)
+    }
+
+    pub fn acos(&self, x: f32) -> f32 {
+        (self.atan2)((self.sqrt)(1.0 - x * x), x)
+    }
+}
 
 #[derive(Clone, Copy, Default)]
 pub struct ColorRGB8 {
@@ -24,7 +56,40 @@ impl From<ColorRGB8> for (u8, u8, u8) {
 }
 
 impl ColorRGB8 {
-    pub fn black() -> Self {
+    /// Color dimmed to step/steps of its brightness
+    pub fn scale(self, step: u32, steps: u32) -> Self {
+        let steps = steps.max(1);
+        let step = step.min(steps);
+        let channel = |value: u8| (value as u32 * step / steps) as u8;
+        Self {
+            r: channel(self.r),
+            g: channel(self.g),
+            b: channel(self.b),
+        }
+    }
+
+    /// Color step/steps of the way from self to other
+    pub fn lerp(self, other: Self, step: u32, steps: u32) -> Self {
+        let steps = steps.max(1) as i32;
+        let step = step.min(steps as u32) as i32;
+        let channel =
+            |from: u8, to: u8| (from as i32 + (to as i32 - from as i32) * step / steps) as u8;
+        Self {
+            r: channel(self.r, other.r),
+            g: channel(self.g, other.g),
+            b: channel(self.b, other.b),
+        }
+    }
+
+    pub const fn white() -> Self {
+        Self {
+            r: 0xff,
+            g: 0xff,
+            b: 0xff,
+        }
+    }
+
+    pub const fn black() -> Self {
         Self {
             r: 0x00,
             g: 0x00,
@@ -32,7 +97,7 @@ impl ColorRGB8 {
         }
     }
 
-    pub fn red() -> Self {
+    pub const fn red() -> Self {
         Self {
             r: 0xff,
             g: 0x00,
@@ -40,7 +105,7 @@ impl ColorRGB8 {
         }
     }
 
-    pub fn green() -> Self {
+    pub const fn green() -> Self {
         Self {
             r: 0x00,
             g: 0xff,
@@ -48,7 +113,7 @@ impl ColorRGB8 {
         }
     }
 
-    pub fn blue() -> Self {
+    pub const fn blue() -> Self {
         Self {
             r: 0x00,
             g: 0x00,
@@ -56,7 +121,7 @@ impl ColorRGB8 {
         }
     }
 
-    pub fn cyan() -> Self {
+    pub const fn cyan() -> Self {
         Self {
             r: 0x00,
             g: 0xff,
@@ -64,7 +129,7 @@ impl ColorRGB8 {
         }
     }
 
-    pub fn yellow() -> Self {
+    pub const fn yellow() -> Self {
         Self {
             r: 0xff,
             g: 0xff,
@@ -72,13 +137,30 @@ impl ColorRGB8 {
         }
     }
 
-    pub fn pink() -> Self {
+    pub const fn pink() -> Self {
         Self {
             r: 0xff,
             g: 0x00,
             b: 0xff,
         }
     }
+
+    pub const fn orange() -> Self {
+        Self {
+            r: 0xff,
+            g: 0x60,
+            b: 0x00,
+        }
+    }
+
+    /// Light of candle, about 2000K
+    pub const fn amber() -> Self {
+        Self {
+            r: 0xff,
+            g: 0x89,
+            b: 0x0e,
+        }
+    }
 }
 
 /// Stores color in RGB565 format (big endian) so it is more suitable for using
@@ -90,6 +172,19 @@ impl ColorRGB565 {
     pub fn to_be(self) -> [u8; 2] {
         self.0.to_be_bytes()
     }
+
+    /// Color step/steps of the way from self to other. Channels are
+    /// interpolated separately.
+    pub fn lerp(self, other: Self, step: u32, steps: u32) -> Self {
+        let steps = steps.max(1) as i32;
+        let step = step.min(steps as u32) as i32;
+        let channel = |shift: u32, mask: u16| {
+            let from = (self.0 >> shift & mask) as i32;
+            let to = (other.0 >> shift & mask) as i32;
+            ((from + (to - from) * step / steps) as u16) << shift
+        };
+        Self(channel(11, 0x1F) | channel(5, 0x3F) | channel(0, 0x1F))
+    }
 }
 
 impl From<u16> for ColorRGB565 {
@@ -0,0 +1,214 @@
+//! Pinout of the board. Every GPIO used by firmware is assigned here, so
+//! boards wired differently only change this module.
+//!
+//! Waveshare shipped revisions of the kit that differ in backlight wiring and
+//! order of displays on CS decoder. Revision is selected with `rev-a`
+//! (default) or `rev-b` feature.
+//!
+//! RP2040 routes SPI, I2C, UART and PWM only to certain pins, so blocks driving
+//! them are named here too. Moving e.g. backlight to other pin may need other
+//! PWM slice, and peripherals taken in [`setup`](crate::hardware::setup) have
+//! to match blocks chosen here.
+
+use rp_pico::Pins;
+
+use crate::{
+    drivers::st7789vwx6::{CsOrder, Display},
+    hal::{
+        gpio::{
+            bank0::{
+                Gpio0, Gpio1, Gpio10, Gpio11, Gpio12, Gpio15, Gpio16, Gpio17, Gpio18, Gpio19,
+                Gpio2, Gpio20, Gpio21, Gpio22, Gpio26, Gpio27, Gpio29, Gpio3, Gpio4, Gpio6, Gpio7,
+                Gpio8, Gpio9,
+            },
+            Pin, PinId,
+        },
+        pac::{I2C1, SPI1, UART0, UART1},
+        pwm::{self, Slice, SliceId, Slices},
+    },
+};
+
+#[cfg(all(feature = "rev-a", feature = "rev-b"))]
+compile_error!("features rev-a and rev-b are exclusive, build rev-b with --no-default-features");
+#[cfg(not(any(feature = "rev-a", feature = "rev-b")))]
+compile_error!("revision of the kit has to be selected with rev-a or rev-b feature");
+#[cfg(all(feature = "led-ring", feature = "uart-console"))]
+compile_error!("features led-ring and uart-console are exclusive, both use GPIO20");
+
+/// Pin in the mode it has after reset
+pub type ResetPin<I> = Pin<I, <I as PinId>::Reset>;
+/// PWM slice in the mode it has after reset
+pub type ResetSlice<S> = Slice<S, <S as SliceId>::Reset>;
+
+/// I2C bus of rtc, humidity sensor and EEPROM
+pub type I2CBlock = I2C1;
+pub type I2CSda = Gpio6;
+pub type I2CScl = Gpio7;
+
+/// Displays are selected by number on three CS lines, see
+/// [`st7789vwx6`](crate::drivers::st7789vwx6)
+pub type DisplayCsa1 = Gpio2;
+pub type DisplayCsa2 = Gpio3;
+pub type DisplayCsa3 = Gpio4;
+pub type DisplayDc = Gpio8;
+pub type DisplayRst = Gpio12;
+pub type DisplaySpi = SPI1;
+/// Hardware CSn of SPI, displays are selected by CS lines above instead
+pub type DisplayCsn = Gpio9;
+pub type DisplayClk = Gpio10;
+pub type DisplayMosi = Gpio11;
+/// Optional TE output of a display wired to spare pin, used on builds with
+/// `display-te` feature
+pub type DisplayTe = Gpio19;
+/// Display TE is taken from. Seconds change the most often, so it is the
+/// last one.
+pub const TE_DISPLAY: Display = Display::D6;
+/// Revision firmware is built for, shown on about screen
+#[cfg(feature = "rev-a")]
+pub const REVISION: &str = "REV A";
+#[cfg(feature = "rev-b")]
+pub const REVISION: &str = "REV B";
+
+/// Order of displays on CS decoder
+#[cfg(feature = "rev-a")]
+pub const CS_ORDER: CsOrder = CsOrder::Descending;
+#[cfg(feature = "rev-b")]
+pub const CS_ORDER: CsOrder = CsOrder::Ascending;
+
+/// Backlight of all displays, driven by PWM
+#[cfg(feature = "rev-a")]
+pub type Backlight = crate::hal::gpio::bank0::Gpio13;
+#[cfg(feature = "rev-a")]
+pub type BacklightSlice = pwm::Pwm6;
+#[cfg(feature = "rev-a")]
+pub type BacklightChannel = pwm::B;
+#[cfg(feature = "rev-b")]
+pub type Backlight = crate::hal::gpio::bank0::Gpio14;
+#[cfg(feature = "rev-b")]
+pub type BacklightSlice = pwm::Pwm7;
+#[cfg(feature = "rev-b")]
+pub type BacklightChannel = pwm::A;
+
+/// Data line of WS2812 LEDs
+pub type LedStrip = Gpio22;
+/// Data line of optional WS2812 ring on spare pin, fitted on builds with
+/// `led-ring` feature
+pub type LedRing = Gpio20;
+
+pub type LeftButton = Gpio15;
+pub type RightButton = Gpio16;
+pub type ModeButton = Gpio17;
+
+/// INT/SQW output of ds3231
+pub type RtcSqw = Gpio18;
+
+/// VSYS through divider on Pico
+pub type Vsys = Gpio29;
+/// Optional backup cell of rtc through divider
+pub type Backup = Gpio26;
+/// Optional microphone module
+pub type Microphone = Gpio27;
+
+/// Optional GPS module on spare pins
+pub type GpsUart = UART0;
+pub type GpsTx = Gpio0;
+pub type GpsRx = Gpio1;
+
+/// Optional command console on spare pins, used on builds with
+/// `uart-console` feature in place of LED ring. TX and RX of the board
+/// respectively.
+pub type ConsoleUart = UART1;
+pub type ConsoleTx = Gpio20;
+pub type ConsoleRx = Gpio21;
+
+/// Pins and PWM slices used by firmware, as they are after reset
+pub struct Pinout {
+    pub i2c_sda: ResetPin<I2CSda>,
+    pub i2c_scl: ResetPin<I2CScl>,
+    pub display_csa1: ResetPin<DisplayCsa1>,
+    pub display_csa2: ResetPin<DisplayCsa2>,
+    pub display_csa3: ResetPin<DisplayCsa3>,
+    pub display_dc: ResetPin<DisplayDc>,
+    pub display_rst: ResetPin<DisplayRst>,
+    pub display_csn: ResetPin<DisplayCsn>,
+    pub display_clk: ResetPin<DisplayClk>,
+    pub display_mosi: ResetPin<DisplayMosi>,
+    pub display_te: ResetPin<DisplayTe>,
+    pub backlight: ResetPin<Backlight>,
+    pub backlight_slice: ResetSlice<BacklightSlice>,
+    pub led_strip: ResetPin<LedStrip>,
+    #[cfg(not(feature = "uart-console"))]
+    pub led_ring: ResetPin<LedRing>,
+    pub left_button: ResetPin<LeftButton>,
+    pub right_button: ResetPin<RightButton>,
+    pub mode_button: ResetPin<ModeButton>,
+    pub rtc_sqw: ResetPin<RtcSqw>,
+    pub vsys: ResetPin<Vsys>,
+    pub backup: ResetPin<Backup>,
+    pub microphone: ResetPin<Microphone>,
+    pub gps_tx: ResetPin<GpsTx>,
+    pub gps_rx: ResetPin<GpsRx>,
+    #[cfg(feature = "uart-console")]
+    pub console_tx: ResetPin<ConsoleTx>,
+    #[cfg(feature = "uart-console")]
+    pub console_rx: ResetPin<ConsoleRx>,
+}
+
+impl Pinout {
+    /// Picks pins used by firmware out of all pins of Pico
+    pub fn new(pins: Pins, pwm_slices: Slices) -> Self {
+        Self {
+            i2c_sda: pins.gpio6,
+            i2c_scl: pins.gpio7,
+            display_csa1: pins.gpio2,
+            display_csa2: pins.gpio3,
+            display_csa3: pins.gpio4,
+            display_dc: pins.gpio8,
+            display_rst: pins.gpio12,
+            display_csn: pins.gpio9,
+            display_clk: pins.gpio10,
+            display_mosi: pins.gpio11,
+            display_te: pins.gpio19,
+            #[cfg(feature = "rev-a")]
+            backlight: pins.gpio13,
+            #[cfg(feature = "rev-a")]
+            backlight_slice: pwm_slices.pwm6,
+            #[cfg(feature = "rev-b")]
+            backlight: pins.gpio14,
+            #[cfg(feature = "rev-b")]
+            backlight_slice: pwm_slices.pwm7,
+            led_strip: pins.gpio22,
+            #[cfg(not(feature = "uart-console"))]
+            led_ring: pins.gpio20,
+            left_button: pins.gpio15,
+            right_button: pins.gpio16,
+            mode_button: pins.gpio17,
+            rtc_sqw: pins.gpio18,
+            vsys: pins.voltage_monitor,
+            backup: pins.gpio26,
+            microphone: pins.gpio27,
+            gps_tx: pins.gpio0,
+            gps_rx: pins.gpio1,
+            #[cfg(feature = "uart-console")]
+            console_tx: pins.gpio20,
+            #[cfg(feature = "uart-console")]
+            console_rx: pins.gpio21,
+        }
+    }
+}
+
+/// Channel of [`BacklightSlice`] that [`Backlight`] is routed to
+#[cfg(feature = "rev-a")]
+pub fn backlight_channel(
+    slice: Slice<BacklightSlice, pwm::FreeRunning>,
+) -> pwm::Channel<BacklightSlice, pwm::FreeRunning, BacklightChannel> {
+    slice.channel_b
+}
+
+/// Channel of [`BacklightSlice`] that [`Backlight`] is routed to
+#[cfg(feature = "rev-b")]
+pub fn backlight_channel(
+    slice: Slice<BacklightSlice, pwm::FreeRunning>,
+) -> pwm::Channel<BacklightSlice, pwm::FreeRunning, BacklightChannel> {
+    slice.channel_a
+}
@@ -0,0 +1,20 @@
+//! Reexports of items needed to bring up and run the clock.
+//!
+//! ```ignore
+//! use lcd_clock::prelude::*;
+//! ```
+
+pub use crate::{
+    drivers::{
+        buttons::{Button, ButtonEvent, Debounce},
+        shared_i2c::SharedI2C,
+        st7789vwx6::{self, Display, ST7789VWx6},
+        ws2812::{self, DmaChannels, WS2812},
+    },
+    gl::{BarStyle, Gl, TextStyle},
+    hardware::{I2CBusTy, LcdClockHardware},
+    lcd_clock::{backlight_duty, Error, LcdClock},
+    misc::{ColorRGB565, ColorRGB8, FloatFuncs, Sin},
+    pinout::Pinout,
+    state::{AppMode, State},
+};
@@ -0,0 +1,251 @@
+//! Tiny QR code encoder.
+//!
+//! Only what clock needs is supported: byte mode, low error correction level
+//! and versions 1 to 4, i.e. up to 78 bytes. These versions have a single
+//! block of error correction codewords and no version information. Mask is
+//! always the first one, readers handle any of them.
+
+const MAX_VERSION: usize = 4;
+/// Modules on a side of the largest code
+pub const MAX_SIZE: usize = 17 + 4 * MAX_VERSION;
+/// Longest data that fits the largest code
+pub const MAX_DATA_LEN: usize = DATA_CODEWORDS[MAX_VERSION - 1] - 2;
+/// Data codewords of each version at low error correction level
+const DATA_CODEWORDS: [usize; MAX_VERSION] = [19, 34, 55, 80];
+/// Error correction codewords of each version at low error correction level
+const EC_CODEWORDS: [usize; MAX_VERSION] = [7, 10, 15, 20];
+const MAX_CODEWORDS: usize = 100;
+/// Bits of low error correction level in format information
+const FORMAT_EC_LEVEL: u32 = 0b01;
+/// Mode indicator of byte mode
+const BYTE_MODE: u8 = 0b0100;
+/// Bytes padding data up to capacity
+const PAD_BYTES: [u8; 2] = [0xEC, 0x11];
+
+pub struct QrCode {
+    size: usize,
+    /// Rows of modules, bit x of row is set for dark module
+    modules: [u64; MAX_SIZE],
+}
+
+impl QrCode {
+    /// Encodes bytes into the smallest code that fits them. Returns None if
+    /// data is too long.
+    pub fn encode(data: &[u8]) -> Option<Self> {
+        // Mode indicator and length take 12 bits, terminator up to 4
+        let version = (1..=MAX_VERSION).find(|&v| data.len() + 2 <= DATA_CODEWORDS[v - 1])?;
+        let mut qr = Self {
+            size: 17 + 4 * version,
+            modules: [0; MAX_SIZE],
+        };
+        // Modules of patterns, which data skips and mask leaves alone
+        let mut function = [0u64; MAX_SIZE];
+
+        qr.draw_function_patterns(version, &mut function);
+        let mut codewords = [0u8; MAX_CODEWORDS];
+        let len = encode_codewords(data, version, &mut codewords);
+        qr.draw_codewords(&codewords[..len], &function);
+        qr.apply_mask(&function);
+        qr.draw_format(&mut function);
+        Some(qr)
+    }
+
+    /// Modules on a side
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Is module at column x and row y dark? Modules outside of code are
+    /// light.
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        x < self.size && y < self.size && self.modules[y] >> x & 1 != 0
+    }
+
+    fn set(&mut self, x: usize, y: usize, is_dark: bool) {
+        if is_dark {
+            self.modules[y] |= 1 << x;
+        } else {
+            self.modules[y] &= !(1 << x);
+        }
+    }
+
+    fn set_function(&mut self, function: &mut [u64; MAX_SIZE], x: usize, y: usize, is_dark: bool) {
+        self.set(x, y, is_dark);
+        function[y] |= 1 << x;
+    }
+
+    fn draw_function_patterns(&mut self, version: usize, function: &mut [u64; MAX_SIZE]) {
+        let size = self.size;
+        // Timing patterns, partly covered by finders
+        for i in 0..size {
+            self.set_function(function, 6, i, i % 2 == 0);
+            self.set_function(function, i, 6, i % 2 == 0);
+        }
+
+        // Finders with separators around them
+        for (x, y) in [(3, 3), (size - 4, 3), (3, size - 4)] {
+            for dy in -4..=4 {
+                for dx in -4..=4 {
+                    let (Some(x), Some(y)) = (x.checked_add_signed(dx), y.checked_add_signed(dy))
+                    else {
+                        continue;
+                    };
+                    if x < size && y < size {
+                        let dist = dx.abs().max(dy.abs());
+                        self.set_function(function, x, y, dist != 2 && dist != 4);
+                    }
+                }
+            }
+        }
+
+        // The only alignment pattern of versions 2 to 4
+        if version > 1 {
+            let center = size - 7;
+            for dy in -2..=2 {
+                for dx in -2..=2 {
+                    let x = center.wrapping_add_signed(dx);
+                    let y = center.wrapping_add_signed(dy);
+                    self.set_function(function, x, y, dx.abs().max(dy.abs()) != 1);
+                }
+            }
+        }
+
+        // Format information is drawn after mask, its modules are only
+        // reserved here
+        self.draw_format(function);
+    }
+
+    /// Draws both copies of format information for mask 0
+    fn draw_format(&mut self, function: &mut [u64; MAX_SIZE]) {
+        let size = self.size;
+        let data = FORMAT_EC_LEVEL << 3;
+        let mut rem = data;
+        for _ in 0..10 {
+            rem = (rem << 1) ^ ((rem >> 9) * 0x537);
+        }
+        let bits = (data << 10 | rem) ^ 0x5412;
+        let bit = |i: usize| bits >> i & 1 != 0;
+
+        // Around top left finder
+        for i in 0..=5 {
+            self.set_function(function, 8, i, bit(i));
+        }
+        self.set_function(function, 8, 7, bit(6));
+        self.set_function(function, 8, 8, bit(7));
+        self.set_function(function, 7, 8, bit(8));
+        for i in 9..15 {
+            self.set_function(function, 14 - i, 8, bit(i));
+        }
+
+        // Next to the other finders
+        for i in 0..8 {
+            self.set_function(function, size - 1 - i, 8, bit(i));
+        }
+        for i in 8..15 {
+            self.set_function(function, 8, size - 15 + i, bit(i));
+        }
+        // Always dark
+        self.set_function(function, 8, size - 8, true);
+    }
+
+    /// Places codewords in zigzag of column pairs from bottom right corner
+    fn draw_codewords(&mut self, codewords: &[u8], function: &[u64; MAX_SIZE]) {
+        let size = self.size;
+        let mut i = 0;
+        let mut right = size - 1;
+        loop {
+            // Vertical timing pattern is skipped as a whole column
+            if right == 6 {
+                right = 5;
+            }
+            let is_upward = (right + 1) & 2 == 0;
+            for vert in 0..size {
+                let y = if is_upward { size - 1 - vert } else { vert };
+                for x in [right, right - 1] {
+                    if function[y] >> x & 1 == 0 && i < codewords.len() * 8 {
+                        self.set(x, y, codewords[i / 8] >> (7 - i % 8) & 1 != 0);
+                        i += 1;
+                    }
+                }
+            }
+            if right < 3 {
+                break;
+            }
+            right -= 2;
+        }
+    }
+
+    /// Inverts modules of data where (x + y) is even
+    fn apply_mask(&mut self, function: &[u64; MAX_SIZE]) {
+        for (y, (row, &reserved)) in self.modules.iter_mut().zip(function).enumerate() {
+            for x in 0..self.size {
+                if reserved >> x & 1 == 0 && (x + y) % 2 == 0 {
+                    *row ^= 1 << x;
+                }
+            }
+        }
+    }
+}
+
+/// Writes data and error correction codewords, returns their number
+fn encode_codewords(data: &[u8], version: usize, codewords: &mut [u8; MAX_CODEWORDS]) -> usize {
+    let data_len = DATA_CODEWORDS[version - 1];
+    let ec_len = EC_CODEWORDS[version - 1];
+
+    // Mode indicator and length are 12 bits, so data is shifted by half of
+    // byte. Terminator and padding to byte boundary are zeros left in place.
+    codewords[0] = BYTE_MODE << 4 | (data.len() >> 4) as u8;
+    codewords[1] = (data.len() << 4) as u8;
+    for (i, &byte) in data.iter().enumerate() {
+        codewords[1 + i] |= byte >> 4;
+        codewords[2 + i] = byte << 4;
+    }
+    for (i, codeword) in codewords[data.len() + 2..data_len].iter_mut().enumerate() {
+        *codeword = PAD_BYTES[i % 2];
+    }
+
+    let mut divisor = [0u8; MAX_CODEWORDS];
+    let divisor = reed_solomon_divisor(&mut divisor[..ec_len]);
+    let (data, ec) = codewords[..data_len + ec_len].split_at_mut(data_len);
+    reed_solomon_remainder(data, divisor, ec);
+    data_len + ec_len
+}
+
+/// Generator polynomial of given degree, leading coefficient is omitted
+fn reed_solomon_divisor(divisor: &mut [u8]) -> &[u8] {
+    let degree = divisor.len();
+    divisor[degree - 1] = 1;
+    let mut root = 1;
+    for _ in 0..degree {
+        for j in 0..degree {
+            divisor[j] = gf_mul(divisor[j], root);
+            if j + 1 < degree {
+                divisor[j] ^= divisor[j + 1];
+            }
+        }
+        root = gf_mul(root, 0x02);
+    }
+    divisor
+}
+
+fn reed_solomon_remainder(data: &[u8], divisor: &[u8], remainder: &mut [u8]) {
+    remainder.fill(0);
+    for &byte in data {
+        let factor = byte ^ remainder[0];
+        remainder.rotate_left(1);
+        remainder[remainder.len() - 1] = 0;
+        for (rem, &coef) in remainder.iter_mut().zip(divisor) {
+            *rem ^= gf_mul(coef, factor);
+        }
+    }
+}
+
+/// Product in GF(2^8) modulo x^8 + x^4 + x^3 + x^2 + 1
+fn gf_mul(x: u8, y: u8) -> u8 {
+    let mut z: u16 = 0;
+    for i in (0..8).rev() {
+        z = (z << 1) ^ ((z >> 7) * 0x11D);
+        z ^= ((y as u16 >> i) & 1) * x as u16;
+    }
+    z as u8
+}
@@ -0,0 +1,113 @@
+//! Birthdays and anniversaries, dates of year celebrated by clock.
+//!
+//! Reminders are kept in settings and set over USB or in menu. On their day
+//! time screen is marked with gift icon and LEDs play rainbow every hour.
+
+use serde::{Deserialize, Serialize};
+
+use crate::drivers::ds3231::{days_in_month, Date};
+
+/// Reminders that fit in settings
+pub const MAX_REMINDERS: usize = 4;
+/// Leap year, so that February 29 can be set
+const ANY_LEAP_YEAR: u16 = 2000;
+
+/// Day of year repeating every year
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Reminder {
+    month: u8,
+    day: u8,
+}
+
+impl Reminder {
+    /// Returns None if there is no such day in leap year
+    pub fn new(month: u8, day: u8) -> Option<Self> {
+        let is_valid = (1..=12).contains(&month) && (1..=last_day(month)).contains(&day);
+        is_valid.then_some(Self { month, day })
+    }
+
+    pub fn month(self) -> u8 {
+        self.month
+    }
+
+    pub fn day(self) -> u8 {
+        self.day
+    }
+
+    /// Is reminder due on date? February 29 is celebrated on February 28 of
+    /// common years.
+    pub fn is_due(self, date: Date) -> bool {
+        let day = self.day.min(days_in_month(date.year, self.month));
+        date.month == self.month && date.date == day
+    }
+
+    /// Moves day by given number of days, wrapping around within month
+    pub fn step_day(self, delta: i8) -> Self {
+        let days = last_day(self.month) as i16;
+        let day = (self.day as i16 - 1 + delta as i16).rem_euclid(days) + 1;
+        Self {
+            day: day as u8,
+            ..self
+        }
+    }
+}
+
+/// Moves month of reminder by given number of months, keeping day if month
+/// has it. Months wrap around through no reminder, so it can be both set and
+/// cleared. New reminder starts on the first day of month.
+pub fn step_month(reminder: Option<Reminder>, delta: i8) -> Option<Reminder> {
+    // 0 stands for no reminder
+    let month = reminder.map_or(0, Reminder::month) as i16;
+    let month = (month + delta as i16).rem_euclid(13) as u8;
+    let day = reminder
+        .map_or(1, Reminder::day)
+        .min(last_day(month.max(1)));
+    Reminder::new(month, day)
+}
+
+fn last_day(month: u8) -> u8 {
+    days_in_month(ANY_LEAP_YEAR, month)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: u16, month: u8, date: u8) -> Date {
+        Date { year, month, date }
+    }
+
+    #[test]
+    fn test_new() {
+        assert!(Reminder::new(2, 29).is_some());
+        assert!(Reminder::new(4, 31).is_none());
+        assert!(Reminder::new(13, 1).is_none());
+        assert!(Reminder::new(1, 0).is_none());
+    }
+
+    #[test]
+    fn test_is_due() {
+        let reminder = Reminder::new(10, 16).unwrap();
+        assert!(reminder.is_due(date(2026, 10, 16)));
+        assert!(!reminder.is_due(date(2026, 10, 17)));
+        assert!(!reminder.is_due(date(2026, 9, 16)));
+
+        let leap_day = Reminder::new(2, 29).unwrap();
+        assert!(leap_day.is_due(date(2024, 2, 29)));
+        assert!(!leap_day.is_due(date(2024, 2, 28)));
+        assert!(leap_day.is_due(date(2025, 2, 28)));
+    }
+
+    #[test]
+    fn test_step() {
+        let reminder = Reminder::new(1, 31).unwrap();
+        assert_eq!(reminder.step_day(1), Reminder::new(1, 1).unwrap());
+        assert_eq!(reminder.step_day(-1), Reminder::new(1, 30).unwrap());
+
+        assert_eq!(step_month(Some(reminder), 1), Reminder::new(2, 29));
+        assert_eq!(step_month(Some(reminder), -1), None);
+        assert_eq!(step_month(None, 1), Reminder::new(1, 1));
+        assert_eq!(step_month(None, -1), Reminder::new(12, 1));
+        assert_eq!(step_month(Reminder::new(12, 25), 1), None);
+    }
+}
@@ -0,0 +1,162 @@
+//! Parser of RTTTL, text format of Nokia ringtones.
+//!
+//! Ringtone is a line of three sections separated by colons: name, defaults
+//! and notes, e.g. `beep:d=4,o=5,b=120:c,8e,g.,2p,c6`. Defaults set duration
+//! (`d`), octave (`o`) and tempo in quarter notes per minute (`b`) of notes
+//! that don't specify them. Each note is
+//! `[duration]<a-g|p>[#][.][octave][.]`, where `p` is a pause and dot makes
+//! note half as long again.
+
+use heapless::Vec;
+
+/// Notes in longest melody, enough for most ringtones
+pub const MAX_NOTES: usize = 64;
+
+/// Frequencies of 4th octave in Hz, starting from C
+const OCTAVE4: [u32; 12] = [262, 277, 294, 311, 330, 349, 370, 392, 415, 440, 466, 494];
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Note {
+    /// Frequency in Hz, 0 for pause
+    pub freq: u16,
+    pub millis: u16,
+}
+
+pub type Melody = Vec<Note, MAX_NOTES>;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ParseError {
+    MissingSection,
+    InvalidDefault,
+    InvalidNote,
+    TooManyNotes,
+}
+
+impl ParseError {
+    pub fn message(self) -> &'static str {
+        match self {
+            Self::MissingSection => "expected name:defaults:notes",
+            Self::InvalidDefault => "invalid default, expected d=, o= or b=",
+            Self::InvalidNote => "invalid note",
+            Self::TooManyNotes => "too many notes",
+        }
+    }
+}
+
+/// Values of notes that don't specify them
+struct Defaults {
+    duration: u32,
+    octave: u32,
+    bpm: u32,
+}
+
+/// Parses ringtone into its notes
+pub fn parse(text: &str) -> Result<Melody, ParseError> {
+    let mut sections = text.trim().splitn(3, ':');
+    let _name = sections.next();
+    let defaults = sections.next().ok_or(ParseError::MissingSection)?;
+    let notes = sections.next().ok_or(ParseError::MissingSection)?;
+
+    // Defaults of RTTTL specification
+    let mut values = Defaults {
+        duration: 4,
+        octave: 6,
+        bpm: 63,
+    };
+    for default in defaults
+        .split(',')
+        .map(str::trim)
+        .filter(|it| !it.is_empty())
+    {
+        let (key, value) = default.split_once('=').ok_or(ParseError::InvalidDefault)?;
+        let value = value.trim();
+        let is_valid = match key.trim() {
+            "d" => parse_duration(value).map(|it| values.duration = it),
+            "o" => parse_octave(value).map(|it| values.octave = it),
+            "b" => parse_bpm(value).map(|it| values.bpm = it),
+            _ => None,
+        };
+        is_valid.ok_or(ParseError::InvalidDefault)?;
+    }
+
+    let mut melody = Melody::new();
+    for note in notes.split(',').map(str::trim).filter(|it| !it.is_empty()) {
+        let note = parse_note(note, &values).ok_or(ParseError::InvalidNote)?;
+        melody.push(note).map_err(|_| ParseError::TooManyNotes)?;
+    }
+    Ok(melody)
+}
+
+fn parse_note(text: &str, defaults: &Defaults) -> Option<Note> {
+    let digits = text.bytes().take_while(u8::is_ascii_digit).count();
+    let duration = match digits {
+        0 => defaults.duration,
+        _ => parse_duration(&text[..digits])?,
+    };
+
+    let mut bytes = text[digits..].bytes().peekable();
+    let semitone = match bytes.next()?.to_ascii_lowercase() {
+        b'c' => Some(0),
+        b'd' => Some(2),
+        b'e' => Some(4),
+        b'f' => Some(5),
+        b'g' => Some(7),
+        b'a' => Some(9),
+        // German notation calls B "h"
+        b'b' | b'h' => Some(11),
+        b'p' => None,
+        _ => return None,
+    };
+    let is_sharp = bytes.next_if_eq(&b'#').is_some();
+    // Dot is found both before and after octave
+    let mut is_dotted = bytes.next_if_eq(&b'.').is_some();
+    let octave = match bytes.next_if(u8::is_ascii_digit) {
+        Some(digit) => parse_octave(core::str::from_utf8(&[digit]).ok()?)?,
+        None => defaults.octave,
+    };
+    is_dotted |= bytes.next_if_eq(&b'.').is_some();
+    if bytes.next().is_some() {
+        return None;
+    }
+
+    let freq = match semitone {
+        Some(semitone) => {
+            let semitone = semitone + is_sharp as usize;
+            // B# is C of next octave
+            let (semitone, octave) = (semitone % 12, octave + semitone as u32 / 12);
+            if octave >= 4 {
+                OCTAVE4[semitone] << (octave - 4)
+            } else {
+                OCTAVE4[semitone] >> (4 - octave)
+            }
+        }
+        None => 0,
+    };
+    let mut millis = 4 * 60_000 / (defaults.bpm * duration);
+    if is_dotted {
+        millis = millis * 3 / 2;
+    }
+    Some(Note {
+        freq: freq as u16,
+        millis: millis as u16,
+    })
+}
+
+/// Parses note duration as fraction of whole note
+fn parse_duration(text: &str) -> Option<u32> {
+    let duration = text.parse().ok()?;
+    matches!(duration, 1 | 2 | 4 | 8 | 16 | 32).then_some(duration)
+}
+
+/// Parses octave, 4th starts with middle C
+fn parse_octave(text: &str) -> Option<u32> {
+    let octave = text.parse().ok()?;
+    (3..=8).contains(&octave).then_some(octave)
+}
+
+/// Parses tempo, quarter notes per minute. Range of RTTTL specification keeps
+/// dotted whole note within u16 milliseconds.
+fn parse_bpm(text: &str) -> Option<u32> {
+    let bpm = text.parse().ok()?;
+    (25..=900).contains(&bpm).then_some(bpm)
+}
@@ -0,0 +1,121 @@
+//! Rates subsystems of main loop run at.
+//!
+//! Each [`Task`] has a period of its own and runs once it is due according to
+//! [`timer`](crate::timer), so buttons are polled far more often than LEDs are
+//! animated, and supply voltages far less often. Displays are drawn with
+//! frames of LEDs, but only what has changed is redrawn. Main loop waits out
+//! the time until the next task is due.
+
+/// Subsystems of main loop
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Task {
+    /// Buttons and microphone (1kHz)
+    Input,
+    /// State, screens and LEDs (~60Hz)
+    Frame,
+    /// Humidity sensor on its screen and history (1Hz)
+    Sensor,
+    /// Supply voltages (1/min)
+    Power,
+    /// Burn-in prevention (1/5min)
+    PixelShift,
+}
+
+const TASK_COUNT: usize = 5;
+/// Period of each task in milliseconds, in order of [`Task`]
+const PERIODS_MS: [u32; TASK_COUNT] = [1, 16, 1000, 60 * 1000, 5 * 60 * 1000];
+
+pub struct Scheduler {
+    /// Timer at which each task is due next
+    due_ms: [u32; TASK_COUNT],
+}
+
+impl Scheduler {
+    /// All tasks are due right away
+    pub fn new(now_ms: u32) -> Self {
+        Self {
+            due_ms: [now_ms; TASK_COUNT],
+        }
+    }
+
+    /// Is task due at given time? If it is, it is scheduled for the next
+    /// period. Task that has fallen behind by more than a period skips runs
+    /// it missed instead of catching up with them.
+    pub fn take(&mut self, task: Task, now_ms: u32) -> bool {
+        let due_ms = &mut self.due_ms[task as usize];
+        if !is_reached(*due_ms, now_ms) {
+            return false;
+        }
+
+        let period_ms = PERIODS_MS[task as usize];
+        *due_ms = due_ms.wrapping_add(period_ms);
+        if is_reached(*due_ms, now_ms) {
+            *due_ms = now_ms.wrapping_add(period_ms);
+        }
+        true
+    }
+
+    /// Milliseconds main loop can wait before the next task is due
+    pub fn ms_until_due(&self, now_ms: u32) -> u32 {
+        self.due_ms
+            .iter()
+            .map(|&due_ms| {
+                if is_reached(due_ms, now_ms) {
+                    0
+                } else {
+                    due_ms.wrapping_sub(now_ms)
+                }
+            })
+            .min()
+            .unwrap_or(0)
+    }
+}
+
+/// Has timer reached given time? Timer wraps around, so times are compared
+/// by their difference.
+fn is_reached(due_ms: u32, now_ms: u32) -> bool {
+    (now_ms.wrapping_sub(due_ms) as i32) >= 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_take() {
+        let mut scheduler = Scheduler::new(1000);
+        assert!(scheduler.take(Task::Frame, 1000));
+        assert!(!scheduler.take(Task::Frame, 1000));
+        assert!(!scheduler.take(Task::Frame, 1015));
+        // Late run doesn't delay the next one
+        assert!(scheduler.take(Task::Frame, 1018));
+        assert!(scheduler.take(Task::Frame, 1032));
+        // Missed runs are skipped
+        assert!(scheduler.take(Task::Frame, 1100));
+        assert!(!scheduler.take(Task::Frame, 1115));
+        assert!(scheduler.take(Task::Frame, 1116));
+    }
+
+    #[test]
+    fn test_ms_until_due() {
+        let mut scheduler = Scheduler::new(u32::MAX - 5);
+        assert_eq!(scheduler.ms_until_due(u32::MAX - 5), 0);
+        for task in [
+            Task::Input,
+            Task::Frame,
+            Task::Sensor,
+            Task::Power,
+            Task::PixelShift,
+        ] {
+            assert!(scheduler.take(task, u32::MAX - 5));
+        }
+        assert_eq!(scheduler.ms_until_due(u32::MAX - 5), 1);
+
+        assert!(scheduler.take(Task::Input, u32::MAX - 4));
+        assert_eq!(scheduler.ms_until_due(u32::MAX - 4), 1);
+        // Frame is due after timer wraps around
+        assert!(scheduler.take(Task::Input, 10));
+        assert_eq!(scheduler.ms_until_due(10), 0);
+        assert!(scheduler.take(Task::Frame, 10));
+    }
+}
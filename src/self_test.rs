@@ -0,0 +1,80 @@
+//! Power-on self test, run by holding mode button while clock boots.
+//!
+//! Every display is filled with red, green, blue and white for a second
+//! each, and LEDs step through the same colors, so dead pixels, swapped
+//! channels and dark LEDs stand out. Then buzzer beeps and each peripheral is
+//! reported as passed or failed until left or right is pressed. Drawing is
+//! done by [`LcdClock`](crate::lcd_clock::LcdClock), this module only keeps
+//! track of steps.
+
+/// Frames each pattern is shown for (~1s)
+const PATTERN_FRAMES: u32 = 60;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Step {
+    Red,
+    Green,
+    Blue,
+    White,
+    /// Pass or fail of each peripheral, shown until test is left
+    Report,
+}
+
+/// Patterns in order they are shown, report follows them
+const PATTERNS: [Step; 4] = [Step::Red, Step::Green, Step::Blue, Step::White];
+
+pub struct SelfTest {
+    /// Frames passed since test has started
+    frames: u32,
+}
+
+impl SelfTest {
+    pub fn new() -> Self {
+        Self { frames: 0 }
+    }
+
+    pub fn step(&self) -> Step {
+        let index = (self.frames / PATTERN_FRAMES) as usize;
+        PATTERNS.get(index).copied().unwrap_or(Step::Report)
+    }
+
+    /// Advances test by a frame. Returns step that starts with this frame, if
+    /// any.
+    pub fn update(&mut self) -> Option<Step> {
+        let is_start = self.frames.is_multiple_of(PATTERN_FRAMES)
+            && self.frames <= PATTERNS.len() as u32 * PATTERN_FRAMES;
+        let step = self.step();
+        self.frames = self.frames.saturating_add(1);
+        is_start.then_some(step)
+    }
+}
+
+impl Default for SelfTest {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_steps() {
+        let mut self_test = SelfTest::new();
+        let starts: Vec<_> = (0..10 * PATTERN_FRAMES)
+            .filter_map(|frame| self_test.update().map(|step| (frame, step)))
+            .collect();
+        assert_eq!(
+            starts,
+            [
+                (0, Step::Red),
+                (PATTERN_FRAMES, Step::Green),
+                (2 * PATTERN_FRAMES, Step::Blue),
+                (3 * PATTERN_FRAMES, Step::White),
+                (4 * PATTERN_FRAMES, Step::Report),
+            ]
+        );
+        assert_eq!(self_test.step(), Step::Report);
+    }
+}
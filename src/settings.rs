@@ -0,0 +1,315 @@
+//! Settings persisted across power cycles.
+//!
+//! Settings are encoded into a short record, which is kept by a [`Storage`]
+//! backend. Board layer decides where it lives: internal flash
+//! ([`Flash`](crate::flash::Flash)) or I2C EEPROM ([`AT24Cxx`]).
+//!
+//! Settings are also exchanged with host as a blob: magic followed by
+//! settings serialized with [postcard](https://docs.rs/postcard). Blob
+//! doesn't depend on layout of record, so backups survive changes of storage.
+
+use crate::{
+    dimming::Dimming,
+    drivers::at24cxx::{self, AT24Cxx},
+    led_strip::NightTint,
+    reminder::{Reminder, MAX_REMINDERS},
+    sun::Location,
+};
+use embedded_hal::blocking::i2c::{Write, WriteRead};
+use heapless::Vec;
+use serde::{Deserialize, Serialize};
+
+/// Marks record as holding settings. Has to be changed whenever layout of
+/// settings changes, so settings written by older firmware are ignored.
+const MAGIC: [u8; 4] = *b"LCSN";
+/// Number of bytes taken by fields of settings other than reminders
+const BASE_FIELDS_LEN: usize = 41;
+/// Number of bytes taken by fields of settings, reminders are month and day
+/// each
+const FIELDS_LEN: usize = BASE_FIELDS_LEN + 2 * MAX_REMINDERS;
+/// Stored in place of optional fields that are not set
+const NONE: u8 = 0xFF;
+/// Stored in place of idle timeout when menus are never left
+const NO_IDLE_TIMEOUT: u16 = u16::MAX;
+/// Stored in place of latitude when location is not set
+const NO_LATITUDE: i16 = i16::MIN;
+/// Record is magic, fields and checksum of fields
+pub const RECORD_LEN: usize = MAGIC.len() + FIELDS_LEN + 1;
+/// Offset of record in EEPROM
+const EEPROM_OFFSET: u16 = 0;
+
+pub type Record = [u8; RECORD_LEN];
+/// Longest blob, enough for all fields taking their longest encoding
+pub const BLOB_LEN: usize = 77;
+
+pub type Blob = Vec<u8, BLOB_LEN>;
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Settings {
+    /// Index in [`THEMES`](crate::theme::THEMES)
+    pub theme: u8,
+    /// Index in [`NUMPICS`](crate::theme::NUMPICS) overriding digits of
+    /// theme
+    pub digit_style: Option<u8>,
+    /// Period of regular screen rotation in seconds
+    pub rotation: Option<u8>,
+    /// Offset of local time from UTC in quarters of hour. Applied to time
+    /// received from GPS.
+    pub utc_offset: i8,
+    /// Hours of night, see [`State::is_night`](crate::state::State::is_night)
+    pub night: Option<(u8, u8)>,
+    /// Brightness of regular screens, from 0 to
+    /// [`MAX_BRIGHTNESS`](crate::state::MAX_BRIGHTNESS)
+    pub brightness: u8,
+    /// Brightness of menus if it differs from brightness
+    pub menu_brightness: Option<u8>,
+    /// Brightness of regular screens at night if it differs from brightness
+    pub night_brightness: Option<u8>,
+    /// Index in [`MELODIES`](crate::bell::MELODIES) of alarm melody
+    pub melody: u8,
+    /// Volume of alarm, from 1 to [`MAX_VOLUME`](crate::bell::MAX_VOLUME)
+    pub volume: u8,
+    /// Is temperature shown in Fahrenheit instead of Celsius?
+    pub is_fahrenheit: bool,
+    /// Index in [`DATE_FORMATS`](crate::state::DATE_FORMATS)
+    pub date_format: u8,
+    /// Are displays of seconds turned off on time screen?
+    pub hide_seconds: bool,
+    /// Will alarm go off?
+    pub alarm_armed: bool,
+    /// Hours and minutes alarm goes off at
+    pub alarm_time: (u8, u8),
+    /// Minutes bell takes to reach full volume and tempo, 0 if it rings at
+    /// full at once
+    pub alarm_ramp: u8,
+    /// Minutes LEDs and backlight rise like sunrise ahead of alarm, None if
+    /// they don't
+    pub sunrise_mins: Option<u8>,
+    /// Index in [`TIME_LAYOUTS`](crate::state::TIME_LAYOUTS). Layouts that
+    /// used to be chosen by flag come first, so the flag reads as index.
+    pub time_layout: u8,
+    /// Hue of solid mode of LED strip in degrees
+    pub led_hue: u16,
+    /// Hue of solid mode of LED ring in degrees
+    pub ring_hue: u16,
+    /// Location sunrise and sunset are computed for
+    pub location: Option<Location>,
+    /// Is night set from sunset to sunrise instead of fixed hours?
+    pub night_from_sun: bool,
+    /// Curve backlight and LEDs are dimmed with over day, None if they are
+    /// not
+    pub dimming: Option<Dimming>,
+    /// Hours LEDs are tinted warm during, None if they never are
+    pub night_tint: Option<NightTint>,
+    /// Clock of display SPI in MHz, None for default clock of board
+    pub spi_clock: Option<u8>,
+    /// Clock of display SPI at night if it differs from clock by day
+    pub night_spi_clock: Option<u8>,
+    /// Altitude of the clock in meters, pressure is normalized to sea level
+    /// from it
+    pub station_altitude: i16,
+    /// Seconds of inactivity after which menus are left, None if they never
+    /// are
+    pub idle_timeout: Option<u16>,
+    /// Dates celebrated every year, empty slots are None
+    pub reminders: [Option<Reminder>; MAX_REMINDERS],
+}
+
+impl Settings {
+    fn to_fields(self) -> [u8; FIELDS_LEN] {
+        let (latitude, longitude) = self.location.map_or((NO_LATITUDE, 0), |location| {
+            (location.latitude(), location.longitude())
+        });
+        let idle_timeout = self.idle_timeout.unwrap_or(NO_IDLE_TIMEOUT);
+        let base: [u8; BASE_FIELDS_LEN] = [
+            self.theme,
+            self.digit_style.unwrap_or(NONE),
+            self.rotation.unwrap_or(NONE),
+            self.utc_offset as u8,
+            self.night.map_or(NONE, |(start, _)| start),
+            self.night.map_or(NONE, |(_, end)| end),
+            self.brightness,
+            self.menu_brightness.unwrap_or(NONE),
+            self.night_brightness.unwrap_or(NONE),
+            self.melody,
+            self.volume,
+            self.is_fahrenheit as u8,
+            self.date_format,
+            self.hide_seconds as u8,
+            self.alarm_armed as u8,
+            self.led_hue as u8,
+            (self.led_hue >> 8) as u8,
+            self.ring_hue as u8,
+            (self.ring_hue >> 8) as u8,
+            self.time_layout,
+            latitude as u8,
+            (latitude >> 8) as u8,
+            longitude as u8,
+            (longitude >> 8) as u8,
+            self.night_from_sun as u8,
+            self.dimming.map_or(NONE, Dimming::start),
+            self.dimming.map_or(NONE, Dimming::end),
+            self.dimming.map_or(NONE, Dimming::level),
+            self.night_tint.map_or(NONE, NightTint::start),
+            self.night_tint.map_or(NONE, NightTint::end),
+            self.night_tint.map_or(NONE, NightTint::strength),
+            self.spi_clock.unwrap_or(NONE),
+            self.night_spi_clock.unwrap_or(NONE),
+            self.station_altitude as u8,
+            (self.station_altitude >> 8) as u8,
+            idle_timeout as u8,
+            (idle_timeout >> 8) as u8,
+            self.alarm_time.0,
+            self.alarm_time.1,
+            self.alarm_ramp,
+            self.sunrise_mins.unwrap_or(NONE),
+        ];
+        let mut fields = [NONE; FIELDS_LEN];
+        fields[..BASE_FIELDS_LEN].copy_from_slice(&base);
+        for (bytes, reminder) in fields[BASE_FIELDS_LEN..]
+            .chunks_exact_mut(2)
+            .zip(self.reminders)
+        {
+            if let Some(reminder) = reminder {
+                bytes.copy_from_slice(&[reminder.month(), reminder.day()]);
+            }
+        }
+        fields
+    }
+
+    fn from_fields(fields: [u8; FIELDS_LEN]) -> Self {
+        let (base, reminder_bytes) = fields.split_at(BASE_FIELDS_LEN);
+        let mut reminders = [None; MAX_REMINDERS];
+        for (reminder, bytes) in reminders.iter_mut().zip(reminder_bytes.chunks_exact(2)) {
+            *reminder = Reminder::new(bytes[0], bytes[1]);
+        }
+        let [theme, digit_style, rotation, utc_offset, night_start, night_end, brightness, menu_brightness, night_brightness, melody, volume, is_fahrenheit, date_format, hide_seconds, alarm_armed, led_hue_lo, led_hue_hi, ring_hue_lo, ring_hue_hi, time_layout, latitude_lo, latitude_hi, longitude_lo, longitude_hi, night_from_sun, dimming_start, dimming_end, dimming_level, tint_start, tint_end, tint_strength, spi_clock, night_spi_clock, altitude_lo, altitude_hi, idle_timeout_lo, idle_timeout_hi, alarm_hours, alarm_mins, alarm_ramp, sunrise_mins] =
+            base.try_into().unwrap_or([0; BASE_FIELDS_LEN]);
+        Self {
+            theme,
+            digit_style: (digit_style != NONE).then_some(digit_style),
+            rotation: (rotation != NONE).then_some(rotation),
+            utc_offset: utc_offset as i8,
+            night: (night_start != NONE).then_some((night_start, night_end)),
+            brightness,
+            menu_brightness: (menu_brightness != NONE).then_some(menu_brightness),
+            night_brightness: (night_brightness != NONE).then_some(night_brightness),
+            melody,
+            volume,
+            is_fahrenheit: is_fahrenheit != 0,
+            date_format,
+            hide_seconds: hide_seconds != 0,
+            alarm_armed: alarm_armed != 0,
+            alarm_time: (alarm_hours, alarm_mins),
+            alarm_ramp,
+            sunrise_mins: (sunrise_mins != NONE).then_some(sunrise_mins),
+            led_hue: u16::from_le_bytes([led_hue_lo, led_hue_hi]),
+            ring_hue: u16::from_le_bytes([ring_hue_lo, ring_hue_hi]),
+            time_layout,
+            location: Location::new(
+                i16::from_le_bytes([latitude_lo, latitude_hi]),
+                i16::from_le_bytes([longitude_lo, longitude_hi]),
+            ),
+            night_from_sun: night_from_sun != 0,
+            dimming: Dimming::new(dimming_start, dimming_end, dimming_level),
+            night_tint: NightTint::new(tint_start, tint_end, tint_strength),
+            spi_clock: (spi_clock != NONE).then_some(spi_clock),
+            night_spi_clock: (night_spi_clock != NONE).then_some(night_spi_clock),
+            station_altitude: i16::from_le_bytes([altitude_lo, altitude_hi]),
+            idle_timeout: Some(u16::from_le_bytes([idle_timeout_lo, idle_timeout_hi]))
+                .filter(|&idle_timeout| idle_timeout != NO_IDLE_TIMEOUT),
+            reminders,
+        }
+    }
+
+    fn to_record(self) -> Record {
+        let mut record = [0; RECORD_LEN];
+        let fields = self.to_fields();
+        record[..4].copy_from_slice(&MAGIC);
+        record[4..4 + FIELDS_LEN].copy_from_slice(&fields);
+        record[4 + FIELDS_LEN] = checksum(&fields);
+        record
+    }
+
+    fn from_record(record: &Record) -> Option<Self> {
+        let fields: [u8; FIELDS_LEN] = record[4..4 + FIELDS_LEN].try_into().ok()?;
+        if record[..4] != MAGIC || record[4 + FIELDS_LEN] != checksum(&fields) {
+            return None;
+        }
+
+        Some(Self::from_fields(fields))
+    }
+
+    /// Serializes settings to be sent to host
+    pub fn to_blob(self) -> Blob {
+        let mut blob = [0; BLOB_LEN];
+        blob[..4].copy_from_slice(&MAGIC);
+        let len = postcard::to_slice(&self, &mut blob[4..]).map_or(0, |fields| fields.len());
+        Vec::from_slice(&blob[..4 + len]).unwrap_or_default()
+    }
+
+    /// Deserializes settings received from host. Returns None if blob was
+    /// made by firmware with other settings or is corrupted.
+    pub fn from_blob(blob: &[u8]) -> Option<Self> {
+        let fields = blob.strip_prefix(&MAGIC)?;
+        match postcard::take_from_bytes(fields) {
+            Ok((settings, [])) => Some(settings),
+            _ => None,
+        }
+    }
+}
+
+fn checksum(bytes: &[u8]) -> u8 {
+    bytes
+        .iter()
+        .fold(0xA5u8, |sum, &byte| sum.rotate_left(1) ^ byte)
+}
+
+/// Place where record of settings is kept
+pub trait Storage {
+    /// Reads record written before. Its content is arbitrary if nothing was
+    /// written yet.
+    fn read(&mut self, record: &mut Record) -> Result<(), Error>;
+    fn write(&mut self, record: &Record) -> Result<(), Error>;
+}
+
+/// Reads settings saved with [`save`]. Returns None if nothing was saved yet
+/// or saved settings are corrupted.
+pub fn load(storage: &mut impl Storage) -> Option<Settings> {
+    let mut record = [0; RECORD_LEN];
+    storage.read(&mut record).ok()?;
+    Settings::from_record(&record)
+}
+
+/// Writes settings to storage. Both flash and EEPROM wear out, so nothing is
+/// written if settings have not changed.
+pub fn save(storage: &mut impl Storage, settings: Settings) -> Result<(), Error> {
+    if load(storage) == Some(settings) {
+        return Ok(());
+    }
+
+    storage.write(&settings.to_record())
+}
+
+/// Wipes saved settings, so [`load`] finds nothing like on the first boot
+pub fn erase(storage: &mut impl Storage) -> Result<(), Error> {
+    storage.write(&[NONE; RECORD_LEN])
+}
+
+impl<I2C> Storage for AT24Cxx<I2C>
+where
+    I2C: Write + WriteRead,
+{
+    fn read(&mut self, record: &mut Record) -> Result<(), Error> {
+        AT24Cxx::read(self, EEPROM_OFFSET, record).map_err(Error::Eeprom)
+    }
+
+    fn write(&mut self, record: &Record) -> Result<(), Error> {
+        AT24Cxx::write(self, EEPROM_OFFSET, record).map_err(Error::Eeprom)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Error {
+    Eeprom(at24cxx::Error),
+}
@@ -0,0 +1,87 @@
+//! Animation shown once clock has booted, before time is read for the first
+//! time.
+//!
+//! Displays come up one after another from the left, each fading in a letter
+//! of the splash, and LED under each display fades in with it. Once all of
+//! them are up, splash stays for a while and clock shows its first screen.
+//! Drawing is done by [`LcdClock`](crate::lcd_clock::LcdClock), this module
+//! only keeps track of frames.
+
+use crate::hardware::DISPLAY_COUNT;
+
+/// Frames fade in of each display takes (~250ms)
+pub const FADE_FRAMES: u32 = 15;
+/// Frames between starts of fade in of neighbouring displays
+const DISPLAY_FRAMES: u32 = 8;
+/// Frames splash stays once all displays are up (~500ms)
+const HOLD_FRAMES: u32 = 30;
+
+#[derive(Clone, Copy)]
+pub struct Splash {
+    /// Frames passed since splash has started
+    frames: u32,
+}
+
+impl Splash {
+    pub fn new() -> Self {
+        Self { frames: 0 }
+    }
+
+    /// Advances splash by a frame
+    pub fn update(&mut self) {
+        self.frames = self.frames.saturating_add(1);
+    }
+
+    /// Progress of fade in of display of given index, from 0 to
+    /// [`FADE_FRAMES`]
+    pub fn fade(&self, display: usize) -> u32 {
+        self.frames
+            .saturating_sub(display as u32 * DISPLAY_FRAMES)
+            .min(FADE_FRAMES)
+    }
+
+    /// Has fade of display of given index changed with the last frame?
+    pub fn is_fading(&self, display: usize) -> bool {
+        let start = display as u32 * DISPLAY_FRAMES;
+        (start + 1..=start + FADE_FRAMES).contains(&self.frames)
+    }
+
+    pub fn is_done(&self) -> bool {
+        let last_start = (DISPLAY_COUNT as u32 - 1) * DISPLAY_FRAMES;
+        self.frames >= last_start + FADE_FRAMES + HOLD_FRAMES
+    }
+}
+
+impl Default for Splash {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fade() {
+        let mut splash = Splash::new();
+        let mut frames = 0;
+        while !splash.is_done() {
+            splash.update();
+            frames += 1;
+            assert_eq!(splash.is_fading(0), frames <= FADE_FRAMES);
+        }
+        assert_eq!(frames, 5 * DISPLAY_FRAMES + FADE_FRAMES + HOLD_FRAMES);
+        assert!((0..DISPLAY_COUNT).all(|display| splash.fade(display) == FADE_FRAMES));
+
+        let mut splash = Splash::new();
+        for _ in 0..DISPLAY_FRAMES + 1 {
+            splash.update();
+        }
+        assert_eq!(splash.fade(0), DISPLAY_FRAMES + 1);
+        assert_eq!(splash.fade(1), 1);
+        assert!(splash.is_fading(1));
+        assert_eq!(splash.fade(2), 0);
+        assert!(!splash.is_fading(2));
+    }
+}
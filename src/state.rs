@@ -1,35 +1,221 @@
-use crate::{drivers::buttons::ButtonEvent, led_strip::LedStripState, misc::Sin};
+//! Application state machine driven by button events.
 
+use crate::{
+    bell::{MAX_VOLUME, MELODIES},
+    dimming::{self, Dimming},
+    drivers::{buttons::ButtonEvent, ds3231::Date},
+    images::Numpic,
+    led_strip::{
+        FlashPattern, LedMode, LedOutput, LedStripState, NightTint, Notification, HUE_STEP,
+        RING_LED_COUNT,
+    },
+    misc::Sin,
+    reminder::{self, Reminder, MAX_REMINDERS},
+    settings::Settings,
+    sun::Location,
+    theme::{Theme, NUMPICS, THEMES},
+};
+use heapless::Vec;
+
+/// Screens of regular mode. They are cycled through with left and right or
+/// automatically, see [`ROTATION_PERIODS`].
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Default)]
 pub enum TimeDateScreen {
     #[default]
     Time,
     Date,
+    /// Year with all four digits and weekday
+    FullDate,
+    /// Sunrise and sunset, shown if location is set
+    Sun,
+    Temperature,
+    Humidity,
 }
 
 impl TimeDateScreen {
     fn left(self) -> Self {
         match self {
-            Self::Time => Self::Date,
+            Self::Time => Self::Humidity,
             Self::Date => Self::Time,
+            Self::FullDate => Self::Date,
+            Self::Sun => Self::FullDate,
+            Self::Temperature => Self::Sun,
+            Self::Humidity => Self::Temperature,
         }
     }
 
     pub fn right(self) -> Self {
         match self {
             Self::Time => Self::Date,
-            Self::Date => Self::Time,
+            Self::Date => Self::FullDate,
+            Self::FullDate => Self::Sun,
+            Self::Sun => Self::Temperature,
+            Self::Temperature => Self::Humidity,
+            Self::Humidity => Self::Time,
+        }
+    }
+}
+
+/// Periods in seconds regular screens can be automatically cycled with. None
+/// disables rotation.
+pub const ROTATION_PERIODS: [Option<u8>; 5] = [None, Some(5), Some(10), Some(30), Some(60)];
+
+/// Part of date shown on a pair of displays
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DatePart {
+    Year,
+    Month,
+    Day,
+}
+
+/// Order parts of date are shown in
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default)]
+pub enum DateFormat {
+    #[default]
+    Ymd,
+    Dmy,
+    Mdy,
+}
+
+impl DateFormat {
+    /// Parts of date from left to right
+    pub fn parts(self) -> [DatePart; 3] {
+        match self {
+            Self::Ymd => [DatePart::Year, DatePart::Month, DatePart::Day],
+            Self::Dmy => [DatePart::Day, DatePart::Month, DatePart::Year],
+            Self::Mdy => [DatePart::Month, DatePart::Day, DatePart::Year],
+        }
+    }
+}
+
+/// Date formats in order they are cycled through
+pub const DATE_FORMATS: [DateFormat; 3] = [DateFormat::Ymd, DateFormat::Dmy, DateFormat::Mdy];
+
+/// Layout of time screen
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default)]
+pub enum TimeLayout {
+    /// Digits of theme on every display
+    #[default]
+    Pictures,
+    /// Large digits of hours and minutes, dot going around displays of
+    /// seconds
+    Large,
+    /// Digits of theme for hours and minutes, arc filling up over a minute
+    /// on displays of seconds
+    Arc,
+}
+
+/// Time layouts in order they are cycled through
+pub const TIME_LAYOUTS: [TimeLayout; 3] =
+    [TimeLayout::Pictures, TimeLayout::Large, TimeLayout::Arc];
+
+/// Offsets of local time from UTC in quarters of hour, from UTC-12:00 to
+/// UTC+14:00
+pub const UTC_OFFSETS: core::ops::RangeInclusive<i8> = -48..=56;
+
+/// Brightness levels go from 0 (backlight off) to MAX_BRIGHTNESS
+pub const MAX_BRIGHTNESS: u32 = 9;
+/// Fastest clock of display SPI in MHz, half of peripheral clock of RP2040
+pub const MAX_SPI_CLOCK: u8 = 62;
+
+/// Hours and minutes alarm goes off at until it is set
+const DEFAULT_ALARM_TIME: (u8, u8) = (7, 0);
+/// Digits of alarm time set one by one, hours and minutes
+const ALARM_DIGITS: usize = 4;
+/// Minutes bell takes to reach full volume and tempo until it is set
+const DEFAULT_ALARM_RAMP_MINS: u8 = 3;
+/// Longest ramp of bell in minutes that can be set
+pub const MAX_ALARM_RAMP_MINS: u8 = 30;
+/// Frames LEDs take to fade in and out while alarm rings
+const ALARM_PULSE_FRAMES: u16 = 60;
+/// Longest sunrise ahead of alarm in minutes that can be set
+pub const MAX_SUNRISE_MINS: u8 = 60;
+
+/// Step of station altitude setting in meters
+const ALTITUDE_STEP: i16 = 10;
+
+/// Seconds without button events after which menus and settings are left
+pub const DEFAULT_IDLE_TIMEOUT_SECS: u16 = 30;
+/// Longest idle timeout in seconds that can be set
+pub const MAX_IDLE_TIMEOUT_SECS: u16 = 3600;
+/// Seconds clock stays awake after last button event at night
+const NIGHT_AWAKE_SECS: u32 = 60;
+/// Seconds screen turned off by brightness 0 is lit after last button event
+const BLANK_AWAKE_SECS: u32 = 10;
+/// Brightness screen turned off by brightness 0 is lit with
+const BLANK_AWAKE_BRIGHTNESS: u32 = 3;
+/// State is updated once per frame, which takes ~16ms
+const FRAMES_PER_SECOND: u32 = 60;
+/// Seconds combinations of buttons are held for, all three reset clock to
+/// factory settings and left with right enter USB bootloader
+const COMBO_SECS: u32 = 5;
+
+/// Quantity measured by humidity sensor shown on TempHumidity screen
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default)]
+pub enum SensorScreen {
+    #[default]
+    Temperature,
+    Humidity,
+    Pressure,
+    /// Charts of all quantities over last 24 hours
+    History,
+}
+
+impl SensorScreen {
+    fn left(self) -> Self {
+        match self {
+            Self::Temperature => Self::History,
+            Self::Humidity => Self::Temperature,
+            Self::Pressure => Self::Humidity,
+            Self::History => Self::Pressure,
+        }
+    }
+
+    fn right(self) -> Self {
+        match self {
+            Self::Temperature => Self::Humidity,
+            Self::Humidity => Self::Pressure,
+            Self::Pressure => Self::History,
+            Self::History => Self::Temperature,
+        }
+    }
+}
+
+/// Page of diagnostics screen
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default)]
+pub enum DiagnosticsPage {
+    /// Peripherals found at init and their status
+    #[default]
+    Status,
+    /// Firmware version and link to manual as QR codes
+    Info,
+    /// Recent events and errors. Hidden from left and right, opened by
+    /// holding mode and pressing either of them.
+    Events,
+}
+
+impl DiagnosticsPage {
+    fn next(self) -> Self {
+        match self {
+            Self::Status => Self::Info,
+            Self::Info | Self::Events => Self::Status,
         }
     }
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
-/// All possible choices in main menu
+/// All possible choices in menus
 pub enum MenuOption {
     /// Set time and date
     SetTime,
     /// Set alarm settings
     SetAlarm,
+    /// Arm or disarm alarm
+    SetArmed,
+    /// Select melody of alarm
+    SetMelody,
+    /// Set volume of alarm
+    SetVolume,
     /// Change behaviour of backlight
     SetRgb,
     /// Set brightness of display
@@ -38,45 +224,168 @@ pub enum MenuOption {
     TempHumidity,
     /// Return back to regular mode
     Return,
+    /// Select color theme
+    SetTheme,
+    /// Select digits independently of theme
+    SetDigits,
+    /// Select period of automatic regular screen changes
+    SetRotation,
+    /// Select unit of temperature
+    SetUnit,
+    /// Select order of year, month and day
+    SetDateFormat,
+    /// Select whether seconds are shown on time screen
+    SetSeconds,
+    /// Select layout of time screen
+    SetLayout,
+    /// Set dates of reminders
+    SetReminders,
+    /// Show peripherals found at init
+    Diagnostics,
+    /// Show version and build of firmware
+    About,
+    /// Open submenu
+    Open(Menu),
+    /// Go back to parent menu
+    Back,
 }
 
 impl MenuOption {
-    pub fn left(self) -> Self {
+    /// Short name shown for options without menu picture
+    pub fn label(self) -> &'static str {
         match self {
-            Self::SetTime => Self::Return,
-            Self::SetAlarm => Self::SetTime,
-            Self::SetRgb => Self::SetAlarm,
-            Self::SetBrightness => Self::SetRgb,
-            Self::TempHumidity => Self::SetBrightness,
-            Self::Return => Self::TempHumidity,
+            Self::SetTime => "TIME",
+            Self::SetAlarm => "ALARM",
+            Self::SetArmed => "ARMED",
+            Self::SetMelody => "MELODY",
+            Self::SetVolume => "VOLUME",
+            Self::SetRgb => "RGB",
+            Self::SetBrightness => "BRIGHT",
+            Self::TempHumidity => "SENSOR",
+            Self::Return => "RETURN",
+            Self::SetTheme => "THEME",
+            Self::SetDigits => "DIGITS",
+            Self::SetRotation => "ROTATE",
+            Self::SetUnit => "UNIT",
+            Self::SetDateFormat => "DATE",
+            Self::SetSeconds => "SECONDS",
+            Self::SetLayout => "LAYOUT",
+            Self::SetReminders => "REMIND",
+            Self::Diagnostics => "DIAG",
+            Self::About => "ABOUT",
+            Self::Open(menu) => menu.label(),
+            Self::Back => "BACK",
         }
     }
+}
 
-    pub fn right(self) -> Self {
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+/// Menus are nested, options related to each other are grouped in submenus
+pub enum Menu {
+    Main,
+    Display,
+    Alarm,
+}
+
+impl Menu {
+    pub fn options(self) -> &'static [MenuOption] {
         match self {
-            Self::SetTime => Self::SetAlarm,
-            Self::SetAlarm => Self::SetRgb,
-            Self::SetRgb => Self::SetBrightness,
-            Self::SetBrightness => Self::TempHumidity,
-            Self::TempHumidity => Self::Return,
-            Self::Return => Self::SetTime,
-        }
-    }
-
-    pub fn all() -> impl Iterator<Item = Self> {
-        [
-            Self::SetTime,
-            Self::SetAlarm,
-            Self::SetRgb,
-            Self::SetBrightness,
-            Self::TempHumidity,
-            Self::Return,
-        ]
-        .iter()
-        .copied()
+            Self::Main => &[
+                MenuOption::SetTime,
+                MenuOption::Open(Self::Alarm),
+                MenuOption::Open(Self::Display),
+                MenuOption::SetReminders,
+                MenuOption::TempHumidity,
+                MenuOption::Diagnostics,
+                MenuOption::About,
+                MenuOption::Return,
+            ],
+            Self::Display => &[
+                MenuOption::SetBrightness,
+                MenuOption::SetRgb,
+                MenuOption::SetTheme,
+                MenuOption::SetDigits,
+                MenuOption::SetRotation,
+                MenuOption::SetUnit,
+                MenuOption::SetDateFormat,
+                MenuOption::SetSeconds,
+                MenuOption::SetLayout,
+                MenuOption::Back,
+            ],
+            Self::Alarm => &[
+                MenuOption::SetAlarm,
+                MenuOption::SetArmed,
+                MenuOption::SetMelody,
+                MenuOption::SetVolume,
+                MenuOption::Back,
+            ],
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Main => "MENU",
+            Self::Display => "DISPLAY",
+            Self::Alarm => "ALARM",
+        }
+    }
+}
+
+/// Option before given one among shown options, menu wraps around
+fn menu_left(options: &[MenuOption], option: MenuOption) -> MenuOption {
+    let index = options.iter().position(|&o| o == option).unwrap_or(0);
+    options[(index + options.len() - 1) % options.len()]
+}
+
+/// Option after given one among shown options, menu wraps around
+fn menu_right(options: &[MenuOption], option: MenuOption) -> MenuOption {
+    let index = options.iter().position(|&o| o == option).unwrap_or(0);
+    options[(index + 1) % options.len()]
+}
+
+/// Unit temperature is shown in
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default)]
+pub enum TemperatureUnit {
+    #[default]
+    Celsius,
+    Fahrenheit,
+}
+
+/// Sources of data shown on screens. Each source is marked dirty when its
+/// data changes and taken by screen that draws it, so frames with nothing new
+/// neither read chips nor draw.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Source {
+    /// Time kept by rtc, marked every second by tick and when time is set
+    Time,
+    /// Measurements of humidity sensor, marked when they are due
+    Sensor,
+    /// Measurements over last day, marked when sample is added
+    History,
+}
+
+impl Source {
+    fn bit(self) -> u8 {
+        1 << self as u8
     }
 }
 
+/// Screens that can have brightness of their own
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BrightnessScreen {
+    /// Regular screens, brightness set in menu
+    Time,
+    /// Menus and settings
+    Menu,
+    /// Regular screens during night while clock is awake
+    Night,
+}
+
+/// How deep menus can be nested
+const MENU_DEPTH: usize = 4;
+/// Options that fit in a menu, has to be enough for the longest one
+const MENU_OPTIONS: usize = 12;
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 /// All possible application states
 pub enum AppMode {
@@ -84,70 +393,1000 @@ pub enum AppMode {
     Menu(MenuOption),
     SetTime(usize),
     SetAlarm(usize),
-    SetRgb,
+    SetArmed,
+    SetMelody,
+    SetVolume,
+    SetRgb(LedOutput),
     SetBrightness,
-    TempHumidity,
+    TempHumidity(SensorScreen),
+    SetTheme,
+    SetDigits,
+    SetRotation,
+    SetUnit,
+    SetDateFormat,
+    SetSeconds,
+    SetLayout,
+    /// Field being set among months and days of reminders, two per reminder
+    SetReminders(usize),
+    /// Peripherals found at init
+    Diagnostics(DiagnosticsPage),
+    /// Version, commit and date firmware was built from and revision of the
+    /// kit it was built for
+    About,
+    /// Text sent over USB console scrolled across displays
+    Message,
+    /// Power-on self test, see [`self_test`](crate::self_test). Left or right
+    /// ends it.
+    SelfTest,
+    /// Report of crash that has reset the clock, see [`crash`](crate::crash).
+    /// Stays until any button is pressed.
+    CrashReport,
+    /// Displays and LEDs are off during night, any button wakes clock up
+    Dormant,
 }
 
-/// State of application. It tries to store all things that may change based
-/// on user input and modify it in a single place. It loosely corresponds to
-/// Controller in MVC.
-pub struct State {
-    /// Store last mode before changing. Application may use this information in
-    /// order to redraw more effeciently.
-    last_mode: AppMode,
-    /// FSM for application mode. It basically enumerates all possible screens.
-    mode: AppMode,
-    /// Led strip has state on its own in order to create animations
-    led_strip: LedStripState,
-    /// Brightness of display (from 0 to 10)
-    brightness: u32,
-    /// Has state transition occured? Application can use this information in
-    /// order to decide whether to redraw or not.
-    transition: bool,
-    /// Is mode button down?
-    is_mode_down: bool,
-    /// Flag used to determine behaviour while setting time/alarm. In this cases
-    /// if mode was held and either of left or right pressed the time is
-    /// changed, otherwise mode button changes mode.
-    lr_pressed_while_mode_down: bool,
+/// State of application. It tries to store all things that may change based
+/// on user input and modify it in a single place. It loosely corresponds to
+/// Controller in MVC.
+pub struct State {
+    /// Store last mode before changing. Application may use this information in
+    /// order to redraw more effeciently.
+    last_mode: AppMode,
+    /// FSM for application mode. It basically enumerates all possible screens.
+    mode: AppMode,
+    /// Menus opened in menu mode, the last one is shown. Main menu is at the
+    /// bottom.
+    menu_stack: Vec<Menu, MENU_DEPTH>,
+    /// Led strip has state on its own in order to create animations
+    led_strip: LedStripState,
+    /// Optional LED ring runs its own mode
+    led_ring: LedStripState<RING_LED_COUNT>,
+    /// Brightness of display (from 0 to MAX_BRIGHTNESS)
+    brightness: u32,
+    /// Brightness in menus, None if it is the same as brightness
+    menu_brightness: Option<u32>,
+    /// Brightness of regular screens at night, None if it is the same as
+    /// brightness
+    night_brightness: Option<u32>,
+    /// Curve backlight of regular screens and LEDs follow over day, None if
+    /// they are not dimmed
+    dimming: Option<Dimming>,
+    /// Percent of brightness dimming is at for current minute
+    dimming_percent: u32,
+    /// Hours LEDs are tinted warm during, None if they never are
+    night_tint: Option<NightTint>,
+    /// Clock of display SPI in MHz, None for default clock of board
+    spi_clock: Option<u8>,
+    /// Clock of display SPI at night, None if it is the same as by day
+    night_spi_clock: Option<u8>,
+    /// Index of selected theme in THEMES
+    theme: u8,
+    /// Index of digits in NUMPICS used instead of digits of theme
+    digit_style: Option<u8>,
+    /// Will alarm go off? Shown on time screen.
+    alarm_armed: bool,
+    /// Hours and minutes alarm goes off at
+    alarm_time: (u8, u8),
+    /// Minutes bell takes to reach full volume and tempo
+    alarm_ramp: u8,
+    /// Is alarm ringing? Any button stops it.
+    alarm_ringing: bool,
+    /// Has ringing alarm been stopped? Taken by application, which silences
+    /// bell.
+    alarm_stopped: bool,
+    /// Minutes LED strip and backlight rise like sunrise ahead of alarm, None
+    /// if they don't
+    sunrise_mins: Option<u8>,
+    /// Percent of sunrise shown, None if it isn't
+    sunrise: Option<u8>,
+    /// Mode of LED strip before sunrise, shown again once it ends
+    sunrise_return: LedMode,
+    /// Index of alarm melody in MELODIES
+    melody: u8,
+    /// Volume of alarm (from 1 to MAX_VOLUME)
+    volume: u8,
+    /// Have persisted settings been changed? Application uses this to decide
+    /// when to save them.
+    settings_changed: bool,
+    /// Altitude of the clock in meters used to normalize pressure to sea
+    /// level
+    station_altitude: i16,
+    temperature_unit: TemperatureUnit,
+    date_format: DateFormat,
+    /// Are two rightmost displays turned off on time screen, so it shows
+    /// only hours and minutes?
+    hide_seconds: bool,
+    time_layout: TimeLayout,
+    /// Has humidity sensor been found? If not, screens other than temperature
+    /// are skipped
+    has_sensor: bool,
+    /// Does humidity sensor measure humidity? If not, its screen is skipped
+    has_humidity: bool,
+    /// Is LED strip working? If not, and there is no ring, its menu option is
+    /// hidden
+    has_led_strip: bool,
+    /// Is LED ring fitted and working? If not, it is skipped in RGB menu
+    has_led_ring: bool,
+    /// Is hardware too broken for anything but diagnostics screen (e.g. rtc
+    /// is missing)?
+    hardware_failed: bool,
+    /// Is time kept by rtc garbage (e.g. because its battery has died)? Stays
+    /// set until user changes time.
+    time_invalid: bool,
+    /// Has state transition occured? Application can use this information in
+    /// order to decide whether to redraw or not.
+    transition: bool,
+    /// Sources whose data has changed since screen last drew it, one bit per
+    /// [`Source`]
+    dirty: u8,
+    /// Seconds after which next regular screen is shown, None if screens
+    /// are changed only manually
+    rotation: Option<u8>,
+    /// Offset of local time from UTC in quarters of hour
+    utc_offset: i8,
+    /// Hours clock sleeps between, see is_night. None if it never does.
+    night: Option<(u8, u8)>,
+    /// Are hours of night updated from sunset and sunrise at location?
+    night_from_sun: bool,
+    /// Location of clock for sunrise and sunset
+    location: Option<Location>,
+    /// Dates celebrated every year, empty slots are None
+    reminders: [Option<Reminder>; MAX_REMINDERS],
+    /// Was it night when rtc was last checked?
+    is_night_now: bool,
+    /// Frames passed since regular screen was last changed
+    rotation_frames: u32,
+    /// Frames passed since last button event outside of regular mode
+    idle_frames: u32,
+    /// Frames passed since last button event in any mode
+    awake_frames: u32,
+    /// Seconds of inactivity after which clock goes back to time screen. None
+    /// if it never does.
+    idle_timeout: Option<u16>,
+    /// Is mode button down?
+    is_mode_down: bool,
+    /// Are left and right buttons down?
+    is_left_down: bool,
+    is_right_down: bool,
+    /// Frames buttons have been held in current combination for
+    combo_frames: u32,
+    /// Has factory reset been asked for? Taken by application, which wipes
+    /// settings and reboots.
+    factory_reset: bool,
+    /// Has USB bootloader been asked for? Taken by application.
+    bootloader: bool,
+    /// Mode crash report returns to, e.g. diagnostics of failed hardware
+    crash_return: AppMode,
+    /// Is clock set up for the first time? Time is set first, and settings
+    /// are saved once it is.
+    first_boot: bool,
+    /// Flag used to determine behaviour while setting time/alarm. In this cases
+    /// if mode was held and either of left or right pressed the time is
+    /// changed, otherwise mode button changes mode.
+    lr_pressed_while_mode_down: bool,
+
+    time_delta: Option<(usize, i8)>,
+}
+
+impl State {
+    pub fn new(sin: Sin, brightness: u32) -> Self {
+        let mode = AppMode::Regular(Default::default());
+        Self {
+            mode,
+            last_mode: mode,
+            menu_stack: Vec::new(),
+            led_strip: LedStripState::new(sin),
+            led_ring: LedStripState::new(sin),
+            brightness,
+            menu_brightness: None,
+            night_brightness: None,
+            dimming: None,
+            dimming_percent: dimming::FULL,
+            night_tint: None,
+            spi_clock: None,
+            night_spi_clock: None,
+            theme: 0,
+            digit_style: None,
+            alarm_armed: false,
+            alarm_time: DEFAULT_ALARM_TIME,
+            alarm_ramp: DEFAULT_ALARM_RAMP_MINS,
+            alarm_ringing: false,
+            alarm_stopped: false,
+            sunrise_mins: None,
+            sunrise: None,
+            sunrise_return: LedMode::default(),
+            melody: 0,
+            volume: MAX_VOLUME,
+            settings_changed: false,
+            station_altitude: 0,
+            temperature_unit: TemperatureUnit::Celsius,
+            date_format: DateFormat::Ymd,
+            hide_seconds: false,
+            time_layout: TimeLayout::Pictures,
+            has_sensor: true,
+            has_humidity: true,
+            has_led_strip: true,
+            has_led_ring: false,
+            hardware_failed: false,
+            time_invalid: false,
+            transition: true,
+            // Nothing has been drawn yet
+            dirty: Source::Time.bit() | Source::Sensor.bit() | Source::History.bit(),
+            rotation: None,
+            utc_offset: 0,
+            night: None,
+            night_from_sun: false,
+            location: None,
+            reminders: [None; MAX_REMINDERS],
+            is_night_now: false,
+            rotation_frames: 0,
+            idle_frames: 0,
+            awake_frames: 0,
+            idle_timeout: Some(DEFAULT_IDLE_TIMEOUT_SECS),
+            is_mode_down: false,
+            is_left_down: false,
+            is_right_down: false,
+            combo_frames: 0,
+            factory_reset: false,
+            bootloader: false,
+            crash_return: mode,
+            first_boot: false,
+            lr_pressed_while_mode_down: false,
+            time_delta: None,
+        }
+    }
+
+    /// Has factory reset been asked for by holding all three buttons?
+    pub fn take_factory_reset(&mut self) -> bool {
+        core::mem::take(&mut self.factory_reset)
+    }
+
+    /// Has USB bootloader been asked for by holding left and right or over
+    /// console?
+    pub fn take_bootloader(&mut self) -> bool {
+        core::mem::take(&mut self.bootloader)
+    }
+
+    /// Enters USB bootloader with the next frame, so reply to console is sent
+    /// before USB disconnects
+    pub fn request_bootloader(&mut self) {
+        self.bootloader = true;
+    }
+
+    pub fn take_time_delta(&mut self) -> Option<(usize, i8)> {
+        self.time_delta.take()
+    }
+
+    pub fn led_strip(&self) -> &LedStripState {
+        &self.led_strip
+    }
+
+    /// Gives access to notifications of LED strip
+    pub fn led_strip_mut(&mut self) -> &mut LedStripState {
+        &mut self.led_strip
+    }
+
+    pub fn led_ring(&self) -> &LedStripState<RING_LED_COUNT> {
+        &self.led_ring
+    }
+
+    pub fn last_mode(&self) -> AppMode {
+        self.last_mode
+    }
+
+    pub fn mode(&self) -> AppMode {
+        self.mode
+    }
+
+    /// Menu shown in menu mode
+    pub fn menu(&self) -> Menu {
+        self.menu_stack.last().copied().unwrap_or(Menu::Main)
+    }
+
+    pub fn rotation(&self) -> Option<u8> {
+        self.rotation
+    }
+
+    pub fn brightness(&self) -> u32 {
+        self.brightness
+    }
+
+    /// Brightness of current screen. Brightness being set is previewed as
+    /// is and backlight is off while clock sleeps. Screen turned off by
+    /// brightness 0 is lit for a while by any button.
+    pub fn screen_brightness(&self) -> u32 {
+        // Backlight rises with sunrise from its dimmest level
+        if let (AppMode::Regular(_), Some(percent)) = (self.mode, self.sunrise) {
+            return 1 + (self.brightness.max(1) - 1) * percent as u32 / 100;
+        }
+        let brightness = match self.mode {
+            AppMode::Regular(_) if self.is_night_now => {
+                self.night_brightness.unwrap_or(self.brightness)
+            }
+            AppMode::Regular(_) | AppMode::SetBrightness => self.brightness,
+            AppMode::Dormant => return 0,
+            // Dead pixels are easier to spot at full brightness
+            AppMode::SelfTest => MAX_BRIGHTNESS,
+            _ => self.menu_brightness.unwrap_or(self.brightness),
+        };
+        if brightness == 0 && self.awake_frames < BLANK_AWAKE_SECS * FRAMES_PER_SECOND {
+            BLANK_AWAKE_BRIGHTNESS
+        } else {
+            brightness
+        }
+    }
+
+    /// Sets brightness of given screen, None makes it the same as brightness
+    /// of time screen. Levels above MAX_BRIGHTNESS are ignored.
+    pub fn set_screen_brightness(&mut self, screen: BrightnessScreen, brightness: Option<u32>) {
+        if brightness.is_some_and(|brightness| brightness > MAX_BRIGHTNESS) {
+            return;
+        }
+
+        match (screen, brightness) {
+            (BrightnessScreen::Time, Some(brightness)) => self.brightness = brightness,
+            (BrightnessScreen::Time, None) => return,
+            (BrightnessScreen::Menu, brightness) => self.menu_brightness = brightness,
+            (BrightnessScreen::Night, brightness) => self.night_brightness = brightness,
+        }
+        self.settings_changed = true;
+    }
+
+    pub fn dimming(&self) -> Option<Dimming> {
+        self.dimming
+    }
+
+    /// Sets curve of dimming, None turns dimming off
+    pub fn set_dimming(&mut self, dimming: Option<Dimming>) {
+        if dimming != self.dimming {
+            self.replace_dimming(dimming);
+            self.settings_changed = true;
+        }
+    }
+
+    fn replace_dimming(&mut self, dimming: Option<Dimming>) {
+        self.dimming = dimming;
+        if dimming.is_none() {
+            self.dimming_percent = dimming::FULL;
+        }
+    }
+
+    /// Follows curve of dimming, called whenever minute of rtc may have
+    /// changed
+    pub fn update_dimming(&mut self, minutes: u16) {
+        self.dimming_percent = self
+            .dimming
+            .map_or(dimming::FULL, |dimming| dimming.percent(minutes));
+    }
+
+    /// Percent of [`screen_brightness`](Self::screen_brightness) backlight
+    /// is lit with. Only regular screens are dimmed.
+    pub fn screen_dimming(&self) -> u32 {
+        match self.mode {
+            AppMode::Regular(_) => self.dimming_percent,
+            _ => dimming::FULL,
+        }
+    }
+
+    /// Percent of brightness of LEDs
+    pub fn led_dimming(&self) -> u32 {
+        self.dimming_percent
+    }
+
+    pub fn night_tint(&self) -> Option<NightTint> {
+        self.night_tint
+    }
+
+    /// Sets hours and strength of warm tint of LEDs, None turns it off
+    pub fn set_night_tint(&mut self, night_tint: Option<NightTint>) {
+        if night_tint != self.night_tint {
+            self.replace_night_tint(night_tint);
+            self.settings_changed = true;
+        }
+    }
+
+    fn replace_night_tint(&mut self, night_tint: Option<NightTint>) {
+        self.night_tint = night_tint;
+        if night_tint.is_none() {
+            self.led_strip.set_tint(0);
+            self.led_ring.set_tint(0);
+        }
+    }
+
+    /// Tints LEDs during hours of tint, called whenever hour of rtc may have
+    /// changed
+    pub fn update_tint(&mut self, hours: u8) {
+        let tint = self.night_tint.map_or(0, |tint| tint.strength_at(hours));
+        self.led_strip.set_tint(tint);
+        self.led_ring.set_tint(tint);
+    }
+
+    /// Clock of display SPI in MHz for this hour, None for default clock of
+    /// board
+    pub fn spi_clock(&self) -> Option<u8> {
+        if self.is_night_now {
+            self.night_spi_clock.or(self.spi_clock)
+        } else {
+            self.spi_clock
+        }
+    }
+
+    /// Sets clock of display SPI by day or at night. None restores default
+    /// clock by day and makes night follow day. Clocks out of range are
+    /// ignored.
+    pub fn set_spi_clock(&mut self, is_night: bool, mhz: Option<u8>) {
+        if !mhz.is_none_or(is_spi_clock_valid) {
+            return;
+        }
+
+        if is_night {
+            self.night_spi_clock = mhz;
+        } else {
+            self.spi_clock = mhz;
+        }
+        self.settings_changed = true;
+    }
+
+    pub fn theme(&self) -> &'static Theme {
+        &THEMES[self.theme as usize]
+    }
+
+    /// Digits used on time, date and sensor screens
+    pub fn numpic(&self) -> &'static Numpic {
+        match self.digit_style {
+            Some(digit_style) => NUMPICS[digit_style as usize],
+            None => self.theme().numpic,
+        }
+    }
+
+    pub fn is_alarm_armed(&self) -> bool {
+        self.alarm_armed
+    }
+
+    /// Hours and minutes alarm goes off at
+    pub fn alarm_time(&self) -> (u8, u8) {
+        self.alarm_time
+    }
+
+    /// Minutes bell takes to reach full volume and tempo
+    pub fn alarm_ramp(&self) -> u8 {
+        self.alarm_ramp
+    }
+
+    /// Sets minutes bell takes to reach full volume and tempo, 0 rings it at
+    /// full at once. Ramps longer than MAX_ALARM_RAMP_MINS are ignored.
+    pub fn set_alarm_ramp(&mut self, mins: u8) {
+        if mins > MAX_ALARM_RAMP_MINS {
+            return;
+        }
+
+        self.alarm_ramp = mins;
+        self.settings_changed = true;
+    }
+
+    pub fn is_alarm_ringing(&self) -> bool {
+        self.alarm_ringing
+    }
+
+    /// Starts ringing alarm, which wakes clock up and pulses LED strip with
+    /// accent of theme until a button is pressed
+    pub fn start_alarm(&mut self) {
+        self.alarm_ringing = true;
+        self.awake_frames = 0;
+        let color = self.theme().accent;
+        self.led_strip.notify(Notification {
+            pattern: FlashPattern::Pulse(ALARM_PULSE_FRAMES),
+            color,
+            frames: None,
+        });
+        if matches!(self.mode, AppMode::Dormant | AppMode::Regular(_)) {
+            self.transition_regular();
+        }
+    }
+
+    /// Minutes of sunrise ahead of alarm, None if there is none
+    pub fn sunrise_mins(&self) -> Option<u8> {
+        self.sunrise_mins
+    }
+
+    /// Sets minutes LED strip and backlight rise like sunrise ahead of alarm,
+    /// None turns sunrise off. Sunrises longer than MAX_SUNRISE_MINS are
+    /// ignored.
+    pub fn set_sunrise_mins(&mut self, mins: Option<u8>) {
+        if !mins.is_none_or(is_sunrise_valid) {
+            return;
+        }
+
+        self.sunrise_mins = mins;
+        self.settings_changed = true;
+    }
+
+    /// Shows given percent of sunrise on LED strip and backlight of regular
+    /// screens, None ends it. Clock is woken up as sunrise starts.
+    pub fn update_sunrise(&mut self, percent: Option<u8>) {
+        if percent == self.sunrise {
+            return;
+        }
+
+        match (self.sunrise, percent) {
+            (None, Some(_)) => {
+                self.sunrise_return = self.led_strip.mode();
+                self.awake_frames = 0;
+                if matches!(self.mode, AppMode::Dormant | AppMode::Regular(_)) {
+                    self.transition_regular();
+                }
+            }
+            (Some(_), None) => self.led_strip.set_mode(self.sunrise_return),
+            _ => {}
+        }
+        if let Some(percent) = percent {
+            self.led_strip.set_mode(LedMode::Sunrise(percent));
+        }
+        self.sunrise = percent;
+    }
+
+    /// Has ringing alarm been stopped by a button?
+    pub fn take_alarm_stopped(&mut self) -> bool {
+        core::mem::take(&mut self.alarm_stopped)
+    }
+
+    /// Changes digit of alarm time at given index, carrying over into hours
+    /// and wrapping around midnight
+    fn change_alarm(&mut self, index: usize, change: i8) {
+        const MINS_PER_DAY: i16 = 24 * 60;
+        const DIGIT_MINS: [i16; ALARM_DIGITS] = [10 * 60, 60, 10, 1];
+        let (hours, mins) = self.alarm_time;
+        let mins = hours as i16 * 60 + mins as i16;
+        let mins = (mins + change as i16 * DIGIT_MINS[index]).rem_euclid(MINS_PER_DAY);
+        self.alarm_time = ((mins / 60) as u8, (mins % 60) as u8);
+        self.transition = true;
+    }
+
+    /// Index of alarm melody in [`MELODIES`]
+    pub fn melody_index(&self) -> u8 {
+        self.melody
+    }
+
+    /// Alarm melody in RTTTL
+    pub fn melody(&self) -> &'static str {
+        MELODIES[self.melody as usize]
+    }
+
+    pub fn volume(&self) -> u8 {
+        self.volume
+    }
+
+    pub fn settings(&self) -> Settings {
+        Settings {
+            theme: self.theme,
+            digit_style: self.digit_style,
+            rotation: self.rotation,
+            utc_offset: self.utc_offset,
+            night: self.night,
+            brightness: self.brightness as u8,
+            menu_brightness: self.menu_brightness.map(|brightness| brightness as u8),
+            night_brightness: self.night_brightness.map(|brightness| brightness as u8),
+            melody: self.melody,
+            volume: self.volume,
+            is_fahrenheit: self.temperature_unit == TemperatureUnit::Fahrenheit,
+            date_format: DATE_FORMATS
+                .iter()
+                .position(|&format| format == self.date_format)
+                .unwrap_or(0) as u8,
+            hide_seconds: self.hide_seconds,
+            time_layout: TIME_LAYOUTS
+                .iter()
+                .position(|&layout| layout == self.time_layout)
+                .unwrap_or(0) as u8,
+            alarm_armed: self.alarm_armed,
+            alarm_time: self.alarm_time,
+            alarm_ramp: self.alarm_ramp,
+            sunrise_mins: self.sunrise_mins,
+            led_hue: self.led_strip.hue(),
+            ring_hue: self.led_ring.hue(),
+            location: self.location,
+            night_from_sun: self.night_from_sun,
+            dimming: self.dimming,
+            night_tint: self.night_tint,
+            spi_clock: self.spi_clock,
+            night_spi_clock: self.night_spi_clock,
+            station_altitude: self.station_altitude,
+            idle_timeout: self.idle_timeout,
+            reminders: self.reminders,
+        }
+    }
+
+    /// Restores settings saved earlier. Invalid values are ignored.
+    pub fn apply_settings(&mut self, settings: Settings) {
+        if (settings.theme as usize) < THEMES.len() {
+            self.theme = settings.theme;
+        }
+        let is_digit_style_valid = match settings.digit_style {
+            Some(digit_style) => (digit_style as usize) < NUMPICS.len(),
+            None => true,
+        };
+        if is_digit_style_valid {
+            self.digit_style = settings.digit_style;
+        }
+        if ROTATION_PERIODS.contains(&settings.rotation) {
+            self.rotation = settings.rotation;
+        }
+        if UTC_OFFSETS.contains(&settings.utc_offset) {
+            self.utc_offset = settings.utc_offset;
+        }
+        if settings.night.is_none_or(is_night_valid) {
+            self.night = settings.night;
+        }
+        let is_brightness_valid = |brightness: u8| brightness as u32 <= MAX_BRIGHTNESS;
+        if is_brightness_valid(settings.brightness) {
+            self.brightness = settings.brightness as u32;
+        }
+        if settings.menu_brightness.is_none_or(is_brightness_valid) {
+            self.menu_brightness = settings.menu_brightness.map(u32::from);
+        }
+        if settings.night_brightness.is_none_or(is_brightness_valid) {
+            self.night_brightness = settings.night_brightness.map(u32::from);
+        }
+        if (settings.melody as usize) < MELODIES.len() {
+            self.melody = settings.melody;
+        }
+        if (1..=MAX_VOLUME).contains(&settings.volume) {
+            self.volume = settings.volume;
+        }
+        self.temperature_unit = match settings.is_fahrenheit {
+            true => TemperatureUnit::Fahrenheit,
+            false => TemperatureUnit::Celsius,
+        };
+        if let Some(&date_format) = DATE_FORMATS.get(settings.date_format as usize) {
+            self.date_format = date_format;
+        }
+        self.hide_seconds = settings.hide_seconds;
+        if let Some(&time_layout) = TIME_LAYOUTS.get(settings.time_layout as usize) {
+            self.time_layout = time_layout;
+        }
+        self.alarm_armed = settings.alarm_armed;
+        let (alarm_hours, alarm_mins) = settings.alarm_time;
+        if alarm_hours < 24 && alarm_mins < 60 {
+            self.alarm_time = settings.alarm_time;
+        }
+        if settings.alarm_ramp <= MAX_ALARM_RAMP_MINS {
+            self.alarm_ramp = settings.alarm_ramp;
+        }
+        if settings.sunrise_mins.is_none_or(is_sunrise_valid) {
+            self.sunrise_mins = settings.sunrise_mins;
+        }
+        if settings.led_hue < 360 {
+            self.led_strip.set_hue(settings.led_hue);
+        }
+        if settings.ring_hue < 360 {
+            self.led_ring.set_hue(settings.ring_hue);
+        }
+        // Blob from host is not checked by deserialization
+        self.location = settings
+            .location
+            .and_then(|location| Location::new(location.latitude(), location.longitude()));
+        self.night_from_sun = settings.night_from_sun && self.location.is_some();
+        let dimming = settings
+            .dimming
+            .and_then(|dimming| Dimming::new(dimming.start(), dimming.end(), dimming.level()));
+        self.replace_dimming(dimming);
+        let night_tint = settings
+            .night_tint
+            .and_then(|tint| NightTint::new(tint.start(), tint.end(), tint.strength()));
+        self.replace_night_tint(night_tint);
+        if settings.spi_clock.is_none_or(is_spi_clock_valid) {
+            self.spi_clock = settings.spi_clock;
+        }
+        if settings.night_spi_clock.is_none_or(is_spi_clock_valid) {
+            self.night_spi_clock = settings.night_spi_clock;
+        }
+        self.station_altitude = settings.station_altitude;
+        if settings.idle_timeout.is_none_or(is_idle_timeout_valid) {
+            self.idle_timeout = settings.idle_timeout;
+        }
+        self.reminders = settings.reminders.map(|reminder| {
+            reminder.and_then(|reminder| Reminder::new(reminder.month(), reminder.day()))
+        });
+    }
+
+    /// Replaces settings with ones received from host. Like in
+    /// [`apply_settings`](Self::apply_settings), invalid values keep current
+    /// ones.
+    pub fn load_settings(&mut self, settings: Settings) {
+        self.apply_settings(settings);
+        self.is_night_now = false;
+        self.settings_changed = true;
+    }
+
+    pub fn take_settings_changed(&mut self) -> bool {
+        core::mem::take(&mut self.settings_changed)
+    }
+
+    /// Offset of local time from UTC in minutes
+    pub fn utc_offset_mins(&self) -> i16 {
+        self.utc_offset as i16 * 15
+    }
+
+    /// Sets offset of local time from UTC in quarters of hour. Offsets out
+    /// of UTC_OFFSETS are ignored.
+    pub fn set_utc_offset(&mut self, utc_offset: i8) {
+        if UTC_OFFSETS.contains(&utc_offset) && utc_offset != self.utc_offset {
+            self.utc_offset = utc_offset;
+            self.settings_changed = true;
+        }
+    }
+
+    pub fn night(&self) -> Option<(u8, u8)> {
+        self.night
+    }
+
+    /// Sets start and end hour of night. Invalid hours are ignored.
+    pub fn set_night(&mut self, night: Option<(u8, u8)>) {
+        if night.is_none_or(is_night_valid) && self.night_from_sun {
+            self.night_from_sun = false;
+            self.settings_changed = true;
+        }
+        self.replace_night(night);
+    }
+
+    pub fn night_from_sun(&self) -> bool {
+        self.night_from_sun
+    }
+
+    /// Makes night last from sunset to sunrise. Ignored if location is not
+    /// set.
+    pub fn set_night_from_sun(&mut self) {
+        if self.location.is_some() && !self.night_from_sun {
+            self.night_from_sun = true;
+            self.settings_changed = true;
+        }
+    }
+
+    /// Sets hours of night computed from sunset and sunrise. Unlike
+    /// [`set_night`](Self::set_night), night keeps following sun.
+    pub fn set_sun_night(&mut self, night: Option<(u8, u8)>) {
+        if self.night_from_sun {
+            self.replace_night(night);
+        }
+    }
+
+    fn replace_night(&mut self, night: Option<(u8, u8)>) {
+        if night.is_none_or(is_night_valid) && night != self.night {
+            self.night = night;
+            self.is_night_now = false;
+            self.settings_changed = true;
+        }
+    }
+
+    pub fn location(&self) -> Option<Location> {
+        self.location
+    }
+
+    /// Sets location of clock. Without location night can't follow sun.
+    pub fn set_location(&mut self, location: Option<Location>) {
+        if location != self.location {
+            self.location = location;
+            self.night_from_sun &= location.is_some();
+            self.settings_changed = true;
+        }
+    }
+
+    pub fn reminders(&self) -> &[Option<Reminder>; MAX_REMINDERS] {
+        &self.reminders
+    }
+
+    /// Is any reminder due on date?
+    pub fn is_reminder_day(&self, date: Date) -> bool {
+        self.reminders
+            .iter()
+            .flatten()
+            .any(|reminder| reminder.is_due(date))
+    }
+
+    /// Puts reminder into the first empty slot. Returns false if all slots
+    /// are taken. Reminder that is already set is kept as is.
+    pub fn add_reminder(&mut self, reminder: Reminder) -> bool {
+        if self.reminders.contains(&Some(reminder)) {
+            return true;
+        }
+        let Some(slot) = self.reminders.iter_mut().find(|slot| slot.is_none()) else {
+            return false;
+        };
+        *slot = Some(reminder);
+        self.settings_changed = true;
+        true
+    }
+
+    /// Clears slot of reminder. Returns false if it is not set.
+    pub fn remove_reminder(&mut self, reminder: Reminder) -> bool {
+        let Some(slot) = self
+            .reminders
+            .iter_mut()
+            .find(|slot| **slot == Some(reminder))
+        else {
+            return false;
+        };
+        *slot = None;
+        self.settings_changed = true;
+        true
+    }
+
+    /// Is given hour part of night? Night starts at the beginning of start
+    /// hour and ends at the beginning of end hour, which may be on next day.
+    pub fn is_night(&self, hours: u8) -> bool {
+        match self.night {
+            Some((start, end)) if start < end => (start..end).contains(&hours),
+            Some((start, end)) => hours >= start || hours < end,
+            None => false,
+        }
+    }
+
+    /// Puts clock to sleep once it has been left alone at night and wakes it
+    /// up when night ends. Called whenever hour of rtc may have changed.
+    pub fn update_night(&mut self, hours: u8) {
+        let is_night = self.is_night(hours);
+        self.is_night_now = is_night;
+        match self.mode {
+            AppMode::Regular(_)
+                if is_night
+                    && !self.alarm_ringing
+                    && self.sunrise.is_none()
+                    && self.awake_frames >= NIGHT_AWAKE_SECS * FRAMES_PER_SECOND =>
+            {
+                self.transition(AppMode::Dormant);
+            }
+            AppMode::Dormant if !is_night => self.transition_regular(),
+            _ => {}
+        }
+    }
+
+    pub fn station_altitude(&self) -> i16 {
+        self.station_altitude
+    }
+
+    pub fn temperature_unit(&self) -> TemperatureUnit {
+        self.temperature_unit
+    }
+
+    pub fn date_format(&self) -> DateFormat {
+        self.date_format
+    }
+
+    pub fn hide_seconds(&self) -> bool {
+        self.hide_seconds
+    }
+
+    pub fn time_layout(&self) -> TimeLayout {
+        self.time_layout
+    }
+
+    pub fn has_humidity(&self) -> bool {
+        self.has_humidity
+    }
+
+    pub fn set_has_humidity(&mut self, has_humidity: bool) {
+        self.has_humidity = has_humidity;
+    }
+
+    pub fn set_has_sensor(&mut self, has_sensor: bool) {
+        self.has_sensor = has_sensor;
+    }
+
+    pub fn set_has_led_strip(&mut self, has_led_strip: bool) {
+        self.has_led_strip = has_led_strip;
+    }
+
+    pub fn set_has_led_ring(&mut self, has_led_ring: bool) {
+        self.has_led_ring = has_led_ring;
+    }
+
+    pub fn set_has_microphone(&mut self, has_microphone: bool) {
+        self.led_strip.set_has_microphone(has_microphone);
+        self.led_ring.set_has_microphone(has_microphone);
+    }
+
+    /// Sets loudness picked up by microphone, from 0 to 1
+    pub fn set_loudness(&mut self, loudness: f32) {
+        self.led_strip.set_loudness(loudness);
+        self.led_ring.set_loudness(loudness);
+    }
+
+    /// Options of shown menu. Ones of peripherals that are missing are
+    /// hidden.
+    pub fn menu_options(&self) -> Vec<MenuOption, MENU_OPTIONS> {
+        let is_shown = |option: &&MenuOption| match option {
+            MenuOption::SetRgb => self.has_led_strip || self.has_led_ring,
+            _ => true,
+        };
+        self.menu()
+            .options()
+            .iter()
+            .filter(is_shown)
+            .copied()
+            .collect()
+    }
+
+    /// Shows diagnostics screen. If hardware has failed, user can't leave it.
+    pub fn show_diagnostics(&mut self, hardware_failed: bool) {
+        self.hardware_failed = hardware_failed;
+        self.transition(AppMode::Diagnostics(DiagnosticsPage::default()));
+    }
+
+    /// Shows message scrolled across displays. Diagnostics screen of failed
+    /// hardware stays.
+    pub fn show_message(&mut self) {
+        if !self.hardware_failed {
+            self.transition(AppMode::Message);
+        }
+    }
+
+    /// Runs power-on self test
+    pub fn show_self_test(&mut self) {
+        self.transition(AppMode::SelfTest);
+    }
 
-    time_delta: Option<(usize, i8)>,
-}
+    /// Shows report of crash before clock carries on. Screen clock would
+    /// have started with follows it.
+    pub fn show_crash_report(&mut self) {
+        self.crash_return = self.mode;
+        self.transition(AppMode::CrashReport);
+    }
 
-impl State {
-    pub fn new(sin: Sin, brightness: u32) -> Self {
-        let mode = AppMode::Regular(Default::default());
-        Self {
-            mode,
-            last_mode: mode,
-            led_strip: LedStripState::new(sin),
-            brightness,
-            transition: true,
-            is_mode_down: false,
-            lr_pressed_while_mode_down: false,
-            time_delta: None,
+    /// Returns to time once message has scrolled by
+    pub fn hide_message(&mut self) {
+        if self.mode == AppMode::Message {
+            self.transition_regular();
         }
     }
 
-    pub fn take_time_delta(&mut self) -> Option<(usize, i8)> {
-        self.time_delta.take()
+    pub fn is_time_invalid(&self) -> bool {
+        self.time_invalid
     }
 
-    pub fn led_strip(&self) -> &LedStripState {
-        &self.led_strip
+    /// Asks user to set time when nothing has been saved yet, e.g. after
+    /// factory reset. Default settings are saved once user leaves.
+    pub fn show_first_boot(&mut self) {
+        self.first_boot = true;
+        self.transition(AppMode::SetTime(Default::default()));
     }
 
-    pub fn last_mode(&self) -> AppMode {
-        self.last_mode
+    /// Marks time as invalid and asks user to set it
+    pub fn set_time_invalid(&mut self) {
+        self.time_invalid = true;
+        self.transition(AppMode::SetTime(Default::default()));
     }
 
-    pub fn mode(&self) -> AppMode {
-        self.mode
+    pub fn clear_time_invalid(&mut self) {
+        self.time_invalid = false;
     }
 
-    pub fn brightness(&self) -> u32 {
-        self.brightness
+    /// Sets seconds of inactivity after which menus and settings are left.
+    /// None keeps them open until mode is pressed. Timeouts out of range are
+    /// ignored.
+    pub fn set_idle_timeout(&mut self, idle_timeout: Option<u16>) {
+        if !idle_timeout.is_none_or(is_idle_timeout_valid) {
+            return;
+        }
+
+        self.idle_timeout = idle_timeout;
+        self.settings_changed = true;
+    }
+
+    /// Makes application redraw current mode as if it was just entered
+    pub fn request_redraw(&mut self) {
+        self.transition = true;
+    }
+
+    /// Marks data of source as changed, so screen showing it is redrawn
+    pub fn mark_dirty(&mut self, source: Source) {
+        self.dirty |= source.bit();
+    }
+
+    /// Has data of source changed since it was last taken?
+    pub fn take_dirty(&mut self, source: Source) -> bool {
+        let is_dirty = self.dirty & source.bit() != 0;
+        self.dirty &= !source.bit();
+        is_dirty
     }
 
     pub fn eat_transition(&mut self) -> bool {
@@ -164,6 +1403,13 @@ impl State {
     ) {
         self.last_mode = self.mode;
 
+        if mode.is_some() || left.is_some() || right.is_some() {
+            self.idle_frames = 0;
+            self.awake_frames = 0;
+            self.rotation_frames = 0;
+            self.combo_frames = 0;
+        }
+
         match mode {
             Some(ButtonEvent::Release) => self.is_mode_down = false,
             Some(ButtonEvent::Press) => {
@@ -172,36 +1418,83 @@ impl State {
             }
             _ => {}
         }
+        for (event, is_down) in [
+            (left, &mut self.is_left_down),
+            (right, &mut self.is_right_down),
+        ] {
+            match event {
+                Some(ButtonEvent::Press) => *is_down = true,
+                Some(ButtonEvent::Release) => *is_down = false,
+                None => {}
+            }
+        }
 
         let mode = matches!(mode, Some(ButtonEvent::Release));
         let left = matches!(left, Some(ButtonEvent::Release));
         let right = matches!(right, Some(ButtonEvent::Release));
+        // Button stopping alarm does nothing else
+        if self.alarm_ringing && (mode || left || right) {
+            self.alarm_ringing = false;
+            self.alarm_stopped = true;
+            self.led_strip.dismiss();
+            self.update_sunrise(None);
+            return;
+        }
         match self.mode {
-            AppMode::Regular(ref mut screen) => {
+            AppMode::Regular(screen) => {
                 if mode {
+                    self.menu_stack.clear();
+                    self.menu_stack.push(Menu::Main).ok();
                     self.transition(AppMode::Menu(MenuOption::Return));
                 } else if left {
-                    *screen = screen.left();
-                    self.transition = true;
+                    let screen = self.step_screen(screen, TimeDateScreen::left);
+                    self.transition(AppMode::Regular(screen));
                 } else if right {
-                    *screen = screen.right();
-                    self.transition = true;
+                    let screen = self.step_screen(screen, TimeDateScreen::right);
+                    self.transition(AppMode::Regular(screen));
                 }
             }
-            AppMode::Menu(menu) => {
+            AppMode::Menu(option) => {
+                let menu = self.menu();
                 if mode {
-                    self.transition(match menu {
+                    let next = match option {
+                        MenuOption::Open(submenu) => {
+                            // Menus are never nested deeper than MENU_DEPTH
+                            self.menu_stack.push(submenu).ok();
+                            AppMode::Menu(self.menu_options()[0])
+                        }
+                        MenuOption::Back => {
+                            self.menu_stack.pop();
+                            AppMode::Menu(MenuOption::Open(menu))
+                        }
                         MenuOption::Return => AppMode::Regular(Default::default()),
                         MenuOption::SetTime => AppMode::SetTime(Default::default()),
                         MenuOption::SetAlarm => AppMode::SetAlarm(Default::default()),
-                        MenuOption::SetRgb => AppMode::SetRgb,
+                        MenuOption::SetArmed => AppMode::SetArmed,
+                        MenuOption::SetMelody => AppMode::SetMelody,
+                        MenuOption::SetVolume => AppMode::SetVolume,
+                        MenuOption::SetRgb if self.has_led_strip => {
+                            AppMode::SetRgb(LedOutput::Strip)
+                        }
+                        MenuOption::SetRgb => AppMode::SetRgb(LedOutput::Ring),
                         MenuOption::SetBrightness => AppMode::SetBrightness,
-                        MenuOption::TempHumidity => AppMode::TempHumidity,
-                    });
+                        MenuOption::TempHumidity => AppMode::TempHumidity(Default::default()),
+                        MenuOption::SetTheme => AppMode::SetTheme,
+                        MenuOption::SetDigits => AppMode::SetDigits,
+                        MenuOption::SetRotation => AppMode::SetRotation,
+                        MenuOption::SetUnit => AppMode::SetUnit,
+                        MenuOption::SetDateFormat => AppMode::SetDateFormat,
+                        MenuOption::SetSeconds => AppMode::SetSeconds,
+                        MenuOption::SetLayout => AppMode::SetLayout,
+                        MenuOption::SetReminders => AppMode::SetReminders(Default::default()),
+                        MenuOption::Diagnostics => AppMode::Diagnostics(DiagnosticsPage::default()),
+                        MenuOption::About => AppMode::About,
+                    };
+                    self.transition(next);
                 } else if left {
-                    self.transition(AppMode::Menu(menu.left()));
+                    self.transition(AppMode::Menu(menu_left(&self.menu_options(), option)));
                 } else if right {
-                    self.transition(AppMode::Menu(menu.right()));
+                    self.transition(AppMode::Menu(menu_right(&self.menu_options(), option)));
                 }
             }
             AppMode::SetTime(ref mut screen_index) => {
@@ -230,48 +1523,115 @@ impl State {
                 }
 
                 if mode && !self.lr_pressed_while_mode_down {
+                    if self.first_boot {
+                        self.first_boot = false;
+                        self.settings_changed = true;
+                    }
                     self.transition_regular();
                 }
             }
-            AppMode::SetAlarm(ref mut screen_index) => {
+            AppMode::SetAlarm(index) => {
                 if self.is_mode_down {
                     if left {
-                        self.time_delta = Some((*screen_index, -1));
+                        self.change_alarm(index, -1);
                         self.lr_pressed_while_mode_down = true;
                     } else if right {
-                        self.time_delta = Some((*screen_index, 1));
+                        self.change_alarm(index, 1);
                         self.lr_pressed_while_mode_down = true;
                     }
                 } else if left {
-                    if *screen_index == 0 {
-                        *screen_index = 11;
-                    } else {
-                        *screen_index -= 1;
-                    }
+                    self.mode = AppMode::SetAlarm((index + ALARM_DIGITS - 1) % ALARM_DIGITS);
                     self.transition = true;
                 } else if right {
-                    if *screen_index == 11 {
-                        *screen_index = 0;
+                    self.mode = AppMode::SetAlarm((index + 1) % ALARM_DIGITS);
+                    self.transition = true;
+                }
+
+                if mode {
+                    if self.lr_pressed_while_mode_down {
+                        // Alarm is saved once mode is released after changing
+                        // it
+                        self.settings_changed = true;
                     } else {
-                        *screen_index += 1;
+                        self.transition_regular();
                     }
+                }
+            }
+            AppMode::SetArmed => {
+                if left || right {
+                    self.alarm_armed = !self.alarm_armed;
                     self.transition = true;
                 }
 
-                if mode && !self.lr_pressed_while_mode_down {
+                if mode {
+                    self.settings_changed = true;
+                    self.transition_regular();
+                }
+            }
+            AppMode::SetMelody => {
+                let count = MELODIES.len() as u8;
+                if left {
+                    self.melody = (self.melody + count - 1) % count;
+                    self.transition = true;
+                } else if right {
+                    self.melody = (self.melody + 1) % count;
+                    self.transition = true;
+                }
+
+                if mode {
+                    self.settings_changed = true;
                     self.transition_regular();
                 }
             }
-            AppMode::SetRgb => {
+            AppMode::SetVolume => {
                 if left {
-                    self.led_strip.left();
+                    self.volume = core::cmp::max(1, self.volume - 1);
+                    self.transition = true;
+                } else if right {
+                    self.volume = core::cmp::min(MAX_VOLUME, self.volume + 1);
+                    self.transition = true;
+                }
+
+                if mode {
+                    self.settings_changed = true;
+                    self.transition_regular();
+                }
+            }
+            AppMode::SetRgb(output) => {
+                // Hue of solid mode is swept the same way as time is set:
+                // holding mode and pressing left or right
+                if self.is_mode_down && matches!(self.led_mode(output), LedMode::Solid(_)) {
+                    if left {
+                        self.shift_hue(output, -HUE_STEP);
+                        self.lr_pressed_while_mode_down = true;
+                        self.transition = true;
+                    } else if right {
+                        self.shift_hue(output, HUE_STEP);
+                        self.lr_pressed_while_mode_down = true;
+                        self.transition = true;
+                    }
+                } else if left {
+                    match output {
+                        LedOutput::Strip => self.led_strip.left(),
+                        LedOutput::Ring => self.led_ring.left(),
+                    }
                     self.transition = true;
                 } else if right {
-                    self.led_strip.right();
+                    match output {
+                        LedOutput::Strip => self.led_strip.right(),
+                        LedOutput::Ring => self.led_ring.right(),
+                    }
                     self.transition = true;
                 }
 
+                // Mode of ring is chosen after the one of strip
+                let mode = mode && !self.lr_pressed_while_mode_down;
                 if mode {
+                    self.settings_changed = true;
+                }
+                if mode && output == LedOutput::Strip && self.has_led_ring {
+                    self.transition(AppMode::SetRgb(LedOutput::Ring));
+                } else if mode {
                     self.transition_regular();
                 }
             }
@@ -280,22 +1640,359 @@ impl State {
                     self.brightness = self.brightness.saturating_sub(1);
                     self.transition = true;
                 } else if right {
-                    self.brightness = core::cmp::min(9, self.brightness + 1);
+                    self.brightness = core::cmp::min(MAX_BRIGHTNESS, self.brightness + 1);
+                    self.transition = true;
+                }
+
+                if mode {
+                    self.settings_changed = true;
+                    self.transition_regular();
+                }
+            }
+            AppMode::TempHumidity(ref mut screen) => {
+                // Station altitude is changed the same way as time: holding
+                // mode and pressing left or right
+                if self.is_mode_down && *screen == SensorScreen::Pressure {
+                    if left {
+                        self.station_altitude = self.station_altitude.saturating_sub(ALTITUDE_STEP);
+                        self.lr_pressed_while_mode_down = true;
+                        self.transition = true;
+                    } else if right {
+                        self.station_altitude = self.station_altitude.saturating_add(ALTITUDE_STEP);
+                        self.lr_pressed_while_mode_down = true;
+                        self.transition = true;
+                    }
+                } else if left {
+                    *screen = screen.left();
+                    if *screen == SensorScreen::Humidity && !self.has_humidity {
+                        *screen = screen.left();
+                    }
+                    self.transition = true;
+                } else if right {
+                    *screen = screen.right();
+                    if *screen == SensorScreen::Humidity && !self.has_humidity {
+                        *screen = screen.right();
+                    }
+                    self.transition = true;
+                }
+
+                // Altitude is saved once mode is released after changing it
+                if mode && self.lr_pressed_while_mode_down {
+                    self.settings_changed = true;
+                }
+                if mode && !self.lr_pressed_while_mode_down {
+                    self.transition_regular();
+                }
+            }
+            AppMode::SetTheme => {
+                // Selecting theme brings back its digits
+                let count = THEMES.len() as u8;
+                if left {
+                    self.theme = (self.theme + count - 1) % count;
+                    self.digit_style = None;
+                    self.led_strip.set_mode(self.theme().led_mode);
+                    self.transition = true;
+                } else if right {
+                    self.theme = (self.theme + 1) % count;
+                    self.digit_style = None;
+                    self.led_strip.set_mode(self.theme().led_mode);
+                    self.transition = true;
+                }
+
+                if mode {
+                    self.settings_changed = true;
+                    self.transition_regular();
+                }
+            }
+            AppMode::SetDigits => {
+                // No digit style stands for digits of theme and goes first
+                let count = NUMPICS.len() as u8 + 1;
+                let index = self.digit_style.map_or(0, |digit_style| digit_style + 1);
+                let index = if left {
+                    Some((index + count - 1) % count)
+                } else if right {
+                    Some((index + 1) % count)
+                } else {
+                    None
+                };
+                if let Some(index) = index {
+                    self.digit_style = index.checked_sub(1);
+                    self.transition = true;
+                }
+
+                if mode {
+                    self.settings_changed = true;
+                    self.transition_regular();
+                }
+            }
+            AppMode::SetRotation => {
+                let index = ROTATION_PERIODS
+                    .iter()
+                    .position(|&rotation| rotation == self.rotation)
+                    .unwrap_or(0);
+                let count = ROTATION_PERIODS.len();
+                let index = if left {
+                    Some((index + count - 1) % count)
+                } else if right {
+                    Some((index + 1) % count)
+                } else {
+                    None
+                };
+                if let Some(index) = index {
+                    self.rotation = ROTATION_PERIODS[index];
+                    self.transition = true;
+                }
+
+                if mode {
+                    self.settings_changed = true;
+                    self.transition_regular();
+                }
+            }
+            AppMode::SetUnit => {
+                if left || right {
+                    self.temperature_unit = match self.temperature_unit {
+                        TemperatureUnit::Celsius => TemperatureUnit::Fahrenheit,
+                        TemperatureUnit::Fahrenheit => TemperatureUnit::Celsius,
+                    };
+                    self.transition = true;
+                }
+
+                if mode {
+                    self.settings_changed = true;
+                    self.transition_regular();
+                }
+            }
+            AppMode::SetDateFormat => {
+                let index = DATE_FORMATS
+                    .iter()
+                    .position(|&format| format == self.date_format)
+                    .unwrap_or(0);
+                let count = DATE_FORMATS.len();
+                let index = if left {
+                    Some((index + count - 1) % count)
+                } else if right {
+                    Some((index + 1) % count)
+                } else {
+                    None
+                };
+                if let Some(index) = index {
+                    self.date_format = DATE_FORMATS[index];
+                    self.transition = true;
+                }
+
+                if mode {
+                    self.settings_changed = true;
+                    self.transition_regular();
+                }
+            }
+            AppMode::SetSeconds => {
+                if left || right {
+                    self.hide_seconds = !self.hide_seconds;
+                    self.transition = true;
+                }
+
+                if mode {
+                    self.settings_changed = true;
+                    self.transition_regular();
+                }
+            }
+            AppMode::SetLayout => {
+                let index = TIME_LAYOUTS
+                    .iter()
+                    .position(|&layout| layout == self.time_layout)
+                    .unwrap_or(0);
+                let count = TIME_LAYOUTS.len();
+                let index = if left {
+                    Some((index + count - 1) % count)
+                } else if right {
+                    Some((index + 1) % count)
+                } else {
+                    None
+                };
+                if let Some(index) = index {
+                    self.time_layout = TIME_LAYOUTS[index];
+                    self.transition = true;
+                }
+
+                if mode {
+                    self.settings_changed = true;
+                    self.transition_regular();
+                }
+            }
+            AppMode::SetReminders(ref mut field) => {
+                // Month or day is changed the same way as time: holding mode
+                // and pressing left or right. Month wraps around through no
+                // reminder, which clears it.
+                let count = 2 * MAX_REMINDERS;
+                let delta = if left { -1 } else { 1 };
+                if self.is_mode_down && (left || right) {
+                    let slot = &mut self.reminders[*field / 2];
+                    *slot = match (*field % 2, *slot) {
+                        (0, reminder) => reminder::step_month(reminder, delta),
+                        (_, reminder) => reminder.map(|reminder| reminder.step_day(delta)),
+                    };
+                    self.lr_pressed_while_mode_down = true;
+                    self.transition = true;
+                } else if left {
+                    *field = (*field + count - 1) % count;
+                    self.transition = true;
+                } else if right {
+                    *field = (*field + 1) % count;
+                    self.transition = true;
+                }
+
+                if mode && !self.lr_pressed_while_mode_down {
+                    self.settings_changed = true;
+                    self.transition_regular();
+                }
+            }
+            AppMode::Diagnostics(ref mut page) => {
+                if self.is_mode_down && (left || right) {
+                    *page = DiagnosticsPage::Events;
+                    self.lr_pressed_while_mode_down = true;
+                    self.transition = true;
+                } else if left || right {
+                    *page = page.next();
                     self.transition = true;
                 }
 
+                if mode && !self.hardware_failed && !self.lr_pressed_while_mode_down {
+                    self.transition_regular();
+                }
+            }
+            AppMode::About => {
                 if mode {
                     self.transition_regular();
                 }
             }
-            AppMode::TempHumidity => {
-                todo!()
+            AppMode::CrashReport => {
+                if mode || left || right {
+                    self.transition(self.crash_return);
+                }
+            }
+            AppMode::Message => {
+                if mode || left || right {
+                    self.transition_regular();
+                }
+            }
+            // Mode is still held from boot when test starts
+            AppMode::SelfTest => {
+                if left || right {
+                    if self.hardware_failed {
+                        self.transition(AppMode::Diagnostics(DiagnosticsPage::default()));
+                    } else {
+                        self.transition_regular();
+                    }
+                }
+            }
+            AppMode::Dormant => {
+                if mode || left || right {
+                    self.transition_regular();
+                }
             }
         }
     }
 
     pub fn update(&mut self) {
         self.led_strip.update();
+        self.led_ring.update();
+        self.awake_frames = self.awake_frames.saturating_add(1);
+        self.update_idle();
+        self.update_rotation();
+        self.update_combo();
+    }
+
+    /// Asks for factory reset or USB bootloader once buttons of their
+    /// combination have been held long enough
+    fn update_combo(&mut self) {
+        self.combo_frames = self.combo_frames.saturating_add(1);
+        if self.combo_frames != COMBO_SECS * FRAMES_PER_SECOND {
+            return;
+        }
+
+        match (self.is_mode_down, self.is_left_down, self.is_right_down) {
+            (true, true, true) => self.factory_reset = true,
+            (false, true, true) => self.bootloader = true,
+            _ => {}
+        }
+    }
+
+    /// Shows next regular screen once rotation period passes
+    fn update_rotation(&mut self) {
+        let (AppMode::Regular(screen), Some(rotation)) = (self.mode, self.rotation) else {
+            self.rotation_frames = 0;
+            return;
+        };
+
+        self.rotation_frames += 1;
+        if self.rotation_frames >= rotation as u32 * FRAMES_PER_SECOND {
+            self.rotation_frames = 0;
+            let screen = self.step_screen(screen, TimeDateScreen::right);
+            self.transition(AppMode::Regular(screen));
+        }
+    }
+
+    /// Steps from screen until one that is shown. Screens of quantities
+    /// sensor doesn't measure are skipped.
+    fn step_screen(
+        &self,
+        screen: TimeDateScreen,
+        step: fn(TimeDateScreen) -> TimeDateScreen,
+    ) -> TimeDateScreen {
+        let mut screen = step(screen);
+        loop {
+            let is_shown = match screen {
+                TimeDateScreen::Time | TimeDateScreen::Date | TimeDateScreen::FullDate => true,
+                TimeDateScreen::Sun => self.location.is_some(),
+                // Rtc measures temperature if sensor is missing
+                TimeDateScreen::Temperature => true,
+                TimeDateScreen::Humidity => self.has_sensor && self.has_humidity,
+            };
+            if is_shown {
+                return screen;
+            }
+            screen = step(screen);
+        }
+    }
+
+    /// Returns to time screen if user has left clock in menu or settings
+    fn update_idle(&mut self) {
+        if matches!(
+            self.mode,
+            AppMode::Regular(_) | AppMode::Dormant | AppMode::SelfTest | AppMode::CrashReport
+        ) || self.hardware_failed
+            || (self.first_boot && matches!(self.mode, AppMode::SetTime(_)))
+        {
+            self.idle_frames = 0;
+            return;
+        }
+
+        self.idle_frames = self.idle_frames.saturating_add(1);
+        let Some(idle_timeout) = self.idle_timeout else {
+            return;
+        };
+        if self.idle_frames >= idle_timeout as u32 * FRAMES_PER_SECOND {
+            // Chosen settings stay, the same as if mode was pressed
+            if matches!(
+                self.mode,
+                AppMode::SetTheme
+                    | AppMode::SetDigits
+                    | AppMode::SetAlarm(_)
+                    | AppMode::SetArmed
+                    | AppMode::SetMelody
+                    | AppMode::SetVolume
+                    | AppMode::SetRotation
+                    | AppMode::SetUnit
+                    | AppMode::SetDateFormat
+                    | AppMode::SetSeconds
+                    | AppMode::SetLayout
+                    | AppMode::SetReminders(_)
+                    | AppMode::SetBrightness
+            ) {
+                self.settings_changed = true;
+            }
+            self.transition(AppMode::Regular(TimeDateScreen::Time));
+        }
     }
 
     fn transition(&mut self, mode: AppMode) {
@@ -303,7 +2000,38 @@ impl State {
         self.transition = true;
     }
 
+    fn led_mode(&self, output: LedOutput) -> LedMode {
+        match output {
+            LedOutput::Strip => self.led_strip.mode(),
+            LedOutput::Ring => self.led_ring.mode(),
+        }
+    }
+
+    fn shift_hue(&mut self, output: LedOutput, delta: i16) {
+        match output {
+            LedOutput::Strip => self.led_strip.shift_hue(delta),
+            LedOutput::Ring => self.led_ring.shift_hue(delta),
+        }
+    }
+
     fn transition_regular(&mut self) {
         self.transition(AppMode::Regular(Default::default()));
     }
 }
+
+/// Night has to start and end at different hours of day
+fn is_night_valid((start, end): (u8, u8)) -> bool {
+    start < 24 && end < 24 && start != end
+}
+
+fn is_spi_clock_valid(mhz: u8) -> bool {
+    (1..=MAX_SPI_CLOCK).contains(&mhz)
+}
+
+fn is_sunrise_valid(mins: u8) -> bool {
+    (1..=MAX_SUNRISE_MINS).contains(&mins)
+}
+
+fn is_idle_timeout_valid(secs: u16) -> bool {
+    (1..=MAX_IDLE_TIMEOUT_SECS).contains(&secs)
+}
@@ -0,0 +1,209 @@
+//! Times of sunrise and sunset at location of clock.
+//!
+//! Follows approximation of NOAA solar calculator, which is good to a couple
+//! of minutes away from polar circles. Trigonometry comes from bootrom, see
+//! [`FloatFuncs`].
+
+use core::f32::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{drivers::ds3231::Date, misc::FloatFuncs, time_util};
+
+/// Cosine of zenith angle of sun at sunrise and sunset, 90.833 degrees. Sun
+/// is a bit below horizon then because of its radius and refraction.
+const COS_ZENITH: f32 = -0.014_544;
+const MINUTES_PER_DAY: i32 = 24 * 60;
+
+/// Place on Earth in hundredths of degree, north and east are positive
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Location {
+    latitude: i16,
+    longitude: i16,
+}
+
+impl Location {
+    /// Returns None if coordinates are out of range
+    pub fn new(latitude: i16, longitude: i16) -> Option<Self> {
+        let is_valid = (-9000..=9000).contains(&latitude) && (-18000..=18000).contains(&longitude);
+        is_valid.then_some(Self {
+            latitude,
+            longitude,
+        })
+    }
+
+    pub fn latitude(self) -> i16 {
+        self.latitude
+    }
+
+    pub fn longitude(self) -> i16 {
+        self.longitude
+    }
+}
+
+/// Sunrise and sunset of a day
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Daylight {
+    /// Minutes since local midnight
+    Sun { sunrise: u16, sunset: u16 },
+    /// Sun doesn't set
+    PolarDay,
+    /// Sun doesn't rise
+    PolarNight,
+}
+
+/// Sunrise and sunset of date at location, in local time with given offset
+/// from UTC
+pub fn daylight(
+    date: Date,
+    location: Location,
+    utc_offset_mins: i16,
+    funcs: &FloatFuncs,
+) -> Daylight {
+    let sin = |x| (funcs.sin)(x);
+    let cos = |x| funcs.cos(x);
+    let radians = PI / 180.0;
+
+    // Fraction of year in radians
+    let year = 2.0 * PI / 365.0 * (time_util::day_of_year(date) as f32 - 1.0);
+    // Minutes solar time is ahead of mean solar time
+    let equation_of_time = 229.18
+        * (0.000_075 + 0.001_868 * cos(year)
+            - 0.032_077 * sin(year)
+            - 0.014_615 * cos(2.0 * year)
+            - 0.040_849 * sin(2.0 * year));
+    let declination = 0.006_918 - 0.399_912 * cos(year) + 0.070_257 * sin(year)
+        - 0.006_758 * cos(2.0 * year)
+        + 0.000_907 * sin(2.0 * year)
+        - 0.002_697 * cos(3.0 * year)
+        + 0.001_48 * sin(3.0 * year);
+
+    let latitude = location.latitude as f32 / 100.0 * radians;
+    let cos_hour_angle =
+        (COS_ZENITH - sin(latitude) * sin(declination)) / (cos(latitude) * cos(declination));
+    if cos_hour_angle > 1.0 {
+        return Daylight::PolarNight;
+    }
+    if cos_hour_angle < -1.0 {
+        return Daylight::PolarDay;
+    }
+
+    // Sun turns by a degree every 4 minutes
+    let half_day = 4.0 * funcs.acos(cos_hour_angle) / radians;
+    let longitude = location.longitude as f32 / 100.0;
+    let noon = 720.0 - 4.0 * longitude - equation_of_time + utc_offset_mins as f32;
+    let minutes = |minutes: f32| (minutes + 0.5) as i32;
+    let local = |minutes: i32| minutes.rem_euclid(MINUTES_PER_DAY) as u16;
+    Daylight::Sun {
+        sunrise: local(minutes(noon - half_day)),
+        sunset: local(minutes(noon + half_day)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern "C" fn sin(x: f32) -> f32 {
+        x.sin()
+    }
+
+    extern "C" fn exp(x: f32) -> f32 {
+        x.exp()
+    }
+
+    extern "C" fn ln(x: f32) -> f32 {
+        x.ln()
+    }
+
+    extern "C" fn atan2(y: f32, x: f32) -> f32 {
+        y.atan2(x)
+    }
+
+    extern "C" fn sqrt(x: f32) -> f32 {
+        x.sqrt()
+    }
+
+    const FUNCS: FloatFuncs = FloatFuncs {
+        sin,
+        exp,
+        ln,
+        atan2,
+        sqrt,
+    };
+
+    fn date(year: u16, month: u8, date: u8) -> Date {
+        Date { year, month, date }
+    }
+
+    fn assert_close(daylight: Daylight, sunrise: (u16, u16), sunset: (u16, u16)) {
+        let Daylight::Sun {
+            sunrise: rise,
+            sunset: set,
+        } = daylight
+        else {
+            panic!("expected sunrise and sunset, got {daylight:?}");
+        };
+        let expected_rise = sunrise.0 * 60 + sunrise.1;
+        let expected_set = sunset.0 * 60 + sunset.1;
+        assert!(
+            rise.abs_diff(expected_rise) <= 2,
+            "sunrise {rise}, expected {expected_rise}"
+        );
+        assert!(
+            set.abs_diff(expected_set) <= 2,
+            "sunset {set}, expected {expected_set}"
+        );
+    }
+
+    #[test]
+    fn test_location() {
+        assert!(Location::new(9000, -18000).is_some());
+        assert!(Location::new(9001, 0).is_none());
+        assert!(Location::new(0, 18001).is_none());
+    }
+
+    #[test]
+    fn test_daylight() {
+        let berlin = Location::new(5252, 1340).unwrap();
+        // Summer time, UTC+2
+        assert_close(
+            daylight(date(2024, 6, 21), berlin, 120, &FUNCS),
+            (4, 43),
+            (21, 33),
+        );
+        assert_close(
+            daylight(date(2024, 12, 21), berlin, 60, &FUNCS),
+            (8, 15),
+            (15, 54),
+        );
+
+        let sydney = Location::new(-3387, 15121).unwrap();
+        assert_close(
+            daylight(date(2024, 6, 21), sydney, 600, &FUNCS),
+            (7, 0),
+            (16, 54),
+        );
+
+        // Sunset after midnight UTC wraps around
+        let honolulu = Location::new(2131, -15786).unwrap();
+        assert_close(
+            daylight(date(2024, 3, 20), honolulu, 0, &FUNCS),
+            (16, 34),
+            (4, 41),
+        );
+    }
+
+    #[test]
+    fn test_polar() {
+        let tromso = Location::new(6965, 1896).unwrap();
+        assert_eq!(
+            daylight(date(2024, 6, 21), tromso, 120, &FUNCS),
+            Daylight::PolarDay
+        );
+        assert_eq!(
+            daylight(date(2024, 12, 21), tromso, 60, &FUNCS),
+            Daylight::PolarNight
+        );
+    }
+}
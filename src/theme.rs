@@ -0,0 +1,29 @@
+//! Color themes of user interface.
+//!
+//! Every directory in misc/img is a theme. It contains digits (numpic/0.jpg
+//! to numpic/9.jpg), menu pictures (menupic/1.jpg to menupic/6.jpg) and
+//! theme.txt with its colors. Theme may borrow menu pictures of other theme
+//! instead of having its own. Build script (build.rs) generates [`THEMES`]
+//! from these directories.
+
+use crate::{
+    images::{self, Menupic, Numpic},
+    led_strip::LedMode,
+    misc::ColorRGB8,
+};
+
+pub struct Theme {
+    /// Digits used on time, date and sensor screens
+    pub numpic: &'static Numpic,
+    pub menupic: &'static Menupic,
+    /// Color of bounding rects highlighting selection
+    pub accent: ColorRGB8,
+    /// Color of screens (or parts of them) that are not covered with pics
+    pub background: ColorRGB8,
+    /// Mode of led strip that is set when theme is selected
+    pub led_mode: LedMode,
+}
+
+// THEMES in order of directory names and NUMPICS with digits of every theme,
+// so they can be selected independently
+include!(concat!(env!("OUT_DIR"), "/themes_gen.rs"));
@@ -0,0 +1,46 @@
+//! Second tick driven by 1Hz square wave from ds3231.
+//!
+//! Rtc pulls its INT/SQW pin low exactly when seconds register increments.
+//! Falling edge raises GPIO interrupt that sets a flag, which main loop checks
+//! with [`take`]. This way rtc is only read over I2C when time has actually
+//! changed and digits are updated right at second boundary.
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::{self, Mutex};
+
+use crate::{
+    hal::{gpio::Interrupt, pac},
+    hardware::SqwPinTy,
+};
+
+static SQW_PIN: Mutex<RefCell<Option<SqwPinTy>>> = Mutex::new(RefCell::new(None));
+static TICKED: Mutex<Cell<bool>> = Mutex::new(Cell::new(false));
+
+/// Hands pin over to interrupt handler and enables interrupt on it. Square
+/// wave itself is enabled when hardware is initialized.
+pub fn init(pin: SqwPinTy) {
+    pin.set_interrupt_enabled(Interrupt::EdgeLow, true);
+    interrupt::free(|cs| SQW_PIN.borrow(cs).replace(Some(pin)));
+    // SAFETY: handler only touches data behind critical section
+    unsafe {
+        pac::NVIC::unmask(pac::Interrupt::IO_IRQ_BANK0);
+    }
+}
+
+/// Must be called from IO_IRQ_BANK0 interrupt handler
+pub fn on_interrupt() {
+    interrupt::free(|cs| {
+        if let Some(pin) = SQW_PIN.borrow(cs).borrow_mut().as_mut() {
+            if pin.interrupt_status(Interrupt::EdgeLow) {
+                pin.clear_interrupt(Interrupt::EdgeLow);
+                TICKED.borrow(cs).set(true);
+            }
+        }
+    })
+}
+
+/// Has second passed since last call?
+pub fn take() -> bool {
+    interrupt::free(|cs| TICKED.borrow(cs).replace(false))
+}
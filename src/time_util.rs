@@ -0,0 +1,148 @@
+//! Calendar calculations on dates kept by rtc.
+//!
+//! Pure functions without hardware, so unlike the rest of firmware they are
+//! tested on host as part of the simulator (`cargo test` in simulator).
+
+use crate::drivers::ds3231::{days_in_month, Date};
+
+/// Phases of moon told apart, see [`moon_phase`]
+pub const MOON_PHASE_COUNT: u32 = 8;
+/// Synodic month, average time between new moons, in ten thousandths of day
+const SYNODIC_MONTH: u32 = 295_306;
+/// New moon of January 6 2000 18:14 UTC, in ten thousandths of day since
+/// January 1 2000
+const NEW_MOON_EPOCH: u32 = 57_597;
+
+/// Day of week of date in Gregorian calendar, 0 is Sunday
+pub fn weekday(date: Date) -> u8 {
+    // Offsets of months, with January and February counted as months of
+    // previous year
+    const MONTH_OFFSETS: [u16; 12] = [0, 3, 2, 5, 0, 3, 5, 1, 4, 6, 2, 4];
+    let month = date.month.clamp(1, 12);
+    let year = date.year - (month < 3) as u16;
+    let offset = MONTH_OFFSETS[month as usize - 1];
+    ((year + year / 4 - year / 100 + year / 400 + offset + date.date as u16) % 7) as u8
+}
+
+/// Day of year, 1 is January 1
+pub fn day_of_year(date: Date) -> u16 {
+    let days_before: u16 = (1..date.month)
+        .map(|month| days_in_month(date.year, month) as u16)
+        .sum();
+    days_before + date.date as u16
+}
+
+/// Days since January 1 2000, dates before it count as that day
+fn days_since_2000(date: Date) -> u32 {
+    let days_before: u32 = (2000..date.year)
+        .map(|year| {
+            let last_day = Date {
+                year,
+                month: 12,
+                date: 31,
+            };
+            day_of_year(last_day) as u32
+        })
+        .sum();
+    days_before + day_of_year(date) as u32 - 1
+}
+
+/// Phase of moon at noon UTC of date, from 0 to [`MOON_PHASE_COUNT`]. 0 is
+/// new moon and half of count is full moon. Mean length of synodic month is
+/// used, so moments of phases may be off by up to a day.
+pub fn moon_phase(date: Date) -> u8 {
+    let noon = days_since_2000(date) * 10_000 + 5_000;
+    let age = (noon + SYNODIC_MONTH - NEW_MOON_EPOCH) % SYNODIC_MONTH;
+    // Rounded, so each phase is centered on its moment
+    let phase = (age * MOON_PHASE_COUNT + SYNODIC_MONTH / 2) / SYNODIC_MONTH;
+    (phase % MOON_PHASE_COUNT) as u8
+}
+
+/// Number of ISO 8601 weeks in year, 53 if it starts or ends on Thursday
+fn iso_weeks_in_year(year: u16) -> u8 {
+    let starts_on = weekday(Date {
+        year,
+        month: 1,
+        date: 1,
+    });
+    let ends_on = weekday(Date {
+        year,
+        month: 12,
+        date: 31,
+    });
+    if starts_on == 4 || ends_on == 4 {
+        53
+    } else {
+        52
+    }
+}
+
+/// ISO 8601 week of date, from 1 to 53. Weeks start on Monday and the first
+/// week of year is the one with its first Thursday, so days at the start or
+/// end of year may belong to week of neighbouring year.
+pub fn iso_week(date: Date) -> u8 {
+    // Monday is 1 and Sunday is 7
+    let weekday = match weekday(date) {
+        0 => 7,
+        weekday => weekday as u16,
+    };
+    let week = (day_of_year(date) + 10 - weekday) / 7;
+    if week == 0 {
+        iso_weeks_in_year(date.year - 1)
+    } else if week > iso_weeks_in_year(date.year) as u16 {
+        1
+    } else {
+        week as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: u16, month: u8, date: u8) -> Date {
+        Date { year, month, date }
+    }
+
+    #[test]
+    fn test_weekday() {
+        assert_eq!(weekday(date(2000, 1, 1)), 6);
+        assert_eq!(weekday(date(2024, 2, 29)), 4);
+        assert_eq!(weekday(date(2025, 12, 31)), 3);
+        assert_eq!(weekday(date(2026, 10, 16)), 5);
+    }
+
+    #[test]
+    fn test_day_of_year() {
+        assert_eq!(day_of_year(date(2025, 1, 1)), 1);
+        assert_eq!(day_of_year(date(2025, 3, 1)), 60);
+        assert_eq!(day_of_year(date(2024, 3, 1)), 61);
+        assert_eq!(day_of_year(date(2024, 12, 31)), 366);
+    }
+
+    #[test]
+    fn test_moon_phase() {
+        assert_eq!(moon_phase(date(2000, 1, 6)), 0);
+        assert_eq!(moon_phase(date(2024, 1, 11)), 0);
+        assert_eq!(moon_phase(date(2024, 1, 18)), 2);
+        assert_eq!(moon_phase(date(2024, 1, 25)), 4);
+        assert_eq!(moon_phase(date(2024, 2, 2)), 6);
+        assert_eq!(moon_phase(date(2025, 3, 14)), 4);
+        assert_eq!(moon_phase(date(2026, 10, 10)), 0);
+    }
+
+    #[test]
+    fn test_iso_week() {
+        assert_eq!(iso_week(date(2025, 6, 15)), 24);
+        assert_eq!(iso_week(date(2026, 10, 16)), 42);
+        // Belongs to the last week of previous year
+        assert_eq!(iso_week(date(2021, 1, 3)), 53);
+        assert_eq!(iso_week(date(2023, 1, 1)), 52);
+        // Belongs to the first week of next year
+        assert_eq!(iso_week(date(2024, 12, 30)), 1);
+        assert_eq!(iso_week(date(2025, 12, 29)), 1);
+        // Years with 53 weeks
+        assert_eq!(iso_week(date(2020, 12, 31)), 53);
+        assert_eq!(iso_week(date(2026, 12, 31)), 53);
+    }
+}
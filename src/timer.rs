@@ -0,0 +1,35 @@
+//! Monotonic time in milliseconds from system timer of RP2040.
+//!
+//! Timer counts microseconds since boot in 64 bits, so it never wraps in
+//! practice. It is only read, which is safe from anywhere, so [`now_ms`]
+//! needs no handle once [`init`] has taken timer out of reset.
+
+use core::cell::RefCell;
+
+use cortex_m::interrupt::{self, Mutex};
+
+use crate::hal::{pac, Timer};
+
+/// Keeps timer, so nothing else can take and reset it
+static TIMER: Mutex<RefCell<Option<Timer>>> = Mutex::new(RefCell::new(None));
+
+pub fn init(timer: Timer) {
+    interrupt::free(|cs| TIMER.borrow(cs).replace(Some(timer)));
+}
+
+/// Milliseconds since boot. Wraps around after 49 days, so only differences
+/// of times should be used.
+pub fn now_ms() -> u32 {
+    // SAFETY: counter registers are read only and reading them has no side
+    // effects
+    let timer = unsafe { &*pac::TIMER::ptr() };
+    // High half is read again in case low half has overflowed in between
+    loop {
+        let high = timer.timerawh.read().bits();
+        let low = timer.timerawl.read().bits();
+        if timer.timerawh.read().bits() == high {
+            let micros = ((high as u64) << 32) | low as u64;
+            return (micros / 1000) as u32;
+        }
+    }
+}